@@ -0,0 +1,136 @@
+//! Empfangsadressen (Subadressen) des CLI.
+//!
+//! Jeder Kontakt erhält eine eigene Subadresse (siehe
+//! [`phantomchat_core::subaddress`]), geführt unter einem Label – dem
+//! Spitznamen des Kontakts bzw. dem bei `pair --label` angegebenen Namen.
+//! Nachrichten an einen Kontakt tragen dessen aktuelle Adresse im
+//! Ratchet‑Header, so dass das Gegenüber eine neue Adresse ohne erneutes
+//! Pairing übernimmt.  Beim Empfang genügt ein ECDH für alle Adressen;
+//! welche Adresse getroffen wurde, zeigt das Tag.
+//!
+//! Rotation: Ist die aktuelle Adresse eines Labels älter als
+//! `address_rotation_days` (Profil), vergeben Senden und `pair` eine neue.
+//! Die alte nimmt danach noch `address_overlap_days` lang Nachrichten an,
+//! für Envelopes unterwegs und Gegenüber, die erst später von der neuen
+//! Adresse erfahren; anschließend werden Envelopes an sie verworfen.
+//! `addresses rotate` rotiert sofort.  Die Hauptadresse bleibt immer
+//! gültig.
+
+use crate::keystore::Keys;
+use crate::output::{fail, ErrorKind};
+use crate::store::Store;
+use phantomchat_core::util::{from_hex, to_hex};
+use phantomchat_core::{PairingBundle, Subaddress};
+use serde::{Deserialize, Serialize};
+
+const DAY_MS: u64 = 24 * 60 * 60 * 1000;
+
+/// Standard‑Überlappung nach einer Rotation.
+pub const DEFAULT_OVERLAP_DAYS: u32 = 7;
+
+/// Rotationsregeln aus dem Profil.
+#[derive(Debug, Clone)]
+pub struct AddressPolicy {
+    /// Alter, ab dem eine Adresse beim nächsten Gebrauch ersetzt wird.
+    pub rotate_after_ms: Option<u64>,
+    /// Wie lange eine ersetzte Adresse noch Nachrichten annimmt.
+    pub overlap_ms: u64,
+}
+
+impl AddressPolicy {
+    pub fn from_days(rotate_after: Option<u32>, overlap: Option<u32>) -> Self {
+        Self {
+            rotate_after_ms: rotate_after.map(|days| days as u64 * DAY_MS),
+            overlap_ms: overlap.unwrap_or(DEFAULT_OVERLAP_DAYS) as u64 * DAY_MS,
+        }
+    }
+}
+
+impl Default for AddressPolicy {
+    fn default() -> Self {
+        Self::from_days(None, None)
+    }
+}
+
+/// Eine vergebene Subadresse.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AddressRecord {
+    pub index: u32,
+    pub label: String,
+    /// Basispunkt und Schlüssel (hex), damit der Empfang nicht jede
+    /// Adresse neu ableiten muss.
+    pub base: String,
+    pub spend_pub: String,
+    pub created_at: u64,
+    /// Zeitpunkt, zu dem eine neuere Adresse sie ersetzt hat.
+    pub retired_at: Option<u64>,
+    /// Danach werden Envelopes an diese Adresse verworfen.
+    pub expires_at: Option<u64>,
+}
+
+impl AddressRecord {
+    pub fn is_active(&self, now: u64) -> bool {
+        self.expires_at.is_none_or(|expires| now < expires)
+    }
+    pub fn address(&self) -> anyhow::Result<Subaddress> {
+        let bytes = from_hex(&format!("{}{}", self.base, self.spend_pub))
+            .ok_or_else(|| fail(ErrorKind::General, "ungültige gespeicherte Adresse"))?;
+        Subaddress::from_bytes(&bytes).ok_or_else(|| fail(ErrorKind::General, "ungültige gespeicherte Adresse"))
+    }
+    pub fn base_bytes(&self) -> anyhow::Result<[u8; 32]> {
+        Ok(self.address()?.base)
+    }
+}
+
+/// Aktuelle Adresse für `label`; legt sie an oder rotiert sie gemäß
+/// `policy`.
+pub fn current(store: &Store, keys: &Keys, label: &str, policy: &AddressPolicy, now: u64) -> anyhow::Result<Subaddress> {
+    let existing = store.addresses()?.into_iter().find(|r| r.label == label && r.retired_at.is_none());
+    let record = match existing {
+        Some(record) if policy.rotate_after_ms.is_none_or(|age| now < record.created_at.saturating_add(age)) => record,
+        _ => rotate(store, keys, label, policy, now)?,
+    };
+    record.address()
+}
+
+/// Vergibt eine neue Adresse für `label`.  Die bisherige nimmt noch
+/// `policy.overlap_ms` lang Nachrichten an.
+pub fn rotate(store: &Store, keys: &Keys, label: &str, policy: &AddressPolicy, now: u64) -> anyhow::Result<AddressRecord> {
+    // Labels folgen denselben Regeln wie Spitznamen
+    crate::contacts::validate_nickname(label)?;
+    let all = store.addresses()?;
+    for mut old in all.iter().filter(|r| r.label == label && r.retired_at.is_none()).cloned() {
+        old.retired_at = Some(now);
+        old.expires_at = Some(now.saturating_add(policy.overlap_ms));
+        store.save_address(&old)?;
+    }
+    let index = all.iter().map(|r| r.index).max().unwrap_or(0) + 1;
+    let address = Subaddress::derive(&keys.view, &keys.spend.public, index);
+    let record = AddressRecord {
+        index,
+        label: label.to_owned(),
+        base: to_hex(&address.base),
+        spend_pub: to_hex(&address.spend_pub),
+        created_at: now,
+        retired_at: None,
+        expires_at: None,
+    };
+    store.save_address(&record)?;
+    Ok(record)
+}
+
+/// Signiertes Pairing‑Bundle für `address` (Hauptadresse ohne Angabe);
+/// mit Feature `pqc` samt KEM‑Schlüssel.
+pub fn pairing_bundle(keys: &Keys, relays: Vec<String>, address: Option<&Subaddress>) -> anyhow::Result<PairingBundle> {
+    let bundle = PairingBundle::new(&keys.identity, &keys.view.public, &keys.spend.public, relays)?;
+    let bundle = match address {
+        Some(address) => bundle.with_subaddress(&keys.identity, address),
+        None => bundle,
+    };
+    #[cfg(feature = "pqc")]
+    let bundle = match keys.kem_public() {
+        Some(kem_pub) => bundle.with_kem_pub(&keys.identity, kem_pub)?,
+        None => bundle,
+    };
+    Ok(bundle)
+}
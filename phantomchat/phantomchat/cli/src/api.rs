@@ -0,0 +1,908 @@
+//! Gemeinsame Operationen von CLI und Daemon.
+//!
+//! [`Local`] bündelt eine entsperrte Identität, die lokale Datenbank und
+//! optional den Relay‑Pool und bietet die Operationen an, die sowohl die
+//! Kommandozeile als auch der Daemon (siehe [`crate::daemon`]) ausführen.
+//! Die Rückgabetypen sind serialisierbar und bilden zugleich die
+//! Ergebnisse der JSON‑RPC‑Methoden.  [`Backend`] wählt zwischen lokaler
+//! Ausführung und einem laufenden Daemon, der die Datenbank bereits
+//! geöffnet hält.
+//!
+//! Nachrichten sind typisierte Inhalte ([`Content`]); Antworten,
+//! Reaktionen, Bearbeitungen und Löschungen verweisen über die `msg_id`
+//! (als Dezimalstring) auf eine Nachricht derselben Konversation.
+//! Gruppen ([`Recipient::Group`]) nehmen dieselben Inhalte an.
+
+use crate::addresses::{self, AddressRecord};
+use crate::attachments::{self, Fetched};
+use crate::contacts::{self, Contact, KeyUpdate};
+use crate::devices::{self, DeviceView};
+use crate::groups::{self, Group, GroupView};
+use crate::keystore::Keys;
+use crate::config::Profile;
+use crate::outgoing::{self, Peer, SendPolicy};
+use crate::receive::{Outcome, Receiver};
+use crate::rpc::Client;
+use crate::store::{self, Conversation, Direction, OutboxState, Store};
+use crate::thread::{self, Entry, Reaction};
+use crate::output::{fail, ErrorKind};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use phantomchat_core::{Content, Envelope, Fingerprint, LinkRequest, PairingBundle};
+use phantomchat_core::util::to_hex;
+use phantomchat_relays::{BridgeHealth, BridgeProvider, NostrRelay, RelayPool};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::Path;
+use x25519_dalek::PublicKey;
+
+/// Überlappung beim Nachladen, um Uhrabweichungen zwischen Sender und
+/// Empfänger auszugleichen; Duplikate fängt der Replay‑Schutz ab.
+const SYNC_OVERLAP_MS: u64 = 2 * 60 * 1000;
+
+/// Empfänger einer Nachricht.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Recipient {
+    /// Spitzname aus dem Kontaktbuch.
+    Contact(String),
+    /// Spend‑Public‑Key (hex oder Base64) ohne Kontakt.
+    Key(String),
+    /// Bestehende Konversation.
+    Conversation(String),
+    /// Gruppe (Name oder ID).
+    Group(String),
+}
+
+/// Ergebnis von `send`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sent {
+    /// `msg_id` als Dezimalstring (JSON‑Zahlen reichen nicht für 128 Bit).
+    pub msg_id: String,
+    pub conversation: String,
+    pub title: String,
+    /// Größe des serialisierten Envelopes in Bytes.
+    pub envelope_len: usize,
+    /// Von mindestens einem Relay angenommen.
+    pub published: bool,
+    /// Das Envelope (Base64), wenn keine Relays konfiguriert sind.
+    pub envelope: Option<String>,
+    /// Envelopes an weitere Geräte (Base64), wenn keine Relays
+    /// konfiguriert sind.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fanout: Vec<String>,
+    /// Anzahl der Envelopes insgesamt (ein Gerät je Envelope).
+    #[serde(default)]
+    pub devices: usize,
+    /// Der Identity‑Key des Kontakts hat sich geändert.
+    pub key_changed: bool,
+}
+
+/// Eine Nachricht zur Anzeige.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageView {
+    pub conversation: String,
+    /// Anzeigename des Gegenübers bzw. der Gruppe.
+    pub peer: String,
+    /// Absender eingehender Gruppennachrichten.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    pub msg_id: String,
+    pub direction: Direction,
+    pub ts: u64,
+    /// Art des Inhalts (`text`, `reply`, `reaction`, …).
+    #[serde(default = "default_kind")]
+    pub kind: String,
+    pub text: String,
+    /// `msg_id` der Nachricht, auf die sich der Inhalt bezieht.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    /// Anfang der zitierten Nachricht einer Antwort.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub reactions: Vec<Reaction>,
+    #[serde(default)]
+    pub edited: bool,
+    /// Vom Absender für alle gelöscht.
+    #[serde(default)]
+    pub deleted: bool,
+    /// Löschzeitpunkt (Millisekunden) bei verschwindenden Nachrichten.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// Zustellstatus ausgehender Nachrichten.
+    pub state: Option<OutboxState>,
+    /// Absender hat einen geänderten, noch nicht geprüften Schlüssel.
+    #[serde(default)]
+    pub key_changed: bool,
+    /// Absender ist kein Kontakt.
+    #[serde(default)]
+    pub unknown_sender: bool,
+}
+
+fn default_kind() -> String {
+    "text".into()
+}
+
+/// Ergebnis von `sync` (Nachladen seit der letzten Synchronisation).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncResult {
+    /// Anzahl geprüfter Envelopes.
+    pub scanned: usize,
+    pub messages: Vec<MessageView>,
+    /// Gründe verworfener Envelopes (auch fremde können darunter sein).
+    #[serde(default)]
+    pub rejected: Vec<String>,
+}
+
+/// Fingerprint eines Kontakts zum Vergleich.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FingerprintView {
+    pub contact: Contact,
+    pub safety_number: String,
+    pub emoji: Vec<String>,
+    pub words: Vec<String>,
+}
+
+/// Gesundheit eines Relays.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelayStatus {
+    pub url: String,
+    pub latency_ms: u32,
+    pub uptime: f32,
+    pub failure_rate: f32,
+}
+
+impl RelayStatus {
+    fn new(url: String, health: BridgeHealth) -> Self {
+        Self { url, latency_ms: health.latency_ms, uptime: health.uptime, failure_rate: health.failure_rate }
+    }
+}
+
+/// Lokale Ausführung mit entsperrter Identität.
+pub struct Local {
+    pub keys: Keys,
+    pub store: Store,
+    pub pool: Option<RelayPool<NostrRelay>>,
+    pub send: SendPolicy,
+    pub min_pow_bits: u32,
+}
+
+impl Local {
+    /// Öffnet die Datenbank des Profils und verbindet (falls konfiguriert)
+    /// die Relays.
+    pub fn open(keys: Keys, profile: &Profile) -> anyhow::Result<Self> {
+        let store = Store::open(&profile.db, &keys.storage_key)?;
+        store.purge_expired(store::now_ms())?;
+        let net = &profile.net;
+        let pool = if net.is_configured() { Some(net.pool(&keys.identity.public)?) } else { None };
+        Ok(Self { keys, store, pool, send: profile.send.clone(), min_pow_bits: profile.min_pow_bits })
+    }
+
+    pub fn receiver(&self) -> Receiver<'_> {
+        Receiver::new(&self.keys, &self.store).with_min_pow_bits(self.min_pow_bits)
+    }
+
+    /// Verschlüsselt, speichert und veröffentlicht eine Textnachricht,
+    /// optional als Antwort auf `reply_to`.
+    pub async fn send(&self, to: &Recipient, message: &str, reply_to: Option<u128>) -> anyhow::Result<Sent> {
+        let content = match reply_to {
+            Some(id) => {
+                self.find_message(&self.conversation_of(to)?, id)?;
+                Content::Reply { to: id, text: message.to_owned() }
+            }
+            None => Content::text(message),
+        };
+        self.send_content(to, &content).await
+    }
+
+    /// Verschlüsselt, speichert und veröffentlicht einen Inhalt.
+    pub async fn send_content(&self, to: &Recipient, content: &Content) -> anyhow::Result<Sent> {
+        if let Some(group) = self.group_of(to)? {
+            return self.send_group(group, content).await;
+        }
+        let (peer, key_changed) = self.peer(to)?;
+        let prepared = outgoing::prepare(&self.keys, &self.store, &self.send, &peer, &content.to_bytes())?;
+        let bytes = prepared.envelope.to_bytes();
+        let mut sent = Sent {
+            msg_id: prepared.msg_id.to_string(),
+            conversation: peer.conversation_id(),
+            title: peer.title.clone(),
+            envelope_len: bytes.len(),
+            published: false,
+            envelope: None,
+            fanout: Vec::new(),
+            devices: 1 + prepared.fanout.len(),
+            key_changed,
+        };
+        match &self.pool {
+            Some(pool) => {
+                outgoing::publish(&self.store, pool, prepared).await?;
+                sent.published = true;
+            }
+            None => {
+                sent.envelope = Some(BASE64.encode(bytes));
+                sent.fanout = prepared.fanout.iter().map(|env| BASE64.encode(env.to_bytes())).collect();
+            }
+        }
+        Ok(sent)
+    }
+
+    /// Schickt einen Inhalt an alle Mitglieder einer Gruppe.  Ausstehende
+    /// Sender‑Keys werden vorher verteilt.
+    async fn send_group(&self, mut group: Group, content: &Content) -> anyhow::Result<Sent> {
+        let pool = self.pool.as_ref().ok_or_else(|| fail(ErrorKind::Network, "Gruppen brauchen Relays (--relay wss://…)"))?;
+        groups::distribute(&self.keys, &self.store, &self.send, pool, &mut group).await?;
+        let prepared = groups::prepare(&self.keys, &self.store, &self.send, &mut group, &content.to_bytes())?;
+        let sent = Sent {
+            msg_id: prepared.msg_id.to_string(),
+            conversation: group.conversation_id(),
+            title: group.name.clone(),
+            envelope_len: prepared.envelope.to_bytes().len(),
+            published: true,
+            envelope: None,
+            fanout: Vec::new(),
+            devices: 1,
+            key_changed: false,
+        };
+        outgoing::publish(&self.store, pool, prepared).await?;
+        Ok(sent)
+    }
+
+    /// Gruppe eines Empfängers, auch als Konversation `group:<id>`.
+    fn group_of(&self, to: &Recipient) -> anyhow::Result<Option<Group>> {
+        match to {
+            Recipient::Group(name) => groups::find(&self.store, name).map(Some),
+            Recipient::Conversation(id) => groups::for_conversation(&self.store, id),
+            Recipient::Contact(_) | Recipient::Key(_) => Ok(None),
+        }
+    }
+
+    /// Konversations‑ID eines Empfängers.
+    fn conversation_of(&self, to: &Recipient) -> anyhow::Result<String> {
+        if let Some(group) = self.group_of(to)? {
+            return Ok(group.conversation_id());
+        }
+        Ok(self.peer(to)?.0.conversation_id())
+    }
+
+    /// Sucht Nachricht `msg_id` im Verlauf von `conversation`.
+    fn find_message(&self, conversation: &str, msg_id: u128) -> anyhow::Result<Entry> {
+        thread::fold(&self.store.messages(conversation)?)
+            .into_iter()
+            .find(|e| e.msg_id == msg_id)
+            .ok_or_else(|| fail(ErrorKind::NotFound, format!("Nachricht {msg_id} nicht in der Konversation")))
+    }
+
+    /// Reagiert auf Nachricht `msg_id` (oder nimmt die Reaktion zurück).
+    pub async fn react(&self, to: &Recipient, msg_id: u128, emoji: &str, remove: bool) -> anyhow::Result<Sent> {
+        if emoji.trim().is_empty() {
+            return Err(fail(ErrorKind::InvalidInput, "leere Reaktion"));
+        }
+        self.find_message(&self.conversation_of(to)?, msg_id)?;
+        self.send_content(to, &Content::Reaction { to: msg_id, emoji: emoji.to_owned(), remove }).await
+    }
+
+    /// Ersetzt den Text der eigenen Nachricht `msg_id`.
+    pub async fn edit(&self, to: &Recipient, msg_id: u128, message: &str) -> anyhow::Result<Sent> {
+        self.own_message(to, msg_id)?;
+        self.send_content(to, &Content::Edit { to: msg_id, text: message.to_owned() }).await
+    }
+
+    /// Löscht die eigene Nachricht `msg_id` für alle.
+    pub async fn delete(&self, to: &Recipient, msg_id: u128) -> anyhow::Result<Sent> {
+        self.own_message(to, msg_id)?;
+        self.send_content(to, &Content::Delete { to: msg_id }).await
+    }
+
+    /// Lädt die Blöcke einer Datei auf die Relays und schickt das Manifest
+    /// mit `caption` als Nachricht.
+    pub async fn send_file(&self, to: &Recipient, path: &Path, caption: &str) -> anyhow::Result<Sent> {
+        let pool = self.pool.as_ref().ok_or_else(|| fail(ErrorKind::Network, "Anhänge brauchen Relays (--relay wss://…)"))?;
+        // Empfänger vor dem Hochladen prüfen
+        self.conversation_of(to)?;
+        let (manifest, chunks) = attachments::seal(path, store::now_ms(), self.send.pow_bits)?;
+        attachments::upload(pool, chunks).await?;
+        self.send_content(to, &Content::Attachment { manifest, caption: caption.to_owned() }).await
+    }
+
+    /// Lädt den Anhang von Nachricht `msg_id` in der Konversation `with`
+    /// nach `out` (Datei oder Verzeichnis).
+    pub async fn fetch(&self, with: &str, msg_id: u128, out: &Path) -> anyhow::Result<Fetched> {
+        let pool = self.pool.as_ref().ok_or_else(|| fail(ErrorKind::Network, "keine Relays konfiguriert (--relay wss://…)"))?;
+        let id = self.conversation_id(with)?;
+        let msg = self
+            .store
+            .messages(&id)?
+            .into_iter()
+            .find(|m| m.msg_id == msg_id)
+            .ok_or_else(|| fail(ErrorKind::NotFound, format!("Nachricht {msg_id} nicht in der Konversation")))?;
+        let Ok(Content::Attachment { manifest, .. }) = Content::from_bytes(&msg.body) else {
+            return Err(fail(ErrorKind::InvalidInput, format!("Nachricht {msg_id} hat keinen Anhang")));
+        };
+        attachments::fetch(pool, &manifest, &attachments::target(&manifest, out), store::now_ms()).await
+    }
+
+    /// Setzt den Timer für verschwindende Nachrichten (`0` schaltet ab).
+    /// Er gilt ab dieser Nachricht für beide Seiten.
+    pub async fn set_timer(&self, to: &Recipient, seconds: u32) -> anyhow::Result<Sent> {
+        self.send_content(to, &Content::Timer { seconds }).await
+    }
+
+    /// Prüft, dass `msg_id` eine eigene, nicht gelöschte Nachricht ist.
+    fn own_message(&self, to: &Recipient, msg_id: u128) -> anyhow::Result<()> {
+        let entry = self.find_message(&self.conversation_of(to)?, msg_id)?;
+        if entry.direction != Direction::Outgoing {
+            return Err(fail(ErrorKind::InvalidInput, "nur eigene Nachrichten lassen sich bearbeiten oder löschen"));
+        }
+        if entry.deleted {
+            return Err(fail(ErrorKind::InvalidInput, "Nachricht ist bereits gelöscht"));
+        }
+        Ok(())
+    }
+
+    /// Bestätigt die ungelesenen Nachrichten einer Konversation (Spitzname
+    /// oder ID) und setzt den Ungelesen‑Zähler zurück.  Ohne Relays oder
+    /// Rückkanal entfällt die Bestätigung.  Gibt die Zahl bestätigter
+    /// Nachrichten zurück.
+    pub async fn mark_read(&self, with: &str) -> anyhow::Result<usize> {
+        let id = self.conversation_id(with)?;
+        let Some(mut conv) = self.store.conversation(&id)? else {
+            return Ok(0);
+        };
+        let ids = thread::unread_ids(&self.store.messages(&id)?, conv.unread);
+        conv.unread = 0;
+        self.store.save_conversation(&conv)?;
+        self.store.flush()?;
+        if ids.is_empty() {
+            return Ok(0);
+        }
+        let (Some(pool), Some(peer)) = (&self.pool, Peer::for_conversation(&self.store, &id)?) else {
+            return Ok(0);
+        };
+        let receipt = Content::Read { ids: ids.clone() }.to_bytes();
+        let prepared = outgoing::prepare(&self.keys, &self.store, &self.send, &peer, &receipt)?;
+        outgoing::publish(&self.store, pool, prepared).await?;
+        Ok(ids.len())
+    }
+
+    /// Anzeigename eines Gruppenmitglieds (Identity‑Key hex); ehemalige
+    /// Mitglieder erscheinen mit Kurz‑Fingerprint.
+    fn member_name(&self, group: Option<&Group>, hex: &str) -> anyhow::Result<String> {
+        let member = group.and_then(|g| g.members.iter().find(|m| to_hex(&m.identity_pub) == hex));
+        match member {
+            Some(member) => groups::display_name(&self.store, member),
+            None => Ok(hex.chars().take(16).collect()),
+        }
+    }
+
+    /// Konversations‑ID zu einem Spitznamen, Gruppennamen oder einer ID.
+    fn conversation_id(&self, with: &str) -> anyhow::Result<String> {
+        if let Some(contact) = self.store.contact(with)? {
+            return Ok(contact.conversation_id());
+        }
+        if let Some(group) = self.store.groups()?.into_iter().find(|g| g.name == with) {
+            return Ok(group.conversation_id());
+        }
+        Ok(with.to_owned())
+    }
+
+    pub fn groups(&self) -> anyhow::Result<Vec<GroupView>> {
+        let own = &self.keys.identity.public;
+        self.store.groups()?.iter().map(|g| GroupView::new(&self.store, g, own)).collect()
+    }
+
+    /// Gründet eine Gruppe mit den Kontakten `members` und lädt sie ein.
+    pub async fn create_group(&self, name: &str, members: &[String]) -> anyhow::Result<GroupView> {
+        let pool = self.pool.as_ref().ok_or_else(|| fail(ErrorKind::Network, "Gruppen brauchen Relays (--relay wss://…)"))?;
+        if name.trim().is_empty() {
+            return Err(fail(ErrorKind::InvalidInput, "leerer Gruppenname"));
+        }
+        let mut others = Vec::new();
+        for nickname in members {
+            let member = groups::member_of(&self.member_contact(nickname)?)?;
+            if member.identity_pub == self.keys.identity.public || others.contains(&member) {
+                continue;
+            }
+            others.push(member);
+        }
+        if others.is_empty() {
+            return Err(fail(ErrorKind::InvalidInput, "eine Gruppe braucht mindestens ein weiteres Mitglied"));
+        }
+        let mut group = Group::create(name, groups::own_member(&self.keys), others);
+        self.store.save_group(&group)?;
+        groups::distribute(&self.keys, &self.store, &self.send, pool, &mut group).await?;
+        GroupView::new(&self.store, &group, &self.keys.identity.public)
+    }
+
+    /// Nimmt den Kontakt `nickname` in die Gruppe auf (nur beim Gründer).
+    pub async fn add_member(&self, group: &str, nickname: &str) -> anyhow::Result<GroupView> {
+        let member = groups::member_of(&self.member_contact(nickname)?)?;
+        self.change_group(group, |g, own| g.add_member(own, member)).await
+    }
+
+    /// Entfernt ein Mitglied (Spitzname oder Name aus der
+    /// Mitgliederliste) aus der Gruppe (nur beim Gründer).
+    pub async fn remove_member(&self, group: &str, name: &str) -> anyhow::Result<GroupView> {
+        let identity = match self.store.contact(name)? {
+            Some(contact) => contact.identity_public()?,
+            None => {
+                let group = groups::find(&self.store, group)?;
+                let member = group.members.iter().find(|m| m.name == name);
+                member.map(|m| m.identity_pub).ok_or_else(|| fail(ErrorKind::NotFound, format!("{name:?} ist kein Mitglied")))?
+            }
+        };
+        self.change_group(group, |g, own| g.remove_member(own, &identity)).await
+    }
+
+    /// Ändert die Mitglieder und verteilt die neue Epoche.
+    async fn change_group(
+        &self,
+        name: &str,
+        change: impl FnOnce(&mut Group, &[u8; 32]) -> anyhow::Result<()>,
+    ) -> anyhow::Result<GroupView> {
+        let pool = self.pool.as_ref().ok_or_else(|| fail(ErrorKind::Network, "Gruppen brauchen Relays (--relay wss://…)"))?;
+        let own = self.keys.identity.public;
+        let mut group = groups::find(&self.store, name)?;
+        change(&mut group, &own)?;
+        self.store.save_group(&group)?;
+        groups::distribute(&self.keys, &self.store, &self.send, pool, &mut group).await?;
+        GroupView::new(&self.store, &group, &own)
+    }
+
+    /// Verlässt eine Gruppe; beim Gründer endet sie für alle.  Gibt die
+    /// Zahl nicht erreichter Mitglieder zurück.
+    pub async fn leave_group(&self, name: &str) -> anyhow::Result<usize> {
+        let pool = self.pool.as_ref().ok_or_else(|| fail(ErrorKind::Network, "Gruppen brauchen Relays (--relay wss://…)"))?;
+        let group = groups::find(&self.store, name)?;
+        groups::leave(&self.keys, &self.store, &self.send, pool, &group).await
+    }
+
+    /// Verschickt ausstehende Steuernachrichten aller Gruppen; gibt die
+    /// Zahl der weiterhin ausstehenden zurück.
+    pub async fn distribute_groups(&self) -> anyhow::Result<usize> {
+        match &self.pool {
+            Some(pool) => groups::distribute_all(&self.keys, &self.store, &self.send, pool).await,
+            None => Ok(0),
+        }
+    }
+
+    fn member_contact(&self, nickname: &str) -> anyhow::Result<Contact> {
+        self.store
+            .contact(nickname)?
+            .ok_or_else(|| fail(ErrorKind::NotFound, format!("Kontakt {nickname:?} unbekannt (siehe `contacts list`)")))
+    }
+
+    /// Empfänger als Gegenüber; zusätzlich, ob sich der Schlüssel des
+    /// Kontakts geändert hat.
+    fn peer(&self, to: &Recipient) -> anyhow::Result<(Peer, bool)> {
+        Ok(match to {
+            Recipient::Contact(name) => {
+                let contact = self
+                    .store
+                    .contact(name)?
+                    .ok_or_else(|| fail(ErrorKind::NotFound, format!("Kontakt {name:?} unbekannt (siehe `contacts list`)")))?;
+                (Peer::from_contact(&contact)?, contact.verification == contacts::Verification::KeyChanged)
+            }
+            Recipient::Key(key) => (Peer::from_key(PublicKey::from(contacts::parse_key(key)?)), false),
+            Recipient::Conversation(id) => {
+                let peer = Peer::for_conversation(&self.store, id)?
+                    .ok_or_else(|| fail(ErrorKind::NotFound, format!("Konversation {id:?} hat keinen Rückkanal")))?;
+                let changed = self
+                    .store
+                    .contact_for_conversation(id)?
+                    .map(|c| c.verification == contacts::Verification::KeyChanged)
+                    .unwrap_or(false);
+                (peer, changed)
+            }
+            Recipient::Group(name) => {
+                return Err(fail(ErrorKind::InvalidInput, format!("{name:?} ist eine Gruppe, kein einzelnes Gegenüber")))
+            }
+        })
+    }
+
+    /// Verarbeitet ein empfangenes Envelope.  Gibt die Nachricht zurück,
+    /// wenn es eine neue für uns war.
+    pub fn handle(&self, env: &Envelope) -> anyhow::Result<Outcome> {
+        self.receiver().handle(env)
+    }
+
+    /// Lädt alles seit der letzten Synchronisation von den Relays nach.
+    pub async fn sync(&self) -> anyhow::Result<SyncResult> {
+        let pool = self.pool.as_ref().ok_or_else(|| fail(ErrorKind::Network, "keine Relays konfiguriert (--relay wss://…)"))?;
+        let started = store::now_ms();
+        let since = self.store.get_meta::<u64>("last_sync")?.unwrap_or(0).saturating_sub(SYNC_OVERLAP_MS);
+        let mut backlog = pool.fetch_since(since).await?;
+        // In Sendereihenfolge, da Steuernachrichten von Gruppen aufeinander
+        // aufbauen (Mitgliederliste vor Sender‑Keys der neuen Mitglieder)
+        backlog.sort_by_key(|env| env.ts);
+        let mut messages = Vec::new();
+        let mut rejected = Vec::new();
+        for env in &backlog {
+            match self.handle(env)? {
+                Outcome::Received(msg) => messages.push(received_view(msg)),
+                Outcome::Rejected(reason) => rejected.push(reason.to_owned()),
+                Outcome::Signal(_) | Outcome::NotForUs | Outcome::Replay => {}
+            }
+        }
+        self.store.set_meta("last_sync", &started)?;
+        groups::distribute_all(&self.keys, &self.store, &self.send, pool).await?;
+        self.store.prune_replay(started)?;
+        self.store.purge_expired(store::now_ms())?;
+        self.store.flush()?;
+        Ok(SyncResult { scanned: backlog.len(), messages, rejected })
+    }
+
+    pub fn conversations(&self) -> anyhow::Result<Vec<Conversation>> {
+        self.store.purge_expired(store::now_ms())?;
+        self.store.conversations()
+    }
+
+    /// Verlauf einer Konversation (Spitzname oder ID) mit eingearbeiteten
+    /// Reaktionen, Bearbeitungen und Löschungen; setzt den
+    /// Ungelesen‑Zähler zurück.
+    pub fn messages(&self, with: &str) -> anyhow::Result<Vec<MessageView>> {
+        self.store.purge_expired(store::now_ms())?;
+        let id = self.conversation_id(with)?;
+        let conv = self.store.conversation(&id)?;
+        let peer = conv.as_ref().map(|c| c.title.clone()).unwrap_or_else(|| id.clone());
+        let group = groups::for_conversation(&self.store, &id)?;
+        let mut views = Vec::new();
+        for entry in thread::fold(&self.store.messages(&id)?) {
+            let state = match entry.direction {
+                Direction::Outgoing => self.store.outbox_entry(entry.msg_id)?.map(|e| e.state),
+                Direction::Incoming => None,
+            };
+            let from = entry.sender.as_deref().map(|hex| self.member_name(group.as_ref(), hex)).transpose()?;
+            let mut view = message_view(&id, entry, &peer, state);
+            view.from = from;
+            views.push(view);
+        }
+        if let Some(mut conv) = conv {
+            conv.unread = 0;
+            self.store.save_conversation(&conv)?;
+            self.store.flush()?;
+        }
+        Ok(views)
+    }
+
+    pub fn contacts(&self) -> anyhow::Result<Vec<Contact>> {
+        self.store.contacts()
+    }
+
+    pub fn add_contact(&self, contact: Contact, signed: bool) -> anyhow::Result<KeyUpdate> {
+        let update = contacts::add(&self.store, contact, signed)?;
+        self.store.flush()?;
+        Ok(update)
+    }
+
+    pub fn remove_contact(&self, name: &str) -> anyhow::Result<()> {
+        if !self.store.remove_contact(name)? {
+            return Err(fail(ErrorKind::NotFound, format!("Kontakt {name:?} unbekannt")));
+        }
+        self.store.flush()
+    }
+
+    pub fn fingerprint(&self, name: &str) -> anyhow::Result<FingerprintView> {
+        let contact = self.store.contact(name)?.ok_or_else(|| fail(ErrorKind::NotFound, format!("Kontakt {name:?} unbekannt")))?;
+        let fp = Fingerprint::new(&self.keys.identity.public, &contact.identity_public()?);
+        Ok(FingerprintView {
+            safety_number: fp.safety_number_grouped(),
+            emoji: fp.emoji.iter().map(|e| e.to_string()).collect(),
+            words: fp.words.iter().map(|w| w.to_string()).collect(),
+            contact,
+        })
+    }
+
+    pub fn verify_contact(&self, name: &str) -> anyhow::Result<()> {
+        contacts::mark_verified(&self.store, name)?;
+        self.store.flush()
+    }
+
+    pub fn addresses(&self) -> anyhow::Result<Vec<AddressRecord>> {
+        self.store.addresses()
+    }
+
+    /// Rotiert die Subadresse `label` sofort.
+    pub fn rotate_address(&self, label: &str) -> anyhow::Result<AddressRecord> {
+        let record = addresses::rotate(&self.store, &self.keys, label, &self.send.addresses, store::now_ms())?;
+        self.store.flush()?;
+        Ok(record)
+    }
+
+    /// Pairing‑Bundle (Textform) mit der aktuellen Subadresse `label`.
+    pub fn address_bundle(&self, label: &str, relays: Vec<String>) -> anyhow::Result<String> {
+        let address = addresses::current(&self.store, &self.keys, label, &self.send.addresses, store::now_ms())?;
+        self.store.flush()?;
+        Ok(addresses::pairing_bundle(&self.keys, relays, Some(&address))?.to_text())
+    }
+
+    pub fn devices(&self) -> anyhow::Result<Vec<DeviceView>> {
+        devices::list(&self.store)
+    }
+
+    /// Verknüpft das Gerät mit dem Link‑Code `code`: stellt das Zertifikat
+    /// aus und schickt ihm Identity‑Key, Geräteliste und Kontaktbuch.
+    pub async fn add_device(&self, code: &str) -> anyhow::Result<DeviceView> {
+        let pool = self.pool.as_ref().ok_or_else(|| fail(ErrorKind::Network, "keine Relays konfiguriert (--relay wss://…)"))?;
+        let request = LinkRequest::from_text(code).map_err(|e| fail(ErrorKind::InvalidInput, format!("ungültiger Link‑Code: {e}")))?;
+        let (own, provisioning) = devices::authorize(&self.store, &self.keys, &request, store::now_ms())?;
+        let envelope = devices::provisioning_envelope(&self.keys, &self.send, &request, &provisioning)?;
+        pool.publish(envelope).await.map_err(|e| fail(ErrorKind::Network, format!("Veröffentlichen fehlgeschlagen: {e:#}")))?;
+        own.save(&self.store)?;
+        self.store.flush()?;
+        let device_id = provisioning.device_id;
+        devices::list(&self.store)?
+            .into_iter()
+            .find(|d| d.device_id == device_id)
+            .ok_or_else(|| fail(ErrorKind::General, "Gerät fehlt in der Geräteliste"))
+    }
+
+    /// Entfernt ein verknüpftes Gerät aus der Geräteliste.
+    pub fn remove_device(&self, device_id: u32) -> anyhow::Result<()> {
+        devices::remove(&self.store, &self.keys, device_id)?;
+        self.store.flush()
+    }
+
+    pub async fn relays(&self) -> Vec<RelayStatus> {
+        match &self.pool {
+            Some(pool) => pool.health_all().await.into_iter().map(|(url, h)| RelayStatus::new(url, h)).collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+/// Ansicht einer gespeicherten Nachricht.
+fn message_view(conversation: &str, entry: Entry, peer: &str, state: Option<OutboxState>) -> MessageView {
+    MessageView {
+        conversation: conversation.to_owned(),
+        peer: peer.to_owned(),
+        from: None,
+        msg_id: entry.msg_id.to_string(),
+        direction: entry.direction,
+        ts: entry.ts,
+        kind: entry.kind.to_owned(),
+        text: entry.text,
+        target: entry.reply_to.map(|id| id.to_string()),
+        quote: entry.quote,
+        reactions: entry.reactions,
+        edited: entry.edited,
+        deleted: entry.deleted,
+        expires_at: entry.expires_at,
+        state,
+        key_changed: false,
+        unknown_sender: false,
+    }
+}
+
+/// Ansicht einer gerade empfangenen Nachricht oder eines Signals; der
+/// Text beschreibt Reaktionen, Bearbeitungen usw. in einer Zeile.
+pub fn received_view(msg: crate::receive::Received) -> MessageView {
+    let content = msg.content();
+    let (peer, from) = match msg.group {
+        Some(group) => (group, Some(msg.sender)),
+        None => (msg.sender, None),
+    };
+    MessageView {
+        key_changed: matches!(&msg.contact, Some(c) if c.verification == contacts::Verification::KeyChanged),
+        unknown_sender: msg.contact.is_none() && msg.direction == Direction::Incoming,
+        conversation: msg.conversation,
+        peer,
+        from,
+        msg_id: msg.msg_id.to_string(),
+        direction: msg.direction,
+        ts: msg.ts,
+        kind: content.as_ref().map_or("unsupported", Content::kind).to_owned(),
+        text: thread::summary(&content),
+        target: content.as_ref().ok().and_then(Content::target).map(|id| id.to_string()),
+        quote: None,
+        reactions: Vec::new(),
+        edited: false,
+        deleted: false,
+        expires_at: None,
+        state: None,
+    }
+}
+
+/// Lokale Ausführung oder Weiterleitung an einen laufenden Daemon.
+pub enum Backend {
+    Local(Box<Local>),
+    Remote(Client),
+}
+
+impl Backend {
+    pub async fn send(&mut self, to: &Recipient, message: &str, reply_to: Option<u128>) -> anyhow::Result<Sent> {
+        match self {
+            Backend::Local(local) => local.send(to, message, reply_to).await,
+            Backend::Remote(client) => {
+                let reply_to = reply_to.map(|id| id.to_string());
+                client.call_as("send", json!({ "to": to, "message": message, "reply_to": reply_to })).await
+            }
+        }
+    }
+    pub async fn react(&mut self, to: &Recipient, msg_id: u128, emoji: &str, remove: bool) -> anyhow::Result<Sent> {
+        match self {
+            Backend::Local(local) => local.react(to, msg_id, emoji, remove).await,
+            Backend::Remote(client) => {
+                let params = json!({ "to": to, "msg_id": msg_id.to_string(), "emoji": emoji, "remove": remove });
+                client.call_as("react", params).await
+            }
+        }
+    }
+    pub async fn edit(&mut self, to: &Recipient, msg_id: u128, message: &str) -> anyhow::Result<Sent> {
+        match self {
+            Backend::Local(local) => local.edit(to, msg_id, message).await,
+            Backend::Remote(client) => {
+                client.call_as("edit", json!({ "to": to, "msg_id": msg_id.to_string(), "message": message })).await
+            }
+        }
+    }
+    pub async fn delete(&mut self, to: &Recipient, msg_id: u128) -> anyhow::Result<Sent> {
+        match self {
+            Backend::Local(local) => local.delete(to, msg_id).await,
+            Backend::Remote(client) => client.call_as("delete", json!({ "to": to, "msg_id": msg_id.to_string() })).await,
+        }
+    }
+    /// `path` muss für einen Daemon absolut sein.
+    pub async fn send_file(&mut self, to: &Recipient, path: &Path, caption: &str) -> anyhow::Result<Sent> {
+        match self {
+            Backend::Local(local) => local.send_file(to, path, caption).await,
+            Backend::Remote(client) => {
+                client.call_as("attachments.send", json!({ "to": to, "path": path, "caption": caption })).await
+            }
+        }
+    }
+    /// `out` muss für einen Daemon absolut sein.
+    pub async fn fetch(&mut self, with: &str, msg_id: u128, out: &Path) -> anyhow::Result<Fetched> {
+        match self {
+            Backend::Local(local) => local.fetch(with, msg_id, out).await,
+            Backend::Remote(client) => {
+                let params = json!({ "conversation": with, "msg_id": msg_id.to_string(), "out": out });
+                client.call_as("attachments.fetch", params).await
+            }
+        }
+    }
+    pub async fn set_timer(&mut self, to: &Recipient, seconds: u32) -> anyhow::Result<Sent> {
+        match self {
+            Backend::Local(local) => local.set_timer(to, seconds).await,
+            Backend::Remote(client) => client.call_as("timer", json!({ "to": to, "seconds": seconds })).await,
+        }
+    }
+    pub async fn mark_read(&mut self, with: &str) -> anyhow::Result<usize> {
+        match self {
+            Backend::Local(local) => local.mark_read(with).await,
+            Backend::Remote(client) => client.call_as("messages.read", json!({ "conversation": with })).await,
+        }
+    }
+    pub async fn sync(&mut self) -> anyhow::Result<SyncResult> {
+        match self {
+            Backend::Local(local) => local.sync().await,
+            Backend::Remote(client) => client.call_as("sync", json!({})).await,
+        }
+    }
+    pub async fn conversations(&mut self) -> anyhow::Result<Vec<Conversation>> {
+        match self {
+            Backend::Local(local) => local.conversations(),
+            Backend::Remote(client) => client.call_as("conversations.list", json!({})).await,
+        }
+    }
+    pub async fn messages(&mut self, with: &str) -> anyhow::Result<Vec<MessageView>> {
+        match self {
+            Backend::Local(local) => local.messages(with),
+            Backend::Remote(client) => client.call_as("messages.list", json!({ "conversation": with })).await,
+        }
+    }
+    pub async fn contacts(&mut self) -> anyhow::Result<Vec<Contact>> {
+        match self {
+            Backend::Local(local) => local.contacts(),
+            Backend::Remote(client) => client.call_as("contacts.list", json!({})).await,
+        }
+    }
+    pub async fn add_contact(&mut self, contact: Contact, signed: bool) -> anyhow::Result<KeyUpdate> {
+        match self {
+            Backend::Local(local) => local.add_contact(contact, signed),
+            Backend::Remote(client) => {
+                client.call_as("contacts.add", json!({ "contact": contact, "signed": signed })).await
+            }
+        }
+    }
+    /// Importiert ein Pairing‑Bundle in Textform; die Signatur prüft die
+    /// ausführende Seite.
+    pub async fn import_contact(&mut self, name: &str, bundle: &str) -> anyhow::Result<KeyUpdate> {
+        match self {
+            Backend::Local(local) => {
+                let bundle = PairingBundle::from_text(bundle)?;
+                local.add_contact(Contact::from_bundle(name, &bundle, store::now_ms()), true)
+            }
+            Backend::Remote(client) => {
+                client.call_as("contacts.import", json!({ "name": name, "bundle": bundle })).await
+            }
+        }
+    }
+    pub async fn remove_contact(&mut self, name: &str) -> anyhow::Result<()> {
+        match self {
+            Backend::Local(local) => local.remove_contact(name),
+            Backend::Remote(client) => client.call_as("contacts.remove", json!({ "name": name })).await,
+        }
+    }
+    pub async fn fingerprint(&mut self, name: &str) -> anyhow::Result<FingerprintView> {
+        match self {
+            Backend::Local(local) => local.fingerprint(name),
+            Backend::Remote(client) => client.call_as("contacts.fingerprint", json!({ "name": name })).await,
+        }
+    }
+    pub async fn verify_contact(&mut self, name: &str) -> anyhow::Result<()> {
+        match self {
+            Backend::Local(local) => local.verify_contact(name),
+            Backend::Remote(client) => client.call_as("contacts.verify", json!({ "name": name })).await,
+        }
+    }
+    pub async fn addresses(&mut self) -> anyhow::Result<Vec<AddressRecord>> {
+        match self {
+            Backend::Local(local) => local.addresses(),
+            Backend::Remote(client) => client.call_as("addresses.list", json!({})).await,
+        }
+    }
+    pub async fn rotate_address(&mut self, label: &str) -> anyhow::Result<AddressRecord> {
+        match self {
+            Backend::Local(local) => local.rotate_address(label),
+            Backend::Remote(client) => client.call_as("addresses.rotate", json!({ "label": label })).await,
+        }
+    }
+    pub async fn devices(&mut self) -> anyhow::Result<Vec<DeviceView>> {
+        match self {
+            Backend::Local(local) => local.devices(),
+            Backend::Remote(client) => client.call_as("devices.list", json!({})).await,
+        }
+    }
+    pub async fn add_device(&mut self, code: &str) -> anyhow::Result<DeviceView> {
+        match self {
+            Backend::Local(local) => local.add_device(code).await,
+            Backend::Remote(client) => client.call_as("devices.add", json!({ "code": code })).await,
+        }
+    }
+    pub async fn remove_device(&mut self, device_id: u32) -> anyhow::Result<()> {
+        match self {
+            Backend::Local(local) => local.remove_device(device_id),
+            Backend::Remote(client) => client.call_as("devices.remove", json!({ "device_id": device_id })).await,
+        }
+    }
+    pub async fn groups(&mut self) -> anyhow::Result<Vec<GroupView>> {
+        match self {
+            Backend::Local(local) => local.groups(),
+            Backend::Remote(client) => client.call_as("groups.list", json!({})).await,
+        }
+    }
+    pub async fn create_group(&mut self, name: &str, members: &[String]) -> anyhow::Result<GroupView> {
+        match self {
+            Backend::Local(local) => local.create_group(name, members).await,
+            Backend::Remote(client) => client.call_as("groups.create", json!({ "name": name, "members": members })).await,
+        }
+    }
+    pub async fn add_member(&mut self, group: &str, member: &str) -> anyhow::Result<GroupView> {
+        match self {
+            Backend::Local(local) => local.add_member(group, member).await,
+            Backend::Remote(client) => client.call_as("groups.add", json!({ "group": group, "member": member })).await,
+        }
+    }
+    pub async fn remove_member(&mut self, group: &str, member: &str) -> anyhow::Result<GroupView> {
+        match self {
+            Backend::Local(local) => local.remove_member(group, member).await,
+            Backend::Remote(client) => client.call_as("groups.remove", json!({ "group": group, "member": member })).await,
+        }
+    }
+    pub async fn leave_group(&mut self, group: &str) -> anyhow::Result<usize> {
+        match self {
+            Backend::Local(local) => local.leave_group(group).await,
+            Backend::Remote(client) => client.call_as("groups.leave", json!({ "group": group })).await,
+        }
+    }
+    pub async fn address_bundle(&mut self, label: &str, relays: Vec<String>) -> anyhow::Result<String> {
+        match self {
+            Backend::Local(local) => local.address_bundle(label, relays),
+            Backend::Remote(client) => {
+                client.call_as("addresses.bundle", json!({ "label": label, "relays": relays })).await
+            }
+        }
+    }
+}
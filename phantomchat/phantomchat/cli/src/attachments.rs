@@ -0,0 +1,137 @@
+//! Anhänge des CLI.
+//!
+//! `send --file` verschlüsselt die Datei (siehe
+//! [`phantomchat_core::attachment`]), legt ihre Blöcke auf den Relays ab
+//! und schickt danach das Manifest als [`phantomchat_core::Content::Attachment`].
+//! `fetch` schreibt die Blöcke in `<ziel>.part` und benennt die Datei erst
+//! um, wenn ihr Hash stimmt.  Bricht ein Download ab, setzt der nächste
+//! Aufruf fort: Blöcke, die in der `.part`‑Datei bereits zum Blockhash
+//! passen, werden nicht erneut geladen.
+
+use crate::output::{fail, ErrorKind};
+use futures::stream::{self, StreamExt, TryStreamExt};
+use phantomchat_core::attachment::{self, CHUNK_SIZE, MAX_SIZE};
+use phantomchat_core::{Chunk, Manifest};
+use phantomchat_relays::BridgeProvider;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+/// Lebensdauer der Blöcke auf den Relays in Sekunden.  Deutlich länger
+/// als bei Nachrichten, weil der Empfänger die Datei erst später lädt.
+pub const ATTACHMENT_TTL: u32 = 7 * 24 * 3600;
+/// Gleichzeitig hochgeladene Blöcke.
+const UPLOAD_PARALLEL: usize = 4;
+/// Blöcke je Abfrage beim Herunterladen.
+const FETCH_BATCH: usize = 32;
+/// Dateiname, wenn der des Absenders unbrauchbar ist.
+const FALLBACK_NAME: &str = "anhang";
+
+/// Ergebnis von `fetch`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fetched {
+    pub path: PathBuf,
+    pub size: u64,
+    pub chunks: usize,
+    /// Aus einem abgebrochenen Download übernommene Blöcke.
+    pub resumed: usize,
+}
+
+/// Liest eine Datei und verschlüsselt sie zu Manifest und Blöcken.
+pub fn seal(path: &Path, ts: u64, pow_bits: u32) -> anyhow::Result<(Manifest, Vec<Chunk>)> {
+    let size = fs::metadata(path).map_err(|e| fail(ErrorKind::NotFound, format!("{}: {e}", path.display())))?.len();
+    if size > MAX_SIZE {
+        return Err(fail(ErrorKind::InvalidInput, format!("{} ist zu groß (höchstens {} MiB)", path.display(), MAX_SIZE >> 20)));
+    }
+    let data = fs::read(path)?;
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_else(|| FALLBACK_NAME.into());
+    Ok(attachment::seal(&name, &data, ts, ATTACHMENT_TTL, pow_bits)?)
+}
+
+/// Legt alle Blöcke auf den Relays ab.
+pub async fn upload<P: BridgeProvider>(relay: &P, chunks: Vec<Chunk>) -> anyhow::Result<()> {
+    stream::iter(chunks.into_iter().enumerate())
+        .map(|(index, chunk)| async move {
+            relay
+                .publish_chunk(chunk)
+                .await
+                .map_err(|e| fail(ErrorKind::Network, format!("Block {index} nicht hochgeladen: {e:#}")))
+        })
+        .buffer_unordered(UPLOAD_PARALLEL)
+        .try_collect::<Vec<()>>()
+        .await?;
+    Ok(())
+}
+
+/// Zielpfad: ein Verzeichnis erhält den Dateinamen aus dem Manifest.
+pub fn target(manifest: &Manifest, out: &Path) -> PathBuf {
+    if !out.is_dir() {
+        return out.to_owned();
+    }
+    // Nur den letzten Pfadbestandteil übernehmen, nie `..` oder absolute Pfade
+    let name = Path::new(&manifest.name).file_name().filter(|n| !n.is_empty()).map(PathBuf::from);
+    out.join(name.unwrap_or_else(|| FALLBACK_NAME.into()))
+}
+
+/// Lädt die Datei nach `path`, setzt einen abgebrochenen Download fort und
+/// prüft Block‑ und Dateihashes.  Ein Manifest, dessen Blockliste nicht
+/// zur Größe passt, wird vor dem ersten Dateizugriff abgelehnt.
+pub async fn fetch<P: BridgeProvider>(relay: &P, manifest: &Manifest, path: &Path, now_ms: u64) -> anyhow::Result<Fetched> {
+    manifest.validate().map_err(|e| fail(ErrorKind::InvalidInput, format!("ungültiges Manifest: {e}")))?;
+    if path.exists() {
+        return Err(fail(ErrorKind::InvalidInput, format!("{} existiert bereits", path.display())));
+    }
+    let part = part_path(path);
+    let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&part)?;
+    file.set_len(manifest.size)?;
+
+    let mut missing = Vec::new();
+    let mut block = vec![0u8; CHUNK_SIZE];
+    for index in 0..manifest.chunks.len() {
+        let block = &mut block[..manifest.block_len(index)];
+        file.seek(SeekFrom::Start(offset(index)))?;
+        file.read_exact(block)?;
+        if !manifest.has_block(index, block) {
+            missing.push(index);
+        }
+    }
+    let resumed = manifest.chunks.len() - missing.len();
+
+    for batch in missing.chunks(FETCH_BATCH) {
+        let ids: Vec<[u8; 32]> = batch.iter().map(|&i| manifest.chunks[i]).collect();
+        let found = relay.fetch_chunks(&ids).await.map_err(|e| fail(ErrorKind::Network, format!("{e:#}")))?;
+        for &index in batch {
+            let Some(chunk) = found.iter().find(|c| c.id() == manifest.chunks[index]) else {
+                let reason = if now_ms > manifest.expires_at { "; der Anhang ist abgelaufen" } else { "" };
+                return Err(fail(ErrorKind::NotFound, format!("Block {index} auf keinem Relay gefunden{reason}")));
+            };
+            let plain = manifest.open_chunk(index, &chunk.data)?;
+            file.seek(SeekFrom::Start(offset(index)))?;
+            file.write_all(&plain)?;
+        }
+        file.sync_data()?;
+    }
+
+    file.seek(SeekFrom::Start(0))?;
+    let mut data = Vec::with_capacity(manifest.size as usize);
+    file.read_to_end(&mut data)?;
+    drop(file);
+    if let Err(e) = manifest.verify(&data) {
+        // Alle Blöcke waren gültig; die Datei passt also nicht zum Manifest
+        fs::remove_file(&part)?;
+        return Err(e.into());
+    }
+    fs::rename(&part, path)?;
+    Ok(Fetched { path: path.to_owned(), size: manifest.size, chunks: manifest.chunks.len(), resumed })
+}
+
+fn offset(index: usize) -> u64 {
+    (index * CHUNK_SIZE) as u64
+}
+
+fn part_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".part");
+    PathBuf::from(name)
+}
@@ -0,0 +1,216 @@
+//! Konfigurationsdatei und Profile.
+//!
+//! Die Konfiguration liegt als TOML unter
+//! `$XDG_CONFIG_HOME/phantomchat/config.toml` und enthält benannte
+//! Profile, etwa für eine dienstliche und eine private Identität.  Jedes
+//! Profil hat einen eigenen Schlüsselspeicher, eine eigene Datenbank und
+//! einen eigenen Daemon‑Socket; gewählt wird es mit `--profile` bzw.
+//! `PHANTOMCHAT_PROFILE`, sonst gilt `default_profile` oder `default`.
+//!
+//! ```toml
+//! default_profile = "privat"
+//!
+//! [profiles.privat]
+//! relays = ["wss://relay.example.org"]
+//! tor = true
+//!
+//! [profiles.arbeit]
+//! relays = ["wss://relay.firma.example"]
+//! keystore = "~/arbeit/keys.json"
+//! padding = { block = 512 }
+//! pow_bits = 18
+//! ttl = 120
+//! address_rotation_days = 30
+//! ```
+//!
+//! Nicht angegebene Pfade folgen den XDG‑Verzeichnissen:
+//! Schlüsselspeicher und Datenbank unter
+//! `$XDG_DATA_HOME/phantomchat/<profil>/`, der Socket unter
+//! `$XDG_RUNTIME_DIR/phantomchat-<profil>.sock`.  Kommandozeilenoptionen
+//! (`--file`, `--db`, `--relay`, `--tor`, `--socket`) haben Vorrang vor dem
+//! Profil.
+
+use crate::addresses::AddressPolicy;
+use crate::net::NetOptions;
+use crate::outgoing::{SendPolicy, MESSAGE_TTL};
+use crate::output::{fail, ErrorKind};
+use crate::receive::MIN_POW_BITS;
+use anyhow::Context;
+use directories::ProjectDirs;
+use phantomchat_core::PaddingPolicy;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+
+/// Name des Profils, wenn weder Option noch Konfiguration eines wählen.
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Umgebungsvariable zur Profilwahl.
+pub const PROFILE_ENV: &str = "PHANTOMCHAT_PROFILE";
+
+/// Inhalt der Konfigurationsdatei.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub default_profile: Option<String>,
+    #[serde(default)]
+    pub profiles: BTreeMap<String, ProfileConfig>,
+}
+
+/// Ein Profil, wie es in der Datei steht.  Fehlende Werte werden beim
+/// Auflösen ergänzt.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfileConfig {
+    pub keystore: Option<PathBuf>,
+    pub db: Option<PathBuf>,
+    pub socket: Option<PathBuf>,
+    #[serde(default)]
+    pub relays: Vec<String>,
+    #[serde(default)]
+    pub tor: bool,
+    /// SOCKS5‑Adresse des Tor‑Clients (Standard `127.0.0.1:9050`).
+    pub tor_proxy: Option<SocketAddr>,
+    pub padding: Option<Padding>,
+    /// Proof‑of‑Work ausgehender Envelopes in Bit.
+    pub pow_bits: Option<u32>,
+    /// Mindest‑Proof‑of‑Work eingehender Envelopes in Bit.
+    pub min_pow_bits: Option<u32>,
+    /// TTL ausgehender Envelopes in Sekunden.
+    pub ttl: Option<u32>,
+    /// Subadressen nach so vielen Tagen rotieren (Standard: nie).
+    pub address_rotation_days: Option<u32>,
+    /// Überlappung nach einer Rotation in Tagen (Standard 7).
+    pub address_overlap_days: Option<u32>,
+}
+
+/// Padding‑Regel in der Konfiguration: `"none"`, `"default"`,
+/// `{ block = 512 }` oder `{ buckets = [256, 1024, 4096] }`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Padding {
+    None,
+    Default,
+    Block(usize),
+    Buckets(Vec<usize>),
+}
+
+impl From<&Padding> for PaddingPolicy {
+    fn from(padding: &Padding) -> Self {
+        match padding {
+            Padding::None => PaddingPolicy::None,
+            Padding::Default => PaddingPolicy::default(),
+            Padding::Block(block) => PaddingPolicy::Block(*block),
+            Padding::Buckets(buckets) => PaddingPolicy::Buckets(buckets.clone()),
+        }
+    }
+}
+
+/// Ein aufgelöstes Profil mit allen Pfaden und Voreinstellungen.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub name: String,
+    pub keystore: PathBuf,
+    pub db: PathBuf,
+    pub socket: PathBuf,
+    pub net: NetOptions,
+    pub send: SendPolicy,
+    pub min_pow_bits: u32,
+}
+
+/// Standardpfad der Konfigurationsdatei.
+pub fn default_path() -> Option<PathBuf> {
+    dirs().map(|d| d.config_dir().join("config.toml"))
+}
+
+fn dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "phantomchat")
+}
+
+impl Config {
+    /// Lädt die Konfiguration.  Fehlt die Datei am Standardpfad, gilt eine
+    /// leere Konfiguration; eine ausdrücklich angegebene Datei muss
+    /// existieren.
+    pub fn load(path: Option<&Path>) -> anyhow::Result<Self> {
+        let (path, explicit) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => match default_path() {
+                Some(path) => (path, false),
+                None => return Ok(Self::default()),
+            },
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !explicit => return Ok(Self::default()),
+            Err(e) => return Err(e).with_context(|| format!("Konfiguration {:?} nicht lesbar", path)),
+        };
+        toml::from_str(&text)
+            .map_err(|e| fail(ErrorKind::InvalidInput, format!("Konfiguration {:?} ungültig: {e}", path)))
+    }
+
+    /// Name des zu verwendenden Profils.
+    pub fn selected(&self, requested: Option<&str>) -> String {
+        requested
+            .map(str::to_owned)
+            .or_else(|| std::env::var(PROFILE_ENV).ok().filter(|p| !p.is_empty()))
+            .or_else(|| self.default_profile.clone())
+            .unwrap_or_else(|| DEFAULT_PROFILE.to_owned())
+    }
+
+    /// Löst ein Profil auf.  Das Profil `default` existiert auch ohne
+    /// Eintrag in der Datei.
+    pub fn profile(&self, name: &str) -> anyhow::Result<Profile> {
+        validate_name(name)?;
+        let config = match self.profiles.get(name) {
+            Some(config) => config.clone(),
+            None if name == DEFAULT_PROFILE => ProfileConfig::default(),
+            None => return Err(fail(ErrorKind::NotFound, format!("Profil {name:?} ist nicht konfiguriert"))),
+        };
+        let data = data_dir(name)?;
+        Ok(Profile {
+            name: name.to_owned(),
+            keystore: config.keystore.map(expand).unwrap_or_else(|| data.join("keys.json")),
+            db: config.db.map(expand).unwrap_or_else(|| data.join("phantomchat.db")),
+            socket: config.socket.map(expand).unwrap_or_else(|| socket_path(name)),
+            net: NetOptions { relays: config.relays, tor: config.tor, tor_proxy: config.tor_proxy },
+            send: SendPolicy {
+                ttl: config.ttl.unwrap_or(MESSAGE_TTL),
+                pow_bits: config.pow_bits.unwrap_or(MIN_POW_BITS),
+                padding: config.padding.as_ref().map(PaddingPolicy::from).unwrap_or_default(),
+                addresses: AddressPolicy::from_days(config.address_rotation_days, config.address_overlap_days),
+            },
+            min_pow_bits: config.min_pow_bits.unwrap_or(MIN_POW_BITS),
+        })
+    }
+}
+
+/// Profilnamen werden Teil von Pfaden, daher nur einfache Zeichen.
+fn validate_name(name: &str) -> anyhow::Result<()> {
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(fail(ErrorKind::InvalidInput, format!("ungültiger Profilname {name:?} (erlaubt: a–z, 0–9, -, _)")));
+    }
+    Ok(())
+}
+
+/// Datenverzeichnis eines Profils.
+fn data_dir(profile: &str) -> anyhow::Result<PathBuf> {
+    let dirs = dirs().ok_or_else(|| fail(ErrorKind::General, "kein Home‑Verzeichnis gefunden"))?;
+    Ok(dirs.data_dir().join(profile))
+}
+
+/// Socket des Daemons eines Profils.
+fn socket_path(profile: &str) -> PathBuf {
+    let dir = directories::BaseDirs::new()
+        .and_then(|d| d.runtime_dir().map(Path::to_path_buf))
+        .unwrap_or_else(std::env::temp_dir);
+    dir.join(format!("phantomchat-{profile}.sock"))
+}
+
+/// Ersetzt ein führendes `~/` durch das Home‑Verzeichnis.
+fn expand(path: PathBuf) -> PathBuf {
+    match (path.strip_prefix("~"), directories::BaseDirs::new()) {
+        (Ok(rest), Some(base)) => base.home_dir().join(rest),
+        _ => path,
+    }
+}
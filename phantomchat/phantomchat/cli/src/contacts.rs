@@ -0,0 +1,290 @@
+//! Kontaktbuch des CLI.
+//!
+//! Ein Kontakt bündelt die öffentlichen Schlüssel eines Gegenübers unter
+//! einem Spitznamen, so dass Befehle wie `send --to alice` keine rohen
+//! Schlüssel mehr brauchen.  Neben View‑, Spend‑ und Identity‑Key wird der
+//! beim ersten Kontakt gesehene Identity‑Key festgehalten (Trust on First
+//! Use); der Verifikationsstatus zeigt, ob die Schlüssel persönlich
+//! geprüft wurden.  Gespeichert werden Kontakte verschlüsselt in der
+//! lokalen Datenbank.
+//!
+//! Gibt ein Kontakt uns eine Subadresse statt seiner Hauptadresse, steht
+//! deren Basispunkt in `base`.  Neue Adressen, die er im Ratchet‑Header
+//! mitschickt, werden übernommen (siehe [`Contact::set_address`]); die
+//! Konversation behält dabei ihre ID.
+//!
+//! Nutzt ein Kontakt mehrere Geräte, steht seine signierte Geräteliste in
+//! `device_list`; sie wird nur durch neuere Listen derselben Identität
+//! ersetzt (siehe [`crate::devices`]).
+
+use crate::output::{fail, ErrorKind};
+use crate::store::Store;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use phantomchat_core::device::PRIMARY_DEVICE;
+use phantomchat_core::util::to_hex;
+use phantomchat_core::{DeviceList, PairingBundle, Subaddress};
+use serde::{Deserialize, Serialize};
+use x25519_dalek::PublicKey;
+
+/// Verifikationsstatus eines Kontakts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Verification {
+    /// Schlüssel wurden noch nicht persönlich geprüft.
+    Unverified,
+    /// Schlüssel wurden geprüft.
+    Verified,
+    /// Der Identity‑Key weicht vom zuerst gesehenen ab.
+    KeyChanged,
+}
+
+impl Verification {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Verification::Unverified => "unverifiziert",
+            Verification::Verified => "verifiziert",
+            Verification::KeyChanged => "SCHLÜSSEL GEÄNDERT",
+        }
+    }
+}
+
+/// Ergebnis von [`Contact::update_from`] bzw. [`add`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KeyUpdate {
+    /// Neuer Kontakt.
+    Added,
+    Unchanged,
+    /// Neue View‑/Spend‑Keys, signiert mit dem bekannten Identity‑Key.
+    Updated,
+    /// Identity‑Key geändert oder unsignierte Schlüsseländerung.
+    Changed,
+}
+
+/// Ein Eintrag im Kontaktbuch.  Schlüssel werden hex‑kodiert gespeichert.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Contact {
+    pub nickname: String,
+    pub view_pub: String,
+    pub spend_pub: String,
+    pub identity_pub: String,
+    pub verification: Verification,
+    /// Beim ersten Hinzufügen gesehener Identity‑Key.
+    pub first_seen_identity: String,
+    pub added_at: u64,
+    /// Relay‑Hinweise aus dem Pairing‑Bundle.
+    #[serde(default)]
+    pub relays: Vec<String>,
+    /// ML‑KEM‑Schlüssel aus dem Pairing‑Bundle (hex), falls das Gegenüber
+    /// den hybriden Schlüsselaustausch unterstützt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kem_pub: Option<String>,
+    /// Basispunkt (hex), wenn `spend_pub` zu einer Subadresse gehört.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+    /// Konversations‑ID aus der Zeit vor einem Adresswechsel.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conversation: Option<String>,
+    /// Signierte Geräteliste des Kontakts (hex).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_list: Option<String>,
+    /// Version unserer Geräteliste, die der Kontakt zuletzt erhalten hat.
+    #[serde(default)]
+    pub devices_announced: u64,
+}
+
+impl Contact {
+    /// Legt einen neuen, unverifizierten Kontakt an.
+    pub fn new(nickname: &str, view_pub: [u8; 32], spend_pub: [u8; 32], identity_pub: [u8; 32], now: u64) -> Self {
+        Self {
+            nickname: nickname.to_owned(),
+            view_pub: to_hex(&view_pub),
+            spend_pub: to_hex(&spend_pub),
+            identity_pub: to_hex(&identity_pub),
+            verification: Verification::Unverified,
+            first_seen_identity: to_hex(&identity_pub),
+            added_at: now,
+            relays: Vec::new(),
+            kem_pub: None,
+            base: None,
+            conversation: None,
+            device_list: None,
+            devices_announced: 0,
+        }
+    }
+    /// Legt einen Kontakt aus einem geprüften Pairing‑Bundle an.
+    pub fn from_bundle(nickname: &str, bundle: &PairingBundle, now: u64) -> Self {
+        let mut contact = Self::new(nickname, bundle.view_pub, bundle.spend_pub, bundle.identity_pub, now);
+        contact.relays = bundle.relays.clone();
+        contact.kem_pub = bundle.kem_pub.as_deref().map(to_hex);
+        contact.base = bundle.base.as_ref().map(|base| to_hex(base));
+        contact
+    }
+    /// Übernimmt die Schlüssel eines erneut hinzugefügten Kontakts.
+    /// Ein anderer Identity‑Key markiert den Kontakt als
+    /// [`Verification::KeyChanged`]; neue View‑/Spend‑Keys gelten nur dann
+    /// als unbedenklich, wenn sie aus einem mit dem bekannten Identity‑Key
+    /// signierten Bundle stammen (`signed`).  Relay‑Hinweise und
+    /// KEM‑Schlüssel werden nur aus signierten Bundles übernommen.  Nur
+    /// bei unbedenklichen Änderungen bleibt die Konversation erhalten.
+    pub fn update_from(&mut self, newer: &Contact, signed: bool) -> KeyUpdate {
+        let same_identity = self.identity_pub == newer.identity_pub;
+        let same_keys = same_identity
+            && self.view_pub == newer.view_pub
+            && self.spend_pub == newer.spend_pub
+            && self.base == newer.base;
+        if signed {
+            self.relays = newer.relays.clone();
+            self.kem_pub = newer.kem_pub.clone();
+        }
+        if same_keys {
+            return KeyUpdate::Unchanged;
+        }
+        let conversation = self.conversation_id();
+        self.identity_pub = newer.identity_pub.clone();
+        self.view_pub = newer.view_pub.clone();
+        self.spend_pub = newer.spend_pub.clone();
+        self.base = newer.base.clone();
+        if same_identity && signed {
+            self.conversation = Some(conversation);
+            return KeyUpdate::Updated;
+        }
+        self.conversation = None;
+        if !same_identity {
+            self.device_list = None;
+            self.devices_announced = 0;
+        }
+        self.verification = Verification::KeyChanged;
+        KeyUpdate::Changed
+    }
+    /// Übernimmt eine Adresse, die der Kontakt in einer authentischen
+    /// Nachricht mitgeschickt hat.  Gibt `true` zurück, wenn sie neu war.
+    pub fn set_address(&mut self, address: &Subaddress) -> bool {
+        let base = (!address.is_primary()).then(|| to_hex(&address.base));
+        let spend_pub = to_hex(&address.spend_pub);
+        if self.spend_pub == spend_pub && self.base == base {
+            return false;
+        }
+        self.conversation = Some(self.conversation_id());
+        self.spend_pub = spend_pub;
+        self.base = base;
+        true
+    }
+    /// ID der Konversation mit diesem Kontakt: der Spend‑Key der ersten
+    /// bekannten Adresse.
+    pub fn conversation_id(&self) -> String {
+        self.conversation.clone().unwrap_or_else(|| self.spend_pub.clone())
+    }
+    /// Adresse, an die wir senden.
+    pub fn address(&self) -> anyhow::Result<Subaddress> {
+        let spend_pub = self.spend_public()?;
+        Ok(match &self.base {
+            Some(base) => Subaddress { base: parse_key(base)?, spend_pub: *spend_pub.as_bytes() },
+            None => Subaddress::primary(&spend_pub),
+        })
+    }
+    /// Geräteliste, falls der Kontakt weitere Geräte verknüpft hat.
+    pub fn device_list(&self) -> anyhow::Result<Option<DeviceList>> {
+        self.device_list.as_deref().map(crate::devices::parse_list).transpose()
+    }
+    /// Übernimmt eine mitgeschickte Geräteliste, wenn sie zum Identity‑Key
+    /// gehört und neuer ist.  Gibt `true` zurück, wenn sie übernommen wurde.
+    pub fn set_device_list(&mut self, list: &DeviceList) -> anyhow::Result<bool> {
+        if list.identity_pub != self.identity_public()? || !list.replaces(self.device_list()?.as_ref()) {
+            return Ok(false);
+        }
+        self.device_list = Some(to_hex(&list.to_bytes()));
+        Ok(true)
+    }
+    /// Ob Gerät `device_id` im Namen des Kontakts senden darf.  Das
+    /// Primärgerät darf es immer.
+    pub fn has_device(&self, device_id: u32) -> anyhow::Result<bool> {
+        Ok(device_id == PRIMARY_DEVICE || self.device_list()?.is_some_and(|list| list.device(device_id).is_some()))
+    }
+    pub fn spend_public(&self) -> anyhow::Result<PublicKey> {
+        Ok(PublicKey::from(parse_key(&self.spend_pub)?))
+    }
+    pub fn identity_public(&self) -> anyhow::Result<[u8; 32]> {
+        parse_key(&self.identity_pub)
+    }
+    pub fn kem_public(&self) -> anyhow::Result<Option<Vec<u8>>> {
+        self.kem_pub
+            .as_deref()
+            .map(|hex| hex::decode(hex).map_err(|_| fail(ErrorKind::InvalidInput, "ungültiger KEM‑Schlüssel")))
+            .transpose()
+    }
+}
+
+/// Speichert einen neuen Kontakt.  Existiert der Spitzname bereits,
+/// werden die Schlüssel übernommen (siehe [`Contact::update_from`]);
+/// `signed` gibt an, ob sie aus einem signierten Pairing‑Bundle stammen.
+pub fn add(store: &Store, contact: Contact, signed: bool) -> anyhow::Result<KeyUpdate> {
+    validate_nickname(&contact.nickname)?;
+    if let Some(mut existing) = store.contact(&contact.nickname)? {
+        let update = existing.update_from(&contact, signed);
+        store.save_contact(&existing)?;
+        return Ok(update);
+    }
+    if let Some(other) = store.contact_for_conversation(&contact.conversation_id())? {
+        return Err(fail(ErrorKind::InvalidInput, format!("Diese Schlüssel gehören bereits zum Kontakt {:?}", other.nickname)));
+    }
+    store.save_contact(&contact)?;
+    // Eine bereits bestehende Konversation übernimmt den Spitznamen
+    if let Some(mut conv) = store.conversation(&contact.conversation_id())? {
+        conv.title = contact.nickname.clone();
+        store.save_conversation(&conv)?;
+    }
+    Ok(KeyUpdate::Added)
+}
+
+/// Markiert einen Kontakt nach erfolgreichem Vergleich als verifiziert.
+/// Der aktuelle Identity‑Key gilt danach als neue Vertrauensbasis.
+pub fn mark_verified(store: &Store, name: &str) -> anyhow::Result<()> {
+    let mut contact = store.contact(name)?.ok_or_else(|| fail(ErrorKind::NotFound, format!("Kontakt {name:?} unbekannt")))?;
+    contact.verification = Verification::Verified;
+    contact.first_seen_identity = contact.identity_pub.clone();
+    store.save_contact(&contact)
+}
+
+/// Prüft einen Spitznamen: nicht leer, keine Leer‑ oder Steuerzeichen.
+pub fn validate_nickname(name: &str) -> anyhow::Result<()> {
+    if name.is_empty() || name.len() > 64 {
+        return Err(fail(ErrorKind::InvalidInput, "Spitzname muss 1–64 Zeichen lang sein"));
+    }
+    if name.chars().any(|c| c.is_whitespace() || c.is_control()) {
+        return Err(fail(ErrorKind::InvalidInput, "Spitzname darf keine Leer‑ oder Steuerzeichen enthalten"));
+    }
+    Ok(())
+}
+
+/// Liest ein Pairing‑Bundle aus `source`: einer Bilddatei mit QR‑Code,
+/// einer Textdatei oder direkt aus der Textform.
+pub fn read_bundle(source: &str) -> anyhow::Result<PairingBundle> {
+    let path = std::path::Path::new(source);
+    let text = if path.is_file() {
+        let is_image = matches!(
+            path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref(),
+            Some("png" | "jpg" | "jpeg")
+        );
+        if is_image {
+            crate::qr::decode_image(path)?
+        } else {
+            std::fs::read_to_string(path)?
+        }
+    } else {
+        source.to_owned()
+    };
+    Ok(PairingBundle::from_text(&text)?)
+}
+
+/// Liest einen 32‑Byte‑Schlüssel, wahlweise hex‑ (64 Zeichen) oder
+/// Base64‑kodiert, so dass die Ausgabe von `pair` direkt übernommen werden
+/// kann.
+pub fn parse_key(input: &str) -> anyhow::Result<[u8; 32]> {
+    let input = input.trim();
+    let bytes = if input.len() == 64 && input.chars().all(|c| c.is_ascii_hexdigit()) {
+        hex::decode(input)?
+    } else {
+        BASE64.decode(input).map_err(|_| fail(ErrorKind::InvalidInput, format!("Schlüssel ist weder Hex noch Base64: {input:?}")))?
+    };
+    bytes.as_slice().try_into().map_err(|_| fail(ErrorKind::InvalidInput, format!("Schlüssel muss 32 Byte lang sein, nicht {}", bytes.len())))
+}
@@ -0,0 +1,442 @@
+//! Lokaler Daemon (`phantomchat daemon`).
+//!
+//! Der Daemon hält den entsperrten Schlüsselspeicher, die Datenbank, den
+//! Relay‑Pool und damit auch die Ratchet‑Sessions dauerhaft offen,
+//! empfängt laufend Nachrichten und stellt die Operationen aus
+//! [`crate::api`] per JSON‑RPC auf einem Unix‑Domain‑Socket bereit
+//! (Protokoll siehe [`crate::rpc`]).  Der Socket ist nur für den
+//! eigenen Benutzer zugänglich (Modus 0600); wer ihn erreicht, handelt im
+//! Namen der entsperrten Identität.
+
+use crate::api::{received_view, Local, Recipient};
+use crate::contacts::Contact;
+use crate::output::{fail, ErrorKind, Output};
+use crate::receive::Outcome;
+use crate::rpc::{self, Request, RpcError};
+use crate::store;
+use anyhow::Context;
+use phantomchat_core::util::to_hex;
+use phantomchat_core::PairingBundle;
+use phantomchat_relays::BridgeProvider;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::broadcast;
+
+/// Puffer für Notifications an langsame Abonnenten.
+const EVENT_BUFFER: usize = 256;
+/// Intervall, in dem abgelaufene Nachrichten gelöscht werden.
+const PURGE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Parameter von `send`.
+#[derive(Deserialize)]
+struct SendParams {
+    to: Recipient,
+    message: String,
+    /// `msg_id` der beantworteten Nachricht (Dezimalstring).
+    #[serde(default)]
+    reply_to: Option<String>,
+}
+
+/// Parameter von `react`.
+#[derive(Deserialize)]
+struct ReactParams {
+    to: Recipient,
+    msg_id: String,
+    emoji: String,
+    #[serde(default)]
+    remove: bool,
+}
+
+/// Parameter von `edit` und `delete`.
+#[derive(Deserialize)]
+struct TargetParams {
+    to: Recipient,
+    msg_id: String,
+    #[serde(default)]
+    message: String,
+}
+
+/// Parameter von `attachments.send`.
+#[derive(Deserialize)]
+struct AttachParams {
+    to: Recipient,
+    path: PathBuf,
+    #[serde(default)]
+    caption: String,
+}
+
+/// Parameter von `attachments.fetch`.
+#[derive(Deserialize)]
+struct FetchParams {
+    conversation: String,
+    msg_id: String,
+    out: PathBuf,
+}
+
+/// Parameter von `timer`.
+#[derive(Deserialize)]
+struct TimerParams {
+    to: Recipient,
+    seconds: u32,
+}
+
+#[derive(Deserialize)]
+struct ConversationParams {
+    conversation: String,
+}
+
+#[derive(Deserialize)]
+struct NameParams {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct AddParams {
+    contact: Contact,
+    #[serde(default)]
+    signed: bool,
+}
+
+#[derive(Deserialize)]
+struct ImportParams {
+    name: String,
+    bundle: String,
+}
+
+#[derive(Deserialize)]
+struct LabelParams {
+    label: String,
+}
+
+#[derive(Deserialize)]
+struct BundleParams {
+    label: String,
+    #[serde(default)]
+    relays: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct CodeParams {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct DeviceParams {
+    device_id: u32,
+}
+
+#[derive(Deserialize)]
+struct GroupCreateParams {
+    name: String,
+    #[serde(default)]
+    members: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct MemberParams {
+    group: String,
+    member: String,
+}
+
+#[derive(Deserialize)]
+struct GroupParams {
+    group: String,
+}
+
+struct Daemon {
+    local: Local,
+    events: broadcast::Sender<Value>,
+}
+
+/// Startet den Daemon und bedient den Socket bis Strg+C.
+pub async fn run(local: Local, socket: &Path, out: Output) -> anyhow::Result<()> {
+    let listener = bind(socket)?;
+    let (events, _) = broadcast::channel(EVENT_BUFFER);
+    let daemon = Arc::new(Daemon { local, events });
+
+    // Empfang: erst nachladen, dann laufend verarbeiten und an
+    // Abonnenten weiterreichen.
+    if let Some(pool) = &daemon.local.pool {
+        let synced = daemon.local.sync().await?;
+        for msg in synced.messages {
+            let _ = daemon.events.send(rpc::notification("message", json!(msg)));
+        }
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+        pool.subscribe(move |env| {
+            let _ = tx.send(env);
+        })
+        .await?;
+        let receiver = daemon.clone();
+        tokio::spawn(async move {
+            while let Some(env) = rx.recv().await {
+                match receiver.local.handle(&env) {
+                    Ok(Outcome::Received(msg)) => {
+                        let _ = receiver.local.store.set_meta("last_sync", &store::now_ms());
+                        let _ = receiver.local.store.flush();
+                        let _ = receiver.events.send(rpc::notification("message", json!(received_view(msg))));
+                    }
+                    Ok(Outcome::Signal(msg)) => {
+                        // Neue Gruppen oder Epochen brauchen eigene Sender‑Keys
+                        if let Err(e) = receiver.local.distribute_groups().await {
+                            eprintln!("Gruppenschlüssel nicht verteilt: {e:#}");
+                        }
+                        let _ = receiver.local.store.flush();
+                        let _ = receiver.events.send(rpc::notification("signal", json!(received_view(msg))));
+                    }
+                    Ok(_) => {}
+                    Err(e) => eprintln!("Fehler beim Verarbeiten eines Envelopes: {e:#}"),
+                }
+            }
+        });
+    }
+
+    out.emit(&json!({ "event": "listening", "socket": socket }), |_| {
+        println!("Daemon lauscht auf {:?}", socket)
+    })?;
+    let mut purge = tokio::time::interval(PURGE_INTERVAL);
+    loop {
+        tokio::select! {
+            _ = purge.tick() => {
+                if let Err(e) = daemon.local.store.purge_expired(store::now_ms()) {
+                    eprintln!("Abgelaufene Nachrichten nicht gelöscht: {e:#}");
+                }
+                if let Err(e) = daemon.local.distribute_groups().await {
+                    eprintln!("Gruppenschlüssel nicht verteilt: {e:#}");
+                }
+            }
+            accepted = listener.accept() => {
+                let (stream, _) = accepted?;
+                let daemon = daemon.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = daemon.serve(stream).await {
+                        eprintln!("Verbindung beendet: {e:#}");
+                    }
+                });
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    let _ = std::fs::remove_file(socket);
+    daemon.local.store.flush()
+}
+
+/// Bindet den Socket.  Ein verwaister Socket einer beendeten Instanz wird
+/// entfernt; läuft bereits ein Daemon, bricht der Start ab.
+fn bind(socket: &Path) -> anyhow::Result<UnixListener> {
+    if socket.exists() {
+        if std::os::unix::net::UnixStream::connect(socket).is_ok() {
+            return Err(fail(ErrorKind::Daemon, format!("auf {:?} läuft bereits ein Daemon", socket)));
+        }
+        std::fs::remove_file(socket)?;
+    }
+    let listener = UnixListener::bind(socket).with_context(|| format!("Socket {:?} nicht zu öffnen", socket))?;
+    std::fs::set_permissions(socket, std::fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+impl Daemon {
+    /// Bedient eine Verbindung: eine Anfrage pro Zeile, nach `subscribe`
+    /// zusätzlich Notifications.
+    async fn serve(self: Arc<Self>, stream: UnixStream) -> anyhow::Result<()> {
+        let (read, mut write) = stream.into_split();
+        let mut lines = BufReader::new(read).lines();
+        let mut events: Option<broadcast::Receiver<Value>> = None;
+        loop {
+            let outgoing = tokio::select! {
+                line = lines.next_line() => {
+                    let Some(line) = line? else { return Ok(()) };
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    match self.request(&line, &mut events).await {
+                        Some(response) => response,
+                        None => continue,
+                    }
+                }
+                event = recv_event(&mut events) => match event {
+                    Some(event) => event,
+                    None => continue,
+                },
+            };
+            let mut bytes = serde_json::to_vec(&outgoing)?;
+            bytes.push(b'\n');
+            write.write_all(&bytes).await?;
+        }
+    }
+
+    /// Verarbeitet eine Anfragezeile.  Notifications des Clients (ohne
+    /// `id`) werden ausgeführt, aber nicht beantwortet.
+    async fn request(&self, line: &str, events: &mut Option<broadcast::Receiver<Value>>) -> Option<Value> {
+        let request: Request = match serde_json::from_str::<Value>(line) {
+            Err(e) => return Some(rpc::response(Value::Null, Err(RpcError::new(rpc::PARSE_ERROR, e.to_string())))),
+            Ok(value) => match serde_json::from_value(value) {
+                Ok(request) => request,
+                Err(e) => {
+                    return Some(rpc::response(Value::Null, Err(RpcError::new(rpc::INVALID_REQUEST, e.to_string()))))
+                }
+            },
+        };
+        let result = if request.jsonrpc != "2.0" {
+            Err(RpcError::new(rpc::INVALID_REQUEST, "jsonrpc muss \"2.0\" sein"))
+        } else if request.method == "subscribe" {
+            *events = Some(self.events.subscribe());
+            Ok(json!(true))
+        } else {
+            self.dispatch(&request.method, request.params).await
+        };
+        request.id.map(|id| rpc::response(id, result))
+    }
+
+    async fn dispatch(&self, method: &str, params: Value) -> Result<Value, RpcError> {
+        let local = &self.local;
+        match method {
+            "status" => Ok(json!({
+                "identity": to_hex(&local.keys.identity.public),
+                "relays": local.relays().await,
+            })),
+            "send" => {
+                let p: SendParams = parse(params)?;
+                let reply_to = p.reply_to.as_deref().map(parse_msg_id).transpose()?;
+                reply(local.send(&p.to, &p.message, reply_to).await)
+            }
+            "react" => {
+                let p: ReactParams = parse(params)?;
+                reply(local.react(&p.to, parse_msg_id(&p.msg_id)?, &p.emoji, p.remove).await)
+            }
+            "edit" => {
+                let p: TargetParams = parse(params)?;
+                reply(local.edit(&p.to, parse_msg_id(&p.msg_id)?, &p.message).await)
+            }
+            "delete" => {
+                let p: TargetParams = parse(params)?;
+                reply(local.delete(&p.to, parse_msg_id(&p.msg_id)?).await)
+            }
+            "timer" => {
+                let p: TimerParams = parse(params)?;
+                reply(local.set_timer(&p.to, p.seconds).await)
+            }
+            "attachments.send" => {
+                let p: AttachParams = parse(params)?;
+                reply(local.send_file(&p.to, absolute(&p.path)?, &p.caption).await)
+            }
+            "attachments.fetch" => {
+                let p: FetchParams = parse(params)?;
+                reply(local.fetch(&p.conversation, parse_msg_id(&p.msg_id)?, absolute(&p.out)?).await)
+            }
+            "sync" => reply(local.sync().await),
+            "conversations.list" => reply(local.conversations()),
+            "messages.list" => {
+                let p: ConversationParams = parse(params)?;
+                reply(local.messages(&p.conversation))
+            }
+            "messages.read" => {
+                let p: ConversationParams = parse(params)?;
+                reply(local.mark_read(&p.conversation).await)
+            }
+            "contacts.list" => reply(local.contacts()),
+            "contacts.add" => {
+                let p: AddParams = parse(params)?;
+                reply(local.add_contact(p.contact, p.signed))
+            }
+            "contacts.import" => {
+                let p: ImportParams = parse(params)?;
+                let bundle = PairingBundle::from_text(&p.bundle)
+                    .map_err(|e| RpcError::new(rpc::INVALID_PARAMS, e.to_string()))?;
+                reply(local.add_contact(Contact::from_bundle(&p.name, &bundle, store::now_ms()), true))
+            }
+            "contacts.remove" => {
+                let p: NameParams = parse(params)?;
+                reply(local.remove_contact(&p.name))
+            }
+            "contacts.fingerprint" => {
+                let p: NameParams = parse(params)?;
+                reply(local.fingerprint(&p.name))
+            }
+            "contacts.verify" => {
+                let p: NameParams = parse(params)?;
+                reply(local.verify_contact(&p.name))
+            }
+            "addresses.list" => reply(local.addresses()),
+            "addresses.rotate" => {
+                let p: LabelParams = parse(params)?;
+                reply(local.rotate_address(&p.label))
+            }
+            "addresses.bundle" => {
+                let p: BundleParams = parse(params)?;
+                reply(local.address_bundle(&p.label, p.relays))
+            }
+            "devices.list" => reply(local.devices()),
+            "devices.add" => {
+                let p: CodeParams = parse(params)?;
+                reply(local.add_device(&p.code).await)
+            }
+            "devices.remove" => {
+                let p: DeviceParams = parse(params)?;
+                reply(local.remove_device(p.device_id))
+            }
+            "groups.list" => reply(local.groups()),
+            "groups.create" => {
+                let p: GroupCreateParams = parse(params)?;
+                reply(local.create_group(&p.name, &p.members).await)
+            }
+            "groups.add" => {
+                let p: MemberParams = parse(params)?;
+                reply(local.add_member(&p.group, &p.member).await)
+            }
+            "groups.remove" => {
+                let p: MemberParams = parse(params)?;
+                reply(local.remove_member(&p.group, &p.member).await)
+            }
+            "groups.leave" => {
+                let p: GroupParams = parse(params)?;
+                reply(local.leave_group(&p.group).await)
+            }
+            _ => Err(RpcError::new(rpc::METHOD_NOT_FOUND, format!("unbekannte Methode {method:?}"))),
+        }
+    }
+}
+
+/// Wartet auf das nächste Ereignis, sofern abonniert.  Verpasste
+/// Ereignisse eines überlasteten Abonnenten werden übersprungen.
+async fn recv_event(events: &mut Option<broadcast::Receiver<Value>>) -> Option<Value> {
+    match events {
+        Some(rx) => loop {
+            match rx.recv().await {
+                Ok(event) => return Some(event),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        },
+        None => std::future::pending().await,
+    }
+}
+
+fn parse<T: DeserializeOwned>(params: Value) -> Result<T, RpcError> {
+    serde_json::from_value(params).map_err(|e| RpcError::new(rpc::INVALID_PARAMS, e.to_string()))
+}
+
+fn parse_msg_id(id: &str) -> Result<u128, RpcError> {
+    id.parse().map_err(|_| RpcError::new(rpc::INVALID_PARAMS, format!("ungültige msg_id {id:?}")))
+}
+
+/// Der Daemon hat ein anderes Arbeitsverzeichnis als der Client.
+fn absolute(path: &Path) -> Result<&Path, RpcError> {
+    if !path.is_absolute() {
+        return Err(RpcError::new(rpc::INVALID_PARAMS, format!("Pfad {} ist nicht absolut", path.display())));
+    }
+    Ok(path)
+}
+
+fn reply<T: serde::Serialize>(result: anyhow::Result<T>) -> Result<Value, RpcError> {
+    match result {
+        Ok(value) => serde_json::to_value(value).map_err(|e| RpcError::new(rpc::APPLICATION_ERROR, e.to_string())),
+        Err(e) => Err(RpcError::application(&e)),
+    }
+}
@@ -0,0 +1,273 @@
+//! Verknüpfte Geräte des CLI.
+//!
+//! Eine Identität kann mehrere Installationen haben (siehe
+//! [`phantomchat_core::device`]).  Jede hat eigene View‑, Spend‑ und
+//! KEM‑Schlüssel sowie eine eigene Datenbank; den Identity‑Key teilen sie.
+//! Die eigene Geräte‑ID und die signierte Geräteliste stehen in `meta`.
+//! Ohne Liste ist die Installation das einzige (Primär‑)Gerät.
+//!
+//! Ablauf: Auf dem neuen Gerät zeigt `phantomchat link` einen Link‑Code
+//! an und wartet.  Auf dem Primärgerät stellt `devices add <code>` das
+//! Zertifikat aus und schickt über die Relays den Identity‑Key, die
+//! Geräteliste und das Kontaktbuch.  Kontakte erfahren die neue Liste mit
+//! der nächsten Nachricht (Ratchet‑Header Typ 4).
+//!
+//! Beim Senden erhält jedes Gerät des Empfängers ein eigenes Envelope mit
+//! eigener Session ([`session_id`]); die eigenen übrigen Geräte bekommen
+//! eine Abschrift ([`SyncMessage`]) über die Konversation
+//! [`SELF_CONVERSATION`].
+
+use crate::contacts::Contact;
+use crate::keystore::Keys;
+use crate::outgoing::{DeviceTarget, SendPolicy};
+use crate::output::{fail, ErrorKind};
+use crate::store::Store;
+use phantomchat_core::device::PRIMARY_DEVICE;
+use phantomchat_core::util::{from_hex, to_hex};
+use phantomchat_core::{DeviceCertificate, DeviceList, Envelope, LinkRequest, Provisioning};
+use phantomchat_relays::BridgeProvider;
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Konversation der eigenen Geräte untereinander.
+pub const SELF_CONVERSATION: &str = "self";
+/// Mindest‑TTL der Provisioning‑Nachricht, damit das wartende Gerät sie
+/// auch bei langsamen Relays noch erhält.
+pub const LINK_TTL: u32 = 600;
+
+/// Geräte‑Zustand dieser Installation.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OwnDevices {
+    /// Eigene Geräte‑ID; 0 ist das Primärgerät.
+    pub device_id: u32,
+    /// Signierte Geräteliste (hex).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub list: Option<String>,
+    /// Höchste bisher vergebene ID; entfernte IDs werden nicht neu
+    /// vergeben, damit alte Sessions nicht weiterverwendet werden.
+    #[serde(default)]
+    pub issued: u32,
+}
+
+impl OwnDevices {
+    pub fn load(store: &Store) -> anyhow::Result<Self> {
+        Ok(store.get_meta("devices")?.unwrap_or_default())
+    }
+    pub fn save(&self, store: &Store) -> anyhow::Result<()> {
+        store.set_meta("devices", self)
+    }
+    pub fn is_primary(&self) -> bool {
+        self.device_id == PRIMARY_DEVICE
+    }
+    pub fn list(&self) -> anyhow::Result<Option<DeviceList>> {
+        self.list.as_deref().map(parse_list).transpose()
+    }
+    /// Übernimmt eine neuere, bereits geprüfte Liste derselben Identität.
+    /// Gibt `false` zurück, wenn sie nicht neuer ist.
+    pub fn update(&mut self, list: &DeviceList) -> anyhow::Result<bool> {
+        if !list.replaces(self.list()?.as_ref()) {
+            return Ok(false);
+        }
+        self.list = Some(to_hex(&list.to_bytes()));
+        self.issued = self.issued.max(list.devices.iter().map(|d| d.device_id).max().unwrap_or(0));
+        Ok(true)
+    }
+    /// Die übrigen eigenen Geräte als Empfänger von Abschriften.
+    pub fn others(&self) -> anyhow::Result<Vec<DeviceTarget>> {
+        Ok(self
+            .list()?
+            .map(|list| list.devices.iter().filter(|d| d.device_id != self.device_id).map(DeviceTarget::from_certificate).collect())
+            .unwrap_or_default())
+    }
+}
+
+/// Liest eine gespeicherte Geräteliste.
+pub fn parse_list(hex: &str) -> anyhow::Result<DeviceList> {
+    let bytes = from_hex(hex).ok_or_else(|| fail(ErrorKind::General, "ungültige gespeicherte Geräteliste"))?;
+    Ok(DeviceList::from_bytes(&bytes)?)
+}
+
+/// Schlüssel der Session mit Gerät `device` eines Gegenübers.  Sessions
+/// mit Primärgeräten behalten die Konversations‑ID als Schlüssel.
+pub fn session_id(conversation: &str, device: u32) -> String {
+    match device {
+        PRIMARY_DEVICE => conversation.to_owned(),
+        device => format!("{conversation}#{device}"),
+    }
+}
+
+/// Inhalt von Nachrichten zwischen eigenen Geräten.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum SyncMessage {
+    /// Abschrift einer auf einem anderen Gerät gesendeten Nachricht.
+    Sent {
+        conversation: String,
+        title: String,
+        /// Empfänger, damit das Gerät den Kontakt bei Bedarf anlegt.
+        contact: Option<Contact>,
+        msg_id: u128,
+        ts: u64,
+        body: Vec<u8>,
+    },
+}
+
+/// Ein Gerät in der Ausgabe von `devices list`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeviceView {
+    pub device_id: u32,
+    pub name: String,
+    pub created_at: u64,
+    pub spend_pub: String,
+    /// Dieses Gerät.
+    pub current: bool,
+}
+
+impl DeviceView {
+    fn of(cert: &DeviceCertificate, own: &OwnDevices) -> Self {
+        Self {
+            device_id: cert.device_id,
+            name: cert.name.clone(),
+            created_at: cert.created_at,
+            spend_pub: to_hex(&cert.spend_pub),
+            current: cert.device_id == own.device_id,
+        }
+    }
+}
+
+/// Alle Geräte der Identität; leer, solange keine verknüpft sind.
+pub fn list(store: &Store) -> anyhow::Result<Vec<DeviceView>> {
+    let own = OwnDevices::load(store)?;
+    Ok(own.list()?.map(|list| list.devices.iter().map(|d| DeviceView::of(d, &own)).collect()).unwrap_or_default())
+}
+
+/// Link‑Anfrage mit den öffentlichen Schlüsseln von `keys`.
+pub fn link_request(keys: &Keys, name: &str) -> anyhow::Result<LinkRequest> {
+    #[cfg(feature = "pqc")]
+    let kem_pub = keys.kem_public();
+    #[cfg(not(feature = "pqc"))]
+    let kem_pub = None;
+    LinkRequest::new(&keys.view.public, &keys.spend.public, kem_pub, name)
+        .map_err(|e| fail(ErrorKind::InvalidInput, format!("ungültiger Gerätename: {e}")))
+}
+
+/// Nimmt das Gerät aus `request` in die Liste auf (nur auf dem
+/// Primärgerät).  Der neue Zustand wird erst gespeichert, wenn die
+/// Provisioning‑Nachricht verschickt ist.
+pub fn authorize(store: &Store, keys: &Keys, request: &LinkRequest, now: u64) -> anyhow::Result<(OwnDevices, Provisioning)> {
+    let mut own = OwnDevices::load(store)?;
+    require_primary(&own)?;
+    let (version, mut devices) = match own.list()? {
+        Some(list) => (list.version + 1, list.devices),
+        None => (1, vec![DeviceCertificate::issue(&keys.identity, PRIMARY_DEVICE, &link_request(keys, "primär")?, now)]),
+    };
+    if devices.iter().any(|d| d.spend_pub == request.spend_pub) {
+        return Err(fail(ErrorKind::InvalidInput, "dieses Gerät ist bereits verknüpft"));
+    }
+    let device_id = own.issued + 1;
+    devices.push(DeviceCertificate::issue(&keys.identity, device_id, request, now));
+    let list = DeviceList::new(&keys.identity, version, devices).map_err(|e| fail(ErrorKind::InvalidInput, e.to_string()))?;
+    own.update(&list)?;
+    let contacts = serde_json::to_vec(&store.contacts()?)?;
+    Ok((own, Provisioning::new(&keys.identity, device_id, list, contacts)))
+}
+
+/// Entfernt Gerät `device_id` aus der Liste (nur auf dem Primärgerät).
+/// Kontakte lehnen seine Nachrichten ab, sobald sie die neue Liste kennen.
+pub fn remove(store: &Store, keys: &Keys, device_id: u32) -> anyhow::Result<()> {
+    let mut own = OwnDevices::load(store)?;
+    require_primary(&own)?;
+    if device_id == PRIMARY_DEVICE {
+        return Err(fail(ErrorKind::InvalidInput, "das Primärgerät kann nicht entfernt werden"));
+    }
+    let list = own.list()?.ok_or_else(|| fail(ErrorKind::NotFound, format!("Gerät {device_id} unbekannt")))?;
+    if list.device(device_id).is_none() {
+        return Err(fail(ErrorKind::NotFound, format!("Gerät {device_id} unbekannt")));
+    }
+    let devices = list.devices.into_iter().filter(|d| d.device_id != device_id).collect();
+    let list = DeviceList::new(&keys.identity, list.version + 1, devices).map_err(|e| fail(ErrorKind::General, e.to_string()))?;
+    own.update(&list)?;
+    own.save(store)
+}
+
+fn require_primary(own: &OwnDevices) -> anyhow::Result<()> {
+    if !own.is_primary() {
+        return Err(fail(ErrorKind::InvalidInput, "Geräte können nur auf dem Primärgerät verwaltet werden"));
+    }
+    Ok(())
+}
+
+/// Envelope mit der Provisioning‑Nachricht an das neue Gerät.
+pub fn provisioning_envelope(
+    keys: &Keys,
+    policy: &SendPolicy,
+    request: &LinkRequest,
+    provisioning: &Provisioning,
+) -> anyhow::Result<Envelope> {
+    let target = DeviceTarget { device_id: provisioning.device_id, address: request_address(request), kem_pub: request.kem_pub.clone() };
+    let policy = SendPolicy { ttl: policy.ttl.max(LINK_TTL), ..policy.clone() };
+    crate::outgoing::seal(
+        keys,
+        PRIMARY_DEVICE,
+        &policy,
+        &target,
+        OsRng.next_u64() as u128,
+        Vec::new(),
+        provisioning.to_bytes().to_vec(),
+    )
+}
+
+fn request_address(request: &LinkRequest) -> phantomchat_core::Subaddress {
+    phantomchat_core::Subaddress::primary(&x25519_dalek::PublicKey::from(request.spend_pub))
+}
+
+/// Wartet auf dem neuen Gerät auf die Provisioning‑Nachricht für `keys`.
+pub async fn wait_for_provisioning<P: BridgeProvider>(pool: &P, keys: &Keys, timeout: Duration) -> anyhow::Result<Provisioning> {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    pool.subscribe(move |env| {
+        let _ = tx.send(env);
+    })
+    .await?;
+    let wait = async {
+        while let Some(env) = rx.recv().await {
+            if let Some(provisioning) = open_provisioning(keys, &env) {
+                return Ok(provisioning);
+            }
+        }
+        Err(fail(ErrorKind::Network, "Verbindung zu allen Relays verloren"))
+    };
+    tokio::time::timeout(timeout, wait)
+        .await
+        .map_err(|_| fail(ErrorKind::Network, "keine Antwort vom Primärgerät (Zeitüberschreitung)"))?
+}
+
+/// Öffnet ein Envelope als Provisioning‑Nachricht für dieses Gerät.
+fn open_provisioning(keys: &Keys, env: &Envelope) -> Option<Provisioning> {
+    #[cfg(feature = "pqc")]
+    let payload = match &keys.kem {
+        Some(kem) => env.open_hybrid(&keys.spend, kem),
+        None => env.open(&keys.spend),
+    }?;
+    #[cfg(not(feature = "pqc"))]
+    let payload = env.open(&keys.spend)?;
+    let provisioning = Provisioning::from_bytes(&payload.body).ok()?;
+    let cert = provisioning.certificate()?;
+    let ours = cert.spend_pub == *keys.spend.public.as_bytes() && cert.view_pub == *keys.view.public.as_bytes();
+    let signed_by_sender = payload.sender.is_some_and(|cert| cert.identity_pub == provisioning.devices.identity_pub);
+    (ours && signed_by_sender).then_some(provisioning)
+}
+
+/// Richtet die Datenbank eines frisch verknüpften Geräts ein: Geräteliste
+/// und Kontaktbuch des Primärgeräts.  Gibt die Anzahl übernommener
+/// Kontakte zurück.
+pub fn finish_link(store: &Store, provisioning: &Provisioning) -> anyhow::Result<usize> {
+    let mut own = OwnDevices { device_id: provisioning.device_id, ..OwnDevices::default() };
+    own.update(&provisioning.devices)?;
+    own.save(store)?;
+    let contacts: Vec<Contact> = serde_json::from_slice(&provisioning.app_data)?;
+    for contact in &contacts {
+        store.save_contact(contact)?;
+    }
+    store.flush()?;
+    Ok(contacts.len())
+}
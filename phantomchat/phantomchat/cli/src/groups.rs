@@ -0,0 +1,554 @@
+//! Gruppen des CLI (siehe [`phantomchat_core::group`]).
+//!
+//! Jede Gruppe liegt im Baum `groups` der Datenbank, ihr Verlauf in der
+//! Konversation `group:<id>`.  Steuernachrichten reisen wie Signale über
+//! die paarweisen Sessions und werden weder gespeichert noch in den
+//! Postausgang gelegt.  Was noch zu verschicken ist (Mitgliederliste beim
+//! Gründer, der eigene Sender‑Key, Austrittsmeldungen an entfernte
+//! Mitglieder), merkt sich die Gruppe je Mitglied; [`distribute`] holt es
+//! bei jeder Gelegenheit nach: nach `sync`, im Daemon, im Chat und vor
+//! jeder Gruppennachricht.
+//!
+//! Mitglieder, die nicht im Kontaktbuch stehen, erreichen wir über die
+//! Adresse aus der Mitgliederliste mit einer eigenen Session
+//! ([`member_conversation`]); mit ihnen werden nur Steuernachrichten
+//! ausgetauscht.  Gruppen werden nicht mit den eigenen weiteren Geräten
+//! abgeglichen.
+
+use crate::contacts::Contact;
+use crate::keystore::Keys;
+use crate::outgoing::{self, Peer, Prepared, SendPolicy};
+use crate::output::{fail, ErrorKind};
+use crate::store::{self, Direction, OutboxEntry, OutboxState, StoredMessage, Store};
+use phantomchat_core::group::{self, GroupHeader};
+use phantomchat_core::secret::SecretBytes;
+use phantomchat_core::util::{from_hex, to_hex};
+use phantomchat_core::{
+    Content, Envelope, GroupControl, GroupId, GroupMember, Sender, SenderKey, SenderKeyDistribution, SenderKeyState,
+    SpendKey, Subaddress,
+};
+use phantomchat_relays::BridgeProvider;
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+/// Präfix der Konversations‑IDs von Gruppen.
+pub const GROUP_PREFIX: &str = "group:";
+/// Höchstzahl vorgemerkter Sender‑Keys künftiger Epochen je Gruppe.
+const MAX_EARLY_KEYS: usize = 64;
+
+/// Konversation der paarweisen Session mit einem Mitglied ohne Kontakt.
+pub fn member_conversation(identity: &[u8; 32]) -> String {
+    format!("member:{}", to_hex(identity))
+}
+
+/// Empfangskette eines anderen Mitglieds.
+#[derive(Debug, Serialize, Deserialize)]
+struct PeerKey {
+    identity_pub: [u8; 32],
+    epoch: u32,
+    state: SenderKeyState,
+}
+
+/// Eine Gruppe mit Gruppengeheimnis und Sender‑Keys.
+#[derive(Serialize, Deserialize)]
+pub struct Group {
+    pub id: GroupId,
+    pub name: String,
+    /// Identity‑Key des Gründers; nur er ändert die Mitgliedschaft.
+    pub admin: [u8; 32],
+    pub epoch: u32,
+    secret: SecretBytes<32>,
+    /// Alle Mitglieder einschließlich uns selbst.
+    pub members: Vec<GroupMember>,
+    own_key: SenderKey,
+    #[serde(default)]
+    keys: Vec<PeerKey>,
+    /// Mitglieder, die die aktuelle Mitgliederliste noch erhalten müssen
+    /// (nur beim Gründer).
+    #[serde(default)]
+    announce: Vec<[u8; 32]>,
+    /// Mitglieder, die unseren Sender‑Key noch erhalten müssen.
+    #[serde(default)]
+    distribute: Vec<[u8; 32]>,
+    /// Entfernte Mitglieder, die davon noch erfahren müssen.
+    #[serde(default)]
+    dismissed: Vec<GroupMember>,
+}
+
+impl Group {
+    /// Neue Gruppe mit uns als Gründer.
+    pub fn create(name: &str, own: GroupMember, others: Vec<GroupMember>) -> Self {
+        let mut group = Self {
+            id: group::new_group_id(),
+            name: name.to_owned(),
+            admin: own.identity_pub,
+            epoch: 0,
+            secret: SecretBytes::new([0u8; 32]),
+            members: std::iter::once(own).chain(others).collect(),
+            own_key: SenderKey::generate(),
+            keys: Vec::new(),
+            announce: Vec::new(),
+            distribute: Vec::new(),
+            dismissed: Vec::new(),
+        };
+        let admin = group.admin;
+        group.rekey(&admin);
+        group
+    }
+
+    pub fn id_hex(&self) -> String {
+        to_hex(&self.id)
+    }
+
+    pub fn conversation_id(&self) -> String {
+        format!("{GROUP_PREFIX}{}", self.id_hex())
+    }
+
+    /// Spend‑Key der Gruppenadresse in der aktuellen Epoche.
+    pub fn spend_key(&self) -> SpendKey {
+        group::group_spend_key(&self.secret)
+    }
+
+    pub fn member(&self, identity: &[u8; 32]) -> Option<&GroupMember> {
+        self.members.iter().find(|m| m.identity_pub == *identity)
+    }
+
+    /// Noch nicht verschickte Steuernachrichten.
+    pub fn pending(&self) -> usize {
+        self.announce.len() + self.distribute.len() + self.dismissed.len()
+    }
+
+    fn others(&self, own: &[u8; 32]) -> Vec<[u8; 32]> {
+        self.members.iter().map(|m| m.identity_pub).filter(|id| id != own).collect()
+    }
+
+    /// Beginnt Epoche `epoch` mit Gruppengeheimnis `secret`: neuer eigener
+    /// Sender‑Key, Empfangsketten älterer Epochen verworfen.
+    fn enter_epoch(&mut self, own: &[u8; 32], epoch: u32, secret: SecretBytes<32>) {
+        self.epoch = epoch;
+        self.secret = secret;
+        self.own_key = SenderKey::generate();
+        self.keys.retain(|k| k.epoch >= epoch);
+        self.distribute = self.others(own);
+    }
+
+    /// Neue Epoche mit frischem Geheimnis, die alle Mitglieder erfahren
+    /// müssen (nur beim Gründer).
+    fn rekey(&mut self, own: &[u8; 32]) {
+        self.enter_epoch(own, self.epoch + 1, group::new_group_secret());
+        self.announce = self.others(own);
+    }
+
+    /// Fügt ein Mitglied hinzu (nur beim Gründer).
+    pub fn add_member(&mut self, own: &[u8; 32], member: GroupMember) -> anyhow::Result<()> {
+        self.require_admin(own)?;
+        if self.member(&member.identity_pub).is_some() {
+            return Err(fail(ErrorKind::InvalidInput, format!("{} ist bereits Mitglied", member.name)));
+        }
+        self.dismissed.retain(|m| m.identity_pub != member.identity_pub);
+        self.members.push(member);
+        self.rekey(own);
+        Ok(())
+    }
+
+    /// Entfernt ein Mitglied (nur beim Gründer); es erhält ein `Leave`.
+    pub fn remove_member(&mut self, own: &[u8; 32], identity: &[u8; 32]) -> anyhow::Result<()> {
+        self.require_admin(own)?;
+        if identity == own {
+            return Err(fail(ErrorKind::InvalidInput, "der Gründer verlässt die Gruppe mit `groups leave`"));
+        }
+        let index = self
+            .members
+            .iter()
+            .position(|m| m.identity_pub == *identity)
+            .ok_or_else(|| fail(ErrorKind::NotFound, "kein Mitglied der Gruppe"))?;
+        self.dismissed.push(self.members.remove(index));
+        self.keys.retain(|k| k.identity_pub != *identity);
+        self.rekey(own);
+        Ok(())
+    }
+
+    fn require_admin(&self, own: &[u8; 32]) -> anyhow::Result<()> {
+        if self.admin != *own {
+            return Err(fail(ErrorKind::InvalidInput, "nur der Gründer ändert die Mitglieder"));
+        }
+        Ok(())
+    }
+
+    /// Verschlüsselt einen kodierten Inhalt mit dem eigenen Sender‑Key.
+    fn encrypt(&mut self, body: &[u8]) -> anyhow::Result<Vec<u8>> {
+        self.own_key.encrypt(&self.id, self.epoch, body).map_err(|e| fail(ErrorKind::General, e.to_string()))
+    }
+
+    /// Entschlüsselt die Gruppennachricht eines Mitglieds.  Der Fehler ist
+    /// der Ablehnungsgrund.
+    pub fn decrypt(&mut self, identity: &[u8; 32], body: &[u8]) -> Result<Vec<u8>, &'static str> {
+        let (header, ciphertext) = GroupHeader::parse(body).map_err(|_| "Gruppennachricht fehlerhaft")?;
+        if header.group != self.id {
+            return Err("Gruppennachricht an eine andere Gruppe");
+        }
+        if header.epoch != self.epoch {
+            return Err("Gruppennachricht aus einer anderen Epoche");
+        }
+        let key = self
+            .keys
+            .iter_mut()
+            .find(|k| k.identity_pub == *identity && k.epoch == header.epoch && k.state.key_id == header.key_id)
+            .ok_or("Sender‑Key unbekannt")?;
+        key.state.decrypt(&header, ciphertext).map_err(|_| "Gruppennachricht lässt sich nicht entschlüsseln")
+    }
+
+    /// Übernimmt den Sender‑Key eines Mitglieds.  Keys künftiger Epochen
+    /// werden vorgemerkt, da die neue Mitgliederliste später eintreffen
+    /// kann.
+    fn add_key(&mut self, identity: &[u8; 32], epoch: u32, key: &SenderKeyDistribution) -> Option<&'static str> {
+        if epoch < self.epoch {
+            return Some("veraltete Gruppenepoche");
+        }
+        if epoch == self.epoch && self.member(identity).is_none() {
+            return Some("Absender nicht in der Gruppe");
+        }
+        if epoch > self.epoch && self.keys.iter().filter(|k| k.epoch > self.epoch).count() >= MAX_EARLY_KEYS {
+            return Some("zu viele vorgemerkte Sender‑Keys");
+        }
+        self.keys.retain(|k| !(k.identity_pub == *identity && k.epoch == epoch));
+        self.keys.push(PeerKey { identity_pub: *identity, epoch, state: SenderKeyState::new(key) });
+        None
+    }
+
+    fn update(&self) -> Content {
+        Content::Group(GroupControl::Update {
+            group: self.id,
+            name: self.name.clone(),
+            epoch: self.epoch,
+            secret: Zeroizing::new(*self.secret.expose()),
+            members: self.members.clone(),
+        })
+    }
+}
+
+/// Übersicht einer Gruppe.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupView {
+    /// Gruppen‑ID (hex).
+    pub id: String,
+    pub name: String,
+    pub conversation: String,
+    pub epoch: u32,
+    /// Wir haben die Gruppe gegründet.
+    pub admin: bool,
+    /// Anzeigenamen der anderen Mitglieder.
+    pub members: Vec<String>,
+    /// Noch nicht verschickte Steuernachrichten.
+    pub pending: usize,
+}
+
+impl GroupView {
+    pub fn new(store: &Store, group: &Group, own: &[u8; 32]) -> anyhow::Result<Self> {
+        let mut members = Vec::new();
+        for member in group.members.iter().filter(|m| m.identity_pub != *own) {
+            members.push(display_name(store, member)?);
+        }
+        Ok(Self {
+            id: group.id_hex(),
+            name: group.name.clone(),
+            conversation: group.conversation_id(),
+            epoch: group.epoch,
+            admin: group.admin == *own,
+            members,
+            pending: group.pending(),
+        })
+    }
+}
+
+/// Eigener Eintrag in der Mitgliederliste (Hauptadresse).
+pub fn own_member(keys: &Keys) -> GroupMember {
+    let identity = keys.identity.public;
+    #[cfg(feature = "pqc")]
+    let kem_pub = keys.kem_public();
+    #[cfg(not(feature = "pqc"))]
+    let kem_pub = None;
+    GroupMember {
+        name: to_hex(&identity)[..16].to_owned(),
+        identity_pub: identity,
+        address: Subaddress::primary(&keys.spend.public).to_bytes().to_vec(),
+        kem_pub,
+    }
+}
+
+/// Ein Kontakt als Mitglied; die anderen Mitglieder erreichen ihn unter
+/// derselben Adresse wie wir.
+pub fn member_of(contact: &Contact) -> anyhow::Result<GroupMember> {
+    Ok(GroupMember {
+        name: contact.nickname.clone(),
+        identity_pub: contact.identity_public()?,
+        address: contact.address()?.to_bytes().to_vec(),
+        kem_pub: contact.kem_public()?,
+    })
+}
+
+/// Spitzname aus dem Kontaktbuch, sonst der Name aus der Mitgliederliste.
+pub fn display_name(store: &Store, member: &GroupMember) -> anyhow::Result<String> {
+    Ok(find_contact(store, &member.identity_pub)?.map_or_else(|| member.name.clone(), |c| c.nickname))
+}
+
+fn find_contact(store: &Store, identity: &[u8; 32]) -> anyhow::Result<Option<Contact>> {
+    Ok(store.contacts()?.into_iter().find(|c| c.identity_public().is_ok_and(|key| key == *identity)))
+}
+
+/// Gegenüber für paarweise Steuernachrichten an ein Mitglied.
+fn member_peer(store: &Store, member: &GroupMember) -> anyhow::Result<Peer> {
+    if let Some(contact) = find_contact(store, &member.identity_pub)? {
+        return Peer::from_contact(&contact);
+    }
+    let address = Subaddress::from_bytes(&member.address)
+        .ok_or_else(|| fail(ErrorKind::InvalidInput, format!("ungültige Adresse von Mitglied {}", member.name)))?;
+    Ok(Peer {
+        address,
+        conversation: member_conversation(&member.identity_pub),
+        title: member.name.clone(),
+        label: None,
+        kem_pub: member.kem_pub.clone(),
+        devices: Vec::new(),
+    })
+}
+
+/// Sucht eine Gruppe nach Name, ID (hex) oder Konversations‑ID.
+pub fn find(store: &Store, name: &str) -> anyhow::Result<Group> {
+    let id = name.strip_prefix(GROUP_PREFIX).unwrap_or(name);
+    let mut matches: Vec<Group> = store.groups()?.into_iter().filter(|g| g.name == name || g.id_hex() == id).collect();
+    match matches.len() {
+        0 => Err(fail(ErrorKind::NotFound, format!("Gruppe {name:?} unbekannt (siehe `groups list`)"))),
+        1 => Ok(matches.remove(0)),
+        _ => Err(fail(ErrorKind::InvalidInput, format!("mehrere Gruppen heißen {name:?}; bitte die ID angeben"))),
+    }
+}
+
+/// Gruppe zu einer Konversations‑ID `group:<id>`.
+pub fn for_conversation(store: &Store, conversation: &str) -> anyhow::Result<Option<Group>> {
+    let Some(id) = conversation.strip_prefix(GROUP_PREFIX).and_then(from_hex) else {
+        return Ok(None);
+    };
+    match <GroupId>::try_from(id.as_slice()) {
+        Ok(id) => store.group(&id),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Wendet eine Steuernachricht von `sender` an.  `known` gibt an, ob der
+/// Absender ein Kontakt ist; nur Kontakte dürfen in neue Gruppen einladen.
+/// Liefert den Grund, falls die Nachricht verworfen wird.
+pub fn apply(
+    store: &Store,
+    own: &[u8; 32],
+    sender: &[u8; 32],
+    known: bool,
+    control: GroupControl,
+) -> anyhow::Result<Option<&'static str>> {
+    let existing = store.group(control.group())?;
+    match control {
+        GroupControl::Update { group: id, name, epoch, secret, members } => {
+            let mut group = match existing {
+                Some(group) if group.admin != *sender => return Ok(Some("Gruppenänderung nicht vom Gründer")),
+                Some(group) if epoch <= group.epoch => return Ok(Some("veraltete Gruppenepoche")),
+                Some(group) => group,
+                None if !known => return Ok(Some("Gruppeneinladung von Unbekannten")),
+                None => Group {
+                    id,
+                    name: String::new(),
+                    admin: *sender,
+                    epoch: 0,
+                    secret: SecretBytes::new([0u8; 32]),
+                    members: Vec::new(),
+                    own_key: SenderKey::generate(),
+                    keys: Vec::new(),
+                    announce: Vec::new(),
+                    distribute: Vec::new(),
+                    dismissed: Vec::new(),
+                },
+            };
+            if !members.iter().any(|m| m.identity_pub == *own) || !members.iter().any(|m| m.identity_pub == *sender) {
+                // Ohne uns in der Liste sind wir nicht (mehr) Mitglied
+                if group.epoch > 0 {
+                    store.remove_group(&id)?;
+                }
+                return Ok(None);
+            }
+            group.name = name;
+            group.members = members;
+            group.enter_epoch(own, epoch, SecretBytes::new(*secret));
+            store.save_group(&group)?;
+        }
+        GroupControl::SenderKey { epoch, key, .. } => {
+            let Some(mut group) = existing else {
+                return Ok(Some("Gruppe unbekannt"));
+            };
+            if let Some(reason) = group.add_key(sender, epoch, &key) {
+                return Ok(Some(reason));
+            }
+            store.save_group(&group)?;
+        }
+        GroupControl::Leave { group: id } => {
+            let Some(mut group) = existing else {
+                return Ok(None);
+            };
+            if group.admin == *sender {
+                // Der Gründer beendet die Gruppe für uns
+                store.remove_group(&id)?;
+                return Ok(None);
+            }
+            if group.member(sender).is_none() {
+                return Ok(Some("Absender nicht in der Gruppe"));
+            }
+            group.members.retain(|m| m.identity_pub != *sender);
+            group.keys.retain(|k| k.identity_pub != *sender);
+            group.announce.retain(|id| id != sender);
+            group.distribute.retain(|id| id != sender);
+            if group.admin == *own {
+                group.rekey(own);
+            }
+            store.save_group(&group)?;
+        }
+    }
+    Ok(None)
+}
+
+/// Schickt einem Mitglied eine Steuernachricht über die paarweise Session.
+async fn send_control<P: BridgeProvider>(
+    keys: &Keys,
+    store: &Store,
+    policy: &SendPolicy,
+    relay: &P,
+    member: &GroupMember,
+    control: &Content,
+) -> anyhow::Result<()> {
+    let peer = member_peer(store, member)?;
+    let prepared = outgoing::prepare(keys, store, policy, &peer, &control.to_bytes())?;
+    outgoing::publish(store, relay, prepared).await
+}
+
+/// Verschickt die ausstehenden Steuernachrichten einer Gruppe: erst die
+/// Mitgliederliste, dann den eigenen Sender‑Key, zuletzt die
+/// Austrittsmeldungen.  Fehlgeschlagene bleiben für den nächsten Versuch
+/// vorgemerkt; zurückgegeben wird ihre Zahl.
+pub async fn distribute<P: BridgeProvider>(
+    keys: &Keys,
+    store: &Store,
+    policy: &SendPolicy,
+    relay: &P,
+    group: &mut Group,
+) -> anyhow::Result<usize> {
+    if group.pending() == 0 {
+        return Ok(0);
+    }
+    let update = group.update();
+    let sender_key = Content::Group(GroupControl::SenderKey {
+        group: group.id,
+        epoch: group.epoch,
+        key: group.own_key.distribution(),
+    });
+    let leave = Content::Group(GroupControl::Leave { group: group.id });
+    group.announce = send_all(keys, store, policy, relay, group, &group.announce, &update).await;
+    group.distribute = send_all(keys, store, policy, relay, group, &group.distribute, &sender_key).await;
+    let mut failed = Vec::new();
+    for member in std::mem::take(&mut group.dismissed) {
+        if send_control(keys, store, policy, relay, &member, &leave).await.is_err() {
+            failed.push(member);
+        }
+    }
+    group.dismissed = failed;
+    store.save_group(group)?;
+    store.flush()?;
+    Ok(group.pending())
+}
+
+/// Schickt `control` an die Mitglieder `identities`; liefert die, die
+/// nicht erreicht wurden.
+async fn send_all<P: BridgeProvider>(
+    keys: &Keys,
+    store: &Store,
+    policy: &SendPolicy,
+    relay: &P,
+    group: &Group,
+    identities: &[[u8; 32]],
+    control: &Content,
+) -> Vec<[u8; 32]> {
+    let mut failed = Vec::new();
+    for identity in identities {
+        let Some(member) = group.member(identity) else {
+            continue;
+        };
+        if send_control(keys, store, policy, relay, member, control).await.is_err() {
+            failed.push(*identity);
+        }
+    }
+    failed
+}
+
+/// [`distribute`] für alle Gruppen mit ausstehenden Steuernachrichten.
+pub async fn distribute_all<P: BridgeProvider>(keys: &Keys, store: &Store, policy: &SendPolicy, relay: &P) -> anyhow::Result<usize> {
+    let mut pending = 0;
+    for mut group in store.groups()?.into_iter().filter(|g| g.pending() > 0) {
+        pending += distribute(keys, store, policy, relay, &mut group).await?;
+    }
+    Ok(pending)
+}
+
+/// Verlässt eine Gruppe: Alle anderen Mitglieder erhalten ein `Leave`,
+/// danach wird die Gruppe gelöscht.  Verlässt der Gründer die Gruppe,
+/// endet sie für alle.  Gibt die Zahl nicht erreichter Mitglieder zurück;
+/// die Gruppe wird trotzdem gelöscht.
+pub async fn leave<P: BridgeProvider>(keys: &Keys, store: &Store, policy: &SendPolicy, relay: &P, group: &Group) -> anyhow::Result<usize> {
+    let own = keys.identity.public;
+    let leave = Content::Group(GroupControl::Leave { group: group.id });
+    let mut unreached = 0;
+    for member in group.members.iter().filter(|m| m.identity_pub != own) {
+        if send_control(keys, store, policy, relay, member, &leave).await.is_err() {
+            unreached += 1;
+        }
+    }
+    store.remove_group(&group.id)?;
+    store.flush()?;
+    Ok(unreached)
+}
+
+/// Verschlüsselt `body` mit dem eigenen Sender‑Key, adressiert das
+/// Envelope an die Gruppenadresse und speichert Nachricht und
+/// Postausgangseintrag.  `body` ist ein kodierter [`Content`].
+pub fn prepare(keys: &Keys, store: &Store, policy: &SendPolicy, group: &mut Group, body: &[u8]) -> anyhow::Result<Prepared> {
+    let msg_id = OsRng.next_u64() as u128;
+    let ciphertext = group.encrypt(body)?;
+    let device_id = crate::devices::OwnDevices::load(store)?.device_id;
+    let sender = Sender { identity: &keys.identity, device_id };
+    let address = Subaddress::primary(&group.spend_key().public);
+    // Gruppennachrichten sind stets klassisch: Der Schlüssel der
+    // Gruppenadresse ist allen Mitgliedern bekannt
+    let envelope = Envelope::new_padded(&address, msg_id, &sender, Vec::new(), ciphertext, policy.ttl, policy.pow_bits, &policy.padding);
+    store.save_group(group)?;
+    let conversation = group.conversation_id();
+    store.add_message(
+        &StoredMessage {
+            conversation: conversation.clone(),
+            msg_id,
+            direction: Direction::Outgoing,
+            ts: envelope.ts,
+            body: body.to_vec(),
+            expires_at: None,
+            sender: None,
+        },
+        &group.name,
+    )?;
+    store.put_outbox(&OutboxEntry {
+        msg_id,
+        conversation,
+        envelope: envelope.to_bytes(),
+        fanout: Vec::new(),
+        state: OutboxState::Pending,
+        attempts: 0,
+        updated_at: store::now_ms(),
+    })?;
+    store.flush()?;
+    Ok(Prepared { msg_id, envelope, fanout: Vec::new() })
+}
+
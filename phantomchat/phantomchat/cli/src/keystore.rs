@@ -0,0 +1,419 @@
+//! Passwortgeschützter Schlüsselspeicher des CLI.
+//!
+//! Die privaten Schlüssel werden nie im Klartext abgelegt.  Aus der
+//! Passphrase wird mit Argon2id ein 256‑Bit‑Schlüssel abgeleitet, mit dem
+//! die serialisierten Schlüssel per XChaCha20‑Poly1305 versiegelt werden.
+//! Die KDF‑Parameter und das Salt stehen im Klartext im Dateikopf, damit
+//! sie später angehoben werden können; der Kopf ist als Associated Data
+//! an den Ciphertext gebunden und kann daher nicht unbemerkt verändert
+//! werden.
+//!
+//! Dateiformat (JSON):
+//!
+//! ```text
+//! { "format": "phantomchat-keystore", "version": 1,
+//!   "kdf": { "alg": "argon2id", "m_cost_kib": .., "t_cost": .., "p_cost": .., "salt": "<b64>" },
+//!   "cipher": "xchacha20poly1305", "nonce": "<b64>", "ciphertext": "<b64>" }
+//! ```
+
+use crate::output::{fail, ErrorKind};
+use anyhow::{anyhow, bail, Context};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, Payload};
+use chacha20poly1305::{KeyInit, XChaCha20Poly1305, XNonce};
+#[cfg(feature = "pqc")]
+use phantomchat_core::pq::KemKey;
+use phantomchat_core::{IdentityKey, SpendKey, ViewKey};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+/// Kennung im Dateikopf.
+pub const FORMAT: &str = "phantomchat-keystore";
+/// Aktuelle Formatversion.
+pub const VERSION: u32 = 1;
+/// Umgebungsvariable für nicht‑interaktive Nutzung (Skripte, CI).
+pub const PASSPHRASE_ENV: &str = "PHANTOMCHAT_PASSPHRASE";
+
+/// Obergrenzen der Argon2id‑Parameter aus dem Dateikopf (1 GiB,
+/// 16 Durchläufe, 8 Lanes).  Ein manipulierter Kopf kann so keine
+/// beliebig große Allokation oder Rechenzeit erzwingen.
+const MAX_M_COST_KIB: u32 = 1024 * 1024;
+const MAX_T_COST: u32 = 16;
+const MAX_P_COST: u32 = 8;
+
+/// Argon2id‑Parameter.  Die Standardwerte folgen der Empfehlung aus
+/// RFC 9106 für speicherbeschränkte Umgebungen (64 MiB, 3 Durchläufe).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KdfParams {
+    pub alg: String,
+    pub m_cost_kib: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+    pub salt: String,
+}
+
+impl KdfParams {
+    fn generate() -> Self {
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        Self { alg: "argon2id".into(), m_cost_kib: 64 * 1024, t_cost: 3, p_cost: 1, salt: BASE64.encode(salt) }
+    }
+    /// Leitet den Versiegelungsschlüssel aus der Passphrase ab.  Die
+    /// Kosten werden auf die Obergrenzen gekappt; ein Kopf mit höheren
+    /// Werten ist manipuliert und scheitert danach an der AEAD‑Prüfung.
+    fn derive(&self, passphrase: &str) -> anyhow::Result<Zeroizing<[u8; 32]>> {
+        if self.alg != "argon2id" {
+            bail!("unbekannte KDF {:?}", self.alg);
+        }
+        let salt = BASE64.decode(&self.salt).context("ungültiges Salt")?;
+        let (m_cost, t_cost, p_cost) = self.bounded();
+        let params = Params::new(m_cost, t_cost, p_cost, Some(32))
+            .map_err(|e| anyhow!("ungültige Argon2‑Parameter: {e}"))?;
+        let mut key = Zeroizing::new([0u8; 32]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), &salt, key.as_mut())
+            .map_err(|e| anyhow!("Argon2 fehlgeschlagen: {e}"))?;
+        Ok(key)
+    }
+    /// Speicher‑, Zeit‑ und Parallelitätskosten, gekappt auf die
+    /// Obergrenzen.
+    fn bounded(&self) -> (u32, u32, u32) {
+        (self.m_cost_kib.min(MAX_M_COST_KIB), self.t_cost.min(MAX_T_COST), self.p_cost.min(MAX_P_COST))
+    }
+}
+
+/// Versiegelte Schlüsseldatei.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct KeystoreFile {
+    format: String,
+    version: u32,
+    kdf: KdfParams,
+    cipher: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl KeystoreFile {
+    /// Associated Data: alle Kopffelder außer Nonce und Ciphertext.
+    fn aad(&self) -> Vec<u8> {
+        serde_json::to_vec(&serde_json::json!({
+            "format": self.format,
+            "version": self.version,
+            "kdf": self.kdf,
+            "cipher": self.cipher,
+        }))
+        .expect("JSON")
+    }
+}
+
+/// Klartextinhalt des Speichers (nur im Speicher, nie auf der Platte);
+/// die Base64‑Strings werden beim Drop überschrieben.
+#[derive(Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+struct SecretKeys {
+    identity_private: String,
+    identity_public: String,
+    view_private: String,
+    view_public: String,
+    spend_private: String,
+    spend_public: String,
+    /// Schlüssel der lokalen Datenbank (siehe [`crate::store`]).  Ältere
+    /// Speicher enthalten ihn noch nicht; er wird beim Entsperren ergänzt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    storage_key: Option<String>,
+    /// Privater ML‑KEM‑768‑Schlüssel (Feature `pqc`).  Builds ohne das
+    /// Feature reichen ihn beim Neuversiegeln unverändert durch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kem_private: Option<String>,
+}
+
+/// Entsperrte Schlüssel einer Identität.
+pub struct Keys {
+    pub identity: IdentityKey,
+    pub view: ViewKey,
+    pub spend: SpendKey,
+    /// Schlüssel der lokalen Datenbank; dieselbe Passphrase entsperrt so
+    /// Schlüssel und Nachrichten.
+    pub storage_key: Zeroizing<[u8; 32]>,
+    /// Gespeicherter privater KEM‑Schlüssel (roh).
+    kem_private: Option<Zeroizing<Vec<u8>>>,
+    /// ML‑KEM‑Schlüssel für den hybriden Schlüsselaustausch.
+    #[cfg(feature = "pqc")]
+    pub kem: Option<KemKey>,
+}
+
+impl Keys {
+    /// Erzeugt eine neue Identität.  Mit Feature `pqc` gehört dazu ein
+    /// ML‑KEM‑Schlüssel.
+    pub fn generate() -> Self {
+        let mut storage_key = Zeroizing::new([0u8; 32]);
+        OsRng.fill_bytes(storage_key.as_mut());
+        #[allow(unused_mut)]
+        let mut keys = Self {
+            identity: IdentityKey::generate(),
+            view: ViewKey::generate(),
+            spend: SpendKey::generate(),
+            storage_key,
+            kem_private: None,
+            #[cfg(feature = "pqc")]
+            kem: None,
+        };
+        #[cfg(feature = "pqc")]
+        keys.set_kem(KemKey::generate());
+        keys
+    }
+    #[cfg(feature = "pqc")]
+    fn set_kem(&mut self, kem: KemKey) {
+        self.kem_private = Some(kem.to_bytes());
+        self.kem = Some(kem);
+    }
+    /// Öffentlicher KEM‑Schlüssel für das Pairing‑Bundle.
+    #[cfg(feature = "pqc")]
+    pub fn kem_public(&self) -> Option<Vec<u8>> {
+        self.kem.as_ref().map(|kem| kem.public.clone())
+    }
+    fn to_secret(&self) -> SecretKeys {
+        SecretKeys {
+            identity_private: BASE64.encode(self.identity.private_bytes()),
+            identity_public: BASE64.encode(self.identity.public),
+            view_private: BASE64.encode(Zeroizing::new(self.view.secret.to_bytes())),
+            view_public: BASE64.encode(self.view.public.as_bytes()),
+            spend_private: BASE64.encode(Zeroizing::new(self.spend.secret.to_bytes())),
+            spend_public: BASE64.encode(self.spend.public.as_bytes()),
+            storage_key: Some(BASE64.encode(self.storage_key.as_ref())),
+            kem_private: self.kem_private.as_ref().map(|key| BASE64.encode(key.as_slice())),
+        }
+    }
+    fn from_secret(secret: &SecretKeys) -> anyhow::Result<Self> {
+        let view_secret = StaticSecret::from(key32(&secret.view_private)?);
+        let spend_secret = StaticSecret::from(key32(&secret.spend_private)?);
+        let storage_key = match &secret.storage_key {
+            Some(b64) => Zeroizing::new(key32(b64)?),
+            None => {
+                let mut key = Zeroizing::new([0u8; 32]);
+                OsRng.fill_bytes(key.as_mut());
+                key
+            }
+        };
+        let kem_private = match &secret.kem_private {
+            Some(b64) => Some(Zeroizing::new(BASE64.decode(b64).context("ungültiges Base64")?)),
+            None => None,
+        };
+        #[cfg(feature = "pqc")]
+        let kem = match &kem_private {
+            Some(bytes) => Some(KemKey::from_bytes(bytes).ok_or_else(|| anyhow!("ungültiger KEM‑Schlüssel"))?),
+            None => None,
+        };
+        Ok(Self {
+            // Der öffentliche Teil wird aus dem Seed neu berechnet, siehe
+            // `IdentityKey::from_private`.
+            identity: IdentityKey::from_private(key32(&secret.identity_private)?),
+            view: ViewKey { public: PublicKey::from(&view_secret), secret: view_secret },
+            spend: SpendKey { public: PublicKey::from(&spend_secret), secret: spend_secret },
+            storage_key,
+            kem_private,
+            #[cfg(feature = "pqc")]
+            kem,
+        })
+    }
+}
+
+/// Serialisiert die Klartextschlüssel in einen Puffer exakt passender
+/// Größe.  Wüchse er beim Schreiben, blieben Kopien der Base64‑Strings
+/// im freigegebenen Speicher zurück.
+fn secret_json(secret: &SecretKeys) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+    struct Counter(usize);
+    impl std::io::Write for Counter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0 += buf.len();
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let mut counter = Counter(0);
+    serde_json::to_writer(&mut counter, secret)?;
+    let mut out = Zeroizing::new(Vec::with_capacity(counter.0));
+    serde_json::to_writer(&mut *out, secret)?;
+    Ok(out)
+}
+
+/// Dekodiert einen Base64‑kodierten 32‑Byte‑Schlüssel.
+fn key32(b64: &str) -> anyhow::Result<[u8; 32]> {
+    let bytes = Zeroizing::new(BASE64.decode(b64).context("ungültiges Base64")?);
+    bytes.as_slice().try_into().map_err(|_| anyhow!("Schlüssel muss 32 Byte lang sein"))
+}
+
+/// Versiegelt `keys` mit `passphrase` und schreibt die Datei.
+pub fn create(path: &Path, keys: &Keys, passphrase: &str) -> anyhow::Result<()> {
+    let plaintext = secret_json(&keys.to_secret())?;
+    let kdf = KdfParams::generate();
+    let key = kdf.derive(passphrase)?;
+    let mut nonce = [0u8; 24];
+    OsRng.fill_bytes(&mut nonce);
+    let mut file = KeystoreFile {
+        format: FORMAT.into(),
+        version: VERSION,
+        kdf,
+        cipher: "xchacha20poly1305".into(),
+        nonce: BASE64.encode(nonce),
+        ciphertext: String::new(),
+    };
+    let aad = file.aad();
+    let cipher = XChaCha20Poly1305::new_from_slice(key.as_ref()).expect("32 Byte");
+    let ciphertext = cipher
+        .encrypt(XNonce::from_slice(&nonce), Payload { msg: &plaintext, aad: &aad })
+        .map_err(|_| anyhow!("Verschlüsselung fehlgeschlagen"))?;
+    file.ciphertext = BASE64.encode(ciphertext);
+    write_private(path, &serde_json::to_vec_pretty(&file)?)
+}
+
+/// Entsperrt den Schlüsselspeicher.
+pub fn unlock(path: &Path, passphrase: &str) -> anyhow::Result<Keys> {
+    let data = fs::read(path).with_context(|| format!("Schlüsseldatei {:?} nicht lesbar", path))?;
+    let value: serde_json::Value = serde_json::from_slice(&data)?;
+    if value.get("spend_private").is_some() {
+        bail!("{:?} ist eine unverschlüsselte Schlüsseldatei; mit `phantomchat passwd` verschlüsseln", path);
+    }
+    let file: KeystoreFile = serde_json::from_value(value).context("ungültige Schlüsseldatei")?;
+    if file.format != FORMAT {
+        bail!("unbekanntes Format {:?}", file.format);
+    }
+    if file.version != VERSION {
+        bail!("nicht unterstützte Keystore‑Version {}", file.version);
+    }
+    if file.cipher != "xchacha20poly1305" {
+        bail!("unbekannter Cipher {:?}", file.cipher);
+    }
+    let key = file.kdf.derive(passphrase)?;
+    let nonce = BASE64.decode(&file.nonce).context("ungültiger Nonce")?;
+    if nonce.len() != 24 {
+        bail!("ungültiger Nonce");
+    }
+    let ciphertext = BASE64.decode(&file.ciphertext).context("ungültiger Ciphertext")?;
+    let cipher = XChaCha20Poly1305::new_from_slice(key.as_ref()).expect("32 Byte");
+    let plaintext = Zeroizing::new(
+        cipher
+            .decrypt(XNonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: &file.aad() })
+            .map_err(|_| fail(ErrorKind::Auth, "falsche Passphrase oder beschädigte Schlüsseldatei"))?,
+    );
+    let secret: SecretKeys = serde_json::from_slice(&plaintext)?;
+    #[allow(unused_mut)]
+    let mut keys = Keys::from_secret(&secret)?;
+    #[allow(unused_mut)]
+    let mut reseal = secret.storage_key.is_none();
+    #[cfg(feature = "pqc")]
+    if keys.kem.is_none() {
+        // Speicher ohne KEM‑Schlüssel erhalten beim ersten Entsperren
+        // mit `pqc` einen.
+        keys.set_kem(KemKey::generate());
+        reseal = true;
+    }
+    if reseal {
+        // Speicher aus der Zeit vor der lokalen Datenbank: den neu
+        // erzeugten Datenbankschlüssel sofort mitversiegeln.
+        create(path, &keys, passphrase)?;
+    }
+    Ok(keys)
+}
+
+/// Liest eine unverschlüsselte Schlüsseldatei im alten Format.
+fn read_legacy(path: &Path) -> anyhow::Result<Option<Keys>> {
+    let data = Zeroizing::new(fs::read(path)?);
+    let value: serde_json::Value = serde_json::from_slice(&data)?;
+    if value.get("spend_private").is_none() {
+        return Ok(None);
+    }
+    let secret: SecretKeys = serde_json::from_value(value)?;
+    Keys::from_secret(&secret).map(Some)
+}
+
+/// Ändert die Passphrase.  Eine unverschlüsselte Schlüsseldatei im alten
+/// Format wird dabei verschlüsselt (`old` wird dann ignoriert).  Es
+/// werden neues Salt und neuer Nonce erzeugt.
+pub fn change_passphrase(path: &Path, old: Option<&str>, new: &str) -> anyhow::Result<()> {
+    let keys = match read_legacy(path)? {
+        Some(keys) => keys,
+        None => unlock(path, old.ok_or_else(|| anyhow!("alte Passphrase fehlt"))?)?,
+    };
+    create(path, &keys, new)
+}
+
+/// Prüft, ob `path` eine unverschlüsselte Schlüsseldatei ist.
+pub fn is_legacy(path: &Path) -> bool {
+    matches!(read_legacy(path), Ok(Some(_)))
+}
+
+/// Fragt eine Passphrase ab.  Ist [`PASSPHRASE_ENV`] gesetzt, wird deren
+/// Wert ohne Rückfrage verwendet.  Mit `confirm` (neue Passphrase) muss
+/// die Eingabe wiederholt werden, und leere Passphrasen werden in beiden
+/// Fällen abgelehnt.
+pub fn read_passphrase(prompt: &str, confirm: bool) -> anyhow::Result<Zeroizing<String>> {
+    let pass = match std::env::var(PASSPHRASE_ENV) {
+        Ok(pass) => Zeroizing::new(pass),
+        Err(_) => {
+            let pass = Zeroizing::new(rpassword::prompt_password(prompt)?);
+            if confirm {
+                let again = Zeroizing::new(rpassword::prompt_password("Passphrase wiederholen: ")?);
+                if *pass != *again {
+                    return Err(fail(ErrorKind::Auth, "Passphrasen stimmen nicht überein"));
+                }
+            }
+            pass
+        }
+    };
+    if confirm && pass.is_empty() {
+        return Err(fail(ErrorKind::Auth, "leere Passphrase nicht erlaubt"));
+    }
+    Ok(pass)
+}
+
+/// Schreibt eine Datei atomar und unter Unix nur für den Eigentümer
+/// lesbar.
+pub fn write_private(path: &Path, data: &[u8]) -> anyhow::Result<()> {
+    let tmp = path.with_extension("tmp");
+    {
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut f = options.open(&tmp)?;
+        std::io::Write::write_all(&mut f, data)?;
+        f.sync_all()?;
+    }
+    fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kdf_costs_are_bounded() {
+        let mut kdf = KdfParams::generate();
+        assert_eq!(kdf.bounded(), (64 * 1024, 3, 1));
+        kdf.m_cost_kib = u32::MAX;
+        kdf.t_cost = u32::MAX;
+        kdf.p_cost = u32::MAX;
+        assert_eq!(kdf.bounded(), (MAX_M_COST_KIB, MAX_T_COST, MAX_P_COST));
+    }
+
+    #[test]
+    fn secret_json_fills_its_buffer_exactly() {
+        let keys = Keys::generate();
+        let json = secret_json(&keys.to_secret()).unwrap();
+        assert_eq!(json.len(), json.capacity());
+        let secret: SecretKeys = serde_json::from_slice(&json).unwrap();
+        assert_eq!(Keys::from_secret(&secret).unwrap().spend.public, keys.spend.public);
+    }
+}
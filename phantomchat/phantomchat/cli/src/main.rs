@@ -3,162 +3,1220 @@
 //! Dieser Kommandozeilen‑Client ermöglicht es, Schlüssel zu generieren,
 //! Pairing‑Informationen auszutauschen sowie Nachrichten zu versenden
 //! und zu empfangen.  Der Code basiert auf der Kernbibliothek
-//! `phantomchat_core` und nutzt `tokio` für asynchrones I/O.  Der
-//! Netzwerkcode für Nostr‑Relays und die QR‑Funktionen sind nur
-//! rudimentär implementiert.
+//! `phantomchat_core`, spricht Nostr‑Relays über `phantomchat_relays` an
+//! und nutzt `tokio` für asynchrones I/O.  Private Schlüssel liegen
+//! ausschließlich in einem passwortgeschützten Schlüsselspeicher (siehe
+//! [`keystore`]).
 
+mod addresses;
+mod api;
+mod attachments;
+mod config;
+mod contacts;
+mod daemon;
+mod devices;
+mod groups;
+mod keystore;
+mod net;
+mod outgoing;
+mod output;
+mod qr;
+mod receive;
+mod rpc;
+mod session;
+mod store;
+mod thread;
+mod tui;
+
+use api::{Backend, Local, MessageView, Recipient};
 use clap::{Parser, Subcommand};
-use phantomchat_core::{IdentityKey, ViewKey, SpendKey, Envelope};
-use x25519_dalek::{PublicKey, StaticSecret};
-use rand_core::OsRng;
-use std::fs;
-use std::path::PathBuf;
-use std::time::Duration;
+use config::{Config, Profile, DEFAULT_PROFILE};
+use contacts::{Contact, KeyUpdate, Verification};
+use phantomchat_core::util::to_hex;
+use phantomchat_core::PairingBundle;
+use output::{fail, ErrorKind, Output};
+use phantomchat_relays::BridgeProvider;
+use receive::Outcome;
+use keystore::Keys;
+use serde::Serialize;
+use serde_json::json;
+use store::{Direction, OutboxState};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
 /// Kommandozeilenoptionen
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
+    /// Konfigurationsdatei (Standard: `$XDG_CONFIG_HOME/phantomchat/config.toml`)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// Profil aus der Konfiguration (auch über `PHANTOMCHAT_PROFILE`)
+    #[arg(long, global = true)]
+    profile: Option<String>,
+    /// Verschlüsselte lokale Datenbank (Standard: aus dem Profil)
+    #[arg(long, global = true)]
+    db: Option<PathBuf>,
+    /// Nostr‑Relay (mehrfach möglich); ersetzt die Relays des Profils
+    #[arg(long = "relay", global = true)]
+    relays: Vec<String>,
+    /// Relays über den lokalen Tor‑SOCKS‑Proxy ansprechen
+    #[arg(long, global = true)]
+    tor: bool,
+    /// Strukturierte JSON‑Ausgabe statt Text (siehe `output`‑Modul für
+    /// Format und Exit‑Codes)
+    #[arg(long, global = true)]
+    json: bool,
+    /// Socket des Daemons (Standard: `$XDG_RUNTIME_DIR/phantomchat-<profil>.sock`).
+    /// Läuft dort ein Daemon, werden Befehle an ihn weitergereicht.
+    #[arg(long, global = true)]
+    socket: Option<PathBuf>,
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Erzeugt ein neues Schlüsselpaar und speichert es als JSON
+    /// Erzeugt ein neues Schlüsselpaar und speichert es verschlüsselt
     Keygen {
-        /// Ausgabeordner
-        #[arg(short, long, default_value = "keys.json")]
-        out: PathBuf,
+        /// Schlüsseldatei (Standard: aus dem Profil)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+        /// Vorhandene Schlüsseldatei überschreiben
+        #[arg(long)]
+        force: bool,
+    },
+    /// Ändert die Passphrase des Schlüsselspeichers bzw. verschlüsselt
+    /// eine alte Klartext‑Schlüsseldatei
+    Passwd {
+        /// Schlüsseldatei (Standard: aus dem Profil)
+        #[arg(short, long)]
+        file: Option<PathBuf>,
     },
-    /// Zeigt Pairing‑Daten (view_pub, spend_pub) an
+    /// Zeigt Pairing‑Daten (identity_pub, view_pub, spend_pub) als
+    /// signiertes Bundle an
     Pair {
-        /// Schlüsseldatei
-        #[arg(short, long, default_value = "keys.json")]
-        file: PathBuf,
+        /// Schlüsseldatei (Standard: aus dem Profil)
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        /// QR‑Code im Terminal anzeigen
+        #[arg(long)]
+        qr: bool,
+        /// QR‑Code zusätzlich als SVG speichern
+        #[arg(long)]
+        svg: Option<PathBuf>,
+        /// QR‑Code zusätzlich als PNG speichern
+        #[arg(long)]
+        png: Option<PathBuf>,
+        /// Eigene Subadresse für dieses Gegenüber (üblicherweise dessen
+        /// künftiger Spitzname) statt der Hauptadresse weitergeben
+        #[arg(long)]
+        label: Option<String>,
     },
     /// Sendet eine Nachricht an einen Empfänger
     Send {
-        /// Schlüsseldatei
-        #[arg(short, long, default_value = "keys.json")]
-        file: PathBuf,
-        /// Empfänger‑Spend‑Public‑Key (hex)
-        #[arg(short = 'r', long)]
-        recipient_spend_pub: String,
-        /// Nachrichtentext
+        /// Schlüsseldatei (Standard: aus dem Profil); `--file` wählt hier
+        /// den Anhang
+        #[arg(short = 'k', long)]
+        keystore: Option<PathBuf>,
+        /// Empfänger aus dem Kontaktbuch (Spitzname)
+        #[arg(short, long, conflicts_with_all = ["recipient_spend_pub", "group"], required_unless_present_any = ["recipient_spend_pub", "group"])]
+        to: Option<String>,
+        /// Empfänger‑Spend‑Public‑Key (hex oder Base64) ohne Kontakt
+        #[arg(short = 'r', long, conflicts_with = "group")]
+        recipient_spend_pub: Option<String>,
+        /// Gruppe (Name oder ID)
+        #[arg(short, long)]
+        group: Option<String>,
+        /// Nachrichtentext; mit `--file` die Bildunterschrift
+        #[arg(short, long, required_unless_present = "file")]
+        message: Option<String>,
+        /// Antwort auf die Nachricht mit dieser ID (siehe `history --ids`)
+        #[arg(long, conflicts_with = "file")]
+        reply_to: Option<u128>,
+        /// Datei als verschlüsselten Anhang senden (höchstens 64 MiB)
+        #[arg(long, alias = "attach")]
+        file: Option<PathBuf>,
+    },
+    /// Lädt den Anhang einer Nachricht herunter; setzt abgebrochene
+    /// Downloads fort
+    Fetch {
+        /// Schlüsseldatei (Standard: aus dem Profil)
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        /// Spitzname oder Konversations‑ID
+        #[arg(short, long)]
+        with: String,
+        /// ID der Nachricht (siehe `history --ids`)
+        msg_id: u128,
+        /// Zieldatei oder ‑verzeichnis (Standard: aktuelles Verzeichnis)
+        path: Option<PathBuf>,
+    },
+    /// Reagiert mit einem Emoji auf eine Nachricht
+    React {
+        /// Schlüsseldatei (Standard: aus dem Profil)
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        /// Kontakt (Spitzname)
+        #[arg(short, long, conflicts_with = "group", required_unless_present = "group")]
+        to: Option<String>,
+        /// Gruppe (Name oder ID)
+        #[arg(short, long)]
+        group: Option<String>,
+        /// ID der Nachricht (siehe `history --ids`)
+        msg_id: u128,
+        emoji: String,
+        /// Eigene Reaktion zurücknehmen
+        #[arg(long)]
+        remove: bool,
+    },
+    /// Ändert den Text einer eigenen Nachricht
+    Edit {
+        /// Schlüsseldatei (Standard: aus dem Profil)
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        /// Kontakt (Spitzname)
+        #[arg(short, long, conflicts_with = "group", required_unless_present = "group")]
+        to: Option<String>,
+        /// Gruppe (Name oder ID)
+        #[arg(short, long)]
+        group: Option<String>,
+        /// ID der Nachricht (siehe `history --ids`)
+        msg_id: u128,
+        /// Neuer Text
         #[arg(short, long)]
         message: String,
     },
-    /// Lauscht auf eingehende Nachrichten (lokaler Relay‑Test)
+    /// Löscht eine eigene Nachricht für alle
+    Delete {
+        /// Schlüsseldatei (Standard: aus dem Profil)
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        /// Kontakt (Spitzname)
+        #[arg(short, long, conflicts_with = "group", required_unless_present = "group")]
+        to: Option<String>,
+        /// Gruppe (Name oder ID)
+        #[arg(short, long)]
+        group: Option<String>,
+        /// ID der Nachricht (siehe `history --ids`)
+        msg_id: u128,
+    },
+    /// Stellt verschwindende Nachrichten für eine Konversation ein; das
+    /// Gegenüber übernimmt den Timer
+    Timer {
+        /// Schlüsseldatei (Standard: aus dem Profil)
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        /// Kontakt (Spitzname)
+        #[arg(short, long, conflicts_with = "group", required_unless_present = "group")]
+        to: Option<String>,
+        /// Gruppe (Name oder ID)
+        #[arg(short, long)]
+        group: Option<String>,
+        /// Lebensdauer neuer Nachrichten, z. B. `30m`, `1d`, `1w`;
+        /// `off` schaltet ab
+        #[arg(value_parser = thread::parse_duration)]
+        duration: u32,
+    },
+    /// Empfängt Nachrichten von den Relays
     Listen {
-        /// Schlüsseldatei
-        #[arg(short, long, default_value = "keys.json")]
-        file: PathBuf,
+        /// Schlüsseldatei (Standard: aus dem Profil)
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        /// Nur seit der letzten Synchronisation nachladen und beenden
+        #[arg(long)]
+        once: bool,
+        /// Verworfene Envelopes mit Grund sowie Lesebestätigungen und
+        /// Tipp‑Hinweise anzeigen
+        #[arg(short, long)]
+        verbose: bool,
+    },
+    /// Zeigt gespeicherte Konversationen bzw. den Verlauf einer
+    /// Konversation
+    History {
+        /// Schlüsseldatei (Standard: aus dem Profil)
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        /// Spitzname oder Konversations‑ID; ohne Angabe werden alle
+        /// Konversationen gelistet
+        #[arg(short, long)]
+        with: Option<String>,
+        /// Nachrichten‑IDs anzeigen (für `--reply-to`, `react`, `edit`,
+        /// `delete`, `fetch`)
+        #[arg(long)]
+        ids: bool,
+        /// Keine Lesebestätigung senden
+        #[arg(long)]
+        no_receipts: bool,
+    },
+    /// Interaktiver Chat im Terminal
+    Chat {
+        /// Schlüsseldatei (Standard: aus dem Profil)
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+    },
+    /// Startet den lokalen Daemon: hält Datenbank und Relays offen,
+    /// empfängt laufend und bietet eine JSON‑RPC‑API auf dem Socket
+    Daemon {
+        /// Schlüsseldatei (Standard: aus dem Profil)
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+    },
+    /// Listet die konfigurierten Profile mit ihren Pfaden
+    Profiles,
+    /// Verwaltet das Kontaktbuch
+    Contacts {
+        /// Schlüsseldatei (Standard: aus dem Profil)
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        #[command(subcommand)]
+        action: ContactsCommand,
+    },
+    /// Verwaltet die eigenen Empfangsadressen (Subadressen)
+    Addresses {
+        /// Schlüsseldatei (Standard: aus dem Profil)
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        #[command(subcommand)]
+        action: AddressesCommand,
+    },
+    /// Richtet diese Installation als weiteres Gerät einer bestehenden
+    /// Identität ein: zeigt einen Link‑Code an und wartet, bis das
+    /// Primärgerät ihn mit `devices add` bestätigt
+    Link {
+        /// Neue Schlüsseldatei (Standard: aus dem Profil)
+        #[arg(short, long)]
+        out: Option<PathBuf>,
+        /// Gerätename, den die anderen Geräte anzeigen (Standard: Hostname)
+        #[arg(long)]
+        name: Option<String>,
+        /// Link‑Code zusätzlich als QR‑Code anzeigen
+        #[arg(long)]
+        qr: bool,
+        /// Wartezeit auf das Primärgerät in Sekunden
+        #[arg(long, default_value_t = 600)]
+        timeout: u64,
+    },
+    /// Verwaltet die verknüpften Geräte dieser Identität
+    Devices {
+        /// Schlüsseldatei (Standard: aus dem Profil)
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        #[command(subcommand)]
+        action: DevicesCommand,
+    },
+    /// Verwaltet Gruppen; Nachrichten gehen mit `send --group` an alle
+    /// Mitglieder
+    Groups {
+        /// Schlüsseldatei (Standard: aus dem Profil)
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        #[command(subcommand)]
+        action: GroupsCommand,
+    },
+}
+
+impl Commands {
+    /// Per `--file` bzw. `--out` (bei `send`: `--keystore`) angegebene
+    /// Schlüsseldatei.
+    fn keystore(&self) -> Option<&PathBuf> {
+        match self {
+            Commands::Keygen { out, .. } | Commands::Link { out, .. } => out.as_ref(),
+            Commands::Send { keystore, .. } => keystore.as_ref(),
+            Commands::Passwd { file }
+            | Commands::Pair { file, .. }
+            | Commands::React { file, .. }
+            | Commands::Edit { file, .. }
+            | Commands::Delete { file, .. }
+            | Commands::Timer { file, .. }
+            | Commands::Fetch { file, .. }
+            | Commands::Listen { file, .. }
+            | Commands::History { file, .. }
+            | Commands::Chat { file }
+            | Commands::Daemon { file }
+            | Commands::Contacts { file, .. }
+            | Commands::Addresses { file, .. }
+            | Commands::Devices { file, .. }
+            | Commands::Groups { file, .. } => file.as_ref(),
+            Commands::Profiles => None,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum ContactsCommand {
+    /// Fügt einen Kontakt hinzu (Schlüssel hex oder Base64, wie von `pair`
+    /// ausgegeben) oder aktualisiert dessen Schlüssel
+    Add {
+        /// Spitzname
+        name: String,
+        #[arg(long)]
+        identity: String,
+        #[arg(long)]
+        view: String,
+        #[arg(long)]
+        spend: String,
+    },
+    /// Importiert einen Kontakt aus einem Pairing‑Bundle (Textform,
+    /// Textdatei oder Bild mit QR‑Code)
+    Import {
+        /// Spitzname
+        name: String,
+        /// `PHANTOMCHAT:1:…`, Pfad zu einer Text‑ oder PNG/JPEG‑Datei
+        source: String,
+    },
+    /// Listet alle Kontakte
+    List,
+    /// Entfernt einen Kontakt
+    Remove {
+        name: String,
+    },
+    /// Zeigt Safety Number, Emoji und Wörter zum Vergleich und markiert den
+    /// Kontakt nach Rückfrage als verifiziert
+    Verify {
+        name: String,
+        /// Ohne Rückfrage bestätigen
+        #[arg(long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum AddressesCommand {
+    /// Listet alle vergebenen Subadressen
+    List,
+    /// Ersetzt die Subadresse eines Labels sofort; die alte nimmt noch
+    /// während der Überlappung Nachrichten an
+    Rotate {
+        label: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum DevicesCommand {
+    /// Listet die Geräte der Identität
+    List,
+    /// Verknüpft ein neues Gerät anhand seines Link‑Codes (nur auf dem
+    /// Primärgerät)
+    Add {
+        /// `PHANTOMCHAT-LINK:1:…`, wie von `link` angezeigt
+        code: String,
+    },
+    /// Entfernt ein Gerät; Kontakte lehnen seine Nachrichten ab, sobald sie
+    /// die neue Geräteliste kennen
+    Remove {
+        device_id: u32,
+    },
+}
+
+#[derive(Subcommand)]
+enum GroupsCommand {
+    /// Gründet eine Gruppe und lädt Kontakte ein
+    Create {
+        name: String,
+        /// Mitglied (Spitzname); mehrfach angeben
+        #[arg(short, long = "member")]
+        members: Vec<String>,
+    },
+    /// Listet alle Gruppen
+    List,
+    /// Nimmt einen Kontakt in eine eigene Gruppe auf (neue Epoche)
+    Add {
+        group: String,
+        /// Spitzname
+        member: String,
+    },
+    /// Entfernt ein Mitglied aus einer eigenen Gruppe (neue Epoche)
+    Remove {
+        group: String,
+        /// Spitzname oder Anzeigename
+        member: String,
+    },
+    /// Verlässt eine Gruppe und löscht sie lokal
+    Leave {
+        group: String,
     },
 }
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+async fn main() -> ExitCode {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        // Hilfe und Version sowie Fehler im Textmodus gibt clap selbst aus
+        Err(e) if !e.use_stderr() || !std::env::args().any(|a| a == "--json") => e.exit(),
+        Err(e) => {
+            return Output { json: true }.error(&fail(ErrorKind::Usage, e.to_string().trim_end()));
+        }
+    };
+    let out = Output { json: cli.json };
+    match run(cli, out).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => out.error(&e),
+    }
+}
+
+async fn run(cli: Cli, out: Output) -> anyhow::Result<()> {
+    let config = Config::load(cli.config.as_deref())?;
+    let mut profile = config.profile(&config.selected(cli.profile.as_deref()))?;
+    // Kommandozeilenoptionen haben Vorrang vor dem Profil
+    if let Some(file) = cli.command.keystore() {
+        profile.keystore = file.clone();
+    }
+    if let Some(db) = cli.db {
+        profile.db = db;
+    }
+    if !cli.relays.is_empty() {
+        profile.net.relays = cli.relays;
+    }
+    profile.net.tor |= cli.tor;
+    if let Some(socket) = cli.socket {
+        profile.socket = socket;
+    }
     match cli.command {
-        Commands::Keygen { out } => {
-            keygen(out)?;
+        Commands::Keygen { force, .. } => {
+            keygen(out, &profile, force)?;
+        }
+        Commands::Passwd { .. } => {
+            passwd(out, &profile.keystore)?;
+        }
+        Commands::Pair { qr, svg, png, label, .. } => {
+            pair(out, &profile, qr, svg.as_deref(), png.as_deref(), label.as_deref()).await?;
+        }
+        Commands::Send { to, recipient_spend_pub, group, message, reply_to, file, .. } => {
+            let recipient = match (to, recipient_spend_pub, group) {
+                (Some(name), ..) => Recipient::Contact(name),
+                (None, Some(key), _) => Recipient::Key(key),
+                (None, None, Some(group)) => Recipient::Group(group),
+                (None, None, None) => return Err(fail(ErrorKind::Usage, "--to, --recipient-spend-pub oder --group angeben")),
+            };
+            let mut backend = backend(&profile).await?;
+            let message = message.unwrap_or_default();
+            match file {
+                Some(path) => {
+                    let sent = backend.send_file(&recipient, &absolute(&path)?, &message).await?;
+                    report_sent(out, &sent, "Datei")?;
+                }
+                None => {
+                    let sent = backend.send(&recipient, &message, reply_to).await?;
+                    report_sent(out, &sent, "Nachricht")?;
+                }
+            }
+        }
+        Commands::React { to, group, msg_id, emoji, remove, .. } => {
+            let mut backend = backend(&profile).await?;
+            let sent = backend.react(&recipient(to, group)?, msg_id, &emoji, remove).await?;
+            report_sent(out, &sent, "Reaktion")?;
+        }
+        Commands::Edit { to, group, msg_id, message, .. } => {
+            let mut backend = backend(&profile).await?;
+            let sent = backend.edit(&recipient(to, group)?, msg_id, &message).await?;
+            report_sent(out, &sent, "Bearbeitung")?;
         }
-        Commands::Pair { file } => {
-            pair(file)?;
+        Commands::Delete { to, group, msg_id, .. } => {
+            let mut backend = backend(&profile).await?;
+            let sent = backend.delete(&recipient(to, group)?, msg_id).await?;
+            report_sent(out, &sent, "Löschung")?;
         }
-        Commands::Send { file, recipient_spend_pub, message } => {
-            send(file, &recipient_spend_pub, &message).await?;
+        Commands::Timer { to, group, duration, .. } => {
+            let mut backend = backend(&profile).await?;
+            let sent = backend.set_timer(&recipient(to, group)?, duration).await?;
+            report_sent(out, &sent, &thread::timer_text(duration))?;
         }
-        Commands::Listen { file } => {
-            listen(file).await?;
+        Commands::Fetch { with, msg_id, path, .. } => {
+            let mut backend = backend(&profile).await?;
+            let fetched = backend.fetch(&with, msg_id, &absolute(path.as_deref().unwrap_or(Path::new(".")))?).await?;
+            out.emit(&fetched, |f| {
+                let resumed = if f.resumed > 0 { format!(", {} von {} Blöcken bereits vorhanden", f.resumed, f.chunks) } else { String::new() };
+                println!("{} gespeichert ({}{resumed})", f.path.display(), thread::format_size(f.size));
+            })?;
+        }
+        Commands::Listen { once, verbose, .. } => {
+            let backend = backend(&profile).await?;
+            listen(out, backend, once, verbose).await?;
+        }
+        Commands::History { with, ids, no_receipts, .. } => {
+            let mut backend = backend(&profile).await?;
+            history(out, &mut backend, with.as_deref(), ids, !no_receipts).await?;
+        }
+        Commands::Chat { .. } => {
+            if out.json {
+                return Err(fail(ErrorKind::Usage, "chat unterstützt kein --json"));
+            }
+            if rpc::Client::connect(&profile.socket).await.is_ok() {
+                return Err(fail(
+                    ErrorKind::Daemon,
+                    format!("Der Daemon auf {:?} hält die Datenbank offen; bitte zuerst beenden", profile.socket),
+                ));
+            }
+            let local = Local::open(unlock(&profile.keystore)?, &profile)?;
+            tui::run(&local).await?;
+        }
+        Commands::Daemon { .. } => {
+            let local = Local::open(unlock(&profile.keystore)?, &profile)?;
+            daemon::run(local, &profile.socket, out).await?;
+        }
+        Commands::Profiles => {
+            profiles(out, &config, &profile.name)?;
+        }
+        Commands::Contacts { action, .. } => {
+            let mut backend = backend(&profile).await?;
+            contacts_cmd(out, &mut backend, action).await?;
+        }
+        Commands::Addresses { action, .. } => {
+            let mut backend = backend(&profile).await?;
+            addresses_cmd(out, &mut backend, action).await?;
+        }
+        Commands::Link { name, qr, timeout, .. } => {
+            let name = name.or_else(|| std::env::var("HOSTNAME").ok()).unwrap_or_else(|| "Gerät".to_owned());
+            link(out, &profile, &name, qr, timeout).await?;
+        }
+        Commands::Devices { action, .. } => {
+            let mut backend = backend(&profile).await?;
+            devices_cmd(out, &mut backend, action).await?;
+        }
+        Commands::Groups { action, .. } => {
+            let mut backend = backend(&profile).await?;
+            groups_cmd(out, &mut backend, action).await?;
         }
     }
     Ok(())
 }
 
-/// Generiert neue Schlüssel und speichert sie in einer JSON‑Datei.
-fn keygen(out: PathBuf) -> anyhow::Result<()> {
-    let id = IdentityKey::generate();
-    let view = ViewKey::generate();
-    let spend = SpendKey::generate();
-    let keys = serde_json::json!({
-        "identity_private": base64::encode(id.private),
-        "identity_public": base64::encode(id.public),
-        "view_private": base64::encode(view.secret.to_bytes()),
-        "view_public": base64::encode(view.public.as_bytes()),
-        "spend_private": base64::encode(spend.secret.to_bytes()),
-        "spend_public": base64::encode(spend.public.as_bytes()),
-    });
-    fs::write(&out, serde_json::to_vec_pretty(&keys)?)?;
-    println!("Schlüssel in {:?} gespeichert", out);
-    Ok(())
+/// Empfänger aus `--to` oder `--group`.
+fn recipient(to: Option<String>, group: Option<String>) -> anyhow::Result<Recipient> {
+    match (to, group) {
+        (Some(name), _) => Ok(Recipient::Contact(name)),
+        (None, Some(group)) => Ok(Recipient::Group(group)),
+        (None, None) => Err(fail(ErrorKind::Usage, "--to oder --group angeben")),
+    }
 }
 
-/// Liest die Schlüsseldatei und zeigt die Pairing‑Daten an.
-fn pair(file: PathBuf) -> anyhow::Result<()> {
-    let data = fs::read(file)?;
-    let json: serde_json::Value = serde_json::from_slice(&data)?;
-    let view_pub = json["view_public"].as_str().unwrap();
-    let spend_pub = json["spend_public"].as_str().unwrap();
-    println!("Pairing‑Daten:\nview_pub: {}\nspend_pub: {}", view_pub, spend_pub);
-    Ok(())
+/// Öffentliche Schlüssel einer Identität.
+#[derive(Serialize)]
+struct PublicKeys {
+    identity_pub: String,
+    view_pub: String,
+    spend_pub: String,
+    /// Basispunkt, wenn `spend_pub` zu einer Subadresse gehört.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    base: Option<String>,
+}
+
+impl PublicKeys {
+    fn of(keys: &Keys) -> Self {
+        Self {
+            identity_pub: to_hex(&keys.identity.public),
+            view_pub: to_hex(keys.view.public.as_bytes()),
+            spend_pub: to_hex(keys.spend.public.as_bytes()),
+            base: None,
+        }
+    }
+    fn of_bundle(bundle: &PairingBundle) -> Self {
+        Self {
+            identity_pub: to_hex(&bundle.identity_pub),
+            view_pub: to_hex(&bundle.view_pub),
+            spend_pub: to_hex(&bundle.spend_pub),
+            base: bundle.base.as_ref().map(|base| to_hex(base)),
+        }
+    }
+}
+
+/// Generiert neue Schlüssel und speichert sie im verschlüsselten
+/// Schlüsselspeicher.
+fn keygen(out: Output, profile: &Profile, force: bool) -> anyhow::Result<()> {
+    let file = &profile.keystore;
+    if file.exists() && !force {
+        return Err(fail(
+            ErrorKind::InvalidInput,
+            format!("{:?} existiert bereits (mit --force überschreiben)", file),
+        ));
+    }
+    let passphrase = keystore::read_passphrase("Neue Passphrase: ", true)?;
+    let keys = Keys::generate();
+    if let Some(dir) = file.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    keystore::create(file, &keys, &passphrase)?;
+    out.emit(&json!({ "profile": profile.name, "file": file, "keys": PublicKeys::of(&keys) }), |_| {
+        println!("Schlüssel verschlüsselt in {:?} gespeichert", file)
+    })
+}
+
+/// Ereignis von `link`; im JSON‑Modus eine Zeile pro Ereignis.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum LinkEvent<'a> {
+    Code { code: &'a str },
+    Linked { profile: &'a str, file: &'a Path, device_id: u32, identity_pub: String, contacts: usize },
 }
 
-/// Lädt Schlüssel, baut ein Envelope und sendet es an den (hier
-/// nur simulierten) Empfänger.  In einer echten Implementierung würde
-/// diese Funktion die Nachricht via Nostr‑Relays übertragen.
-async fn send(file: PathBuf, recipient_spend_pub_hex: &str, message: &str) -> anyhow::Result<()> {
-    // Schlüssel laden
-    let data = fs::read(file)?;
-    let json: serde_json::Value = serde_json::from_slice(&data)?;
-    let spend_priv = base64::decode(json["spend_private"].as_str().unwrap())?;
-    let view_priv = base64::decode(json["view_private"].as_str().unwrap())?;
-    let _id_priv = base64::decode(json["identity_private"].as_str().unwrap())?;
-    let spend_secret = StaticSecret::from(spend_priv.as_slice().try_into().unwrap());
-    let spend_key = SpendKey {
-        secret: spend_secret.clone(),
-        public: PublicKey::from(&spend_secret),
+/// Richtet ein weiteres Gerät ein.  Die Schlüssel entstehen lokal; der
+/// Identity‑Key kommt mit der Provisioning‑Nachricht des Primärgeräts.
+/// Der Schlüsselspeicher wird erst danach geschrieben, so dass ein
+/// Abbruch keine halbe Identität hinterlässt.
+async fn link(out: Output, profile: &Profile, name: &str, show_qr: bool, timeout: u64) -> anyhow::Result<()> {
+    let file = &profile.keystore;
+    if file.exists() {
+        return Err(fail(ErrorKind::InvalidInput, format!("{:?} existiert bereits", file)));
+    }
+    if !profile.net.is_configured() {
+        return Err(fail(ErrorKind::Network, "keine Relays konfiguriert (--relay wss://…)"));
+    }
+    let passphrase = keystore::read_passphrase("Neue Passphrase: ", true)?;
+    let mut keys = Keys::generate();
+    let code = devices::link_request(&keys, name)?.to_text();
+    out.emit(&LinkEvent::Code { code: &code }, |_| {
+        if show_qr {
+            if let Ok(qr) = qr::encode(&code) {
+                println!("{}", qr::render_terminal(&qr));
+            }
+        }
+        println!("Link‑Code:\n{code}\n");
+        println!("Auf dem Primärgerät bestätigen mit:\nphantomchat devices add {code}\n");
+        println!("Warte auf das Primärgerät …");
+    })?;
+    let pool = profile.net.pool(&keys.identity.public)?;
+    let provisioning = devices::wait_for_provisioning(&pool, &keys, std::time::Duration::from_secs(timeout)).await?;
+    keys.identity = provisioning.identity();
+    if let Some(dir) = file.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    keystore::create(file, &keys, &passphrase)?;
+    let store = store::Store::open(&profile.db, &keys.storage_key)?;
+    let contacts = devices::finish_link(&store, &provisioning)?;
+    out.emit(
+        &LinkEvent::Linked {
+            profile: &profile.name,
+            file,
+            device_id: provisioning.device_id,
+            identity_pub: to_hex(&keys.identity.public),
+            contacts,
+        },
+        |_| {
+            println!("Als Gerät {} verknüpft; Schlüssel in {:?} gespeichert", provisioning.device_id, file);
+            println!("identity_pub: {}\n{contacts} Kontakt(e) übernommen", to_hex(&keys.identity.public));
+            println!("Vergleichen Sie identity_pub mit der Ausgabe von `pair` auf dem Primärgerät.");
+        },
+    )
+}
+
+/// Ändert die Passphrase des Schlüsselspeichers.
+fn passwd(out: Output, file: &Path) -> anyhow::Result<()> {
+    let legacy = keystore::is_legacy(file);
+    let old = if legacy {
+        if !out.json {
+            println!("{:?} ist unverschlüsselt und wird jetzt verschlüsselt.", file);
+        }
+        None
+    } else {
+        Some(keystore::read_passphrase("Aktuelle Passphrase: ", false)?)
     };
-    // Empfänger‑Spend‑Public‑Key parsen
-    let rec_bytes = hex::decode(recipient_spend_pub_hex)?;
-    let recipient_spend_pub = PublicKey::from(rec_bytes.as_slice().try_into().unwrap());
-    // Dummy Ratchet header
-    let ratchet_header = vec![0u8];
-    // msg_id generieren
-    let mut rng = rand_core::OsRng;
-    let msg_id = rng.next_u64() as u128;
-    let envelope = Envelope::new(
-        &recipient_spend_pub,
-        msg_id,
-        0,
-        ratchet_header,
-        message.as_bytes().to_vec(),
-        60,
-        16,
-    );
-    // In echter Implementierung: envelope an Relays senden.
-    println!("Envelope gebaut ({} Bytes)", envelope.to_bytes().len());
-    println!("Serielles Envelope (Base64): {}", base64::encode(envelope.to_bytes()));
-    Ok(())
+    // Im nicht‑interaktiven Modus liefert die Umgebungsvariable nur eine
+    // Passphrase; dann wird nur neu versiegelt (neues Salt und Nonce).
+    let new = keystore::read_passphrase("Neue Passphrase: ", true)?;
+    keystore::change_passphrase(file, old.as_deref().map(|s| s.as_str()), &new)?;
+    out.emit(&json!({ "file": file, "migrated": legacy }), |_| println!("Passphrase für {:?} geändert", file))
+}
+
+/// Fragt die Passphrase ab und entsperrt den Schlüsselspeicher.
+fn unlock(file: &Path) -> anyhow::Result<Keys> {
+    let passphrase = keystore::read_passphrase("Passphrase: ", false)?;
+    keystore::unlock(file, &passphrase)
+}
+
+/// Verbindet sich mit dem laufenden Daemon des Profils.  Ohne Daemon
+/// wird der Schlüsselspeicher entsperrt und die Datenbank direkt geöffnet.
+async fn backend(profile: &Profile) -> anyhow::Result<Backend> {
+    if let Ok(client) = rpc::Client::connect(&profile.socket).await {
+        return Ok(Backend::Remote(client));
+    }
+    let local = Local::open(unlock(&profile.keystore)?, profile)?;
+    Ok(Backend::Local(Box::new(local)))
+}
+
+/// Ein Profil mit seinen aufgelösten Pfaden.
+#[derive(Serialize)]
+struct ProfileView {
+    name: String,
+    selected: bool,
+    keystore: PathBuf,
+    db: PathBuf,
+    socket: PathBuf,
+    relays: Vec<String>,
+    tor: bool,
+}
+
+/// Listet die Profile der Konfiguration.
+fn profiles(out: Output, config: &Config, selected: &str) -> anyhow::Result<()> {
+    let mut names: Vec<&str> = config.profiles.keys().map(String::as_str).collect();
+    if !names.contains(&DEFAULT_PROFILE) && config.default_profile.is_none() {
+        names.insert(0, DEFAULT_PROFILE);
+    }
+    let mut views = Vec::new();
+    for name in names {
+        let profile = config.profile(name)?;
+        views.push(ProfileView {
+            selected: profile.name == selected,
+            name: profile.name,
+            keystore: profile.keystore,
+            db: profile.db,
+            socket: profile.socket,
+            relays: profile.net.relays,
+            tor: profile.net.tor,
+        });
+    }
+    out.emit(&views, |views| {
+        for p in views {
+            let mark = if p.selected { "*" } else { " " };
+            println!("{mark} {:<12} {}", p.name, p.keystore.display());
+            println!("  {:<12} Datenbank {}, {} Relay(s){}", "", p.db.display(), p.relays.len(), if p.tor { ", Tor" } else { "" });
+        }
+    })
+}
+
+/// Ergebnis von `pair`.
+#[derive(Serialize)]
+struct Pairing {
+    bundle: String,
+    #[serde(flatten)]
+    keys: PublicKeys,
+    relays: Vec<String>,
+    svg: Option<PathBuf>,
+    png: Option<PathBuf>,
+}
+
+/// Entsperrt die Schlüsseldatei und zeigt die Pairing‑Daten an: das
+/// signierte Bundle in Textform, optional als QR‑Code, sowie die
+/// einzelnen Schlüssel hex‑kodiert für `contacts add`.  Die Relays des
+/// Profils werden dem Gegenüber als Hinweis mitgegeben.  Im JSON‑Modus
+/// entfällt der QR‑Code im Terminal.  Mit `label` enthält das Bundle
+/// statt der Hauptadresse die Subadresse dieses Labels; dafür wird die
+/// Datenbank gebraucht (ggf. über den Daemon).
+async fn pair(
+    out: Output,
+    profile: &Profile,
+    show_qr: bool,
+    svg: Option<&Path>,
+    png: Option<&Path>,
+    label: Option<&str>,
+) -> anyhow::Result<()> {
+    let relays = profile.net.relays.clone();
+    let bundle = match label {
+        Some(label) => PairingBundle::from_text(&backend(profile).await?.address_bundle(label, relays).await?)?,
+        None => addresses::pairing_bundle(&unlock(&profile.keystore)?, relays, None)?,
+    };
+    let text = bundle.to_text();
+    if show_qr || svg.is_some() || png.is_some() {
+        let code = qr::encode(&text)?;
+        if show_qr && !out.json {
+            println!("{}", qr::render_terminal(&code));
+        }
+        if let Some(path) = svg {
+            std::fs::write(path, qr::to_svg(&code))?;
+        }
+        if let Some(path) = png {
+            qr::save_png(&code, path, 8)?;
+        }
+    }
+    let pairing = Pairing {
+        bundle: text,
+        keys: PublicKeys::of_bundle(&bundle),
+        relays: bundle.relays,
+        svg: svg.map(Path::to_path_buf),
+        png: png.map(Path::to_path_buf),
+    };
+    out.emit(&pairing, |p| {
+        if let Some(path) = &p.svg {
+            println!("QR‑Code als SVG in {:?} gespeichert", path);
+        }
+        if let Some(path) = &p.png {
+            println!("QR‑Code als PNG in {:?} gespeichert", path);
+        }
+        println!("Pairing‑Code:\n{}\n", p.bundle);
+        println!("identity_pub: {}\nview_pub: {}\nspend_pub: {}", p.keys.identity_pub, p.keys.view_pub, p.keys.spend_pub);
+        if let Some(base) = &p.keys.base {
+            println!("base: {base}");
+        }
+        println!("\nGegenüber fügt Sie hinzu mit:\nphantomchat contacts import <name> {}", p.bundle);
+    })
+}
+
+/// Meldet eine gesendete Nachricht (oder Reaktion, Bearbeitung, …).
+/// Ohne konfigurierte Relays wurde das Envelope nur gespeichert und wird
+/// als Base64 ausgegeben.
+/// Pfade relativ zum Arbeitsverzeichnis des Aufrufers auflösen, bevor
+/// sie an einen Daemon gehen.
+fn absolute(path: &Path) -> anyhow::Result<PathBuf> {
+    Ok(std::env::current_dir()?.join(path))
+}
+
+fn report_sent(out: Output, sent: &api::Sent, what: &str) -> anyhow::Result<()> {
+    out.emit(sent, |sent| {
+        if sent.key_changed {
+            eprintln!("Warnung: Der Schlüssel von {} hat sich geändert; bitte neu verifizieren.", sent.title);
+        }
+        match &sent.envelope {
+            Some(envelope) => {
+                println!("Keine Relays konfiguriert; Envelope nur lokal gespeichert.");
+                println!("Serielles Envelope (Base64): {}", envelope);
+            }
+            None => println!("{what} an {} veröffentlicht", sent.title),
+        }
+    })
+}
+
+/// Ereignis von `listen`; im JSON‑Modus eine Zeile pro Ereignis.
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum ListenEvent<'a> {
+    Message(&'a MessageView),
+    Rejected { reason: &'a str },
+    Synced { scanned: usize, messages: usize },
 }
 
-/// Lauscht auf eingehende Nachrichten (Nur Demo: Keine echten Relays)
-async fn listen(file: PathBuf) -> anyhow::Result<()> {
-    // Schlüssel laden
-    let data = fs::read(file)?;
-    let json: serde_json::Value = serde_json::from_slice(&data)?;
-    let spend_priv = base64::decode(json["spend_private"].as_str().unwrap())?;
-    let spend_secret = StaticSecret::from(spend_priv.as_slice().try_into().unwrap());
-    let spend_key = SpendKey {
-        secret: spend_secret.clone(),
-        public: PublicKey::from(&spend_secret),
+/// Empfängt Nachrichten: lädt zunächst alles seit der letzten
+/// Synchronisation nach und abonniert danach die Relays (außer mit
+/// `--once`).  Läuft ein Daemon, empfängt dieser bereits; dann werden nur
+/// seine Benachrichtigungen angezeigt.
+async fn listen(out: Output, mut backend: Backend, once: bool, verbose: bool) -> anyhow::Result<()> {
+    print_sync(out, &backend.sync().await?, verbose)?;
+    if once {
+        return Ok(());
+    }
+    let mut client = match backend {
+        Backend::Local(local) => return listen_local(out, &local, verbose).await,
+        Backend::Remote(client) => client,
     };
-    println!("Warte auf Nachrichten ... drücken Sie Ctrl+C zum Beenden.");
+    client.call("subscribe", json!({})).await?;
+    if !out.json {
+        println!("Warte auf Nachrichten (über den Daemon) ... drücken Sie Ctrl+C zum Beenden.");
+    }
     loop {
-        // In einer echten Implementierung würde hier ein Relay‑Abo
-        // verarbeitet.  Zur Demonstration schlafen wir kurz.
-        tokio::time::sleep(Duration::from_secs(5)).await;
-        // Keine Nachrichten verfügbar
+        tokio::select! {
+            note = client.next_notification() => {
+                let Some(note) = note? else { return Err(fail(ErrorKind::Daemon, "Daemon hat die Verbindung beendet")) };
+                if note.method == "message" || (note.method == "signal" && verbose) {
+                    print_event(out, &ListenEvent::Message(&serde_json::from_value(note.params)?))?;
+                }
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    Ok(())
+}
+
+/// Empfang ohne Daemon direkt über die Relays.
+async fn listen_local(out: Output, local: &Local, verbose: bool) -> anyhow::Result<()> {
+    let pool = local.pool.as_ref().ok_or_else(|| fail(ErrorKind::Network, "keine Relays konfiguriert (--relay wss://…)"))?;
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    pool.subscribe(move |env| {
+        let _ = tx.send(env);
+    })
+    .await?;
+    if !out.json {
+        println!("Warte auf Nachrichten ... drücken Sie Ctrl+C zum Beenden.");
     }
-}
\ No newline at end of file
+    loop {
+        tokio::select! {
+            env = rx.recv() => {
+                let Some(env) = env else { return Err(fail(ErrorKind::Network, "Verbindung zu allen Relays verloren")) };
+                match local.handle(&env)? {
+                    Outcome::Received(msg) => print_event(out, &ListenEvent::Message(&api::received_view(msg)))?,
+                    Outcome::Signal(msg) if verbose => print_event(out, &ListenEvent::Message(&api::received_view(msg)))?,
+                    Outcome::Rejected(reason) if verbose => print_event(out, &ListenEvent::Rejected { reason })?,
+                    _ => {}
+                }
+                local.store.set_meta("last_sync", &store::now_ms())?;
+                local.store.flush()?;
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        }
+    }
+    Ok(())
+}
+
+/// Gibt das Ergebnis einer Synchronisation aus.  Verworfene Envelopes
+/// können ebenso fremde sein, daher erscheinen sie nur mit `verbose`.
+fn print_sync(out: Output, synced: &api::SyncResult, verbose: bool) -> anyhow::Result<()> {
+    for msg in &synced.messages {
+        print_event(out, &ListenEvent::Message(msg))?;
+    }
+    if verbose {
+        for reason in &synced.rejected {
+            print_event(out, &ListenEvent::Rejected { reason })?;
+        }
+    }
+    print_event(out, &ListenEvent::Synced { scanned: synced.scanned, messages: synced.messages.len() })
+}
+
+/// Gibt ein Ereignis von `listen` aus.
+fn print_event(out: Output, event: &ListenEvent) -> anyhow::Result<()> {
+    out.emit(event, |event| match event {
+        ListenEvent::Message(msg) => {
+            let warning = if msg.unknown_sender {
+                format!(" [unbekannter Absender, Verlauf: history --with {}]", msg.conversation)
+            } else if msg.key_changed {
+                " [Schlüssel geändert!]".to_owned()
+            } else {
+                String::new()
+            };
+            let peer = match &msg.from {
+                Some(from) => format!("{from} @ {}", msg.peer),
+                None => msg.peer.clone(),
+            };
+            match msg.direction {
+                Direction::Incoming => println!("[{}] {peer}{}: {}", receive::format_ts(msg.ts), warning, msg.text),
+                Direction::Outgoing => println!("[{}] -> {}: {}", receive::format_ts(msg.ts), msg.peer, msg.text),
+            }
+        }
+        ListenEvent::Rejected { reason } => eprintln!("Envelope verworfen: {reason}"),
+        ListenEvent::Synced { scanned, messages } => {
+            println!("{messages} neue Nachricht(en) aus {scanned} Envelopes")
+        }
+    })
+}
+
+/// Listet Konversationen oder zeigt den Verlauf einer Konversation.  Vor
+/// der Anzeige werden ungelesene Nachrichten bestätigt (außer mit
+/// `receipts = false`); scheitert das Senden, bleibt es bei einer Warnung.
+async fn history(out: Output, backend: &mut Backend, with: Option<&str>, ids: bool, receipts: bool) -> anyhow::Result<()> {
+    match with {
+        None => out.emit(&backend.conversations().await?, |convs| {
+            for conv in convs {
+                let timer = match conv.expire_secs {
+                    0 => String::new(),
+                    secs => format!("  ⏱ {}", thread::format_duration(secs)),
+                };
+                println!("{}  {}  ({} ungelesen){timer}", conv.id, conv.title, conv.unread);
+            }
+        }),
+        Some(with) => {
+            if receipts {
+                if let Err(e) = backend.mark_read(with).await {
+                    eprintln!("Warnung: Lesebestätigung nicht gesendet: {e:#}");
+                }
+            }
+            out.emit(&backend.messages(with).await?, |msgs| {
+                for msg in msgs {
+                    println!("{}", history_line(msg, ids));
+                }
+            })
+        }
+    }
+}
+
+/// Eine Zeile des Verlaufs mit Zitat, Reaktionen und Zustellstatus.
+fn history_line(msg: &MessageView, ids: bool) -> String {
+    let mut line = format!("[{}] ", receive::format_ts(msg.ts));
+    if ids {
+        line.push_str(&format!("#{} ", msg.msg_id));
+    }
+    line.push_str(match msg.direction {
+        Direction::Incoming => "<- ",
+        Direction::Outgoing => "-> ",
+    });
+    if let Some(from) = &msg.from {
+        line.push_str(&format!("{from}: "));
+    }
+    if let Some(quote) = &msg.quote {
+        line.push_str(&format!("(↪ „{quote}“) "));
+    }
+    if msg.deleted {
+        line.push_str("(gelöscht)");
+    } else {
+        line.push_str(&msg.text);
+    }
+    if msg.edited && !msg.deleted {
+        line.push_str(" (bearbeitet)");
+    }
+    if !msg.reactions.is_empty() {
+        let emoji: Vec<&str> = msg.reactions.iter().map(|r| r.emoji.as_str()).collect();
+        line.push_str(&format!("  [{}]", emoji.join(" ")));
+    }
+    if msg.state == Some(OutboxState::Pending) {
+        line.push_str("  (ausstehend)");
+    }
+    if let Some(expires_at) = msg.expires_at {
+        line.push_str(&format!("  (⏱ bis {})", receive::format_ts(expires_at)));
+    }
+    line
+}
+
+/// Führt einen `contacts`‑Unterbefehl aus.
+async fn contacts_cmd(out: Output, backend: &mut Backend, action: ContactsCommand) -> anyhow::Result<()> {
+    match action {
+        ContactsCommand::Add { name, identity, view, spend } => {
+            let contact = Contact::new(
+                &name,
+                contacts::parse_key(&view)?,
+                contacts::parse_key(&spend)?,
+                contacts::parse_key(&identity)?,
+                store::now_ms(),
+            );
+            let update = backend.add_contact(contact, false).await?;
+            report_update(out, &name, update, &[])
+        }
+        ContactsCommand::Import { name, source } => {
+            // Bilder und Dateien werden lokal gelesen und die Signatur
+            // geprüft; die ausführende Seite prüft sie erneut.
+            let bundle = contacts::read_bundle(&source)?;
+            let update = backend.import_contact(&name, &bundle.to_text()).await?;
+            report_update(out, &name, update, &bundle.relays)
+        }
+        ContactsCommand::List => out.emit(&backend.contacts().await?, |contacts| {
+            for c in contacts {
+                println!("{:<16} {:<20} {}", c.nickname, c.verification.label(), &c.identity_pub[..16]);
+            }
+        }),
+        ContactsCommand::Remove { name } => {
+            backend.remove_contact(&name).await?;
+            out.emit(&json!({ "removed": name }), |_| println!("Kontakt {name} entfernt"))
+        }
+        ContactsCommand::Verify { name, yes } => {
+            let fp = backend.fingerprint(&name).await?;
+            // Im JSON‑Modus wird nicht nachgefragt: ohne --yes werden nur
+            // die Vergleichswerte ausgegeben.
+            let verified = if out.json {
+                yes
+            } else {
+                let contact = &fp.contact;
+                if contact.verification == Verification::KeyChanged {
+                    println!("Achtung: Der Identity‑Key von {name} hat sich geändert!");
+                    println!("zuerst gesehen: {}\naktuell:        {}\n", contact.first_seen_identity, contact.identity_pub);
+                }
+                println!("Vergleichen Sie mit {name} persönlich oder über einen vertrauenswürdigen Kanal:\n");
+                println!("Safety Number:\n{}\n", fp.safety_number);
+                println!("Emoji:  {}", fp.emoji.join("  "));
+                println!("Wörter: {}\n", fp.words.join(" "));
+                yes || confirm("Stimmen die Werte überein? [j/N] ")?
+            };
+            if verified {
+                backend.verify_contact(&name).await?;
+            }
+            out.emit(&json!({ "fingerprint": fp, "verified": verified }), |_| {
+                if verified {
+                    println!("{name} ist jetzt verifiziert");
+                } else {
+                    println!("Nicht verifiziert");
+                }
+            })
+        }
+    }
+}
+
+/// Führt einen `addresses`‑Unterbefehl aus.
+async fn addresses_cmd(out: Output, backend: &mut Backend, action: AddressesCommand) -> anyhow::Result<()> {
+    match action {
+        AddressesCommand::List => {
+            let now = store::now_ms();
+            out.emit(&backend.addresses().await?, |records| {
+                for r in records {
+                    let state = match r.expires_at {
+                        None => "aktiv".to_owned(),
+                        Some(_) if r.is_active(now) => {
+                            format!("ersetzt, gültig bis {}", receive::format_ts(r.expires_at.unwrap_or(now)))
+                        }
+                        Some(_) => "abgelaufen".to_owned(),
+                    };
+                    println!("{:>4}  {:<16} {}  {}", r.index, r.label, &r.spend_pub[..16], state);
+                }
+            })
+        }
+        AddressesCommand::Rotate { label } => {
+            let record = backend.rotate_address(&label).await?;
+            out.emit(&record, |r| println!("Neue Subadresse {} für {}; Kontakte erfahren sie mit der nächsten Nachricht", r.index, r.label))
+        }
+    }
+}
+
+/// Führt einen `devices`‑Unterbefehl aus.
+async fn devices_cmd(out: Output, backend: &mut Backend, action: DevicesCommand) -> anyhow::Result<()> {
+    match action {
+        DevicesCommand::List => out.emit(&backend.devices().await?, |devices| {
+            if devices.is_empty() {
+                println!("Keine weiteren Geräte verknüpft");
+            }
+            for d in devices {
+                let mark = if d.current { "*" } else { " " };
+                println!("{mark} {:>3}  {:<20} {}  seit {}", d.device_id, d.name, &d.spend_pub[..16], receive::format_ts(d.created_at));
+            }
+        }),
+        DevicesCommand::Add { code } => {
+            let device = backend.add_device(&code).await?;
+            out.emit(&device, |d| {
+                println!("Gerät {} ({}) verknüpft; Kontakte erfahren es mit der nächsten Nachricht", d.device_id, d.name)
+            })
+        }
+        DevicesCommand::Remove { device_id } => {
+            backend.remove_device(device_id).await?;
+            out.emit(&json!({ "removed": device_id }), |_| {
+                println!("Gerät {device_id} entfernt; Kontakte erfahren es mit der nächsten Nachricht")
+            })
+        }
+    }
+}
+
+/// Führt einen `groups`‑Unterbefehl aus.
+async fn groups_cmd(out: Output, backend: &mut Backend, action: GroupsCommand) -> anyhow::Result<()> {
+    match action {
+        GroupsCommand::Create { name, members } => {
+            let group = backend.create_group(&name, &members).await?;
+            out.emit(&group, |g| println!("Gruppe {} gegründet mit {}", g.name, g.members.join(", ")))
+        }
+        GroupsCommand::List => out.emit(&backend.groups().await?, |groups| {
+            if groups.is_empty() {
+                println!("Keine Gruppen");
+            }
+            for g in groups {
+                let admin = if g.admin { "*" } else { " " };
+                let pending = if g.pending > 0 { format!("  ({} ausstehend)", g.pending) } else { String::new() };
+                println!("{admin} {:<20} {}  Epoche {}  {}{pending}", g.name, &g.id[..16], g.epoch, g.members.join(", "));
+            }
+        }),
+        GroupsCommand::Add { group, member } => {
+            let group = backend.add_member(&group, &member).await?;
+            out.emit(&group, |g| println!("{member} in {} aufgenommen (Epoche {})", g.name, g.epoch))
+        }
+        GroupsCommand::Remove { group, member } => {
+            let group = backend.remove_member(&group, &member).await?;
+            out.emit(&group, |g| println!("{member} aus {} entfernt (Epoche {})", g.name, g.epoch))
+        }
+        GroupsCommand::Leave { group } => {
+            let unreached = backend.leave_group(&group).await?;
+            out.emit(&json!({ "left": group, "unreached": unreached }), |_| {
+                println!("Gruppe {group} verlassen");
+                if unreached > 0 {
+                    eprintln!("Warnung: {unreached} Mitglied(er) nicht erreicht");
+                }
+            })
+        }
+    }
+}
+
+/// Meldet das Ergebnis von `contacts add`/`import`.
+fn report_update(out: Output, name: &str, update: KeyUpdate, relays: &[String]) -> anyhow::Result<()> {
+    out.emit(&json!({ "contact": name, "update": update, "relays": relays }), |_| {
+        match update {
+            KeyUpdate::Added => println!("Kontakt {name} hinzugefügt (unverifiziert)"),
+            KeyUpdate::Unchanged => println!("Schlüssel von {name} unverändert"),
+            KeyUpdate::Updated => println!("Schlüssel von {name} aktualisiert (signiert mit bekanntem Identity‑Key)"),
+            KeyUpdate::Changed => {
+                eprintln!("WARNUNG: Die Schlüssel von {name} haben sich geändert.");
+                eprintln!("Prüfen Sie die Safety Number mit `contacts verify {name}`.");
+            }
+        }
+        if !relays.is_empty() {
+            println!("Relays: {}", relays.join(", "));
+        }
+    })
+}
+
+/// Fragt auf dem Terminal nach einer Ja/Nein‑Bestätigung.
+fn confirm(prompt: &str) -> anyhow::Result<bool> {
+    use std::io::Write;
+    print!("{prompt}");
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "j" | "ja" | "y" | "yes"))
+}
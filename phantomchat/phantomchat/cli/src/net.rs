@@ -0,0 +1,46 @@
+//! Relay‑Anbindung des CLI.
+//!
+//! Baut aus den Relays des Profils bzw. den per `--relay` angegebenen URLs
+//! einen [`RelayPool`] aus Nostr‑Relays.  Mit `--tor` laufen alle Verbindungen
+//! über den lokalen Tor‑SOCKS‑Proxy; die Stream‑Isolation erfolgt pro
+//! Identität.
+
+use crate::output::{fail, ErrorKind};
+use phantomchat_core::util::to_hex;
+use phantomchat_relays::{NostrRelay, RelayConfig, RelayPool};
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+/// Netzwerkoptionen aus Profil und Kommandozeile.
+#[derive(Debug, Clone, Default)]
+pub struct NetOptions {
+    pub relays: Vec<String>,
+    pub tor: bool,
+    /// Abweichende Adresse des Tor‑SOCKS‑Proxys.
+    pub tor_proxy: Option<SocketAddr>,
+}
+
+impl NetOptions {
+    pub fn is_configured(&self) -> bool {
+        !self.relays.is_empty()
+    }
+    /// Verbindet die konfigurierten Relays zu einem Pool.
+    pub fn pool(&self, identity_pub: &[u8; 32]) -> anyhow::Result<RelayPool<NostrRelay>> {
+        if self.relays.is_empty() {
+            return Err(fail(ErrorKind::Network, "keine Relays konfiguriert (--relay wss://…)"));
+        }
+        let identity = to_hex(identity_pub);
+        let relays = self
+            .relays
+            .iter()
+            .map(|url| {
+                let mut config = if self.tor { RelayConfig::tor(url) } else { RelayConfig::direct(url) };
+                if let (Some(tor), Some(proxy)) = (config.tor.as_mut(), self.tor_proxy) {
+                    tor.proxy = proxy;
+                }
+                Arc::new(NostrRelay::with_config(config, &identity))
+            })
+            .collect();
+        Ok(RelayPool::new(relays))
+    }
+}
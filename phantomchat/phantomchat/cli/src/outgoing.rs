@@ -0,0 +1,319 @@
+//! Sendepfad des CLI.
+//!
+//! Eine Nachricht wird mit der Ratchet‑Session der Konversation
+//! verschlüsselt, in ein Envelope verpackt und zusammen mit einem
+//! Postausgangseintrag gespeichert, bevor sie veröffentlicht wird.  So geht
+//! bei einem Abbruch nichts verloren, und der Zustand im Postausgang
+//! (`PENDING → PUBLISHED`) zeigt, ob die Relays sie angenommen haben.
+//!
+//! Nachrichten an Kontakte tragen im Ratchet‑Header die Subadresse, unter
+//! der wir für diesen Kontakt erreichbar sind (siehe [`crate::addresses`]).
+//!
+//! Hat das Gegenüber mehrere Geräte, erhält jedes ein eigenes Envelope aus
+//! einer eigenen Session; unsere übrigen Geräte erhalten eine Abschrift
+//! (siehe [`crate::devices`]).  Alle Envelopes einer Nachricht teilen die
+//! `msg_id` und landen im selben Postausgangseintrag.
+//!
+//! Lesebestätigungen und Tipp‑Hinweise sind flüchtig: Sie werden weder
+//! gespeichert noch in den Postausgang gelegt noch abgeschrieben.  Dasselbe
+//! gilt für Steuernachrichten von Gruppen (siehe [`crate::groups`]).
+
+use crate::addresses::{self, AddressPolicy};
+use crate::contacts::Contact;
+use crate::devices::{self, OwnDevices, SyncMessage, SELF_CONVERSATION};
+use crate::keystore::Keys;
+use crate::output::{fail, ErrorKind};
+use crate::session;
+use crate::store::{self, Direction, OutboxEntry, OutboxState, StoredMessage, Store};
+use phantomchat_core::device::PRIMARY_DEVICE;
+use phantomchat_core::util::{from_hex, to_hex};
+use phantomchat_core::{Content, DeviceCertificate, Envelope, PaddingPolicy, RatchetHeader, Sender, Subaddress};
+use phantomchat_relays::BridgeProvider;
+use rand_core::{OsRng, RngCore};
+use x25519_dalek::PublicKey;
+
+/// TTL ausgehender Envelopes in Sekunden.
+pub const MESSAGE_TTL: u32 = 60;
+
+/// Parameter ausgehender Envelopes (aus dem Profil, siehe
+/// [`crate::config`]).
+#[derive(Debug, Clone)]
+pub struct SendPolicy {
+    /// TTL in Sekunden.
+    pub ttl: u32,
+    /// Proof‑of‑Work in Bit.
+    pub pow_bits: u32,
+    pub padding: PaddingPolicy,
+    /// Rotation der eigenen Subadressen.
+    pub addresses: AddressPolicy,
+}
+
+impl Default for SendPolicy {
+    fn default() -> Self {
+        Self {
+            ttl: MESSAGE_TTL,
+            pow_bits: crate::receive::MIN_POW_BITS,
+            padding: PaddingPolicy::default(),
+            addresses: AddressPolicy::default(),
+        }
+    }
+}
+
+/// Ein einzelnes Gerät als Empfänger.
+#[derive(Debug, Clone)]
+pub struct DeviceTarget {
+    pub device_id: u32,
+    pub address: Subaddress,
+    /// ML‑KEM‑Schlüssel des Geräts, falls bekannt.
+    #[cfg_attr(not(feature = "pqc"), allow(dead_code))]
+    pub kem_pub: Option<Vec<u8>>,
+}
+
+impl DeviceTarget {
+    pub fn from_certificate(cert: &DeviceCertificate) -> Self {
+        Self { device_id: cert.device_id, address: cert.address(), kem_pub: cert.kem_pub.clone() }
+    }
+}
+
+/// Empfänger einer Nachricht.
+#[derive(Debug, Clone)]
+pub struct Peer {
+    /// Adresse des (Primär‑)Geräts des Gegenübers (Haupt‑ oder Subadresse).
+    pub address: Subaddress,
+    pub conversation: String,
+    /// Anzeigename der Konversation.
+    pub title: String,
+    /// Label unserer Subadresse für dieses Gegenüber; nur bei Kontakten.
+    pub label: Option<String>,
+    /// ML‑KEM‑Schlüssel des Gegenübers, falls bekannt.
+    pub kem_pub: Option<Vec<u8>>,
+    /// Weitere Geräte des Gegenübers aus seiner Geräteliste.
+    pub devices: Vec<DeviceTarget>,
+}
+
+impl Peer {
+    pub fn from_contact(contact: &Contact) -> anyhow::Result<Self> {
+        let devices = match contact.device_list()? {
+            Some(list) => list.devices.iter().filter(|d| d.device_id != PRIMARY_DEVICE).map(DeviceTarget::from_certificate).collect(),
+            None => Vec::new(),
+        };
+        Ok(Self {
+            address: contact.address()?,
+            conversation: contact.conversation_id(),
+            title: contact.nickname.clone(),
+            label: Some(contact.nickname.clone()),
+            kem_pub: contact.kem_public()?,
+            devices,
+        })
+    }
+    /// Empfänger ohne Kontaktbucheintrag.
+    pub fn from_key(spend_pub: PublicKey) -> Self {
+        let hex = to_hex(spend_pub.as_bytes());
+        Self {
+            address: Subaddress::primary(&spend_pub),
+            title: hex[..16].to_owned(),
+            conversation: hex,
+            label: None,
+            kem_pub: None,
+            devices: Vec::new(),
+        }
+    }
+    /// Gegenüber einer bestehenden Konversation: Kontakt oder, bei
+    /// Konversationen ohne Kontakt, der Spend‑Key aus der ID.
+    /// Konversationen mit unbekannten Absendern (`fp:…`) haben keinen
+    /// Rückkanal.
+    pub fn for_conversation(store: &Store, conversation: &str) -> anyhow::Result<Option<Self>> {
+        if let Some(contact) = store.contact_for_conversation(conversation)? {
+            return Ok(Some(Self::from_contact(&contact)?));
+        }
+        Ok(from_hex(conversation)
+            .and_then(|bytes| <[u8; 32]>::try_from(bytes.as_slice()).ok())
+            .map(|key| Self::from_key(PublicKey::from(key))))
+    }
+    pub fn conversation_id(&self) -> String {
+        self.conversation.clone()
+    }
+    /// Alle Geräte des Gegenübers, das Primärgerät zuerst.
+    pub fn targets(&self) -> Vec<DeviceTarget> {
+        let primary = DeviceTarget { device_id: PRIMARY_DEVICE, address: self.address, kem_pub: self.kem_pub.clone() };
+        std::iter::once(primary).chain(self.devices.iter().cloned()).collect()
+    }
+}
+
+/// Eine verschlüsselte, gespeicherte, aber noch nicht veröffentlichte
+/// Nachricht.
+pub struct Prepared {
+    pub msg_id: u128,
+    /// Envelope an das Primärgerät des Gegenübers.
+    pub envelope: Envelope,
+    /// Envelopes an dessen weitere Geräte und an unsere eigenen.
+    pub fanout: Vec<Envelope>,
+}
+
+/// Verschlüsselt `body` für `peer` und speichert Nachricht und
+/// Postausgangseintrag.  Mit Feature `pqc` erhalten Gegenüber mit
+/// KEM‑Schlüssel hybride Envelopes, alle anderen klassische.  Kontakte
+/// erfahren dabei unsere aktuelle Subadresse für sie und, falls neu,
+/// unsere Geräteliste.  `body` ist ein kodierter [`Content`].
+pub fn prepare(keys: &Keys, store: &Store, policy: &SendPolicy, peer: &Peer, body: &[u8]) -> anyhow::Result<Prepared> {
+    let conversation = peer.conversation_id();
+    let signal = Content::from_bytes(body).is_ok_and(|c| c.is_signal() || c.is_control());
+    let own = OwnDevices::load(store)?;
+    let own_list = own.list()?;
+    let mut contact = match &peer.label {
+        Some(name) => store.contact(name)?,
+        None => None,
+    };
+    let sender_device = (!own.is_primary()).then_some(own.device_id);
+    let mut extras = RatchetHeader { sender_device, ..RatchetHeader::default() };
+    if let (Some(label), true) = (&peer.label, own.is_primary()) {
+        let reply = addresses::current(store, keys, label, &policy.addresses, store::now_ms())?;
+        extras.reply_address = Some(reply.to_bytes().to_vec());
+    }
+    if let (Some(list), Some(contact)) = (&own_list, &mut contact) {
+        if contact.devices_announced < list.version {
+            extras.device_list = Some(list.to_bytes());
+            contact.devices_announced = list.version;
+        }
+    }
+    let msg_id = OsRng.next_u64() as u128;
+    let mut envelopes = Vec::new();
+    for target in peer.targets() {
+        let session_id = devices::session_id(&conversation, target.device_id);
+        envelopes.push(encrypt(keys, store, policy, &own, &session_id, &target, msg_id, &extras, body)?);
+    }
+    let ts = envelopes[0].ts;
+    // Abschrift an die eigenen übrigen Geräte, stets mit aktueller Liste
+    let others = if signal { Vec::new() } else { own.others()? };
+    if !others.is_empty() {
+        let transcript = serde_json::to_vec(&SyncMessage::Sent {
+            conversation: conversation.clone(),
+            title: peer.title.clone(),
+            contact: contact.clone(),
+            msg_id,
+            ts,
+            body: body.to_vec(),
+        })?;
+        let extras = RatchetHeader {
+            sender_device,
+            device_list: own_list.as_ref().map(|list| list.to_bytes()),
+            ..RatchetHeader::default()
+        };
+        for target in &others {
+            let session_id = devices::session_id(SELF_CONVERSATION, target.device_id);
+            envelopes.push(encrypt(keys, store, policy, &own, &session_id, target, msg_id, &extras, &transcript)?);
+        }
+    }
+    if let Some(contact) = &contact {
+        store.save_contact(contact)?;
+    }
+    let envelope = envelopes.remove(0);
+    if !signal {
+        store.add_message(
+            &StoredMessage {
+                conversation: conversation.clone(),
+                msg_id,
+                direction: Direction::Outgoing,
+                ts,
+                body: body.to_vec(),
+                expires_at: None,
+                sender: None,
+            },
+            &peer.title,
+        )?;
+        store.put_outbox(&OutboxEntry {
+            msg_id,
+            conversation,
+            envelope: envelope.to_bytes(),
+            fanout: envelopes.iter().map(Envelope::to_bytes).collect(),
+            state: OutboxState::Pending,
+            attempts: 0,
+            updated_at: store::now_ms(),
+        })?;
+    }
+    store.flush()?;
+    Ok(Prepared { msg_id, envelope, fanout: envelopes })
+}
+
+/// Verschlüsselt `body` in der Session mit einem Gerät und versiegelt
+/// das Envelope.  `extras` liefert die zusätzlichen Header‑Felder.
+#[allow(clippy::too_many_arguments)]
+fn encrypt(
+    keys: &Keys,
+    store: &Store,
+    policy: &SendPolicy,
+    own: &OwnDevices,
+    session_id: &str,
+    target: &DeviceTarget,
+    msg_id: u128,
+    extras: &RatchetHeader,
+    body: &[u8],
+) -> anyhow::Result<Envelope> {
+    let mut session = session::outgoing(store, session_id, keys, own.device_id, target)?;
+    let (ciphertext, mut ratchet_header) = session.current().encrypt_with_header(body);
+    ratchet_header.reply_address = extras.reply_address.clone();
+    ratchet_header.device_list = extras.device_list.clone();
+    ratchet_header.sender_device = extras.sender_device;
+    let envelope = seal(keys, own.device_id, policy, target, msg_id, ratchet_header.to_bytes(), ciphertext)?;
+    store.save_session(session_id, &session)?;
+    Ok(envelope)
+}
+
+/// Baut das Envelope an ein Gerät, hybrid sofern möglich.  Die Payload
+/// trägt das Absenderzertifikat von Gerät `device_id` unserer Identität.
+pub fn seal(
+    keys: &Keys,
+    device_id: u32,
+    policy: &SendPolicy,
+    target: &DeviceTarget,
+    msg_id: u128,
+    ratchet_header: Vec<u8>,
+    ciphertext: Vec<u8>,
+) -> anyhow::Result<Envelope> {
+    let sender = Sender { identity: &keys.identity, device_id };
+    #[cfg(feature = "pqc")]
+    if let Some(kem_pub) = &target.kem_pub {
+        return Envelope::new_hybrid(
+            &target.address,
+            kem_pub,
+            msg_id,
+            &sender,
+            ratchet_header,
+            ciphertext,
+            policy.ttl,
+            policy.pow_bits,
+            &policy.padding,
+        )
+        .ok_or_else(|| fail(ErrorKind::InvalidInput, "ungültiger KEM‑Schlüssel des Kontakts"));
+    }
+    Ok(Envelope::new_padded(
+        &target.address,
+        msg_id,
+        &sender,
+        ratchet_header,
+        ciphertext,
+        policy.ttl,
+        policy.pow_bits,
+        &policy.padding,
+    ))
+}
+
+/// Veröffentlicht eine vorbereitete Nachricht und aktualisiert den
+/// Postausgang.
+pub async fn publish<P: BridgeProvider>(store: &Store, relay: &P, prepared: Prepared) -> anyhow::Result<()> {
+    let mut result = relay.publish(prepared.envelope).await;
+    for envelope in prepared.fanout {
+        let published = relay.publish(envelope).await;
+        result = result.and(published);
+    }
+    if let Some(mut entry) = store.outbox_entry(prepared.msg_id)? {
+        entry.attempts += 1;
+        entry.updated_at = store::now_ms();
+        if result.is_ok() {
+            entry.state = OutboxState::Published;
+        }
+        store.put_outbox(&entry)?;
+        store.flush()?;
+    }
+    result.map_err(|e| fail(ErrorKind::Network, format!("Veröffentlichen fehlgeschlagen: {e:#}")))
+}
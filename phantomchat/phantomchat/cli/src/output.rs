@@ -0,0 +1,130 @@
+//! Ausgabe für Menschen oder Skripte.
+//!
+//! Mit `--json` geben alle Befehle strukturierte Daten statt deutscher
+//! Prosa aus: ein JSON‑Objekt pro Befehl bzw. eine Zeile pro Ereignis bei
+//! `listen`.  Fehler erscheinen dann als
+//! `{"error":{"kind":"not_found","exit_code":4,"message":"…"}}`.
+//!
+//! Die Exit‑Codes sind stabil und unabhängig von `--json`:
+//!
+//! | Code | `kind`          | Bedeutung |
+//! |------|-----------------|-----------|
+//! | 0    | –               | Erfolg |
+//! | 1    | `general`       | sonstiger Fehler |
+//! | 2    | `usage`         | ungültige Argumente |
+//! | 3    | `auth`          | falsche Passphrase, Schlüsselspeicher nicht entsperrbar |
+//! | 4    | `not_found`     | Kontakt, Konversation oder Datei fehlt |
+//! | 5    | `invalid_input` | ungültiger Schlüssel, Spitzname oder Pairing‑Code |
+//! | 6    | `network`       | keine oder keine erreichbaren Relays |
+//! | 7    | `daemon`        | Daemon nicht erreichbar oder Protokollfehler |
+
+use crate::rpc::{self, RpcError};
+use phantomchat_core::PairingError;
+use serde::{Deserialize, Serialize};
+use std::process::ExitCode;
+
+/// Fehlerklasse mit festem Exit‑Code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    General,
+    Usage,
+    Auth,
+    NotFound,
+    InvalidInput,
+    Network,
+    Daemon,
+}
+
+impl ErrorKind {
+    pub fn exit_code(self) -> u8 {
+        match self {
+            ErrorKind::General => 1,
+            ErrorKind::Usage => 2,
+            ErrorKind::Auth => 3,
+            ErrorKind::NotFound => 4,
+            ErrorKind::InvalidInput => 5,
+            ErrorKind::Network => 6,
+            ErrorKind::Daemon => 7,
+        }
+    }
+}
+
+/// Fehler mit bekannter Klasse.
+#[derive(Debug)]
+pub struct Failure {
+    pub kind: ErrorKind,
+    pub message: String,
+}
+
+impl std::fmt::Display for Failure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Failure {}
+
+/// Baut einen klassifizierten Fehler.
+pub fn fail(kind: ErrorKind, message: impl Into<String>) -> anyhow::Error {
+    Failure { kind, message: message.into() }.into()
+}
+
+/// Ordnet einen Fehler einer Klasse zu.  Maßgeblich ist die innerste
+/// bekannte Ursache in der Fehlerkette.
+pub fn classify(err: &anyhow::Error) -> ErrorKind {
+    for cause in err.chain() {
+        if let Some(failure) = cause.downcast_ref::<Failure>() {
+            return failure.kind;
+        }
+        if cause.is::<PairingError>() {
+            return ErrorKind::InvalidInput;
+        }
+        if let Some(rpc) = cause.downcast_ref::<RpcError>() {
+            return match rpc.code {
+                rpc::INVALID_PARAMS => ErrorKind::InvalidInput,
+                rpc::APPLICATION_ERROR => ErrorKind::General,
+                _ => ErrorKind::Daemon,
+            };
+        }
+        if let Some(io) = cause.downcast_ref::<std::io::Error>() {
+            if io.kind() == std::io::ErrorKind::NotFound {
+                return ErrorKind::NotFound;
+            }
+        }
+    }
+    ErrorKind::General
+}
+
+/// Ausgabemodus eines Aufrufs.
+#[derive(Debug, Clone, Copy)]
+pub struct Output {
+    pub json: bool,
+}
+
+impl Output {
+    /// Gibt `value` als JSON‑Zeile aus oder ruft `human` für die
+    /// Textausgabe auf.
+    pub fn emit<T: Serialize>(&self, value: &T, human: impl FnOnce(&T)) -> anyhow::Result<()> {
+        if self.json {
+            println!("{}", serde_json::to_string(value)?);
+        } else {
+            human(value);
+        }
+        Ok(())
+    }
+
+    /// Meldet einen Fehler und liefert den passenden Exit‑Code.
+    pub fn error(&self, err: &anyhow::Error) -> ExitCode {
+        let kind = classify(err);
+        if self.json {
+            let error = serde_json::json!({
+                "error": { "kind": kind, "exit_code": kind.exit_code(), "message": format!("{err:#}") }
+            });
+            println!("{error}");
+        } else {
+            eprintln!("Fehler: {err:#}");
+        }
+        ExitCode::from(kind.exit_code())
+    }
+}
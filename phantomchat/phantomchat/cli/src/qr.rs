@@ -0,0 +1,90 @@
+//! QR‑Codes für das Pairing.
+//!
+//! Erzeugt QR‑Codes aus der Textform eines
+//! [`PairingBundle`](phantomchat_core::PairingBundle) und gibt sie im
+//! Terminal, als SVG oder als PNG aus.  Umgekehrt werden Codes aus
+//! Bilddateien gelesen.
+
+use crate::output::{fail, ErrorKind};
+use anyhow::{anyhow, Context};
+use qrcodegen::{QrCode, QrCodeEcc};
+use std::path::Path;
+
+/// Ruhezone um den Code in Modulen.
+const BORDER: i32 = 2;
+
+/// Kodiert `text` als QR‑Code.  Die mittlere Fehlerkorrektur hält den
+/// Code bei typischen Bundle‑Größen noch gut scanbar; Bundles mit
+/// KEM‑Schlüssel passen notfalls nur mit niedriger Fehlerkorrektur.
+pub fn encode(text: &str) -> anyhow::Result<QrCode> {
+    QrCode::encode_text(text, QrCodeEcc::Medium)
+        .or_else(|_| QrCode::encode_text(text, QrCodeEcc::Low))
+        .map_err(|_| anyhow!("Daten zu groß für einen QR‑Code"))
+}
+
+/// Darstellung im Terminal mit Halbblock‑Zeichen (zwei Modulzeilen pro
+/// Textzeile).  Helle Module werden gezeichnet, so dass der Code auf
+/// dunklem Terminalhintergrund korrekt erscheint.
+pub fn render_terminal(code: &QrCode) -> String {
+    let size = code.size();
+    let light = |x: i32, y: i32| !code.get_module(x, y);
+    let mut out = String::new();
+    let mut y = -BORDER;
+    while y < size + BORDER {
+        for x in -BORDER..size + BORDER {
+            out.push(match (light(x, y), light(x, y + 1)) {
+                (true, true) => '█',
+                (true, false) => '▀',
+                (false, true) => '▄',
+                (false, false) => ' ',
+            });
+        }
+        out.push('\n');
+        y += 2;
+    }
+    out
+}
+
+/// SVG‑Darstellung (ein Pfad, schwarze Module auf weißem Grund).
+pub fn to_svg(code: &QrCode) -> String {
+    let size = code.size();
+    let dim = size + 2 * BORDER;
+    let mut path = String::new();
+    for y in 0..size {
+        for x in 0..size {
+            if code.get_module(x, y) {
+                path.push_str(&format!("M{},{}h1v1h-1z", x + BORDER, y + BORDER));
+            }
+        }
+    }
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <svg xmlns=\"http://www.w3.org/2000/svg\" version=\"1.1\" viewBox=\"0 0 {dim} {dim}\" stroke=\"none\">\n\
+         <rect width=\"100%\" height=\"100%\" fill=\"#FFFFFF\"/>\n\
+         <path d=\"{path}\" fill=\"#000000\"/>\n\
+         </svg>\n"
+    )
+}
+
+/// Schreibt den Code als PNG mit `scale` Pixeln pro Modul.
+pub fn save_png(code: &QrCode, path: &Path, scale: u32) -> anyhow::Result<()> {
+    let dim = (code.size() + 2 * BORDER) as u32 * scale;
+    let img = image::GrayImage::from_fn(dim, dim, |px, py| {
+        let x = (px / scale) as i32 - BORDER;
+        let y = (py / scale) as i32 - BORDER;
+        image::Luma([if code.get_module(x, y) { 0 } else { 255 }])
+    });
+    img.save(path).with_context(|| format!("PNG {:?} nicht schreibbar", path))
+}
+
+/// Liest den ersten dekodierbaren QR‑Code aus einer Bilddatei.
+pub fn decode_image(path: &Path) -> anyhow::Result<String> {
+    let img = image::open(path).with_context(|| format!("Bild {:?} nicht lesbar", path))?.to_luma8();
+    let mut prepared = rqrr::PreparedImage::prepare(img);
+    for grid in prepared.detect_grids() {
+        if let Ok((_, content)) = grid.decode() {
+            return Ok(content);
+        }
+    }
+    Err(fail(ErrorKind::InvalidInput, format!("kein lesbarer QR‑Code in {:?}", path)))
+}
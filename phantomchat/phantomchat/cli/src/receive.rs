@@ -0,0 +1,535 @@
+//! Empfangspipeline des CLI.
+//!
+//! Jedes Envelope von den Relays durchläuft dieselben Schritte
+//! (SPEC.md 4.3): Ablauf‑ und Proof‑of‑Work‑Prüfung, Entschlüsseln mit dem
+//! Spend‑Key (und ggf. KEM‑Schlüssel) samt Tag‑Prüfung, Replay‑Prüfung, Ratchet, Speichern.  Die msg_id
+//! landet erst nach vollständiger Annahme im Replay‑Cache.  Die
+//! billigen Prüfungen kommen zuerst, damit fremde oder ungültige
+//! Envelopes keine teure Kryptographie auslösen.
+//!
+//! Die Tag‑Prüfung erkennt neben der Hauptadresse alle vergebenen
+//! Subadressen; Envelopes an abgelaufene Subadressen werden verworfen.
+//! Schickt ein Kontakt eine neue Adresse mit, wird sie übernommen.
+//!
+//! Hat der Kontakt mehrere Geräte, gibt es je Gerät eine Session; Geräte,
+//! die nicht in seiner Geräteliste stehen, werden abgelehnt.  Envelopes
+//! mit dem eigenen Fingerprint stammen von unseren anderen Geräten und
+//! tragen Abschriften gesendeter Nachrichten (siehe [`crate::devices`]).
+//!
+//! Absender werden allein über das geprüfte Absenderzertifikat der Payload
+//! zugeordnet ([`phantomchat_core::sealed`]); Payloads ohne Zertifikat
+//! gelten als von Unbekannten, auch wenn `sender_fp` passt.
+//!
+//! Lesebestätigungen und Tipp‑Hinweise ([`Content::is_signal`]) werden
+//! nicht gespeichert; eine Lesebestätigung setzt die betroffenen
+//! Postausgangseinträge auf `Acked`.
+//!
+//! Envelopes, die keine eigene Adresse treffen, werden gegen die
+//! Gruppenadressen geprüft (siehe [`crate::groups`]); Gruppennachrichten
+//! entschlüsselt der Sender‑Key des Mitglieds aus dem Absenderzertifikat.
+//! Steuernachrichten von Gruppen kommen über die paarweisen Sessions und
+//! ändern nur den Gruppenzustand.  Mitglieder ohne Kontakt haben dafür eine
+//! eigene Session ([`crate::groups::member_conversation`]).
+
+use crate::addresses::AddressRecord;
+use crate::contacts::Contact;
+use crate::devices::{self, OwnDevices, SyncMessage, SELF_CONVERSATION};
+use crate::groups::{self, Group};
+use crate::keystore::Keys;
+use crate::session;
+use crate::store::{self, Direction, OutboxState, StoredMessage, Store};
+use phantomchat_core::device::PRIMARY_DEVICE;
+use phantomchat_core::util::to_hex;
+use phantomchat_core::{Content, ContentError, DeviceList, Envelope, Payload, RatchetHeader, Subaddress};
+
+/// Mindestschwierigkeit des Proof‑of‑Work eingehender Envelopes.
+pub const MIN_POW_BITS: u32 = 16;
+/// Erlaubte Abweichung der Senderuhr in die Zukunft.
+pub const MAX_CLOCK_SKEW_MS: u64 = 5 * 60 * 1000;
+
+/// Eine empfangene Nachricht.
+#[derive(Debug, Clone)]
+pub struct Received {
+    pub conversation: String,
+    /// Spitzname des Kontakts bzw. Kurz‑Fingerprint bei Unbekannten.
+    pub sender: String,
+    /// `Outgoing` bei Abschriften, die ein anderes eigenes Gerät gesendet hat.
+    pub direction: Direction,
+    pub contact: Option<Contact>,
+    /// Name der Gruppe bei Gruppennachrichten.
+    pub group: Option<String>,
+    pub msg_id: u128,
+    pub ts: u64,
+    pub body: Vec<u8>,
+}
+
+impl Received {
+    /// Dekodierter Inhalt des Bodys.
+    pub fn content(&self) -> Result<Content, ContentError> {
+        Content::from_bytes(&self.body)
+    }
+
+    /// Absender, bei Gruppennachrichten mit Gruppe.
+    pub fn origin(&self) -> String {
+        match &self.group {
+            Some(group) => format!("{} in {group}", self.sender),
+            None => self.sender.clone(),
+        }
+    }
+}
+
+/// Ergebnis der Verarbeitung eines Envelopes.
+#[derive(Debug)]
+pub enum Outcome {
+    Received(Received),
+    /// Lesebestätigung, Tipp‑Hinweis oder Steuernachricht einer Gruppe;
+    /// nicht gespeichert.
+    Signal(Received),
+    /// Nicht für uns bestimmt (der Normalfall).
+    NotForUs,
+    /// Bereits verarbeitet.
+    Replay,
+    /// Verworfen, mit Grund.
+    Rejected(&'static str),
+}
+
+/// Verarbeitet Envelopes für eine entsperrte Identität.
+pub struct Receiver<'a> {
+    keys: &'a Keys,
+    store: &'a Store,
+    min_pow_bits: u32,
+}
+
+impl<'a> Receiver<'a> {
+    pub fn new(keys: &'a Keys, store: &'a Store) -> Self {
+        Self { keys, store, min_pow_bits: MIN_POW_BITS }
+    }
+
+    /// Verlangt mindestens `bits` Proof‑of‑Work.
+    pub fn with_min_pow_bits(mut self, bits: u32) -> Self {
+        self.min_pow_bits = bits;
+        self
+    }
+
+    /// Prüft, entschlüsselt und speichert ein Envelope.
+    pub fn handle(&self, env: &Envelope) -> anyhow::Result<Outcome> {
+        let now = store::now_ms();
+        if env.is_expired(now) {
+            return Ok(Outcome::Rejected("abgelaufen"));
+        }
+        if env.ts > now + MAX_CLOCK_SKEW_MS {
+            return Ok(Outcome::Rejected("Zeitstempel in der Zukunft"));
+        }
+        if !env.verify_pow(self.min_pow_bits) {
+            return Ok(Outcome::Rejected("Proof‑of‑Work ungenügend"));
+        }
+        let Some((payload, address)) = self.open(env)? else {
+            return match self.open_group(env)? {
+                Some((payload, group)) => self.handle_group(env, payload, group),
+                None => Ok(Outcome::NotForUs),
+            };
+        };
+        if address.is_some_and(|a| !a.is_active(now)) {
+            return Ok(Outcome::Rejected("Subadresse abgelaufen"));
+        }
+        if self.store.is_replay(payload.msg_id)? {
+            return Ok(Outcome::Replay);
+        }
+        let header = RatchetHeader::from_bytes(&payload.ratchet_header);
+        let claimed_device = header.as_ref().and_then(|h| h.sender_device).unwrap_or(PRIMARY_DEVICE);
+        let sender_device = payload.sender.as_ref().map_or(claimed_device, |cert| cert.device_id);
+        if sender_device != claimed_device {
+            return Ok(Outcome::Rejected("Geräteangabe widerspricht dem Absenderzertifikat"));
+        }
+        let sender_identity = payload.sender.as_ref().map(|cert| cert.identity_pub);
+        let own = OwnDevices::load(self.store)?;
+        let from_self = sender_identity == Some(self.keys.identity.public);
+        if from_self && own.list.is_some() && sender_device != own.device_id {
+            return self.handle_sync(env, &payload, header.as_ref(), own, sender_device);
+        }
+        let mut contact = match &sender_identity {
+            Some(identity) => self.find_sender(identity)?,
+            None => None,
+        };
+        if let (Some(contact), Some(bytes)) = (&mut contact, header.as_ref().and_then(|h| h.device_list.as_deref())) {
+            let Ok(list) = DeviceList::from_bytes(bytes) else {
+                return Ok(Outcome::Rejected("ungültige Geräteliste"));
+            };
+            if contact.set_device_list(&list)? {
+                self.store.save_contact(contact)?;
+            }
+        }
+        if let Some(contact) = &contact {
+            if !contact.has_device(sender_device)? {
+                return Ok(Outcome::Rejected("Gerät nicht autorisiert"));
+            }
+        }
+        // Gruppenmitglieder ohne Kontakt haben eine eigene Session
+        let member = match (&contact, &sender_identity) {
+            (None, Some(identity)) => self.find_member(identity)?,
+            _ => None,
+        };
+        let (conversation, sender, peer_identity) = match (&contact, &member) {
+            (Some(c), _) => (c.conversation_id(), c.nickname.clone(), c.identity_public()?),
+            (None, Some(m)) => (groups::member_conversation(&m.identity_pub), m.name.clone(), m.identity_pub),
+            (None, None) => {
+                let fp = format!("{:08x}", payload.sender_fp);
+                (format!("fp:{fp}"), format!("unbekannt ({fp})"), sender_identity.unwrap_or(self.keys.identity.public))
+            }
+        };
+        let session_id = devices::session_id(&conversation, sender_device);
+        let Some(body) = self.decrypt(&payload, &session_id, own.device_id, &peer_identity, sender_device)? else {
+            return Ok(Outcome::Rejected("Ratchet‑Entschlüsselung fehlgeschlagen"));
+        };
+        // Nur das Primärgerät vergibt Subadressen
+        let reply = header
+            .filter(|_| sender_device == PRIMARY_DEVICE)
+            .and_then(|h| h.reply_address)
+            .and_then(|bytes| Subaddress::from_bytes(&bytes));
+        if let (Some(mut contact), Some(reply)) = (contact.clone(), reply) {
+            if contact.set_address(&reply) {
+                self.store.save_contact(&contact)?;
+            }
+        }
+        let received = Received {
+            conversation,
+            sender,
+            direction: Direction::Incoming,
+            contact,
+            group: None,
+            msg_id: payload.msg_id,
+            ts: env.ts,
+            body,
+        };
+        match received.content() {
+            Ok(Content::Group(control)) => {
+                let Some(identity) = sender_identity else {
+                    return Ok(Outcome::Rejected("Gruppensteuerung ohne Absenderzertifikat"));
+                };
+                let own = &self.keys.identity.public;
+                if let Some(reason) = groups::apply(self.store, own, &identity, received.contact.is_some(), control)? {
+                    return Ok(Outcome::Rejected(reason));
+                }
+                return self.accept(env, Outcome::Signal(received));
+            }
+            _ if member.is_some() => return Ok(Outcome::Rejected("Gruppenmitglied ohne Kontakt")),
+            Ok(Content::Read { ids }) => {
+                self.apply_read(&received.conversation, &ids)?;
+                return self.accept(env, Outcome::Signal(received));
+            }
+            Ok(Content::Typing { .. }) => return self.accept(env, Outcome::Signal(received)),
+            _ => {}
+        }
+        self.store.add_message(
+            &StoredMessage {
+                conversation: received.conversation.clone(),
+                msg_id: received.msg_id,
+                direction: Direction::Incoming,
+                ts: received.ts,
+                body: received.body.clone(),
+                expires_at: None,
+                sender: None,
+            },
+            &received.sender,
+        )?;
+        self.accept(env, Outcome::Received(received))
+    }
+
+    /// Trägt die `msg_id` einer angenommenen Nachricht in den
+    /// Replay‑Cache ein.  Vorher prüft [`Store::is_replay`] nur lesend,
+    /// damit ein gefälschtes Envelope, das an der Ratchet oder den
+    /// Absenderprüfungen scheitert, keine fremde ID verbraucht.
+    fn accept(&self, env: &Envelope, outcome: Outcome) -> anyhow::Result<Outcome> {
+        if let Outcome::Received(received) | Outcome::Signal(received) = &outcome {
+            self.store.record_replay(received.msg_id, env.expires_at())?;
+        }
+        Ok(outcome)
+    }
+
+    /// Setzt gelesene eigene Nachrichten auf `Acked`.  Nur Einträge der
+    /// Konversation des Bestätigenden zählen, damit ein Kontakt keine
+    /// fremden Nachrichten quittieren kann.
+    fn apply_read(&self, conversation: &str, ids: &[u128]) -> anyhow::Result<()> {
+        for &id in ids {
+            let entry = self.store.outbox_entry(id)?;
+            if entry.is_some_and(|e| e.conversation == conversation && e.state != OutboxState::Acked) {
+                self.store.set_outbox_state(id, OutboxState::Acked)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Verarbeitet die Abschrift einer Nachricht, die ein anderes eigenes
+    /// Gerät gesendet hat.  Eine mitgeschickte neuere Geräteliste wird
+    /// übernommen; den Empfänger legt das Gerät bei Bedarf als Kontakt an.
+    fn handle_sync(
+        &self,
+        env: &Envelope,
+        payload: &Payload,
+        header: Option<&RatchetHeader>,
+        mut own: OwnDevices,
+        sender_device: u32,
+    ) -> anyhow::Result<Outcome> {
+        let identity = self.keys.identity.public;
+        if let Some(bytes) = header.and_then(|h| h.device_list.as_deref()) {
+            let Ok(list) = DeviceList::from_bytes(bytes) else {
+                return Ok(Outcome::Rejected("ungültige Geräteliste"));
+            };
+            if list.identity_pub == identity && own.update(&list)? {
+                own.save(self.store)?;
+            }
+        }
+        if own.list()?.is_none_or(|list| list.device(sender_device).is_none()) {
+            return Ok(Outcome::Rejected("Gerät nicht autorisiert"));
+        }
+        let session_id = devices::session_id(SELF_CONVERSATION, sender_device);
+        let Some(plain) = self.decrypt(payload, &session_id, own.device_id, &identity, sender_device)? else {
+            return Ok(Outcome::Rejected("Ratchet‑Entschlüsselung fehlgeschlagen"));
+        };
+        let Ok(SyncMessage::Sent { conversation, title, contact, msg_id, ts, body }) = serde_json::from_slice(&plain) else {
+            return Ok(Outcome::Rejected("ungültige Abschrift"));
+        };
+        let contact = match contact {
+            Some(contact) => self.adopt_contact(contact)?,
+            None => None,
+        };
+        let conversation = contact.as_ref().map(Contact::conversation_id).unwrap_or(conversation);
+        self.store.add_message(
+            &StoredMessage {
+                conversation: conversation.clone(),
+                msg_id,
+                direction: Direction::Outgoing,
+                ts,
+                body: body.clone(),
+                expires_at: None,
+                sender: None,
+            },
+            &title,
+        )?;
+        self.store.record_replay(payload.msg_id, env.expires_at())?;
+        Ok(Outcome::Received(Received {
+            conversation,
+            sender: title,
+            direction: Direction::Outgoing,
+            contact,
+            group: None,
+            msg_id,
+            ts,
+            body,
+        }))
+    }
+
+    /// Verarbeitet eine Nachricht an eine Gruppenadresse.  Unsere eigenen
+    /// Gruppennachrichten kommen von den Relays zurück und werden
+    /// übergangen.
+    fn handle_group(&self, env: &Envelope, payload: Payload, mut group: Group) -> anyhow::Result<Outcome> {
+        let Some(cert) = payload.sender.as_ref() else {
+            return Ok(Outcome::Rejected("Gruppennachricht ohne Absenderzertifikat"));
+        };
+        let identity = cert.identity_pub;
+        if identity == self.keys.identity.public {
+            return Ok(Outcome::NotForUs);
+        }
+        let Some(member) = group.member(&identity).cloned() else {
+            return Ok(Outcome::Rejected("Absender nicht in der Gruppe"));
+        };
+        if self.store.is_replay(payload.msg_id)? {
+            return Ok(Outcome::Replay);
+        }
+        let body = match group.decrypt(&identity, &payload.body) {
+            Ok(body) => body,
+            Err(reason) => return Ok(Outcome::Rejected(reason)),
+        };
+        self.store.save_group(&group)?;
+        let contact = self.find_sender(&identity)?;
+        let received = Received {
+            conversation: group.conversation_id(),
+            sender: contact.as_ref().map_or_else(|| member.name.clone(), |c| c.nickname.clone()),
+            direction: Direction::Incoming,
+            contact,
+            group: Some(group.name.clone()),
+            msg_id: payload.msg_id,
+            ts: env.ts,
+            body,
+        };
+        match received.content() {
+            Ok(Content::Group(_)) => return Ok(Outcome::Rejected("Gruppensteuerung an die Gruppenadresse")),
+            // Lesebestätigungen gelten in Gruppen nicht für den Postausgang
+            Ok(content) if content.is_signal() => return self.accept(env, Outcome::Signal(received)),
+            _ => {}
+        }
+        self.store.add_message(
+            &StoredMessage {
+                conversation: received.conversation.clone(),
+                msg_id: received.msg_id,
+                direction: Direction::Incoming,
+                ts: received.ts,
+                body: received.body.clone(),
+                expires_at: None,
+                sender: Some(to_hex(&identity)),
+            },
+            &group.name,
+        )?;
+        self.accept(env, Outcome::Received(received))
+    }
+
+    /// Entschlüsselt den Inhalt mit der Session `session_id` und speichert
+    /// den neuen Session‑Zustand.  `None`, wenn die Ratchet ablehnt.
+    fn decrypt(
+        &self,
+        payload: &Payload,
+        session_id: &str,
+        own_device: u32,
+        peer_identity: &[u8; 32],
+        peer_device: u32,
+    ) -> anyhow::Result<Option<Vec<u8>>> {
+        let Some(mut session) =
+            session::incoming(self.store, session_id, self.keys, own_device, peer_identity, peer_device, &payload.ratchet_header)?
+        else {
+            return Ok(None);
+        };
+        let Ok(body) = session.decrypt(&payload.ratchet_header, &payload.body) else {
+            return Ok(None);
+        };
+        self.store.save_session(session_id, &session)?;
+        Ok(Some(body))
+    }
+
+    /// Ordnet den Empfänger einer Abschrift einem eigenen Kontakt zu oder
+    /// legt ihn an, sofern der Spitzname frei ist.
+    fn adopt_contact(&self, contact: Contact) -> anyhow::Result<Option<Contact>> {
+        let known = self.find_sender(&contact.identity_public()?)?;
+        if known.is_some() {
+            return Ok(known);
+        }
+        if self.store.contact(&contact.nickname)?.is_some() {
+            return Ok(None);
+        }
+        self.store.save_contact(&contact)?;
+        Ok(Some(contact))
+    }
+
+    /// Entschlüsselt ein Envelope und prüft das Tag gegen Hauptadresse
+    /// und Subadressen.  Mit KEM‑Schlüssel werden klassische und hybride
+    /// Envelopes angenommen.  Liefert auch die getroffene Subadresse.
+    fn open(&self, env: &Envelope) -> anyhow::Result<Option<(Payload, Option<AddressRecord>)>> {
+        let records = self.store.addresses()?;
+        let bases = records.iter().map(AddressRecord::base_bytes).collect::<anyhow::Result<Vec<_>>>()?;
+        #[cfg(feature = "pqc")]
+        let opened = match &self.keys.kem {
+            Some(kem) => env.open_hybrid_subaddress(&self.keys.spend, kem, &bases),
+            None => env.open_subaddress(&self.keys.spend, &bases),
+        };
+        #[cfg(not(feature = "pqc"))]
+        let opened = env.open_subaddress(&self.keys.spend, &bases);
+        Ok(opened.map(|(payload, index)| (payload, index.map(|i| records[i].clone()))))
+    }
+
+    /// Prüft ein Envelope gegen die Adressen aller Gruppen.
+    fn open_group(&self, env: &Envelope) -> anyhow::Result<Option<(Payload, Group)>> {
+        Ok(self.store.groups()?.into_iter().find_map(|group| env.open(&group.spend_key()).map(|payload| (payload, group))))
+    }
+
+    /// Sucht einen Identity‑Key in den Mitgliederlisten der Gruppen.
+    fn find_member(&self, identity: &[u8; 32]) -> anyhow::Result<Option<phantomchat_core::GroupMember>> {
+        Ok(self.store.groups()?.into_iter().find_map(|group| group.member(identity).cloned()))
+    }
+
+    /// Ordnet einen (zertifizierten) Identity‑Key einem Kontakt zu.
+    fn find_sender(&self, identity: &[u8; 32]) -> anyhow::Result<Option<Contact>> {
+        Ok(self.store.contacts()?.into_iter().find(|c| c.identity_public().is_ok_and(|key| key == *identity)))
+    }
+}
+
+/// Formatiert einen Zeitstempel (UNIX‑Millisekunden) als
+/// `JJJJ-MM-TT hh:mm:ss` in UTC.
+pub fn format_ts(ms: u64) -> String {
+    let secs = ms / 1000;
+    let (h, m, s) = ((secs / 3600) % 24, (secs / 60) % 60, secs % 60);
+    // Kalenderdatum aus Tagen seit 1970 (Algorithmus von H. Hinnant)
+    let z = (secs / 86_400) as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{year:04}-{month:02}-{day:02} {h:02}:{m:02}:{s:02}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::outgoing::{self, Peer, SendPolicy};
+    use phantomchat_relays::{BridgeProvider, InMemoryRelay};
+
+    const POW_BITS: u32 = 8;
+
+    struct Party {
+        keys: Keys,
+        store: Store,
+    }
+
+    impl Party {
+        fn new() -> Self {
+            let keys = Keys::generate();
+            let store = Store::temporary(&keys.storage_key).unwrap();
+            Self { keys, store }
+        }
+        fn receiver(&self) -> Receiver<'_> {
+            Receiver::new(&self.keys, &self.store).with_min_pow_bits(POW_BITS)
+        }
+    }
+
+    /// Versiegelt eine Textnachricht von `from` an `to` und schickt sie
+    /// über das Relay; geliefert wird, was das Relay ausliefert.
+    async fn send(relay: &InMemoryRelay, from: &Party, to: &Party, text: &str) -> Envelope {
+        let policy = SendPolicy { pow_bits: POW_BITS, ..SendPolicy::default() };
+        let peer = Peer::from_key(to.keys.spend.public);
+        let prepared = outgoing::prepare(&from.keys, &from.store, &policy, &peer, &Content::text(text).to_bytes()).unwrap();
+        let since = prepared.envelope.ts;
+        relay.publish(prepared.envelope).await.unwrap();
+        relay.fetch_since(since).await.unwrap().pop().unwrap()
+    }
+
+    #[tokio::test]
+    async fn sealed_message_is_received_once() {
+        let relay = InMemoryRelay::new("mem");
+        let (alice, bob) = (Party::new(), Party::new());
+        let env = send(&relay, &alice, &bob, "hallo").await;
+
+        let Outcome::Received(received) = bob.receiver().handle(&env).unwrap() else { panic!("nicht empfangen") };
+        assert_eq!(received.content().unwrap(), Content::text("hallo"));
+        assert_eq!(bob.store.messages(&received.conversation).unwrap().len(), 1);
+        assert!(matches!(bob.receiver().handle(&env).unwrap(), Outcome::Replay));
+        assert!(matches!(alice.receiver().handle(&env).unwrap(), Outcome::NotForUs));
+
+        let env = send(&relay, &alice, &bob, "weiter").await;
+        assert!(matches!(bob.receiver().handle(&env).unwrap(), Outcome::Received(_)));
+    }
+
+    #[tokio::test]
+    async fn expired_and_underworked_envelopes_are_rejected() {
+        let relay = InMemoryRelay::new("mem");
+        let (alice, bob) = (Party::new(), Party::new());
+
+        let mut expired = send(&relay, &alice, &bob, "alt").await;
+        expired.restamp(store::now_ms() - expired.ttl as u64 * 1000 - 1, POW_BITS);
+        assert!(matches!(bob.receiver().handle(&expired).unwrap(), Outcome::Rejected("abgelaufen")));
+
+        let mut cheap = send(&relay, &alice, &bob, "billig").await;
+        while cheap.verify_pow(POW_BITS) {
+            cheap.pow_nonce += 1;
+        }
+        assert!(matches!(bob.receiver().handle(&cheap).unwrap(), Outcome::Rejected("Proof‑of‑Work ungenügend")));
+
+        // Abgelehnte Envelopes verbrauchen weder Replay‑Eintrag noch
+        // Ratchet‑Zustand.
+        let Outcome::Received(received) = bob.receiver().handle(&send(&relay, &alice, &bob, "frisch").await).unwrap() else {
+            panic!("nicht empfangen")
+        };
+        assert_eq!(bob.store.messages(&received.conversation).unwrap().len(), 1);
+    }
+}
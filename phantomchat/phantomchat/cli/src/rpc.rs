@@ -0,0 +1,193 @@
+//! JSON‑RPC‑Protokoll zwischen CLI und Daemon.
+//!
+//! JSON‑RPC 2.0 über einen Unix‑Domain‑Socket (pro Profil, siehe
+//! [`crate::config`]), eine Nachricht pro Zeile (newline‑delimited JSON).  Methoden:
+//!
+//! | Methode                | Parameter                          | Ergebnis |
+//! |------------------------|------------------------------------|----------|
+//! | `status`               | –                                  | Identität, Relays |
+//! | `send`                 | `to`, `message`, optional `reply_to` | [`Sent`](crate::api::Sent) |
+//! | `react`                | `to`, `msg_id`, `emoji`, `remove`  | [`Sent`](crate::api::Sent) |
+//! | `edit`                 | `to`, `msg_id`, `message`          | [`Sent`](crate::api::Sent) |
+//! | `delete`               | `to`, `msg_id`                     | [`Sent`](crate::api::Sent) |
+//! | `timer`                | `to`, `seconds` (`0` = aus)        | [`Sent`](crate::api::Sent) |
+//! | `attachments.send`     | `to`, `path`, optional `caption`   | [`Sent`](crate::api::Sent) |
+//! | `attachments.fetch`    | `conversation`, `msg_id`, `out`    | [`Fetched`](crate::attachments::Fetched) |
+//! | `sync`                 | –                                  | [`SyncResult`](crate::api::SyncResult) |
+//! | `conversations.list`   | –                                  | Konversationen |
+//! | `messages.list`        | `conversation` (ID oder Spitzname) | Nachrichten |
+//! | `messages.read`        | `conversation` (ID oder Spitzname) | Anzahl bestätigter Nachrichten |
+//! | `contacts.list`        | –                                  | Kontakte |
+//! | `contacts.add`         | `contact`, `signed`                | [`KeyUpdate`](crate::contacts::KeyUpdate) |
+//! | `contacts.import`      | `name`, `bundle`                   | [`KeyUpdate`](crate::contacts::KeyUpdate) |
+//! | `contacts.remove`      | `name`                             | `null` |
+//! | `contacts.fingerprint` | `name`                             | Safety Number, Emoji, Wörter |
+//! | `contacts.verify`      | `name`                             | `null` |
+//! | `addresses.list`       | –                                  | Subadressen |
+//! | `addresses.rotate`     | `label`                            | [`AddressRecord`](crate::addresses::AddressRecord) |
+//! | `addresses.bundle`     | `label`, `relays`                  | Pairing‑Code mit Subadresse |
+//! | `devices.list`         | –                                  | Geräte der Identität |
+//! | `devices.add`          | `code`                             | [`DeviceView`](crate::devices::DeviceView) des neuen Geräts |
+//! | `devices.remove`       | `device_id`                        | `null` |
+//! | `groups.list`          | –                                  | [`GroupView`](crate::groups::GroupView)s |
+//! | `groups.create`        | `name`, `members` (Spitznamen)     | [`GroupView`](crate::groups::GroupView) |
+//! | `groups.add`           | `group`, `member`                  | [`GroupView`](crate::groups::GroupView) |
+//! | `groups.remove`        | `group`, `member`                  | [`GroupView`](crate::groups::GroupView) |
+//! | `groups.leave`         | `group`                            | Anzahl nicht erreichter Mitglieder |
+//! | `subscribe`            | –                                  | `true`, danach Notifications |
+//!
+//! Nach `subscribe` sendet der Daemon für jede eingehende Nachricht eine
+//! Notification `{"jsonrpc":"2.0","method":"message","params":{…}}`,
+//! für Lesebestätigungen und Tipp‑Hinweise `"method":"signal"`.
+//! `msg_id` und `reply_to` sind Dezimalstrings; Pfade müssen absolut
+//! sein.
+//! `to` ist `{"contact": "alice"}`, `{"key": "<hex>"}`,
+//! `{"conversation": "<id>"}` oder `{"group": "<Name oder ID>"}`.
+
+use crate::output::{self, ErrorKind};
+use anyhow::Context;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::Path;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::UnixStream;
+
+/// Ungültiges JSON.
+pub const PARSE_ERROR: i64 = -32700;
+/// Keine gültige JSON‑RPC‑Anfrage.
+pub const INVALID_REQUEST: i64 = -32600;
+/// Unbekannte Methode.
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// Parameter passen nicht zur Methode.
+pub const INVALID_PARAMS: i64 = -32602;
+/// Fehler bei der Ausführung.
+pub const APPLICATION_ERROR: i64 = -32000;
+
+/// Eine Anfrage.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Request {
+    pub jsonrpc: String,
+    /// Fehlt bei Notifications.
+    #[serde(default)]
+    pub id: Option<Value>,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+/// Fehlerobjekt einer Antwort.  Bei Anwendungsfehlern enthält `data` die
+/// Fehlerklasse (`{"kind": "not_found"}`, siehe [`crate::output`]).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<Value>,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into(), data: None }
+    }
+
+    /// Anwendungsfehler mit Fehlerklasse.
+    pub fn application(err: &anyhow::Error) -> Self {
+        let kind = output::classify(err);
+        Self { data: Some(json!({ "kind": kind })), ..Self::new(APPLICATION_ERROR, format!("{err:#}")) }
+    }
+
+    /// Die vom Daemon gemeldete Fehlerklasse, sofern vorhanden.
+    pub fn kind(&self) -> Option<ErrorKind> {
+        serde_json::from_value(self.data.as_ref()?.get("kind")?.clone()).ok()
+    }
+}
+
+impl std::fmt::Display for RpcError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (Code {})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+/// Baut eine Antwortzeile.
+pub fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({ "jsonrpc": "2.0", "id": id, "error": error }),
+    }
+}
+
+/// Baut eine Notification.
+pub fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+/// Client für den Daemon.
+pub struct Client {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+    next_id: u64,
+}
+
+impl Client {
+    /// Verbindet sich mit dem Daemon.
+    pub async fn connect(path: &Path) -> std::io::Result<Self> {
+        let (read, write) = UnixStream::connect(path).await?.into_split();
+        Ok(Self { reader: BufReader::new(read), writer: write, next_id: 1 })
+    }
+
+    /// Ruft `method` auf und wartet auf die Antwort.  Zwischenzeitlich
+    /// eintreffende Notifications werden übersprungen.
+    pub async fn call(&mut self, method: &str, params: Value) -> anyhow::Result<Value> {
+        let id = self.next_id;
+        self.next_id += 1;
+        let request = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+        let mut line = serde_json::to_vec(&request)?;
+        line.push(b'\n');
+        self.writer.write_all(&line).await?;
+        loop {
+            let value = self
+                .read()
+                .await?
+                .ok_or_else(|| output::fail(ErrorKind::Daemon, "Daemon hat die Verbindung beendet"))?;
+            if value.get("id") != Some(&json!(id)) {
+                continue;
+            }
+            if let Some(error) = value.get("error") {
+                let error: RpcError = serde_json::from_value(error.clone())?;
+                return Err(match error.kind() {
+                    Some(kind) => output::fail(kind, error.message),
+                    None => error.into(),
+                });
+            }
+            return Ok(value.get("result").cloned().unwrap_or(Value::Null));
+        }
+    }
+
+    /// Wie [`Client::call`], deserialisiert aber das Ergebnis.
+    pub async fn call_as<T: DeserializeOwned>(&mut self, method: &str, params: Value) -> anyhow::Result<T> {
+        let result = self.call(method, params).await?;
+        serde_json::from_value(result).with_context(|| format!("unerwartete Antwort auf {method}"))
+    }
+
+    /// Wartet auf die nächste Notification (nach `subscribe`).
+    pub async fn next_notification(&mut self) -> anyhow::Result<Option<Request>> {
+        while let Some(value) = self.read().await? {
+            if value.get("id").is_none() && value.get("method").is_some() {
+                return Ok(Some(serde_json::from_value(value)?));
+            }
+        }
+        Ok(None)
+    }
+
+    async fn read(&mut self) -> anyhow::Result<Option<Value>> {
+        let mut line = String::new();
+        if self.reader.read_line(&mut line).await? == 0 {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&line)?))
+    }
+}
@@ -0,0 +1,120 @@
+//! Ratchet‑Sessions pro Konversation.
+//!
+//! Jede Konversation besitzt einen [`SessionRecord`], der in der lokalen
+//! Datenbank liegt.  Mit mehreren Geräten gibt es einen pro Gerät des
+//! Gegenübers (siehe [`crate::devices::session_id`]).  Fehlt er, baut der
+//! Sender eine Session auf, ähnlich X3DH: Er erzeugt einen
+//! Session‑Ephemeral‑Key, bildet damit ein ECDH mit der Adresse des
+//! Empfangsgeräts ([`phantomchat_core::Subaddress::agree`]) und leitet
+//! daraus den Root‑Key ab ([`phantomchat_core::ratchet::session_root`]);
+//! sein Identity‑Key und die Geräte‑IDs beider Seiten gehen als Kontext
+//! ein.  Der Ephemeral‑Key reist im Ratchet‑Header, bis die erste Antwort
+//! eintrifft, und der Empfänger rechnet dasselbe Geheimnis mit seinem
+//! privaten Spend‑Key nach.  Wer nur die öffentlichen Schlüssel kennt,
+//! kann den Root‑Key nicht berechnen; dass der Header vom angegebenen
+//! Absender stammt, belegt das Absenderzertifikat des Envelopes.
+//!
+//! Mit Feature `pqc` wird eine neue Session zu einem Gegenüber mit
+//! KEM‑Schlüssel hybrid aufgebaut: Der Sender kapselt zusätzlich ein
+//! Geheimnis für dessen ML‑KEM‑Schlüssel, mischt es in den Root‑Key
+//! ([`phantomchat_core::pq::hybrid_root`]) und hängt den KEM‑Ciphertext
+//! ebenfalls an den Ratchet‑Header.  Der Empfänger gewinnt das Geheimnis
+//! daraus zurück, wenn er die Session anlegt.  Solche Sessions führen
+//! außerdem den Sparse‑PQ‑Ratchet ([`phantomchat_core::pq_ratchet`]), der
+//! laufend frische KEM‑Geheimnisse einmischt.
+
+use crate::keystore::Keys;
+use crate::outgoing::DeviceTarget;
+use crate::store::Store;
+use phantomchat_core::ratchet::session_root;
+use phantomchat_core::secret::SecretBytes;
+#[cfg(feature = "pqc")]
+use phantomchat_core::pq_ratchet::{PqConfig, PqRatchet};
+use phantomchat_core::{RatchetHeader, RatchetState, SessionRecord};
+use rand_core::OsRng;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+/// Kontext des Root‑Keys: Identity‑Key und Gerät des Initiators, Gerät
+/// der Gegenseite.
+fn context(initiator_identity: &[u8; 32], initiator_device: u32, responder_device: u32) -> Vec<u8> {
+    let mut out = initiator_identity.to_vec();
+    out.extend_from_slice(&initiator_device.to_be_bytes());
+    out.extend_from_slice(&responder_device.to_be_bytes());
+    out
+}
+
+/// Lädt die Sessions mit einem Gerät für eine ausgehende Nachricht oder
+/// baut eine neue auf; gesendet wird auf [`SessionRecord::current`].
+pub fn outgoing(
+    store: &Store,
+    session_id: &str,
+    keys: &Keys,
+    own_device: u32,
+    target: &DeviceTarget,
+) -> anyhow::Result<SessionRecord> {
+    if let Some(record) = store.load_session(session_id)? {
+        return Ok(record);
+    }
+    let ephemeral = StaticSecret::random_from_rng(OsRng);
+    let (origin, shared) = target.address.agree(&ephemeral);
+    let root = session_root(&shared, &context(&keys.identity.public, own_device, target.device_id));
+    #[cfg(feature = "pqc")]
+    if let Some(kem_pub) = &target.kem_pub {
+        let (ct, secret) = phantomchat_core::pq::encapsulate(kem_pub)
+            .ok_or_else(|| crate::output::fail(crate::output::ErrorKind::InvalidInput, "ungültiger KEM‑Schlüssel des Kontakts"))?;
+        let root = phantomchat_core::pq::hybrid_root(root.expose(), &secret);
+        let state = RatchetState::initiator(root, origin)
+            .with_session_kem(ct)
+            .with_pq(PqRatchet::new(true, PqConfig::default()));
+        return Ok(SessionRecord::new(state));
+    }
+    Ok(SessionRecord::new(RatchetState::initiator(root, origin)))
+}
+
+/// Lädt die Sessions mit Gerät `peer_device` für eine eingehende
+/// Nachricht.  Trägt der Ratchet‑Header einen noch unbekannten
+/// Session‑Ephemeral‑Key, wird die Session als Gegenseite angelegt und
+/// zur aktuellen; `peer_identity` ist dann der Identity‑Key des
+/// Initiators.  `None`, wenn es weder eine Session noch einen
+/// Session‑Aufbau gibt.
+pub fn incoming(
+    store: &Store,
+    session_id: &str,
+    keys: &Keys,
+    own_device: u32,
+    peer_identity: &[u8; 32],
+    peer_device: u32,
+    header: &[u8],
+) -> anyhow::Result<Option<SessionRecord>> {
+    let record = store.load_session(session_id)?;
+    let Some(header) = RatchetHeader::from_bytes(header) else {
+        return Ok(record);
+    };
+    let Some(origin) = header.session_ephemeral.filter(|origin| record.as_ref().is_none_or(|r| !r.contains(origin))) else {
+        return Ok(record);
+    };
+    let shared = keys.spend.ecdh(&PublicKey::from(origin));
+    let root = session_root(&shared, &context(peer_identity, peer_device, own_device));
+    let peer_ratchet = PublicKey::from(header.ratchet_pub);
+    let state = session_state(keys, &header, root, origin, peer_ratchet);
+    Ok(Some(match record {
+        Some(mut record) => {
+            record.adopt(state);
+            record
+        }
+        None => SessionRecord::new(state),
+    }))
+}
+
+/// Session der Gegenseite; ein KEM‑Ciphertext im Header macht sie hybrid.
+#[cfg_attr(not(feature = "pqc"), allow(unused_variables))]
+fn session_state(keys: &Keys, header: &RatchetHeader, root: SecretBytes<32>, origin: [u8; 32], peer_ratchet: PublicKey) -> RatchetState {
+    #[cfg(feature = "pqc")]
+    if let (Some(kem), Some(kem_ct)) = (&keys.kem, header.session_kem.as_deref()) {
+        if let Some(secret) = kem.decapsulate(kem_ct) {
+            let root = phantomchat_core::pq::hybrid_root(root.expose(), &secret);
+            return RatchetState::responder(root, origin, peer_ratchet).with_pq(PqRatchet::new(false, PqConfig::default()));
+        }
+    }
+    RatchetState::responder(root, origin, peer_ratchet)
+}
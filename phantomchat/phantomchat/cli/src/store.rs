@@ -0,0 +1,762 @@
+//! Verschlüsselte lokale Datenbank des CLI.
+//!
+//! Als Speicher dient eine eingebettete `sled`‑Datenbank.  Jeder Wert
+//! wird einzeln mit XChaCha20‑Poly1305 versiegelt (`nonce || ciphertext`);
+//! Baumname und Schlüssel sind als Associated Data gebunden, so dass
+//! Werte nicht unbemerkt vertauscht werden können.  Schlüssel, die
+//! Konversationen benennen, werden vorher mit einem HMAC über den
+//! Datenbankschlüssel pseudonymisiert.  Der Datenbankschlüssel selbst liegt
+//! im Schlüsselspeicher (siehe [`crate::keystore`]), so dass eine einzige
+//! Passphrase Schlüssel und Nachrichten entsperrt.
+//!
+//! Bäume:
+//!
+//! * `meta` – Schemaversion, Prüfwert und Einstellungen
+//! * `conversations` – Konversationen
+//! * `messages` – Nachrichten, sortiert nach Konversation und Zeit
+//! * `expiry` – Ablaufindex verschwindender Nachrichten
+//!   (`expires_at(8, BE) | Nachrichtenschlüssel`)
+//! * `sessions` – serialisierte Ratchet‑Sessions je Gerät ([`SessionRecord`])
+//! * `outbox` – Zustand gesendeter Nachrichten
+//!   (`PENDING → PUBLISHED → DELIVERED → ACKED`, siehe SPEC.md 5.2)
+//! * `replay` – bereits verarbeitete `msg_id`s mit Ablaufzeit
+//! * `contacts` – Kontaktbuch (siehe [`crate::contacts`])
+//! * `addresses` – vergebene Subadressen (siehe [`crate::addresses`])
+//! * `groups` – Gruppen mit Gruppengeheimnis und Sender‑Keys (siehe
+//!   [`crate::groups`])
+//!
+//! Verschwindende Nachrichten lassen sich in `sled` nicht löschen: Die
+//! Datenbank schreibt log‑strukturiert, ältere Kopien eines Datensatzes
+//! bleiben bis zur Kompaktierung in der Datei.  Sie werden daher
+//! zusätzlich unter einem eigenen Schlüssel versiegelt, dessen
+//! Zufallswert in einem 32‑Byte‑Platz der Datei `<db>.msgkeys` neben der
+//! Datenbank liegt ([`KeySlots`]).  Beim Ablauf wird der Platz an Ort und
+//! Stelle mit Nullen überschrieben; damit sind alle Kopien der Nachricht
+//! unlesbar, auch mit Passphrase und Datenbankschlüssel.
+
+use crate::addresses::AddressRecord;
+use crate::contacts::Contact;
+use crate::groups::Group;
+use anyhow::{anyhow, bail, Context};
+use chacha20poly1305::aead::{Aead, Payload};
+use chacha20poly1305::{KeyInit, XChaCha20Poly1305, XNonce};
+use hmac::{Hmac, Mac};
+use phantomchat_core::util::to_hex;
+use phantomchat_core::{Content, GroupId, SessionRecord};
+use rand_core::{OsRng, RngCore};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::Mutex;
+use zeroize::Zeroizing;
+
+/// Aktuelle Schemaversion.  Jede Erhöhung braucht einen Eintrag in
+/// [`MIGRATIONS`].
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// Migration von Version `i` auf `i + 1` steht an Index `i`.
+type Migration = fn(&Store) -> anyhow::Result<()>;
+const MIGRATIONS: &[Migration] = &[migrate_v1, migrate_v2, migrate_v3];
+
+/// 0 → 1: Grundschema; die Bäume legt `sled` beim ersten Zugriff an.
+fn migrate_v1(_store: &Store) -> anyhow::Result<()> {
+    Ok(())
+}
+
+/// 1 → 2: Ratchet‑Zustände vor der echten Verschlüsselung lassen sich
+/// nicht weiterführen; die Sessions werden beim nächsten Kontakt neu
+/// aufgebaut.
+fn migrate_v2(store: &Store) -> anyhow::Result<()> {
+    store.db.open_tree("sessions")?.clear()?;
+    Ok(())
+}
+
+/// 2 → 3: Der Root‑Key bisheriger Sessions ließ sich aus öffentlichen
+/// Schlüsseln berechnen; sie werden verworfen und per
+/// Session‑Ephemeral‑Key neu aufgebaut.
+fn migrate_v3(store: &Store) -> anyhow::Result<()> {
+    store.db.open_tree("sessions")?.clear()?;
+    Ok(())
+}
+
+/// Bekannter Klartext, an dem ein falscher Datenbankschlüssel erkannt wird.
+const CHECK_VALUE: &[u8] = b"phantomchat-store";
+
+/// Jetzt als UNIX‑Zeitstempel in Millisekunden.
+pub fn now_ms() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
+/// Richtung einer Nachricht.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    Incoming,
+    Outgoing,
+}
+
+/// Zustand einer gesendeten Nachricht (SPEC.md 5.2).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OutboxState {
+    Pending,
+    Published,
+    Delivered,
+    Acked,
+}
+
+/// Eine Konversation mit einem Gegenüber.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conversation {
+    /// Stabile ID (hex‑kodierter Spend‑Public‑Key des Gegenübers).
+    pub id: String,
+    /// Anzeigename.
+    pub title: String,
+    pub created_at: u64,
+    pub last_activity: u64,
+    pub unread: u32,
+    /// Lebensdauer neuer Nachrichten in Sekunden; `0` = unbegrenzt.
+    #[serde(default)]
+    pub expire_secs: u32,
+    /// Zeitstempel der Timer‑Nachricht, die `expire_secs` gesetzt hat.
+    #[serde(default)]
+    pub timer_ts: u64,
+}
+
+/// Eine gespeicherte Nachricht.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredMessage {
+    pub conversation: String,
+    pub msg_id: u128,
+    pub direction: Direction,
+    /// Zeitstempel des Envelopes (Millisekunden).
+    pub ts: u64,
+    pub body: Vec<u8>,
+    /// Löschzeitpunkt (Millisekunden) bei verschwindenden Nachrichten;
+    /// setzt [`Store::add_message`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<u64>,
+    /// Identity‑Key (hex) des Absenders eingehender Gruppennachrichten.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sender: Option<String>,
+}
+
+/// Eintrag im Postausgang.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub msg_id: u128,
+    pub conversation: String,
+    /// Serialisiertes Envelope für erneutes Senden.
+    pub envelope: Vec<u8>,
+    /// Envelopes an weitere Geräte (siehe [`crate::devices`]).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fanout: Vec<Vec<u8>>,
+    pub state: OutboxState,
+    pub attempts: u32,
+    pub updated_at: u64,
+}
+
+/// Datensatz im Baum `messages` für eine verschwindende Nachricht: die
+/// unter dem Schlüssel aus Platz `slot` versiegelte [`StoredMessage`].
+/// Nachrichten ohne Ablaufzeit liegen direkt im Baum.
+#[derive(Serialize, Deserialize)]
+struct ShreddedMessage {
+    slot: u32,
+    sealed: Vec<u8>,
+}
+
+/// Schlüsselplätze verschwindender Nachrichten: eine Datei aus
+/// 32‑Byte‑Plätzen, die an Ort und Stelle überschrieben werden.  Ein Platz
+/// aus Nullen ist frei.  Der Zufallswert eines Platzes ergibt erst
+/// zusammen mit dem Datenbankschlüssel den Nachrichtenschlüssel.
+struct KeySlots {
+    inner: Mutex<SlotFile>,
+}
+
+struct SlotFile {
+    file: fs::File,
+    count: u32,
+    free: BTreeSet<u32>,
+}
+
+impl KeySlots {
+    const SLOT: u64 = 32;
+
+    fn open(path: &Path) -> anyhow::Result<Self> {
+        let mut options = fs::OpenOptions::new();
+        options.read(true).write(true).create(true).truncate(false);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(path).with_context(|| format!("Schlüsselplätze {:?} nicht zu öffnen", path))?;
+        let mut data = Zeroizing::new(Vec::new());
+        file.read_to_end(&mut data)?;
+        let count = (data.len() as u64 / Self::SLOT) as u32;
+        let free = (0..count).filter(|&i| data[i as usize * 32..][..32].iter().all(|&b| b == 0)).collect();
+        Ok(Self { inner: Mutex::new(SlotFile { file, count, free }) })
+    }
+
+    /// Belegt einen Platz mit einem frischen Zufallswert.
+    fn allocate(&self) -> anyhow::Result<(u32, Zeroizing<[u8; 32]>)> {
+        let mut value = Zeroizing::new([0u8; 32]);
+        while value.iter().all(|&b| b == 0) {
+            OsRng.fill_bytes(value.as_mut());
+        }
+        let mut inner = self.inner.lock().unwrap();
+        let slot = match inner.free.pop_first() {
+            Some(slot) => slot,
+            None => {
+                inner.count += 1;
+                inner.count - 1
+            }
+        };
+        inner.write(slot, value.as_ref())?;
+        Ok((slot, value))
+    }
+
+    /// Wert eines belegten Platzes; `None`, wenn er vernichtet ist.
+    fn read(&self, slot: u32) -> anyhow::Result<Option<Zeroizing<[u8; 32]>>> {
+        let mut inner = self.inner.lock().unwrap();
+        if slot >= inner.count || inner.free.contains(&slot) {
+            return Ok(None);
+        }
+        let mut value = Zeroizing::new([0u8; 32]);
+        inner.file.seek(SeekFrom::Start(slot as u64 * Self::SLOT))?;
+        inner.file.read_exact(value.as_mut())?;
+        Ok(Some(value).filter(|v| v.iter().any(|&b| b != 0)))
+    }
+
+    /// Überschreibt den Platz mit Nullen und gibt ihn frei.
+    fn destroy(&self, slot: u32) -> anyhow::Result<()> {
+        let mut inner = self.inner.lock().unwrap();
+        if slot < inner.count && inner.free.insert(slot) {
+            inner.write(slot, &[0u8; 32])?;
+        }
+        Ok(())
+    }
+}
+
+impl SlotFile {
+    fn write(&mut self, slot: u32, value: &[u8]) -> anyhow::Result<()> {
+        self.file.seek(SeekFrom::Start(slot as u64 * KeySlots::SLOT))?;
+        self.file.write_all(value)?;
+        self.file.sync_data()?;
+        Ok(())
+    }
+}
+
+/// Versiegelt `plain` als `nonce || ciphertext`.
+fn seal(cipher: &XChaCha20Poly1305, aad: &[u8], plain: &[u8]) -> Vec<u8> {
+    let mut nonce = [0u8; 24];
+    OsRng.fill_bytes(&mut nonce);
+    let ct = cipher.encrypt(XNonce::from_slice(&nonce), Payload { msg: plain, aad }).expect("encrypt");
+    let mut out = nonce.to_vec();
+    out.extend_from_slice(&ct);
+    out
+}
+
+/// Gegenstück zu [`seal`]; `None` bei verkürzten oder falschen Daten.
+fn open(cipher: &XChaCha20Poly1305, aad: &[u8], sealed: &[u8]) -> Option<Vec<u8>> {
+    if sealed.len() < 24 + 16 {
+        return None;
+    }
+    let (nonce, ct) = sealed.split_at(24);
+    cipher.decrypt(XNonce::from_slice(nonce), Payload { msg: ct, aad }).ok()
+}
+
+/// Geöffnete, entsperrte Datenbank.
+pub struct Store {
+    db: sled::Db,
+    cipher: XChaCha20Poly1305,
+    index_key: [u8; 32],
+    /// Basis der Nachrichtenschlüssel verschwindender Nachrichten.
+    shred_key: Zeroizing<[u8; 32]>,
+    slots: KeySlots,
+}
+
+impl Store {
+    /// Öffnet (oder erzeugt) die Datenbank unter `path` mit dem
+    /// Datenbankschlüssel aus dem Schlüsselspeicher und führt ausstehende
+    /// Migrationen aus.
+    pub fn open(path: &Path, key: &[u8; 32]) -> anyhow::Result<Self> {
+        let db = sled::open(path).with_context(|| format!("Datenbank {:?} nicht zu öffnen", path))?;
+        Self::with_db(db, KeySlots::open(&path.with_extension("msgkeys"))?, key)
+    }
+
+    /// Flüchtige Datenbank für Tests; sie wird beim Drop gelöscht.
+    #[cfg(test)]
+    pub fn temporary(key: &[u8; 32]) -> anyhow::Result<Self> {
+        let path = std::env::temp_dir().join(format!("pc-msgkeys-{:016x}", OsRng.next_u64()));
+        let slots = KeySlots::open(&path)?;
+        fs::remove_file(&path)?;
+        Self::with_db(sled::Config::new().temporary(true).open()?, slots, key)
+    }
+
+    fn with_db(db: sled::Db, slots: KeySlots, key: &[u8; 32]) -> anyhow::Result<Self> {
+        // Getrennte Teilschlüssel für Verschlüsselung und Index‑HMAC
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC key");
+        mac.update(b"pc.store.index");
+        let index_key: [u8; 32] = mac.finalize().into_bytes().into();
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC key");
+        mac.update(b"pc.store.enc");
+        let enc_key = mac.finalize().into_bytes();
+        let cipher = XChaCha20Poly1305::new_from_slice(&enc_key).expect("32 Byte");
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC key");
+        mac.update(b"pc.store.shred");
+        let shred_key = Zeroizing::new(mac.finalize().into_bytes().into());
+        let store = Self { db, cipher, index_key, shred_key, slots };
+        store.check_key()?;
+        store.migrate()?;
+        Ok(store)
+    }
+
+    /// Prüft den Schlüssel anhand des Prüfwerts bzw. legt ihn an.
+    fn check_key(&self) -> anyhow::Result<()> {
+        let meta = self.db.open_tree("meta")?;
+        match meta.get("check")? {
+            Some(sealed) => {
+                let plain = self
+                    .open_value("meta", b"check", &sealed)
+                    .map_err(|_| anyhow!("Datenbank gehört zu einem anderen Schlüsselspeicher"))?;
+                if plain != CHECK_VALUE {
+                    bail!("Datenbank‑Prüfwert ungültig");
+                }
+            }
+            None => {
+                meta.insert("check", self.seal_value("meta", b"check", CHECK_VALUE))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Aktuelle Schemaversion der Datei (0 bei neuer Datenbank).
+    pub fn schema_version(&self) -> anyhow::Result<u32> {
+        Ok(self.get_meta::<u32>("schema_version")?.unwrap_or(0))
+    }
+
+    /// Führt alle Migrationen bis [`SCHEMA_VERSION`] aus.
+    fn migrate(&self) -> anyhow::Result<()> {
+        let mut version = self.schema_version()?;
+        if version > SCHEMA_VERSION {
+            bail!("Datenbank hat Schemaversion {version}, unterstützt wird {SCHEMA_VERSION}");
+        }
+        while version < SCHEMA_VERSION {
+            MIGRATIONS[version as usize](self).with_context(|| format!("Migration {version} fehlgeschlagen"))?;
+            version += 1;
+            self.set_meta("schema_version", &version)?;
+        }
+        self.db.flush()?;
+        Ok(())
+    }
+
+    // --- Verschlüsselung ---------------------------------------------
+
+    fn aad(tree: &str, key: &[u8]) -> Vec<u8> {
+        let mut aad = Vec::with_capacity(tree.len() + 1 + key.len());
+        aad.extend_from_slice(tree.as_bytes());
+        aad.push(0);
+        aad.extend_from_slice(key);
+        aad
+    }
+
+    fn seal_value(&self, tree: &str, key: &[u8], plain: &[u8]) -> Vec<u8> {
+        seal(&self.cipher, &Self::aad(tree, key), plain)
+    }
+
+    fn open_value(&self, tree: &str, key: &[u8], sealed: &[u8]) -> anyhow::Result<Vec<u8>> {
+        open(&self.cipher, &Self::aad(tree, key), sealed).ok_or_else(|| anyhow!("Datensatz in {tree} nicht entschlüsselbar"))
+    }
+
+    /// Schlüssel einer verschwindenden Nachricht aus dem Wert ihres Platzes.
+    fn message_cipher(&self, slot_value: &[u8; 32]) -> XChaCha20Poly1305 {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(self.shred_key.as_ref()).expect("HMAC key");
+        mac.update(slot_value);
+        XChaCha20Poly1305::new_from_slice(&mac.finalize().into_bytes()).expect("32 Byte")
+    }
+
+    /// Pseudonymisierter Indexschlüssel für eine logische ID.
+    fn index(&self, id: &str) -> [u8; 16] {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.index_key).expect("HMAC key");
+        mac.update(id.as_bytes());
+        let digest = mac.finalize().into_bytes();
+        digest[..16].try_into().expect("16 Byte")
+    }
+
+    fn msg_index(&self, msg_id: u128) -> [u8; 16] {
+        self.index(&format!("msg:{msg_id}"))
+    }
+
+    fn put<T: Serialize>(&self, tree: &str, key: &[u8], value: &T) -> anyhow::Result<()> {
+        let plain = zeroize::Zeroizing::new(serde_json::to_vec(value)?);
+        self.db.open_tree(tree)?.insert(key, self.seal_value(tree, key, &plain))?;
+        Ok(())
+    }
+
+    fn get<T: DeserializeOwned>(&self, tree: &str, key: &[u8]) -> anyhow::Result<Option<T>> {
+        match self.db.open_tree(tree)?.get(key)? {
+            Some(sealed) => {
+                let plain = zeroize::Zeroizing::new(self.open_value(tree, key, &sealed)?);
+                Ok(Some(serde_json::from_slice(&plain)?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn scan<T: DeserializeOwned>(&self, tree: &str, prefix: &[u8]) -> anyhow::Result<Vec<T>> {
+        let mut out = Vec::new();
+        for item in self.db.open_tree(tree)?.scan_prefix(prefix) {
+            let (key, sealed) = item?;
+            let plain = zeroize::Zeroizing::new(self.open_value(tree, &key, &sealed)?);
+            out.push(serde_json::from_slice(&plain)?);
+        }
+        Ok(out)
+    }
+
+    // --- Einstellungen ----------------------------------------------------
+
+    pub fn get_meta<T: DeserializeOwned>(&self, name: &str) -> anyhow::Result<Option<T>> {
+        self.get("meta", name.as_bytes())
+    }
+
+    pub fn set_meta<T: Serialize>(&self, name: &str, value: &T) -> anyhow::Result<()> {
+        self.put("meta", name.as_bytes(), value)
+    }
+
+    // --- Konversationen ---------------------------------------------------
+
+    pub fn conversation(&self, id: &str) -> anyhow::Result<Option<Conversation>> {
+        self.get("conversations", &self.index(id))
+    }
+
+    pub fn save_conversation(&self, conv: &Conversation) -> anyhow::Result<()> {
+        self.put("conversations", &self.index(&conv.id), conv)
+    }
+
+    /// Alle Konversationen, zuletzt aktive zuerst.
+    pub fn conversations(&self) -> anyhow::Result<Vec<Conversation>> {
+        let mut all: Vec<Conversation> = self.scan("conversations", &[])?;
+        all.sort_by_key(|c| std::cmp::Reverse(c.last_activity));
+        Ok(all)
+    }
+
+    /// Legt die Konversation bei Bedarf an und aktualisiert die Aktivität.
+    pub fn touch_conversation(&self, id: &str, title: &str, ts: u64, unread: bool) -> anyhow::Result<Conversation> {
+        let mut conv = self.conversation(id)?.unwrap_or_else(|| Conversation {
+            id: id.to_owned(),
+            title: title.to_owned(),
+            created_at: ts,
+            last_activity: ts,
+            unread: 0,
+            expire_secs: 0,
+            timer_ts: 0,
+        });
+        conv.last_activity = conv.last_activity.max(ts);
+        if unread {
+            conv.unread += 1;
+        }
+        self.save_conversation(&conv)?;
+        Ok(conv)
+    }
+
+    // --- Nachrichten ------------------------------------------------------
+
+    fn message_key(&self, conversation: &str, ts: u64, msg_id: u128) -> Vec<u8> {
+        let mut key = self.index(conversation).to_vec();
+        key.extend_from_slice(&ts.to_be_bytes());
+        key.extend_from_slice(&msg_id.to_be_bytes());
+        key
+    }
+
+    /// Speichert eine Nachricht und aktualisiert die Konversation.  Als
+    /// ungelesen zählen nur eingehende Texte und Antworten, nicht etwa
+    /// Reaktionen oder Bearbeitungen.
+    ///
+    /// Eine Timer‑Nachricht setzt den Timer der Konversation, sofern sie
+    /// neuer ist als die letzte; so einigen sich beide Seiten (und alle
+    /// Geräte) auf den zuletzt gesetzten Wert.  Ist ein Timer aktiv, läuft
+    /// die Nachricht ab dem Speichern ab.
+    pub fn add_message(&self, msg: &StoredMessage, title: &str) -> anyhow::Result<()> {
+        let content = Content::from_bytes(&msg.body);
+        let unread = msg.direction == Direction::Incoming && content.as_ref().map_or(true, |c| c.is_message());
+        let mut conv = self.touch_conversation(&msg.conversation, title, msg.ts, unread)?;
+        if let Ok(Content::Timer { seconds }) = content {
+            if msg.ts >= conv.timer_ts {
+                conv.expire_secs = seconds;
+                conv.timer_ts = msg.ts;
+                self.save_conversation(&conv)?;
+            }
+        }
+        let mut msg = msg.clone();
+        if conv.expire_secs > 0 && msg.expires_at.is_none() {
+            msg.expires_at = Some(now_ms() + conv.expire_secs as u64 * 1000);
+        }
+        let key = self.message_key(&msg.conversation, msg.ts, msg.msg_id);
+        let Some(expires_at) = msg.expires_at else {
+            return self.put("messages", &key, &msg);
+        };
+        let (slot, value) = self.slots.allocate()?;
+        let plain = Zeroizing::new(serde_json::to_vec(&msg)?);
+        let sealed = seal(&self.message_cipher(&value), &key, &plain);
+        self.put("messages", &key, &ShreddedMessage { slot, sealed })?;
+        let mut index = expires_at.to_be_bytes().to_vec();
+        index.extend_from_slice(&key);
+        self.put("expiry", &index, &())
+    }
+
+    /// Öffnet eine verschwindende Nachricht; `None`, wenn ihr Schlüssel
+    /// vernichtet ist oder der Platz inzwischen einer anderen gehört.
+    fn unshred(&self, key: &[u8], slot: u32, sealed: &[u8]) -> anyhow::Result<Option<StoredMessage>> {
+        let Some(value) = self.slots.read(slot)? else {
+            return Ok(None);
+        };
+        match open(&self.message_cipher(&value), key, sealed) {
+            Some(plain) => Ok(Some(serde_json::from_slice(&Zeroizing::new(plain))?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Löscht alle bis `now` abgelaufenen Nachrichten samt
+    /// Postausgangseintrag und vernichtet ihre Schlüssel.  Gibt die Zahl
+    /// gelöschter Nachrichten zurück.
+    pub fn purge_expired(&self, now: u64) -> anyhow::Result<usize> {
+        let expiry = self.db.open_tree("expiry")?;
+        let messages = self.db.open_tree("messages")?;
+        let mut removed = 0;
+        // Indexschlüssel beginnen mit dem Ablaufzeitpunkt (BE)
+        for item in expiry.range(..(now + 1).to_be_bytes().to_vec()) {
+            let (index, _) = item?;
+            let key = &index[8..];
+            if let Ok(Some(ShreddedMessage { slot, sealed })) = self.get("messages", key) {
+                // Nur den eigenen Platz vernichten, nicht einen, der nach
+                // einem Abbruch schon neu vergeben wurde
+                if self.unshred(key, slot, &sealed)?.is_some() {
+                    self.slots.destroy(slot)?;
+                }
+            }
+            messages.remove(key)?;
+            let msg_id = u128::from_be_bytes(key[key.len() - 16..].try_into()?);
+            self.db.open_tree("outbox")?.remove(self.msg_index(msg_id))?;
+            expiry.remove(&index)?;
+            removed += 1;
+        }
+        if removed > 0 {
+            self.db.flush()?;
+        }
+        Ok(removed)
+    }
+
+    /// Nachrichten einer Konversation in zeitlicher Reihenfolge.
+    /// Verschwindende Nachrichten mit vernichtetem Schlüssel fehlen.
+    pub fn messages(&self, conversation: &str) -> anyhow::Result<Vec<StoredMessage>> {
+        let mut out = Vec::new();
+        for item in self.db.open_tree("messages")?.scan_prefix(self.index(conversation)) {
+            let (key, sealed) = item?;
+            let plain = Zeroizing::new(self.open_value("messages", &key, &sealed)?);
+            match serde_json::from_slice::<ShreddedMessage>(&plain) {
+                Ok(ShreddedMessage { slot, sealed }) => out.extend(self.unshred(&key, slot, &sealed)?),
+                Err(_) => out.push(serde_json::from_slice(&plain)?),
+            }
+        }
+        Ok(out)
+    }
+
+    // --- Sessions ---------------------------------------------------------
+
+    pub fn load_session(&self, conversation: &str) -> anyhow::Result<Option<SessionRecord>> {
+        let bytes: Option<Vec<u8>> = self.get("sessions", &self.index(conversation))?;
+        match bytes {
+            Some(bytes) => {
+                let bytes = zeroize::Zeroizing::new(bytes);
+                Ok(Some(SessionRecord::from_bytes(&bytes).ok_or_else(|| anyhow!("ungültiger Ratchet‑Zustand"))?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn save_session(&self, conversation: &str, record: &SessionRecord) -> anyhow::Result<()> {
+        let bytes = zeroize::Zeroizing::new(record.to_bytes());
+        self.put("sessions", &self.index(conversation), &*bytes)
+    }
+
+    // --- Postausgang ------------------------------------------------------
+
+    pub fn put_outbox(&self, entry: &OutboxEntry) -> anyhow::Result<()> {
+        self.put("outbox", &self.msg_index(entry.msg_id), entry)
+    }
+
+    pub fn outbox_entry(&self, msg_id: u128) -> anyhow::Result<Option<OutboxEntry>> {
+        self.get("outbox", &self.msg_index(msg_id))
+    }
+
+    /// Setzt den Zustand eines Postausgangseintrags.
+    pub fn set_outbox_state(&self, msg_id: u128, state: OutboxState) -> anyhow::Result<()> {
+        if let Some(mut entry) = self.outbox_entry(msg_id)? {
+            entry.state = state;
+            entry.updated_at = now_ms();
+            self.put_outbox(&entry)?;
+        }
+        Ok(())
+    }
+
+    // --- Kontakte ---------------------------------------------------------
+
+    fn contact_index(&self, nickname: &str) -> [u8; 16] {
+        self.index(&format!("contact:{nickname}"))
+    }
+
+    pub fn contact(&self, nickname: &str) -> anyhow::Result<Option<Contact>> {
+        self.get("contacts", &self.contact_index(nickname))
+    }
+
+    pub fn save_contact(&self, contact: &Contact) -> anyhow::Result<()> {
+        self.put("contacts", &self.contact_index(&contact.nickname), contact)
+    }
+
+    /// Entfernt einen Kontakt.  Gibt `false` zurück, wenn er nicht existierte.
+    pub fn remove_contact(&self, nickname: &str) -> anyhow::Result<bool> {
+        Ok(self.db.open_tree("contacts")?.remove(self.contact_index(nickname))?.is_some())
+    }
+
+    /// Alle Kontakte, alphabetisch nach Spitzname.
+    pub fn contacts(&self) -> anyhow::Result<Vec<Contact>> {
+        let mut all: Vec<Contact> = self.scan("contacts", &[])?;
+        all.sort_by(|a, b| a.nickname.cmp(&b.nickname));
+        Ok(all)
+    }
+
+    /// Sucht den Kontakt zu einer Konversations‑ID.
+    pub fn contact_for_conversation(&self, conversation: &str) -> anyhow::Result<Option<Contact>> {
+        Ok(self.contacts()?.into_iter().find(|c| c.conversation_id() == conversation))
+    }
+
+    // --- Subadressen -------------------------------------------------------
+
+    pub fn save_address(&self, record: &AddressRecord) -> anyhow::Result<()> {
+        self.put("addresses", &self.index(&format!("address:{}", record.index)), record)
+    }
+
+    /// Alle vergebenen Subadressen, nach Index sortiert.
+    pub fn addresses(&self) -> anyhow::Result<Vec<AddressRecord>> {
+        let mut all: Vec<AddressRecord> = self.scan("addresses", &[])?;
+        all.sort_by_key(|r| r.index);
+        Ok(all)
+    }
+
+    // --- Gruppen -----------------------------------------------------------
+
+    fn group_index(&self, id: &GroupId) -> [u8; 16] {
+        self.index(&format!("group:{}", to_hex(id)))
+    }
+
+    pub fn group(&self, id: &GroupId) -> anyhow::Result<Option<Group>> {
+        self.get("groups", &self.group_index(id))
+    }
+
+    pub fn save_group(&self, group: &Group) -> anyhow::Result<()> {
+        self.put("groups", &self.group_index(&group.id), group)
+    }
+
+    /// Entfernt eine Gruppe; ihr Verlauf bleibt erhalten.
+    pub fn remove_group(&self, id: &GroupId) -> anyhow::Result<bool> {
+        Ok(self.db.open_tree("groups")?.remove(self.group_index(id))?.is_some())
+    }
+
+    /// Alle Gruppen, alphabetisch nach Name.
+    pub fn groups(&self) -> anyhow::Result<Vec<Group>> {
+        let mut all: Vec<Group> = self.scan("groups", &[])?;
+        all.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(all)
+    }
+
+    // --- Replay‑Schutz ----------------------------------------------------
+
+    /// Prüft, ob `msg_id` bereits verarbeitet wurde, ohne sie
+    /// einzutragen.
+    pub fn is_replay(&self, msg_id: u128) -> anyhow::Result<bool> {
+        Ok(self.get::<u64>("replay", &self.msg_index(msg_id))?.is_some())
+    }
+
+    /// Trägt `msg_id` bis `expires_at` in den Replay‑Cache ein.  Erst
+    /// nach vollständiger Annahme der Nachricht aufrufen, damit gefälschte
+    /// Envelopes keine fremde ID verbrauchen.
+    pub fn record_replay(&self, msg_id: u128, expires_at: u64) -> anyhow::Result<()> {
+        self.put("replay", &self.msg_index(msg_id), &expires_at)
+    }
+
+    /// Entfernt abgelaufene Einträge aus dem Replay‑Cache.
+    pub fn prune_replay(&self, now: u64) -> anyhow::Result<usize> {
+        let tree = self.db.open_tree("replay")?;
+        let mut removed = 0;
+        for item in tree.iter() {
+            let (key, sealed) = item?;
+            let expires: u64 = serde_json::from_slice(&self.open_value("replay", &key, &sealed)?)?;
+            if expires <= now {
+                tree.remove(key)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Schreibt alle Änderungen auf die Platte.
+    pub fn flush(&self) -> anyhow::Result<()> {
+        self.db.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(msg_id: u128, expires_at: Option<u64>) -> StoredMessage {
+        StoredMessage {
+            conversation: "c".into(),
+            msg_id,
+            direction: Direction::Incoming,
+            ts: 1_000 + msg_id as u64,
+            body: Content::text(format!("nachricht {msg_id}")).to_bytes(),
+            expires_at,
+            sender: None,
+        }
+    }
+
+    #[test]
+    fn expired_messages_are_shredded() {
+        let store = Store::temporary(&[3u8; 32]).unwrap();
+        store.add_message(&message(1, None), "c").unwrap();
+        store.add_message(&message(2, Some(5_000)), "c").unwrap();
+        assert_eq!(store.messages("c").unwrap().len(), 2);
+
+        // Kopie des Datensatzes, wie sie im Log von `sled` zurückbleibt
+        let key = store.message_key("c", 1_002, 2);
+        let leftover = store.db.open_tree("messages").unwrap().get(&key).unwrap().unwrap();
+        assert_eq!(store.purge_expired(4_999).unwrap(), 0);
+        assert_eq!(store.purge_expired(5_000).unwrap(), 1);
+        let ids: Vec<_> = store.messages("c").unwrap().iter().map(|m| m.msg_id).collect();
+        assert_eq!(ids, vec![1]);
+
+        store.db.open_tree("messages").unwrap().insert(&key, leftover).unwrap();
+        let ids: Vec<_> = store.messages("c").unwrap().iter().map(|m| m.msg_id).collect();
+        assert_eq!(ids, vec![1]);
+    }
+
+    #[test]
+    fn freed_slots_are_reused_without_reviving_old_messages() {
+        let store = Store::temporary(&[3u8; 32]).unwrap();
+        store.add_message(&message(1, Some(5_000)), "c").unwrap();
+        let key = store.message_key("c", 1_001, 1);
+        let leftover = store.db.open_tree("messages").unwrap().get(&key).unwrap().unwrap();
+        store.purge_expired(5_000).unwrap();
+
+        store.add_message(&message(2, Some(9_000)), "c").unwrap();
+        assert_eq!(store.slots.inner.lock().unwrap().count, 1);
+        store.db.open_tree("messages").unwrap().insert(&key, leftover).unwrap();
+        let ids: Vec<_> = store.messages("c").unwrap().iter().map(|m| m.msg_id).collect();
+        assert_eq!(ids, vec![2]);
+    }
+}
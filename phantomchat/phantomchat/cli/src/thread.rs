@@ -0,0 +1,242 @@
+//! Darstellung typisierter Inhalte (siehe [`phantomchat_core::content`]).
+//!
+//! Reaktionen, Bearbeitungen und Löschungen werden wie Nachrichten
+//! gespeichert, aber nicht einzeln angezeigt: [`fold`] wendet sie auf die
+//! Nachricht an, auf die sie verweisen.  Bearbeiten und Löschen darf nur
+//! die Seite, die die Nachricht geschrieben hat; Reaktionen gelten je Seite
+//! und Emoji einmal.  In Gruppen ist jedes Mitglied eine eigene Seite.  Verweise auf unbekannte Nachrichten bleiben ohne
+//! Wirkung.  Timer‑Nachrichten erscheinen als Hinweis im Verlauf.
+
+use crate::store::{Direction, StoredMessage};
+use phantomchat_core::{Content, ContentError, GroupControl, Manifest};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Anzeigetext für Inhalte, die dieser Client nicht lesen kann.
+pub const UNSUPPORTED: &str = "[nicht unterstützter Inhalt]";
+
+/// Länge von Zitaten in Zeichen.
+const QUOTE_CHARS: usize = 40;
+
+/// Reaktion einer Seite auf eine Nachricht.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reaction {
+    pub emoji: String,
+    /// `Outgoing` für eigene Reaktionen.
+    pub direction: Direction,
+    /// Absender (Identity‑Key, hex) in Gruppen.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sender: Option<String>,
+}
+
+/// Eine Nachricht mit allen auf sie angewandten Inhalten.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub msg_id: u128,
+    pub direction: Direction,
+    /// Absender (Identity‑Key, hex) eingehender Gruppennachrichten.
+    pub sender: Option<String>,
+    pub ts: u64,
+    /// `text`, `reply`, `attachment`, `timer` oder `unsupported`.
+    pub kind: &'static str,
+    pub text: String,
+    /// Zitierte Nachricht einer Antwort.
+    pub reply_to: Option<u128>,
+    /// Anfang des zitierten Texts, sofern die Nachricht bekannt ist.
+    pub quote: Option<String>,
+    pub reactions: Vec<Reaction>,
+    pub edited: bool,
+    pub deleted: bool,
+    /// Löschzeitpunkt bei verschwindenden Nachrichten.
+    pub expires_at: Option<u64>,
+}
+
+impl Entry {
+    fn new(msg: &StoredMessage, kind: &'static str, text: String, reply_to: Option<u128>) -> Self {
+        Self {
+            msg_id: msg.msg_id,
+            direction: msg.direction,
+            sender: msg.sender.clone(),
+            ts: msg.ts,
+            kind,
+            text,
+            reply_to,
+            quote: None,
+            reactions: Vec::new(),
+            edited: false,
+            deleted: false,
+            expires_at: msg.expires_at,
+        }
+    }
+
+    /// Gekürzter Text für Zitate.
+    fn excerpt(&self) -> String {
+        if self.deleted {
+            return "(gelöscht)".into();
+        }
+        let mut chars = self.text.chars();
+        let mut out: String = chars.by_ref().take(QUOTE_CHARS).collect();
+        if chars.next().is_some() {
+            out.push('…');
+        }
+        out
+    }
+}
+
+/// Faltet einen Verlauf (zeitlich sortiert) zu anzeigbaren Nachrichten.
+pub fn fold(messages: &[StoredMessage]) -> Vec<Entry> {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut index: HashMap<u128, usize> = HashMap::new();
+    for msg in messages {
+        let content = Content::from_bytes(&msg.body);
+        let target = content.as_ref().ok().and_then(Content::target).and_then(|id| index.get(&id).copied());
+        let target = target.filter(|&i| !entries[i].deleted && entries[i].kind != "unsupported");
+        // Bearbeiten und Löschen nur durch die Seite, die geschrieben hat
+        let own = target.filter(|&i| entries[i].direction == msg.direction && entries[i].sender == msg.sender);
+        let entry = match content {
+            Ok(Content::Text { text }) => Some(Entry::new(msg, "text", text, None)),
+            Ok(Content::Reply { to, text }) => Some(Entry::new(msg, "reply", text, Some(to))),
+            Ok(Content::Attachment { manifest, caption }) => {
+                Some(Entry::new(msg, "attachment", attachment_text(&manifest, &caption), None))
+            }
+            Ok(Content::Timer { seconds }) => Some(Entry::new(msg, "timer", timer_text(seconds), None)),
+            Ok(Content::Reaction { emoji, remove, .. }) => {
+                if let Some(i) = target {
+                    let reactions = &mut entries[i].reactions;
+                    reactions.retain(|r| !(r.direction == msg.direction && r.sender == msg.sender && r.emoji == emoji));
+                    if !remove {
+                        reactions.push(Reaction { emoji, direction: msg.direction, sender: msg.sender.clone() });
+                    }
+                }
+                None
+            }
+            Ok(Content::Edit { text, .. }) => {
+                if let Some(i) = own {
+                    entries[i].text = text;
+                    entries[i].edited = true;
+                }
+                None
+            }
+            Ok(Content::Delete { .. }) => {
+                if let Some(i) = own {
+                    let entry = &mut entries[i];
+                    entry.text.clear();
+                    entry.reactions.clear();
+                    entry.deleted = true;
+                }
+                None
+            }
+            Ok(Content::Read { .. } | Content::Typing { .. } | Content::Group(_)) => None,
+            Err(_) => Some(Entry::new(msg, "unsupported", UNSUPPORTED.into(), None)),
+        };
+        if let Some(entry) = entry {
+            index.insert(entry.msg_id, entries.len());
+            entries.push(entry);
+        }
+    }
+    // Zitate erst zum Schluss, damit sie spätere Bearbeitungen zeigen
+    for i in 0..entries.len() {
+        let quote = entries[i].reply_to.and_then(|id| index.get(&id)).map(|&j| entries[j].excerpt());
+        entries[i].quote = quote;
+    }
+    entries
+}
+
+/// Einzeilige Beschreibung eines gerade empfangenen Inhalts.
+pub fn summary(content: &Result<Content, ContentError>) -> String {
+    match content {
+        Ok(Content::Text { text } | Content::Reply { text, .. }) => text.clone(),
+        Ok(Content::Attachment { manifest, caption }) => attachment_text(manifest, caption),
+        Ok(Content::Timer { seconds }) => timer_text(*seconds),
+        Ok(Content::Reaction { emoji, remove: false, .. }) => format!("reagiert mit {emoji}"),
+        Ok(Content::Reaction { emoji, remove: true, .. }) => format!("nimmt Reaktion {emoji} zurück"),
+        Ok(Content::Edit { text, .. }) => format!("(bearbeitet) {text}"),
+        Ok(Content::Delete { .. }) => "(Nachricht gelöscht)".into(),
+        Ok(Content::Read { ids }) => format!("hat {} Nachricht(en) gelesen", ids.len()),
+        Ok(Content::Typing { active: true }) => "tippt …".into(),
+        Ok(Content::Typing { active: false }) => "tippt nicht mehr".into(),
+        Ok(Content::Group(control)) => group_text(control),
+        Err(_) => UNSUPPORTED.into(),
+    }
+}
+
+/// Anzeigetext eines Anhangs: Dateiname, Größe, Bildunterschrift.
+pub fn attachment_text(manifest: &Manifest, caption: &str) -> String {
+    let mut text = format!("📎 {} ({})", manifest.name, format_size(manifest.size));
+    if !caption.is_empty() {
+        text.push_str(" – ");
+        text.push_str(caption);
+    }
+    text
+}
+
+/// Beschreibung einer Steuernachricht einer Gruppe.
+pub fn group_text(control: &GroupControl) -> String {
+    match control {
+        GroupControl::Update { name, members, .. } => format!("Gruppe „{name}“ mit {} Mitgliedern", members.len()),
+        GroupControl::SenderKey { .. } => "schickt einen Gruppenschlüssel".into(),
+        GroupControl::Leave { .. } => "verlässt eine Gruppe".into(),
+    }
+}
+
+/// Hinweistext einer Timer‑Nachricht.
+pub fn timer_text(seconds: u32) -> String {
+    match seconds {
+        0 => "⏱ Verschwindende Nachrichten aus".into(),
+        _ => format!("⏱ Verschwindende Nachrichten: {}", format_duration(seconds)),
+    }
+}
+
+/// Dauer in der größten glatten Einheit (`30 s`, `5 min`, `2 h`, `7 d`).
+pub fn format_duration(seconds: u32) -> String {
+    match seconds {
+        s if s >= 86_400 && s % 86_400 == 0 => format!("{} d", s / 86_400),
+        s if s >= 3600 && s % 3600 == 0 => format!("{} h", s / 3600),
+        s if s >= 60 && s % 60 == 0 => format!("{} min", s / 60),
+        s => format!("{s} s"),
+    }
+}
+
+/// Liest eine Dauer wie `30s`, `5m`, `2h`, `1d`, `1w` (ohne Einheit:
+/// Sekunden); `off` und `0` schalten ab.
+pub fn parse_duration(text: &str) -> Result<u32, String> {
+    let text = text.trim();
+    if text == "off" || text == "aus" {
+        return Ok(0);
+    }
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: u32 = number.parse().map_err(|_| format!("ungültige Dauer {text:?}"))?;
+    let factor = match unit.trim() {
+        "" | "s" => 1,
+        "m" | "min" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        "w" => 7 * 86_400,
+        other => return Err(format!("unbekannte Einheit {other:?} (s, m, h, d, w)")),
+    };
+    number.checked_mul(factor).ok_or_else(|| format!("Dauer {text:?} zu groß"))
+}
+
+/// Größe in B, KiB oder MiB.
+pub fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{bytes} B"),
+        1024..=1_048_575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+/// `msg_id`s der letzten `unread` eingehenden Nachrichten, zeitlich
+/// sortiert; Grundlage einer Lesebestätigung.
+pub fn unread_ids(messages: &[StoredMessage], unread: u32) -> Vec<u128> {
+    let mut ids: Vec<u128> = messages
+        .iter()
+        .rev()
+        .filter(|m| m.direction == Direction::Incoming && Content::from_bytes(&m.body).map_or(true, |c| c.is_message()))
+        .take(unread as usize)
+        .map(|m| m.msg_id)
+        .collect();
+    ids.reverse();
+    ids
+}
@@ -0,0 +1,528 @@
+//! Interaktive Terminal‑Oberfläche (`phantomchat chat`).
+//!
+//! Links die Konversationsliste, rechts der Verlauf der gewählten
+//! Konversation mit Zustellstatus aus dem Postausgang, darunter das
+//! Eingabefeld.  Die Statuszeile zeigt die Relay‑Gesundheit und die
+//! letzte Meldung.  Empfang und Versand nutzen dieselben Pfade wie
+//! `listen` und `send` ([`crate::receive`], [`crate::outgoing`]).
+//!
+//! Reaktionen, Bearbeitungen und Löschungen erscheinen an der Nachricht,
+//! auf die sie sich beziehen ([`crate::thread`]).  Mit Relays bestätigt
+//! die Oberfläche ungelesene Nachrichten der geöffneten Konversation und
+//! meldet dem Gegenüber, wenn getippt wird.  Abgelaufene verschwindende
+//! Nachrichten verschwinden beim nächsten Auffrischen.  Gruppen stehen mit
+//! `#` in der Liste; ausstehende Sender‑Keys werden beim Auffrischen
+//! verteilt (siehe [`crate::groups`]).
+//!
+//! Tasten: `Tab` wechselt zwischen Liste und Eingabe, `↑`/`↓` wählt eine
+//! Konversation, `Bild↑`/`Bild↓` blättert im Verlauf, `Enter` sendet,
+//! `Esc` oder `Strg+C` beendet.
+
+use crate::contacts::{Contact, Verification};
+use crate::groups::{self, GROUP_PREFIX};
+use crate::keystore::Keys;
+use crate::api::Local;
+use crate::outgoing::{self, Peer, SendPolicy};
+use crate::receive::{format_ts, Outcome};
+use crate::store::{self, Conversation, Direction, OutboxState, Store};
+use crate::thread::{self, Entry};
+use crossterm::event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::ExecutableCommand;
+use futures::StreamExt;
+use phantomchat_core::util::to_hex;
+use phantomchat_core::Content;
+use phantomchat_relays::{BridgeHealth, BridgeProvider, NostrRelay, RelayPool};
+use ratatui::backend::CrosstermBackend;
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{Frame, Terminal};
+use std::collections::HashMap;
+use std::io::Stdout;
+use std::time::{Duration, Instant};
+
+/// Intervall, in dem Relay‑Health und Postausgang neu gelesen werden.
+const REFRESH: Duration = Duration::from_secs(2);
+
+/// Mindestabstand zwischen zwei Tipp‑Hinweisen.
+const TYPING_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Relay‑Health‑Werte ab dieser Fehlerrate gelten als gestört.
+const UNHEALTHY_FAILURE_RATE: f32 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    List,
+    Compose,
+}
+
+/// Zustand der Oberfläche.
+struct App<'a> {
+    keys: &'a Keys,
+    store: &'a Store,
+    policy: &'a SendPolicy,
+    conversations: Vec<Conversation>,
+    contacts: Vec<Contact>,
+    list_state: ListState,
+    messages: Vec<Entry>,
+    /// Anzeigenamen der Absender in Gruppen (Identity‑Key hex → Name).
+    senders: HashMap<String, String>,
+    /// Noch zu sendende Lesebestätigung (Konversation, `msg_id`s).
+    receipt: Option<(String, Vec<u128>)>,
+    /// Zeitpunkt des letzten Tipp‑Hinweises.
+    typing_sent: Option<Instant>,
+    /// Zeilen vom Ende des Verlaufs aus gescrollt.
+    scroll: u16,
+    input: String,
+    focus: Focus,
+    health: Vec<(String, BridgeHealth)>,
+    status: String,
+}
+
+impl<'a> App<'a> {
+    fn new(keys: &'a Keys, store: &'a Store, policy: &'a SendPolicy) -> anyhow::Result<Self> {
+        let mut app = Self {
+            keys,
+            store,
+            policy,
+            conversations: Vec::new(),
+            contacts: Vec::new(),
+            list_state: ListState::default(),
+            messages: Vec::new(),
+            senders: HashMap::new(),
+            receipt: None,
+            typing_sent: None,
+            scroll: 0,
+            input: String::new(),
+            focus: Focus::Compose,
+            health: Vec::new(),
+            status: "Tab: Liste/Eingabe · Enter: senden · Esc: beenden".into(),
+        };
+        app.reload()?;
+        if !app.conversations.is_empty() {
+            app.list_state.select(Some(0));
+            app.load_messages()?;
+        }
+        Ok(app)
+    }
+
+    /// Liest Konversationen und Kontakte neu; die Auswahl bleibt an der
+    /// Konversations‑ID hängen.
+    fn reload(&mut self) -> anyhow::Result<()> {
+        let selected = self.selected().map(|c| c.id.clone());
+        self.conversations = self.store.conversations()?;
+        self.contacts = self.store.contacts()?;
+        // Kontakte ohne Konversation erscheinen ebenfalls, damit man sie
+        // anschreiben kann.
+        for contact in &self.contacts {
+            if !self.conversations.iter().any(|c| c.id == contact.conversation_id()) {
+                self.conversations.push(Conversation {
+                    id: contact.conversation_id(),
+                    title: contact.nickname.clone(),
+                    created_at: contact.added_at,
+                    last_activity: 0,
+                    unread: 0,
+                    expire_secs: 0,
+                    timer_ts: 0,
+                });
+            }
+        }
+        for group in self.store.groups()? {
+            if !self.conversations.iter().any(|c| c.id == group.conversation_id()) {
+                self.conversations.push(Conversation {
+                    id: group.conversation_id(),
+                    title: group.name.clone(),
+                    created_at: 0,
+                    last_activity: 0,
+                    unread: 0,
+                    expire_secs: 0,
+                    timer_ts: 0,
+                });
+            }
+        }
+        if let Some(id) = selected {
+            let index = self.conversations.iter().position(|c| c.id == id);
+            self.list_state.select(index.or(Some(0)));
+        }
+        Ok(())
+    }
+
+    fn selected(&self) -> Option<&Conversation> {
+        self.list_state.selected().and_then(|i| self.conversations.get(i))
+    }
+
+    fn contact_for(&self, conversation: &str) -> Option<&Contact> {
+        self.contacts.iter().find(|c| c.conversation_id() == conversation)
+    }
+
+    /// Lädt den Verlauf der gewählten Konversation und setzt den
+    /// Ungelesen‑Zähler zurück; die ungelesenen Nachrichten werden zur
+    /// Bestätigung vorgemerkt.
+    fn load_messages(&mut self) -> anyhow::Result<()> {
+        let Some(id) = self.selected().map(|c| c.id.clone()) else {
+            self.messages.clear();
+            return Ok(());
+        };
+        let stored = self.store.messages(&id)?;
+        self.messages = thread::fold(&stored);
+        self.senders.clear();
+        if let Some(group) = groups::for_conversation(self.store, &id)? {
+            for member in &group.members {
+                self.senders.insert(to_hex(&member.identity_pub), groups::display_name(self.store, member)?);
+            }
+        }
+        if let Some(mut conv) = self.store.conversation(&id)? {
+            if conv.unread > 0 {
+                self.receipt = Some((id.clone(), thread::unread_ids(&stored, conv.unread)));
+                conv.unread = 0;
+                self.store.save_conversation(&conv)?;
+                self.store.flush()?;
+                if let Some(i) = self.list_state.selected() {
+                    self.conversations[i].unread = 0;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn select(&mut self, delta: isize) -> anyhow::Result<()> {
+        if self.conversations.is_empty() {
+            return Ok(());
+        }
+        let len = self.conversations.len() as isize;
+        let current = self.list_state.selected().unwrap_or(0) as isize;
+        self.list_state.select(Some((current + delta).rem_euclid(len) as usize));
+        self.scroll = 0;
+        self.load_messages()
+    }
+
+    /// Zustellstatus einer ausgehenden Nachricht.
+    fn delivery_marker(&self, msg_id: u128) -> &'static str {
+        match self.store.outbox_entry(msg_id).ok().flatten().map(|e| e.state) {
+            Some(OutboxState::Pending) => "…",
+            Some(OutboxState::Published) => "✓",
+            Some(OutboxState::Delivered) => "✓✓",
+            Some(OutboxState::Acked) => "✓✓✓",
+            None => " ",
+        }
+    }
+
+    fn relay_summary(&self) -> String {
+        if self.health.is_empty() {
+            return "offline (keine Relays)".into();
+        }
+        let healthy: Vec<&BridgeHealth> =
+            self.health.iter().map(|(_, h)| h).filter(|h| h.failure_rate < UNHEALTHY_FAILURE_RATE).collect();
+        let latency = if healthy.is_empty() {
+            0
+        } else {
+            healthy.iter().map(|h| h.latency_ms as u64).sum::<u64>() / healthy.len() as u64
+        };
+        format!("Relays {}/{} ok · ⌀ {} ms", healthy.len(), self.health.len(), latency)
+    }
+}
+
+/// Stellt das Terminal auch bei Fehlern oder Panics wieder her.
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> anyhow::Result<Self> {
+        enable_raw_mode()?;
+        std::io::stdout().execute(EnterAlternateScreen)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = std::io::stdout().execute(LeaveAlternateScreen);
+    }
+}
+
+/// Startet die Oberfläche.  Ohne Relays läuft sie offline und zeigt nur
+/// den gespeicherten Verlauf.
+pub async fn run(local: &Local) -> anyhow::Result<()> {
+    let (store, pool) = (&local.store, local.pool.as_ref());
+    let receiver = local.receiver();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut app = App::new(&local.keys, store, &local.send)?;
+    if let Some(pool) = pool {
+        let since = store.get_meta::<u64>("last_sync")?.unwrap_or(0);
+        for env in pool.fetch_since(since).await? {
+            receiver.handle(&env)?;
+        }
+        pool.subscribe(move |env| {
+            let _ = tx.send(env);
+        })
+        .await?;
+        app.health = pool.health_all().await;
+        app.reload()?;
+        app.load_messages()?;
+    }
+
+    let _guard = TerminalGuard::enter()?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
+    let mut events = EventStream::new();
+    let mut refresh = tokio::time::interval(REFRESH);
+    loop {
+        draw(&mut terminal, &mut app)?;
+        tokio::select! {
+            event = events.next() => {
+                let Some(event) = event else { break };
+                if let Event::Key(key) = event? {
+                    if key.kind == KeyEventKind::Press && !handle_key(&mut app, pool, key).await? {
+                        break;
+                    }
+                }
+            }
+            Some(env) = rx.recv() => {
+                let msg = match receiver.handle(&env)? {
+                    Outcome::Received(msg) => {
+                        app.status = match (msg.direction, msg.content().is_ok_and(|c| c.is_message())) {
+                            (Direction::Incoming, true) => format!("Neue Nachricht von {}", msg.origin()),
+                            (Direction::Incoming, false) => format!("{} {}", msg.origin(), thread::summary(&msg.content())),
+                            (Direction::Outgoing, _) => format!("Von anderem Gerät an {} gesendet", msg.sender),
+                        };
+                        msg
+                    }
+                    Outcome::Signal(msg) => {
+                        app.status = format!("{} {}", msg.origin(), thread::summary(&msg.content()));
+                        msg
+                    }
+                    _ => continue,
+                };
+                store.set_meta("last_sync", &store::now_ms())?;
+                store.flush()?;
+                app.reload()?;
+                if app.selected().map(|c| c.id == msg.conversation).unwrap_or(false) {
+                    app.load_messages()?;
+                }
+            }
+            _ = refresh.tick() => {
+                if let Some(pool) = pool {
+                    app.health = pool.health_all().await;
+                    local.distribute_groups().await?;
+                }
+                if store.purge_expired(store::now_ms())? > 0 {
+                    app.reload()?;
+                    app.load_messages()?;
+                }
+            }
+        }
+        if let (Some(pool), Some((conversation, ids))) = (pool, app.receipt.take()) {
+            if !ids.is_empty() {
+                send_signal(&mut app, pool, &conversation, Content::Read { ids }).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Sendet eine Lesebestätigung oder einen Tipp‑Hinweis.  Beides ist
+/// flüchtig; scheitert das Senden, steht es nur in der Statuszeile.
+async fn send_signal(app: &mut App<'_>, pool: &RelayPool<NostrRelay>, conversation: &str, content: Content) -> anyhow::Result<()> {
+    let Some(peer) = Peer::for_conversation(app.store, conversation)? else {
+        return Ok(());
+    };
+    let prepared = outgoing::prepare(app.keys, app.store, app.policy, &peer, &content.to_bytes())?;
+    if let Err(e) = outgoing::publish(app.store, pool, prepared).await {
+        app.status = format!("{} nicht gesendet: {e}", content.kind());
+    }
+    Ok(())
+}
+
+/// Verarbeitet einen Tastendruck.  Gibt `false` zum Beenden zurück.
+async fn handle_key(app: &mut App<'_>, pool: Option<&RelayPool<NostrRelay>>, key: KeyEvent) -> anyhow::Result<bool> {
+    if key.code == KeyCode::Esc || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL)) {
+        return Ok(false);
+    }
+    match (app.focus, key.code) {
+        (_, KeyCode::Tab) => {
+            app.focus = if app.focus == Focus::List { Focus::Compose } else { Focus::List };
+        }
+        (_, KeyCode::PageUp) => app.scroll = app.scroll.saturating_add(5),
+        (_, KeyCode::PageDown) => app.scroll = app.scroll.saturating_sub(5),
+        (Focus::List, KeyCode::Up) => app.select(-1)?,
+        (Focus::List, KeyCode::Down) => app.select(1)?,
+        (Focus::List, KeyCode::Enter) => app.focus = Focus::Compose,
+        (Focus::Compose, KeyCode::Char(c)) => {
+            app.input.push(c);
+            let due = app.typing_sent.is_none_or(|at| at.elapsed() >= TYPING_INTERVAL);
+            if let (Some(pool), Some(conversation), true) = (pool, app.selected().map(|c| c.id.clone()), due) {
+                app.typing_sent = Some(Instant::now());
+                send_signal(app, pool, &conversation, Content::Typing { active: true }).await?;
+            }
+        }
+        (Focus::Compose, KeyCode::Backspace) => {
+            app.input.pop();
+        }
+        (Focus::Compose, KeyCode::Enter) => send_input(app, pool).await?,
+        _ => {}
+    }
+    Ok(true)
+}
+
+/// Sendet den Inhalt des Eingabefelds an die gewählte Konversation.
+async fn send_input(app: &mut App<'_>, pool: Option<&RelayPool<NostrRelay>>) -> anyhow::Result<()> {
+    let text = app.input.trim().to_owned();
+    if text.is_empty() {
+        return Ok(());
+    }
+    let Some(conversation) = app.selected().map(|c| c.id.clone()) else {
+        app.status = "Keine Konversation gewählt".into();
+        return Ok(());
+    };
+    let body = Content::text(text).to_bytes();
+    let (prepared, title) = match groups::for_conversation(app.store, &conversation)? {
+        Some(mut group) => {
+            if let Some(pool) = pool {
+                groups::distribute(app.keys, app.store, app.policy, pool, &mut group).await?;
+            }
+            (groups::prepare(app.keys, app.store, app.policy, &mut group, &body)?, group.name)
+        }
+        None => {
+            let Some(peer) = Peer::for_conversation(app.store, &conversation)? else {
+                app.status = "Kein Rückkanal zu unbekanntem Absender – zuerst als Kontakt importieren".into();
+                return Ok(());
+            };
+            (outgoing::prepare(app.keys, app.store, app.policy, &peer, &body)?, peer.title)
+        }
+    };
+    app.input.clear();
+    app.typing_sent = None;
+    app.scroll = 0;
+    app.status = match pool {
+        Some(pool) => match outgoing::publish(app.store, pool, prepared).await {
+            Ok(()) => format!("An {title} gesendet"),
+            Err(e) => format!("Senden fehlgeschlagen (bleibt im Postausgang): {e}"),
+        },
+        None => "Offline: Nachricht im Postausgang gespeichert".into(),
+    };
+    app.reload()?;
+    app.load_messages()
+}
+
+fn draw(terminal: &mut Terminal<CrosstermBackend<Stdout>>, app: &mut App<'_>) -> anyhow::Result<()> {
+    terminal.draw(|frame| render(frame, app))?;
+    Ok(())
+}
+
+fn render(frame: &mut Frame, app: &mut App<'_>) {
+    let [main, status] = Layout::vertical([Constraint::Min(5), Constraint::Length(1)]).areas(frame.size());
+    let [list_area, chat_area] = Layout::horizontal([Constraint::Length(28), Constraint::Min(20)]).areas(main);
+
+    // Konversationsliste mit Verifikationsstatus und Ungelesen‑Zähler
+    let items: Vec<ListItem> = app
+        .conversations
+        .iter()
+        .map(|conv| {
+            let (mark, style) = match app.contact_for(&conv.id).map(|c| c.verification) {
+                _ if conv.id.starts_with(GROUP_PREFIX) => ("# ", Style::default()),
+                Some(Verification::Verified) => ("✓ ", Style::default().fg(Color::Green)),
+                Some(Verification::KeyChanged) => ("⚠ ", Style::default().fg(Color::Red)),
+                Some(Verification::Unverified) => ("  ", Style::default()),
+                None => ("? ", Style::default().fg(Color::DarkGray)),
+            };
+            let mut spans = vec![Span::styled(mark, style), Span::raw(conv.title.clone())];
+            if conv.unread > 0 {
+                spans.push(Span::styled(format!(" ({})", conv.unread), Style::default().add_modifier(Modifier::BOLD)));
+            }
+            ListItem::new(Line::from(spans))
+        })
+        .collect();
+    let list = List::new(items)
+        .block(focused_block("Konversationen", app.focus == Focus::List))
+        .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+    frame.render_stateful_widget(list, list_area, &mut app.list_state);
+
+    // Warnung bei geändertem Schlüssel über dem Verlauf
+    let warning = app
+        .selected()
+        .and_then(|c| app.contact_for(&c.id))
+        .filter(|c| c.verification == Verification::KeyChanged)
+        .map(|c| format!("⚠ Schlüssel von {} geändert – mit `contacts verify {}` prüfen", c.nickname, c.nickname));
+    let [warn_area, history_area, compose_area] = Layout::vertical([
+        Constraint::Length(if warning.is_some() { 1 } else { 0 }),
+        Constraint::Min(3),
+        Constraint::Length(3),
+    ])
+    .areas(chat_area);
+    if let Some(text) = warning {
+        frame.render_widget(
+            Paragraph::new(text).style(Style::default().fg(Color::White).bg(Color::Red)),
+            warn_area,
+        );
+    }
+
+    // Verlauf, am Ende verankert
+    let title = match app.selected() {
+        Some(conv) if conv.expire_secs > 0 => format!("{} ⏱ {}", conv.title, thread::format_duration(conv.expire_secs)),
+        Some(conv) => conv.title.clone(),
+        None => String::new(),
+    };
+    let lines: Vec<Line> = app
+        .messages
+        .iter()
+        .map(|msg| {
+            let dim = Style::default().fg(Color::DarkGray);
+            let mut spans = vec![Span::styled(format!("{} ", format_ts(msg.ts)), dim)];
+            spans.push(match msg.direction {
+                Direction::Incoming => Span::styled("← ", Style::default().fg(Color::Cyan)),
+                Direction::Outgoing => Span::styled("→ ", Style::default().fg(Color::Yellow)),
+            });
+            if let Some(sender) = &msg.sender {
+                let name = app.senders.get(sender).cloned().unwrap_or_else(|| sender.chars().take(16).collect());
+                spans.push(Span::styled(format!("{name}: "), Style::default().fg(Color::Cyan)));
+            }
+            if let Some(quote) = &msg.quote {
+                spans.push(Span::styled(format!("↪ „{quote}“ "), dim));
+            }
+            if msg.deleted {
+                spans.push(Span::styled("(gelöscht)", dim.add_modifier(Modifier::ITALIC)));
+            } else {
+                spans.push(Span::raw(msg.text.clone()));
+            }
+            if msg.edited && !msg.deleted {
+                spans.push(Span::styled(" (bearbeitet)", dim));
+            }
+            if !msg.reactions.is_empty() {
+                let emoji: Vec<&str> = msg.reactions.iter().map(|r| r.emoji.as_str()).collect();
+                spans.push(Span::raw(format!(" [{}]", emoji.join(" "))));
+            }
+            if msg.direction == Direction::Outgoing {
+                spans.push(Span::styled(format!(" {}", app.delivery_marker(msg.msg_id)), dim));
+            }
+            Line::from(spans)
+        })
+        .collect();
+    let visible = history_area.height.saturating_sub(2);
+    let bottom = (lines.len() as u16).saturating_sub(visible);
+    app.scroll = app.scroll.min(bottom);
+    let history = Paragraph::new(lines)
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .wrap(Wrap { trim: false })
+        .scroll((bottom - app.scroll, 0));
+    frame.render_widget(history, history_area);
+
+    let compose = Paragraph::new(app.input.as_str()).block(focused_block("Nachricht", app.focus == Focus::Compose));
+    frame.render_widget(compose, compose_area);
+    if app.focus == Focus::Compose {
+        let x = compose_area.x + 1 + app.input.chars().count() as u16;
+        frame.set_cursor(x.min(compose_area.right().saturating_sub(2)), compose_area.y + 1);
+    }
+
+    let status_line = Line::from(vec![
+        Span::styled(format!(" {} ", app.relay_summary()), Style::default().fg(Color::Black).bg(Color::Gray)),
+        Span::raw(" "),
+        Span::raw(app.status.clone()),
+    ]);
+    frame.render_widget(Paragraph::new(status_line), status);
+}
+
+fn focused_block(title: &str, focused: bool) -> Block<'static> {
+    let style = if focused { Style::default().fg(Color::Yellow) } else { Style::default() };
+    Block::default().borders(Borders::ALL).border_style(style).title(title.to_owned())
+}
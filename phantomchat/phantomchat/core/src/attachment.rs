@@ -0,0 +1,291 @@
+//! Verschlüsselte Anhänge.
+//!
+//! Dateien passen nicht in ein Envelope.  Der Absender verschlüsselt sie
+//! daher mit einem zufälligen Dateischlüssel in Blöcken von
+//! [`CHUNK_SIZE`] Bytes (der letzte wird mit Nullbytes aufgefüllt) und
+//! veröffentlicht jeden Block als [`Chunk`] unter dem SHA‑256 seines
+//! Ciphertexts.  Die Blöcke tragen wie Envelopes einen Proof‑of‑Work und
+//! eine TTL.  Schlüssel, Blockhashes und der Hash der ganzen Datei gehen
+//! als [`Manifest`] über die Ratchet an den Empfänger (siehe
+//! [`crate::content`]).
+//!
+//! Block `i` von `n` wird mit XChaCha20‑Poly1305 verschlüsselt; Nonce ist
+//! `i` (64 Bit, LE, mit Nullen auf 24 Byte aufgefüllt), Associated Data
+//! `"pc.attachment.v1" | i(4, BE) | n(4, BE)`.  Da der Schlüssel nur für
+//! eine Datei gilt, wiederholt sich kein Nonce.  Die Verschlüsselung ist
+//! damit deterministisch: Wer einen Block im Klartext hat, kann ihn gegen
+//! den Blockhash prüfen, ohne ihn erneut zu laden.
+//!
+//! Format eines Blocks:
+//! `ts(8, LE) | ttl(4, LE) | pow_nonce(8, LE) | data`.  Der
+//! Proof‑of‑Work läuft über `id | ts | ttl`.
+
+use crate::pow::Hashcash;
+use crate::util::sha256;
+use chacha20poly1305::aead::{Aead, Payload};
+use chacha20poly1305::{KeyInit, XChaCha20Poly1305, XNonce};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+/// Klartextgröße eines Blocks.  Mit Base64 und Event‑Hülle bleibt ein
+/// Block unter den üblichen Größengrenzen von Nostr‑Relays.
+pub const CHUNK_SIZE: usize = 32 * 1024;
+/// Größte Datei, die als Anhang verschickt wird.
+pub const MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+const DOMAIN: &[u8] = b"pc.attachment.v1";
+const HEADER_LEN: usize = 8 + 4 + 8;
+
+/// Fehler beim Ver‑ oder Entschlüsseln eines Anhangs.
+#[derive(Debug, thiserror::Error)]
+pub enum AttachmentError {
+    #[error("Datei zu groß ({0} Bytes, höchstens {MAX_SIZE})")]
+    TooLarge(u64),
+    #[error("Block {0} existiert nicht")]
+    Index(usize),
+    #[error("Manifest nennt {0} Blöcke, die Dateigröße {1}")]
+    ChunkCount(usize, u64),
+    #[error("Block {0} passt nicht zu seinem Hash")]
+    Hash(usize),
+    #[error("Block {0} lässt sich nicht entschlüsseln")]
+    Decrypt(usize),
+    #[error("Datei passt nicht zu ihrem Hash")]
+    Digest,
+}
+
+/// Ein verschlüsselter Block auf den Relays.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    /// Zeitstempel in Millisekunden seit UNIX‑Epoche.
+    pub ts: u64,
+    /// Time‑to‑Live in Sekunden.
+    pub ttl: u32,
+    pub pow_nonce: u64,
+    /// Ciphertext des Blocks.
+    pub data: Vec<u8>,
+}
+
+impl Chunk {
+    /// Erzeugt einen Block und berechnet den Proof‑of‑Work.
+    pub fn new(data: Vec<u8>, ts: u64, ttl: u32, pow_difficulty: u32) -> Self {
+        let mut chunk = Self { ts, ttl, pow_nonce: 0, data };
+        chunk.pow_nonce = Hashcash::new(pow_difficulty).compute_nonce(&chunk.pow_input());
+        chunk
+    }
+    /// Inhaltsadresse: SHA‑256 des Ciphertexts.
+    pub fn id(&self) -> [u8; 32] {
+        chunk_id(&self.data)
+    }
+    fn pow_input(&self) -> Vec<u8> {
+        let mut out = self.id().to_vec();
+        out.extend_from_slice(&self.ts.to_le_bytes());
+        out.extend_from_slice(&self.ttl.to_le_bytes());
+        out
+    }
+    pub fn verify_pow(&self, difficulty: u32) -> bool {
+        Hashcash::new(difficulty).verify(&self.pow_input(), self.pow_nonce)
+    }
+    /// Ablaufzeitpunkt in Millisekunden; `ts` stammt vom Relay und wird
+    /// deshalb sättigend addiert.
+    pub fn expires_at(&self) -> u64 {
+        self.ts.saturating_add(self.ttl as u64 * 1000)
+    }
+    /// Prüft wie [`crate::Envelope::is_expired`], ob die TTL abgelaufen ist.
+    pub fn is_expired(&self, now_ms: u64) -> bool {
+        now_ms >= self.expires_at()
+    }
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(HEADER_LEN + self.data.len());
+        out.extend_from_slice(&self.ts.to_le_bytes());
+        out.extend_from_slice(&self.ttl.to_le_bytes());
+        out.extend_from_slice(&self.pow_nonce.to_le_bytes());
+        out.extend_from_slice(&self.data);
+        out
+    }
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        if data.len() < HEADER_LEN {
+            return None;
+        }
+        Some(Self {
+            ts: u64::from_le_bytes(data[0..8].try_into().ok()?),
+            ttl: u32::from_le_bytes(data[8..12].try_into().ok()?),
+            pow_nonce: u64::from_le_bytes(data[12..20].try_into().ok()?),
+            data: data[HEADER_LEN..].to_vec(),
+        })
+    }
+}
+
+/// SHA‑256 eines Block‑Ciphertexts.
+pub fn chunk_id(data: &[u8]) -> [u8; 32] {
+    sha256(data).try_into().expect("SHA-256 hat 32 Byte")
+}
+
+/// Beschreibung eines Anhangs; reist verschlüsselt über die Ratchet.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    /// Dateiname ohne Pfad.
+    pub name: String,
+    /// Größe in Bytes.
+    pub size: u64,
+    /// SHA‑256 des Klartexts.
+    pub digest: [u8; 32],
+    /// Dateischlüssel.
+    pub key: [u8; 32],
+    /// Blockhashes in Dateireihenfolge.
+    pub chunks: Vec<[u8; 32]>,
+    /// Ablauf der Blöcke auf den Relays (Millisekunden).
+    pub expires_at: u64,
+}
+
+impl std::fmt::Debug for Manifest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Manifest")
+            .field("name", &self.name)
+            .field("size", &self.size)
+            .field("chunks", &self.chunks.len())
+            .field("expires_at", &self.expires_at)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Verschlüsselt `data` und liefert Manifest und Blöcke.
+pub fn seal(name: &str, data: &[u8], ts: u64, ttl: u32, pow_difficulty: u32) -> Result<(Manifest, Vec<Chunk>), AttachmentError> {
+    if data.len() as u64 > MAX_SIZE {
+        return Err(AttachmentError::TooLarge(data.len() as u64));
+    }
+    let mut key = Zeroizing::new([0u8; 32]);
+    OsRng.fill_bytes(key.as_mut());
+    // Auch leere Dateien bestehen aus einem (aufgefüllten) Block
+    let count = data.len().div_ceil(CHUNK_SIZE).max(1);
+    let mut chunks = Vec::with_capacity(count);
+    for index in 0..count {
+        let block = &data[(index * CHUNK_SIZE).min(data.len())..((index + 1) * CHUNK_SIZE).min(data.len())];
+        chunks.push(Chunk::new(encrypt_block(&key, index, count, block), ts, ttl, pow_difficulty));
+    }
+    let manifest = Manifest {
+        name: name.to_owned(),
+        size: data.len() as u64,
+        digest: chunk_id(data),
+        key: *key,
+        chunks: chunks.iter().map(Chunk::id).collect(),
+        expires_at: ts.saturating_add(ttl as u64 * 1000),
+    };
+    Ok((manifest, chunks))
+}
+
+impl Manifest {
+    /// Prüft, ob Größe und Blockliste zusammenpassen: höchstens
+    /// [`MAX_SIZE`] Bytes und genau ein Block je angefangene
+    /// [`CHUNK_SIZE`] Bytes, bei leeren Dateien einer.  Das Manifest stammt
+    /// vom Absender und muss vor jedem Dateizugriff geprüft sein.
+    pub fn validate(&self) -> Result<(), AttachmentError> {
+        if self.size > MAX_SIZE {
+            return Err(AttachmentError::TooLarge(self.size));
+        }
+        if self.chunks.len() as u64 != self.size.div_ceil(CHUNK_SIZE as u64).max(1) {
+            return Err(AttachmentError::ChunkCount(self.chunks.len(), self.size));
+        }
+        Ok(())
+    }
+    /// Klartextlänge von Block `index` (der letzte ist kürzer).
+    pub fn block_len(&self, index: usize) -> usize {
+        let start = (index * CHUNK_SIZE) as u64;
+        self.size.saturating_sub(start).min(CHUNK_SIZE as u64) as usize
+    }
+    /// Prüft Hash und Tag von Block `index` und entschlüsselt ihn.
+    pub fn open_chunk(&self, index: usize, data: &[u8]) -> Result<Vec<u8>, AttachmentError> {
+        let expected = self.chunks.get(index).ok_or(AttachmentError::Index(index))?;
+        if chunk_id(data) != *expected {
+            return Err(AttachmentError::Hash(index));
+        }
+        let cipher = XChaCha20Poly1305::new_from_slice(&self.key).expect("32 Byte");
+        let aad = associated_data(index, self.chunks.len());
+        let mut plain = cipher
+            .decrypt(&block_nonce(index), Payload { msg: data, aad: &aad })
+            .map_err(|_| AttachmentError::Decrypt(index))?;
+        plain.truncate(self.block_len(index));
+        Ok(plain)
+    }
+    /// Ob `block` der Klartext von Block `index` ist.  Erlaubt das
+    /// Fortsetzen abgebrochener Downloads ohne gespeicherten Zustand.
+    pub fn has_block(&self, index: usize, block: &[u8]) -> bool {
+        block.len() == self.block_len(index)
+            && self.chunks.get(index).is_some_and(|id| chunk_id(&encrypt_block(&self.key, index, self.chunks.len(), block)) == *id)
+    }
+    /// Prüft die zusammengesetzte Datei.
+    pub fn verify(&self, data: &[u8]) -> Result<(), AttachmentError> {
+        if data.len() as u64 != self.size || chunk_id(data) != self.digest {
+            return Err(AttachmentError::Digest);
+        }
+        Ok(())
+    }
+}
+
+/// Verschlüsselt einen Block, aufgefüllt auf [`CHUNK_SIZE`].
+fn encrypt_block(key: &[u8; 32], index: usize, count: usize, block: &[u8]) -> Vec<u8> {
+    let mut padded = Zeroizing::new(vec![0u8; CHUNK_SIZE]);
+    padded[..block.len()].copy_from_slice(block);
+    let cipher = XChaCha20Poly1305::new_from_slice(key).expect("32 Byte");
+    let aad = associated_data(index, count);
+    cipher.encrypt(&block_nonce(index), Payload { msg: &padded, aad: &aad }).expect("XChaCha20-Poly1305")
+}
+
+fn block_nonce(index: usize) -> XNonce {
+    let mut nonce = [0u8; 24];
+    nonce[..8].copy_from_slice(&(index as u64).to_le_bytes());
+    XNonce::from(nonce)
+}
+
+fn associated_data(index: usize, count: usize) -> Vec<u8> {
+    let mut aad = DOMAIN.to_vec();
+    aad.extend_from_slice(&(index as u32).to_be_bytes());
+    aad.extend_from_slice(&(count as u32).to_be_bytes());
+    aad
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let data = vec![5u8; CHUNK_SIZE + 10];
+        let (manifest, chunks) = seal("a.bin", &data, 0, 60, 0).unwrap();
+        manifest.validate().unwrap();
+        let mut out = Vec::new();
+        for (index, chunk) in chunks.iter().enumerate() {
+            out.extend(manifest.open_chunk(index, &chunk.data).unwrap());
+        }
+        manifest.verify(&out).unwrap();
+    }
+
+    #[test]
+    fn inconsistent_manifests_are_rejected() {
+        let (manifest, _) = seal("leer", &[], 0, 60, 0).unwrap();
+        assert_eq!(manifest.chunks.len(), 1);
+        manifest.validate().unwrap();
+        let check = |size: u64, chunks: usize| Manifest { size, chunks: vec![[0u8; 32]; chunks], ..manifest.clone() }.validate();
+        assert!(matches!(check(0, 0), Err(AttachmentError::ChunkCount(0, 0))));
+        assert!(matches!(check(10, 2), Err(AttachmentError::ChunkCount(2, 10))));
+        assert!(matches!(check(CHUNK_SIZE as u64 + 1, 1), Err(AttachmentError::ChunkCount(1, _))));
+        assert!(check(CHUNK_SIZE as u64 + 1, 2).is_ok());
+        assert!(matches!(check(MAX_SIZE + 1, 2049), Err(AttachmentError::TooLarge(_))));
+        assert!(matches!(check(u64::MAX, 0), Err(AttachmentError::TooLarge(_))));
+    }
+
+    #[test]
+    fn expiry_saturates_on_relay_timestamps() {
+        let chunk = Chunk::new(vec![1, 2, 3], u64::MAX - 10, 60, 0);
+        let chunk = Chunk::from_bytes(&chunk.to_bytes()).unwrap();
+        assert_eq!(chunk.expires_at(), u64::MAX);
+        assert!(!chunk.is_expired(u64::MAX - 1));
+        assert!(chunk.is_expired(u64::MAX));
+
+        let chunk = Chunk::new(Vec::new(), 1_000, 1, 0);
+        assert!(!chunk.is_expired(1_999));
+        assert!(chunk.is_expired(2_000));
+        let (manifest, _) = seal("spät", &[], u64::MAX, 60, 0).unwrap();
+        assert_eq!(manifest.expires_at, u64::MAX);
+    }
+}
@@ -0,0 +1,214 @@
+//! Typisierte Nachrichteninhalte.
+//!
+//! `Payload::body` trägt bislang rohen UTF‑8‑Text.  Inhalte mit Struktur
+//! (Antworten, Reaktionen, Bearbeitungen, Löschungen, Lesebestätigungen,
+//! Tipp‑Hinweise, Timer) werden als CBOR kodiert und mit einem Marker und der
+//! Schemaversion versehen:
+//!
+//! ```text
+//! 0x00 | version(1) | CBOR(Content)
+//! ```
+//!
+//! Text beginnt nie mit einem Nullbyte; Bodies ohne Marker sind daher
+//! Klartext älterer Clients und werden als [`Content::Text`] gelesen.
+//! Verweise auf andere Nachrichten nutzen deren `msg_id`.  Anhänge
+//! reisen als [`Manifest`]; die Datei selbst liegt in verschlüsselten
+//! Blöcken auf den Relays (siehe [`crate::attachment`]).  Steuernachrichten
+//! von Gruppen ([`Content::Group`]) reisen über die paarweisen Sessions
+//! der Mitglieder (siehe [`crate::group`]).
+
+use crate::attachment::Manifest;
+use crate::group::GroupControl;
+use serde::{Deserialize, Serialize};
+
+/// Aktuelle Schemaversion.
+pub const CONTENT_VERSION: u8 = 1;
+/// Erstes Byte kodierter Inhalte.
+const MARKER: u8 = 0x00;
+
+/// Fehler beim Lesen eines Inhalts.
+#[derive(Debug, thiserror::Error)]
+pub enum ContentError {
+    #[error("nicht unterstützte Inhaltsversion {0}")]
+    Version(u8),
+    #[error("Inhalt fehlerhaft")]
+    Malformed,
+}
+
+/// Inhalt einer Nachricht.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Content {
+    /// Textnachricht.
+    Text { text: String },
+    /// Antwort, die Nachricht `to` zitiert.
+    Reply { to: u128, text: String },
+    /// Reaktion auf Nachricht `to`; `remove` nimmt sie zurück.
+    Reaction {
+        to: u128,
+        emoji: String,
+        #[serde(default)]
+        remove: bool,
+    },
+    /// Neuer Text für die eigene Nachricht `to`.
+    Edit { to: u128, text: String },
+    /// Löscht die eigene Nachricht `to` für alle.
+    Delete { to: u128 },
+    /// Lesebestätigung für die Nachrichten `ids`.
+    Read { ids: Vec<u128> },
+    /// Tipp‑Hinweis.
+    Typing { active: bool },
+    /// Datei mit optionaler Bildunterschrift.
+    Attachment {
+        manifest: Manifest,
+        #[serde(default)]
+        caption: String,
+    },
+    /// Lebensdauer künftiger Nachrichten der Konversation in Sekunden;
+    /// `0` schaltet verschwindende Nachrichten ab.
+    Timer { seconds: u32 },
+    /// Steuernachricht einer Gruppe (Einladung, Sender‑Key, Austritt).
+    Group(GroupControl),
+}
+
+impl Content {
+    pub fn text(text: impl Into<String>) -> Self {
+        Content::Text { text: text.into() }
+    }
+
+    /// Kodiert den Inhalt.  Reiner Text bleibt aus Kompatibilität mit
+    /// älteren Clients unkodiert.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        if let Content::Text { text } = self {
+            if !text.starts_with('\0') {
+                return text.as_bytes().to_vec();
+            }
+        }
+        let mut out = vec![MARKER, CONTENT_VERSION];
+        ciborium::into_writer(self, &mut out).expect("CBOR in Vec");
+        out
+    }
+
+    /// Liest einen Body.  Bodies ohne Marker gelten als Text.
+    pub fn from_bytes(body: &[u8]) -> Result<Self, ContentError> {
+        match body {
+            [MARKER, CONTENT_VERSION, cbor @ ..] => ciborium::from_reader(cbor).map_err(|_| ContentError::Malformed),
+            [MARKER, version, ..] => Err(ContentError::Version(*version)),
+            [MARKER] => Err(ContentError::Malformed),
+            text => Ok(Content::text(String::from_utf8_lossy(text))),
+        }
+    }
+
+    /// Nachricht, auf die sich der Inhalt bezieht.
+    pub fn target(&self) -> Option<u128> {
+        match self {
+            Content::Reply { to, .. } | Content::Reaction { to, .. } | Content::Edit { to, .. } | Content::Delete { to } => {
+                Some(*to)
+            }
+            Content::Text { .. }
+            | Content::Read { .. }
+            | Content::Typing { .. }
+            | Content::Attachment { .. }
+            | Content::Timer { .. }
+            | Content::Group(_) => None,
+        }
+    }
+
+    /// Flüchtige Signale (Lesebestätigung, Tipp‑Hinweis), die nicht als
+    /// Nachricht gespeichert werden.
+    pub fn is_signal(&self) -> bool {
+        matches!(self, Content::Read { .. } | Content::Typing { .. })
+    }
+
+    /// Steuernachrichten von Gruppen; sie werden wie Signale nicht als
+    /// Nachricht gespeichert.
+    pub fn is_control(&self) -> bool {
+        matches!(self, Content::Group(_))
+    }
+
+    /// Ob der Inhalt als neue Nachricht zählt (Text, Antwort, Anhang).
+    pub fn is_message(&self) -> bool {
+        matches!(self, Content::Text { .. } | Content::Reply { .. } | Content::Attachment { .. })
+    }
+
+    /// Kurzname der Variante, etwa für JSON‑Ausgaben.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Content::Text { .. } => "text",
+            Content::Reply { .. } => "reply",
+            Content::Reaction { .. } => "reaction",
+            Content::Edit { .. } => "edit",
+            Content::Delete { .. } => "delete",
+            Content::Read { .. } => "read",
+            Content::Typing { .. } => "typing",
+            Content::Attachment { .. } => "attachment",
+            Content::Timer { .. } => "timer",
+            Content::Group(_) => "group",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::group::{GroupMember, SenderKeyDistribution};
+    use zeroize::Zeroizing;
+
+    #[test]
+    fn every_variant_round_trips() {
+        let manifest = Manifest {
+            name: "bild.png".into(),
+            size: 70_000,
+            digest: [1u8; 32],
+            key: [2u8; 32],
+            chunks: vec![[3u8; 32], [4u8; 32]],
+            expires_at: 1_700_000_000_000,
+        };
+        let member = GroupMember { name: "Bob".into(), identity_pub: [5u8; 32], address: vec![6u8; 64], kem_pub: None };
+        let distribution = SenderKeyDistribution { key_id: 7, iteration: 3, chain_key: Zeroizing::new([8u8; 32]) };
+        let group = [9u8; 16];
+        let all = [
+            Content::text("hallo"),
+            Content::text("\0beginnt mit Nullbyte"),
+            Content::Reply { to: 1, text: "ja".into() },
+            Content::Reaction { to: 2, emoji: "👍".into(), remove: true },
+            Content::Edit { to: 3, text: "korrigiert".into() },
+            Content::Delete { to: 4 },
+            Content::Read { ids: vec![5, u128::MAX] },
+            Content::Typing { active: true },
+            Content::Attachment { manifest, caption: "Urlaub".into() },
+            Content::Timer { seconds: 3600 },
+            Content::Group(GroupControl::Update {
+                group,
+                name: "Familie".into(),
+                epoch: 2,
+                secret: Zeroizing::new([10u8; 32]),
+                members: vec![member],
+            }),
+            Content::Group(GroupControl::SenderKey { group, epoch: 2, key: distribution }),
+            Content::Group(GroupControl::Leave { group }),
+        ];
+        for content in all {
+            let bytes = content.to_bytes();
+            assert_eq!(bytes.first() == Some(&MARKER), content != Content::text("hallo"), "{}", content.kind());
+            assert_eq!(Content::from_bytes(&bytes).unwrap(), content);
+        }
+    }
+
+    #[test]
+    fn legacy_plain_text_is_read_as_text() {
+        assert_eq!(Content::text("hallo").to_bytes(), b"hallo");
+        assert_eq!(Content::from_bytes(b"hallo").unwrap(), Content::text("hallo"));
+        assert_eq!(Content::from_bytes(b"").unwrap(), Content::text(""));
+        assert_eq!(Content::from_bytes(&[0x01, 0xff, b'a']).unwrap(), Content::text("\u{1}\u{fffd}a"));
+    }
+
+    #[test]
+    fn unknown_version_and_garbage_are_errors() {
+        let mut future = Content::Delete { to: 4 }.to_bytes();
+        future[1] = CONTENT_VERSION + 1;
+        assert!(matches!(Content::from_bytes(&future), Err(ContentError::Version(v)) if v == CONTENT_VERSION + 1));
+        assert!(matches!(Content::from_bytes(&[MARKER]), Err(ContentError::Malformed)));
+        assert!(matches!(Content::from_bytes(&[MARKER, CONTENT_VERSION, 0xff]), Err(ContentError::Malformed)));
+    }
+}
@@ -0,0 +1,477 @@
+//! Verknüpfte Geräte einer Identität.
+//!
+//! Eine Identität kann auf mehreren Geräten zugleich aktiv sein, etwa auf
+//! Laptop und Telefon.  Jedes Gerät besitzt eigene View‑, Spend‑ und
+//! KEM‑Schlüssel; das Primärgerät (ID 0) beglaubigt weitere Geräte mit
+//! einem [`DeviceCertificate`], das der Identity‑Key signiert.  Alle
+//! Zertifikate zusammen bilden die ebenfalls signierte [`DeviceList`],
+//! deren Version mit jeder Änderung steigt.  Kontakte übernehmen nur
+//! neuere Listen und lehnen Nachrichten von Geräten ab, die nicht (mehr)
+//! darin stehen.
+//!
+//! Sender schicken jede Nachricht an jedes Gerät des Empfängers mit einer
+//! eigenen Ratchet‑Session (nach dem Vorbild von Signals Sesame) und eine
+//! Abschrift an ihre eigenen übrigen Geräte.
+//!
+//! Verknüpfen: Das neue Gerät zeigt eine [`LinkRequest`] mit seinen
+//! öffentlichen Schlüsseln als Code `PHANTOMCHAT-LINK:1:<HEX>` (Kodierung
+//! wie bei Pairing‑Bundles).  Das Primärgerät stellt das Zertifikat aus und
+//! schickt dem neuen Gerät über die Relays eine [`Provisioning`]‑Nachricht
+//! mit dem privaten Identity‑Key, der Geräteliste und Anwendungsdaten (im
+//! CLI den Kontakten).
+//!
+//! Formate (Zahlen Big‑Endian):
+//!
+//! ```text
+//! Zertifikat:   version(1) | identity_pub(32) | device_id(4) | view_pub(32) | spend_pub(32)
+//!               | created_at(8) | name_len(1) | name | kem_len(2) | kem_pub | signature(64)
+//! Liste:        identity_pub(32) | version(8) | count(1) | { len(2) | zertifikat }* | signature(64)
+//! Link‑Anfrage: version(1) | view_pub(32) | spend_pub(32) | name_len(1) | name | kem_len(2) | kem_pub
+//! Provisioning: "pc.provision.v1" | identity_private(32) | device_id(4) | list_len(2) | liste
+//!               | app_len(4) | app_data
+//! ```
+//!
+//! Zertifikate werden mit dem Domänentrenner `pc.device.v1` signiert,
+//! Listen mit `pc.devices.v1`.
+
+use crate::keys::{verify_identity_signature, IdentityKey};
+use crate::pairing::{decode_text, encode_text, PairingError};
+use crate::secret::SecretBytes;
+use crate::subaddress::Subaddress;
+use x25519_dalek::PublicKey;
+use zeroize::Zeroizing;
+
+/// ID des Primärgeräts.  Nachrichten ohne Geräteangabe stammen von ihm.
+pub const PRIMARY_DEVICE: u32 = 0;
+/// Höchstzahl der Geräte einer Identität.
+pub const MAX_DEVICES: usize = 8;
+/// Präfix der Textform einer [`LinkRequest`].
+pub const LINK_PREFIX: &str = "PHANTOMCHAT-LINK:1:";
+
+const CERT_VERSION: u8 = 1;
+const LINK_VERSION: u8 = 1;
+const CERT_DOMAIN: &[u8] = b"pc.device.v1";
+const LIST_DOMAIN: &[u8] = b"pc.devices.v1";
+const PROVISION_MAGIC: &[u8] = b"pc.provision.v1";
+
+/// Fehler beim Ausstellen oder Lesen von Gerätedaten.
+#[derive(Debug, thiserror::Error)]
+pub enum DeviceError {
+    #[error("kein PhantomChat‑Link‑Code")]
+    NotALinkCode,
+    #[error(transparent)]
+    Code(PairingError),
+    #[error("nicht unterstützte Version {0}")]
+    Version(u8),
+    #[error("Gerätedaten verkürzt oder fehlerhaft")]
+    Malformed,
+    #[error("Signatur ungültig")]
+    BadSignature,
+    #[error("höchstens {} Geräte pro Identität", MAX_DEVICES)]
+    TooManyDevices,
+    #[error("Zertifikat gehört zu einer anderen Identität")]
+    ForeignIdentity,
+}
+
+/// Öffentliche Schlüssel eines neuen Geräts, das verknüpft werden will.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkRequest {
+    pub view_pub: [u8; 32],
+    pub spend_pub: [u8; 32],
+    pub kem_pub: Option<Vec<u8>>,
+    /// Anzeigename, etwa „Laptop“.
+    pub name: String,
+}
+
+impl LinkRequest {
+    pub fn new(view_pub: &PublicKey, spend_pub: &PublicKey, kem_pub: Option<Vec<u8>>, name: &str) -> Result<Self, DeviceError> {
+        if name.len() > u8::MAX as usize || kem_pub.as_ref().is_some_and(|k| k.len() > u16::MAX as usize) {
+            return Err(DeviceError::Malformed);
+        }
+        Ok(Self { view_pub: *view_pub.as_bytes(), spend_pub: *spend_pub.as_bytes(), kem_pub, name: name.to_owned() })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![LINK_VERSION];
+        out.extend_from_slice(&self.view_pub);
+        out.extend_from_slice(&self.spend_pub);
+        put_name_kem(&mut out, &self.name, &self.kem_pub);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DeviceError> {
+        let mut pos = 0;
+        let version = take(bytes, &mut pos, 1)?[0];
+        if version != LINK_VERSION {
+            return Err(DeviceError::Version(version));
+        }
+        let view_pub = take32(bytes, &mut pos)?;
+        let spend_pub = take32(bytes, &mut pos)?;
+        let (name, kem_pub) = take_name_kem(bytes, &mut pos)?;
+        if pos != bytes.len() {
+            return Err(DeviceError::Malformed);
+        }
+        Ok(Self { view_pub, spend_pub, kem_pub, name })
+    }
+
+    /// Textform für QR‑Code und Kopieren.
+    pub fn to_text(&self) -> String {
+        encode_text(LINK_PREFIX, &self.to_bytes())
+    }
+
+    pub fn from_text(text: &str) -> Result<Self, DeviceError> {
+        let bytes = decode_text(LINK_PREFIX, text).map_err(|e| match e {
+            PairingError::NotAPairingCode => DeviceError::NotALinkCode,
+            e => DeviceError::Code(e),
+        })?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+/// Vom Identity‑Key signierte Beglaubigung eines Geräts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceCertificate {
+    pub identity_pub: [u8; 32],
+    pub device_id: u32,
+    pub view_pub: [u8; 32],
+    pub spend_pub: [u8; 32],
+    /// Ausstellungszeit (UNIX‑Millisekunden).
+    pub created_at: u64,
+    pub name: String,
+    pub kem_pub: Option<Vec<u8>>,
+    pub signature: [u8; 64],
+}
+
+impl DeviceCertificate {
+    /// Beglaubigt die Schlüssel aus `request` als Gerät `device_id`.
+    pub fn issue(identity: &IdentityKey, device_id: u32, request: &LinkRequest, created_at: u64) -> Self {
+        let mut cert = Self {
+            identity_pub: identity.public,
+            device_id,
+            view_pub: request.view_pub,
+            spend_pub: request.spend_pub,
+            created_at,
+            name: request.name.clone(),
+            kem_pub: request.kem_pub.clone(),
+            signature: [0u8; 64],
+        };
+        cert.signature = identity.sign(&cert.signed_bytes());
+        cert
+    }
+
+    /// Hauptadresse des Geräts.
+    pub fn address(&self) -> Subaddress {
+        Subaddress::primary(&PublicKey::from(self.spend_pub))
+    }
+
+    fn body(&self) -> Vec<u8> {
+        let mut out = vec![CERT_VERSION];
+        out.extend_from_slice(&self.identity_pub);
+        out.extend_from_slice(&self.device_id.to_be_bytes());
+        out.extend_from_slice(&self.view_pub);
+        out.extend_from_slice(&self.spend_pub);
+        out.extend_from_slice(&self.created_at.to_be_bytes());
+        put_name_kem(&mut out, &self.name, &self.kem_pub);
+        out
+    }
+
+    fn signed_bytes(&self) -> Vec<u8> {
+        let mut msg = CERT_DOMAIN.to_vec();
+        msg.extend_from_slice(&self.body());
+        msg
+    }
+
+    pub fn verify(&self) -> bool {
+        verify_identity_signature(&self.identity_pub, &self.signed_bytes(), &self.signature)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.body();
+        out.extend_from_slice(&self.signature);
+        out
+    }
+
+    /// Liest ein Zertifikat und prüft die Signatur.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DeviceError> {
+        let mut pos = 0;
+        let version = take(bytes, &mut pos, 1)?[0];
+        if version != CERT_VERSION {
+            return Err(DeviceError::Version(version));
+        }
+        let identity_pub = take32(bytes, &mut pos)?;
+        let device_id = u32::from_be_bytes(take(bytes, &mut pos, 4)?.try_into().unwrap());
+        let view_pub = take32(bytes, &mut pos)?;
+        let spend_pub = take32(bytes, &mut pos)?;
+        let created_at = u64::from_be_bytes(take(bytes, &mut pos, 8)?.try_into().unwrap());
+        let (name, kem_pub) = take_name_kem(bytes, &mut pos)?;
+        let signature: [u8; 64] = take(bytes, &mut pos, 64)?.try_into().unwrap();
+        if pos != bytes.len() {
+            return Err(DeviceError::Malformed);
+        }
+        let cert = Self { identity_pub, device_id, view_pub, spend_pub, created_at, name, kem_pub, signature };
+        if !cert.verify() {
+            return Err(DeviceError::BadSignature);
+        }
+        Ok(cert)
+    }
+}
+
+/// Signierte Liste aller Geräte einer Identität.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceList {
+    pub identity_pub: [u8; 32],
+    /// Steigt mit jeder Änderung; ältere Listen werden ignoriert.
+    pub version: u64,
+    pub devices: Vec<DeviceCertificate>,
+    pub signature: [u8; 64],
+}
+
+impl DeviceList {
+    /// Erstellt und signiert eine Liste.  Geräte‑IDs müssen eindeutig
+    /// sein, die Zertifikate zur Identität gehören.
+    pub fn new(identity: &IdentityKey, version: u64, mut devices: Vec<DeviceCertificate>) -> Result<Self, DeviceError> {
+        if devices.len() > MAX_DEVICES {
+            return Err(DeviceError::TooManyDevices);
+        }
+        if devices.iter().any(|d| d.identity_pub != identity.public) {
+            return Err(DeviceError::ForeignIdentity);
+        }
+        devices.sort_by_key(|d| d.device_id);
+        if devices.windows(2).any(|w| w[0].device_id == w[1].device_id) {
+            return Err(DeviceError::Malformed);
+        }
+        let mut list = Self { identity_pub: identity.public, version, devices, signature: [0u8; 64] };
+        list.signature = identity.sign(&list.signed_bytes());
+        Ok(list)
+    }
+
+    /// Zertifikat des Geräts `device_id`.
+    pub fn device(&self, device_id: u32) -> Option<&DeviceCertificate> {
+        self.devices.iter().find(|d| d.device_id == device_id)
+    }
+
+    /// Ob diese Liste `current` ersetzen darf: Sie muss zur selben
+    /// Identität gehören und eine höhere Version tragen.
+    pub fn replaces(&self, current: Option<&DeviceList>) -> bool {
+        current.is_none_or(|c| c.identity_pub == self.identity_pub && c.version < self.version)
+    }
+
+    fn body(&self) -> Vec<u8> {
+        let mut out = self.identity_pub.to_vec();
+        out.extend_from_slice(&self.version.to_be_bytes());
+        out.push(self.devices.len() as u8);
+        for device in &self.devices {
+            let bytes = device.to_bytes();
+            out.extend_from_slice(&(bytes.len() as u16).to_be_bytes());
+            out.extend_from_slice(&bytes);
+        }
+        out
+    }
+
+    fn signed_bytes(&self) -> Vec<u8> {
+        let mut msg = LIST_DOMAIN.to_vec();
+        msg.extend_from_slice(&self.body());
+        msg
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.body();
+        out.extend_from_slice(&self.signature);
+        out
+    }
+
+    /// Liest eine Liste und prüft ihre Signatur sowie die aller
+    /// Zertifikate.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DeviceError> {
+        let mut pos = 0;
+        let identity_pub = take32(bytes, &mut pos)?;
+        let version = u64::from_be_bytes(take(bytes, &mut pos, 8)?.try_into().unwrap());
+        let count = take(bytes, &mut pos, 1)?[0] as usize;
+        if count > MAX_DEVICES {
+            return Err(DeviceError::TooManyDevices);
+        }
+        let mut devices = Vec::with_capacity(count);
+        for _ in 0..count {
+            let len = u16::from_be_bytes(take(bytes, &mut pos, 2)?.try_into().unwrap()) as usize;
+            let cert = DeviceCertificate::from_bytes(take(bytes, &mut pos, len)?)?;
+            if cert.identity_pub != identity_pub {
+                return Err(DeviceError::ForeignIdentity);
+            }
+            devices.push(cert);
+        }
+        let signature: [u8; 64] = take(bytes, &mut pos, 64)?.try_into().unwrap();
+        if pos != bytes.len() {
+            return Err(DeviceError::Malformed);
+        }
+        let list = Self { identity_pub, version, devices, signature };
+        if !verify_identity_signature(&identity_pub, &list.signed_bytes(), &signature) {
+            return Err(DeviceError::BadSignature);
+        }
+        Ok(list)
+    }
+}
+
+/// Nachricht des Primärgeräts an ein neu verknüpftes Gerät.
+#[derive(Debug)]
+pub struct Provisioning {
+    identity: SecretBytes<32>,
+    /// ID, unter der das neue Gerät in `devices` steht.
+    pub device_id: u32,
+    pub devices: DeviceList,
+    /// Daten der Anwendung, etwa das Kontaktbuch.
+    pub app_data: Vec<u8>,
+}
+
+impl Provisioning {
+    pub fn new(identity: &IdentityKey, device_id: u32, devices: DeviceList, app_data: Vec<u8>) -> Self {
+        Self { identity: SecretBytes::new(*identity.private_bytes()), device_id, devices, app_data }
+    }
+
+    /// Der übertragene Identity‑Key.
+    pub fn identity(&self) -> IdentityKey {
+        IdentityKey::from_private(*self.identity.expose())
+    }
+
+    /// Zertifikat des neuen Geräts.
+    pub fn certificate(&self) -> Option<&DeviceCertificate> {
+        self.devices.device(self.device_id)
+    }
+
+    pub fn to_bytes(&self) -> Zeroizing<Vec<u8>> {
+        let list = self.devices.to_bytes();
+        let mut out = Zeroizing::new(PROVISION_MAGIC.to_vec());
+        out.extend_from_slice(self.identity.expose());
+        out.extend_from_slice(&self.device_id.to_be_bytes());
+        out.extend_from_slice(&(list.len() as u16).to_be_bytes());
+        out.extend_from_slice(&list);
+        out.extend_from_slice(&(self.app_data.len() as u32).to_be_bytes());
+        out.extend_from_slice(&self.app_data);
+        out
+    }
+
+    /// Liest eine Provisioning‑Nachricht.  Die Liste muss zum
+    /// übertragenen Identity‑Key gehören und das Gerät enthalten.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DeviceError> {
+        let rest = bytes.strip_prefix(PROVISION_MAGIC).ok_or(DeviceError::Malformed)?;
+        let mut pos = 0;
+        let identity = SecretBytes::new(take32(rest, &mut pos)?);
+        let device_id = u32::from_be_bytes(take(rest, &mut pos, 4)?.try_into().unwrap());
+        let len = u16::from_be_bytes(take(rest, &mut pos, 2)?.try_into().unwrap()) as usize;
+        let devices = DeviceList::from_bytes(take(rest, &mut pos, len)?)?;
+        let len = u32::from_be_bytes(take(rest, &mut pos, 4)?.try_into().unwrap()) as usize;
+        let app_data = take(rest, &mut pos, len)?.to_vec();
+        if pos != rest.len() {
+            return Err(DeviceError::Malformed);
+        }
+        let provisioning = Self { identity, device_id, devices, app_data };
+        if provisioning.identity().public != provisioning.devices.identity_pub {
+            return Err(DeviceError::ForeignIdentity);
+        }
+        if provisioning.certificate().is_none() {
+            return Err(DeviceError::Malformed);
+        }
+        Ok(provisioning)
+    }
+}
+
+fn put_name_kem(out: &mut Vec<u8>, name: &str, kem_pub: &Option<Vec<u8>>) {
+    out.push(name.len() as u8);
+    out.extend_from_slice(name.as_bytes());
+    let kem_pub = kem_pub.as_deref().unwrap_or_default();
+    out.extend_from_slice(&(kem_pub.len() as u16).to_be_bytes());
+    out.extend_from_slice(kem_pub);
+}
+
+fn take_name_kem(bytes: &[u8], pos: &mut usize) -> Result<(String, Option<Vec<u8>>), DeviceError> {
+    let len = take(bytes, pos, 1)?[0] as usize;
+    let name = std::str::from_utf8(take(bytes, pos, len)?).map_err(|_| DeviceError::Malformed)?.to_owned();
+    let len = u16::from_be_bytes(take(bytes, pos, 2)?.try_into().unwrap()) as usize;
+    let kem_pub = Some(take(bytes, pos, len)?.to_vec()).filter(|k| !k.is_empty());
+    Ok((name, kem_pub))
+}
+
+/// Liest `n` Bytes ab `pos` und rückt `pos` vor.
+fn take<'a>(bytes: &'a [u8], pos: &mut usize, n: usize) -> Result<&'a [u8], DeviceError> {
+    let slice = bytes.get(*pos..*pos + n).ok_or(DeviceError::Malformed)?;
+    *pos += n;
+    Ok(slice)
+}
+
+fn take32(bytes: &[u8], pos: &mut usize) -> Result<[u8; 32], DeviceError> {
+    Ok(take(bytes, pos, 32)?.try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::{SpendKey, ViewKey};
+
+    fn request(name: &str) -> LinkRequest {
+        LinkRequest::new(&ViewKey::generate().public, &SpendKey::generate().public, Some(vec![5u8; 1184]), name).unwrap()
+    }
+
+    #[test]
+    fn certificate_is_verified() {
+        let identity = IdentityKey::generate();
+        let cert = DeviceCertificate::issue(&identity, 1, &request("Laptop"), 1_700_000_000_000);
+        assert!(cert.verify());
+        assert_eq!(DeviceCertificate::from_bytes(&cert.to_bytes()).unwrap(), cert);
+
+        let mut renamed = cert.clone();
+        renamed.name = "Telefon".into();
+        assert!(matches!(DeviceCertificate::from_bytes(&renamed.to_bytes()), Err(DeviceError::BadSignature)));
+        let mut forged = cert.to_bytes();
+        *forged.last_mut().unwrap() ^= 1;
+        assert!(matches!(DeviceCertificate::from_bytes(&forged), Err(DeviceError::BadSignature)));
+    }
+
+    #[test]
+    fn device_list_is_verified() {
+        let identity = IdentityKey::generate();
+        let devices = vec![
+            DeviceCertificate::issue(&identity, 2, &request("Tablet"), 2),
+            DeviceCertificate::issue(&identity, 1, &request("Laptop"), 1),
+        ];
+        let list = DeviceList::new(&identity, 3, devices).unwrap();
+        let parsed = DeviceList::from_bytes(&list.to_bytes()).unwrap();
+        assert_eq!(parsed, list);
+        assert_eq!(parsed.device(2).unwrap().name, "Tablet");
+        assert!(parsed.device(7).is_none());
+
+        // Die Version liegt unmittelbar hinter dem Identity‑Key.
+        let mut bumped = list.to_bytes();
+        bumped[39] += 1;
+        assert!(matches!(DeviceList::from_bytes(&bumped), Err(DeviceError::BadSignature)));
+    }
+
+    #[test]
+    fn list_signed_by_a_foreign_identity_is_rejected() {
+        let (identity, foreign) = (IdentityKey::generate(), IdentityKey::generate());
+        let cert = DeviceCertificate::issue(&identity, 1, &request("Laptop"), 1);
+        assert!(matches!(DeviceList::new(&foreign, 1, vec![cert.clone()]), Err(DeviceError::ForeignIdentity)));
+
+        // Fremde Signatur über die Liste der echten Identität.
+        let mut list = DeviceList::new(&identity, 1, vec![cert.clone()]).unwrap();
+        list.signature = foreign.sign(&list.signed_bytes());
+        assert!(matches!(DeviceList::from_bytes(&list.to_bytes()), Err(DeviceError::BadSignature)));
+
+        // Gültig signierte fremde Liste mit einem Zertifikat der echten
+        // Identität.
+        let mut smuggled = DeviceList::new(&foreign, 1, vec![]).unwrap();
+        smuggled.devices.push(cert);
+        smuggled.signature = foreign.sign(&smuggled.signed_bytes());
+        assert!(matches!(DeviceList::from_bytes(&smuggled.to_bytes()), Err(DeviceError::ForeignIdentity)));
+    }
+
+    #[test]
+    fn only_newer_lists_replace_the_current_one() {
+        let (identity, foreign) = (IdentityKey::generate(), IdentityKey::generate());
+        let v1 = DeviceList::new(&identity, 1, vec![]).unwrap();
+        let v2 = DeviceList::new(&identity, 2, vec![DeviceCertificate::issue(&identity, 1, &request("Laptop"), 1)]).unwrap();
+        assert!(v1.replaces(None));
+        assert!(v2.replaces(Some(&v1)));
+        assert!(!v1.replaces(Some(&v2)));
+        assert!(!v2.replaces(Some(&v2)));
+        assert!(!DeviceList::new(&foreign, 9, vec![]).unwrap().replaces(Some(&v2)));
+    }
+}
@@ -7,28 +7,54 @@
 //! HKDF abgeleitet.  Anschließend wird die Payload mit
 //! XChaCha20‑Poly1305 verschlüsselt, und es wird ein Proof‑of‑Work
 //! berechnet.
+//!
+//! Version 2 (Feature `pqc`) kombiniert das ECDH‑Geheimnis mit einem
+//! ML‑KEM‑768‑Geheimnis; der KEM‑Ciphertext steht dann im Feld `kem_ct`
+//! (siehe [`crate::pq`]).  Version‑1‑Envelopes bleiben unverändert.
+//!
+//! Envelopes an eine Subadresse ([`crate::subaddress`]) haben dasselbe
+//! Format; nur der Ephemeral‑Key wird über deren Basispunkt gebildet, und
+//! das Tag bezieht den Basispunkt ein.
+//!
+//! Die Payload trägt ein vom Identity‑Key signiertes Absenderzertifikat
+//! (Sealed Sender, siehe [`crate::sealed`]); es wird beim Öffnen geprüft.
 
-use crate::keys::{SpendKey, ViewKey};
+use crate::keys::SpendKey;
+use crate::padding::PaddingPolicy;
+#[cfg(feature = "pqc")]
+use crate::pq::{self, KemKey};
 use crate::pow::Hashcash;
+use crate::sealed::{SealContext, Sender, SenderCertificate};
+use crate::secret::ct_eq;
+use crate::subaddress::Subaddress;
 use crate::util::sha256;
 use hkdf::Hkdf;
 use hmac::{Hmac, Mac};
 use rand_core::{OsRng, RngCore};
 use sha2::Sha256;
 use x25519_dalek::{PublicKey, StaticSecret};
+use chacha20poly1305::aead::Aead;
 use chacha20poly1305::{KeyInit, XChaCha20Poly1305, XNonce};
+use zeroize::Zeroizing;
 
 /// Struktur der Klartextnutzlast.  Für die Demonstration ist die
 /// Serialisierung sehr einfach gehalten: Alle Felder werden in der
 /// gegebenen Reihenfolge hintereinander als Big‑Endian‑Bytes
 /// geschrieben; Strings werden mit ihrer Länge und anschließendem
-/// UTF‑8‑Inhalt kodiert.
+/// UTF‑8‑Inhalt kodiert.  Hinter dem Body folgt optional das
+/// Absenderzertifikat (`len(2) | zertifikat`); Bytes danach (Padding)
+/// werden beim Deserialisieren ignoriert.  Da das Padding aus Nullbytes
+/// besteht, lesen ältere Payloads sich als solche ohne Zertifikat.
 #[derive(Debug, Clone)]
 pub struct Payload {
     pub msg_id: u128,
+    /// Kurz‑Fingerprint des Absenders; nur ein Hinweis.
     pub sender_fp: u32,
     pub ratchet_header: Vec<u8>,
     pub body: Vec<u8>,
+    /// Absenderzertifikat.  In einer geöffneten Payload ist es bereits
+    /// gegen den Kontext des Envelopes geprüft.
+    pub sender: Option<SenderCertificate>,
 }
 
 impl Payload {
@@ -41,6 +67,11 @@ impl Payload {
         out.extend_from_slice(&self.ratchet_header);
         out.extend_from_slice(&(self.body.len() as u32).to_le_bytes());
         out.extend_from_slice(&self.body);
+        if let Some(cert) = &self.sender {
+            let cert = cert.to_bytes();
+            out.extend_from_slice(&(cert.len() as u16).to_le_bytes());
+            out.extend_from_slice(&cert);
+        }
         out
     }
     /// Deserialisiert eine Nutzlast aus einem Byte‑Slice.  Diese
@@ -61,7 +92,31 @@ impl Payload {
         cursor += 4;
         if cursor + body_len > data.len() { return None; }
         let body = data[cursor..cursor+body_len].to_vec();
-        Some(Self { msg_id, sender_fp, ratchet_header, body })
+        cursor += body_len;
+        let mut sender = None;
+        if cursor + 2 <= data.len() {
+            let cert_len = u16::from_le_bytes(data[cursor..cursor+2].try_into().ok()?) as usize;
+            cursor += 2;
+            if cert_len > 0 {
+                if cursor + cert_len > data.len() { return None; }
+                sender = Some(SenderCertificate::from_bytes(&data[cursor..cursor+cert_len])?);
+            }
+        }
+        Some(Self { msg_id, sender_fp, ratchet_header, body, sender })
+    }
+    /// Kontext, über den das Absenderzertifikat signiert wird.
+    fn context<'a>(&'a self, ver: u8, epk: &'a [u8; 32], kem_ct: &'a [u8]) -> SealContext<'a> {
+        SealContext { ver, epk, kem_ct, msg_id: self.msg_id, ratchet_header: &self.ratchet_header, body: &self.body }
+    }
+    /// Signiert die Payload im Kontext des entstehenden Envelopes (falls
+    /// ein Absender angegeben ist), serialisiert sie und füllt sie auf.
+    fn seal_bytes(mut self, sender: Option<&Sender>, ver: u8, epk: &[u8; 32], kem_ct: &[u8], padding: &PaddingPolicy) -> Vec<u8> {
+        if let Some(sender) = sender {
+            self.sender = Some(SenderCertificate::sign(sender, &self.context(ver, epk, kem_ct)));
+        }
+        let mut bytes = self.to_bytes();
+        padding.pad(&mut bytes);
+        bytes
     }
 }
 
@@ -72,6 +127,8 @@ pub struct Envelope {
     pub ts: u64,
     pub ttl: u32,
     pub epk: [u8; 32],
+    /// ML‑KEM‑Ciphertext, nur ab Version 2 (sonst leer).
+    pub kem_ct: Vec<u8>,
     pub tag: Vec<u8>,
     pub pow_nonce: u64,
     pub nonce: [u8; 24],
@@ -83,14 +140,19 @@ impl Envelope {
     /// Erzeugt ein neues Envelope aus View/Spend‑Schlüsseln, Payload und
     /// Konfigurationsparametern.  Diese Funktion führt folgende
     /// Schritte aus:
-    /// 1. Generiert einen zufälligen ephemeren Secret und Public Key.
+    /// 1. Generiert einen zufälligen ephemeren Secret und Public Key
+    ///    (bei Subadressen über deren Basispunkt).
     /// 2. Berechnet das ECDH‑Geheimnis K = ECDH(ephemeral, spend_pub).
     /// 3. Leitet mittels HKDF `enc_key` und `tag_key` ab.
-    /// 4. Berechnet `tag` = HMAC(tag_key, msg_id).
+    /// 4. Berechnet `tag` = HMAC(tag_key, msg_id), bei Subadressen
+    ///    zusätzlich über den Basispunkt.
     /// 5. Serialisiert den Payload und verschlüsselt ihn mit
     ///    XChaCha20‑Poly1305 unter Verwendung von `enc_key` und einem
     ///    zufälligen Nonce.
     /// 6. Berechnet ein Proof‑of‑Work über die Header und den Nonce.
+    ///
+    /// Die Payload wird nicht aufgefüllt und trägt kein
+    /// Absenderzertifikat; siehe [`Envelope::new_padded`].
     pub fn new(
         spend_pub: &PublicKey,
         msg_id: u128,
@@ -100,56 +162,182 @@ impl Envelope {
         ttl: u32,
         pow_difficulty: u32,
     ) -> Self {
-        // 1. Ephemerer Schlüssel
-        let eph_secret = StaticSecret::new(&mut OsRng);
-        let eph_public = PublicKey::from(&eph_secret);
-        let epk_bytes = *eph_public.as_bytes();
-        // 2. ECDH mit spend_pub
-        let shared = eph_secret.diffie_hellman(spend_pub);
-        let shared_bytes = shared.as_bytes();
+        let to = Subaddress::primary(spend_pub);
+        let payload = Payload { msg_id, sender_fp, ratchet_header, body, sender: None };
+        Self::seal(&to, None, msg_id, ttl, pow_difficulty, |ver, epk, kem_ct| {
+            payload.seal_bytes(None, ver, epk, kem_ct, &PaddingPolicy::None)
+        })
+    }
+    /// Wie [`Envelope::new`], aber an eine beliebige Adresse `to`
+    /// (Haupt‑ oder Subadresse) und mit Absenderzertifikat von `sender`.
+    /// Die serialisierte Payload wird vor der Verschlüsselung gemäß
+    /// `padding` aufgefüllt, so dass die Größe des Envelopes nur noch den
+    /// Bucket verrät.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_padded(
+        to: &Subaddress,
+        msg_id: u128,
+        sender: &Sender,
+        ratchet_header: Vec<u8>,
+        body: Vec<u8>,
+        ttl: u32,
+        pow_difficulty: u32,
+        padding: &PaddingPolicy,
+    ) -> Self {
+        let payload = Payload { msg_id, sender_fp: sender.fingerprint(), ratchet_header, body, sender: None };
+        Self::seal(to, None, msg_id, ttl, pow_difficulty, |ver, epk, kem_ct| {
+            payload.seal_bytes(Some(sender), ver, epk, kem_ct, padding)
+        })
+    }
+    /// Wie [`Envelope::new_padded`], aber hybrid: Zusätzlich zum ECDH mit
+    /// der Adresse `to` wird ein Geheimnis für den ML‑KEM‑Schlüssel
+    /// `kem_pub` gekapselt (Version 2).  `None` bei ungültigem `kem_pub`.
+    #[cfg(feature = "pqc")]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_hybrid(
+        to: &Subaddress,
+        kem_pub: &[u8],
+        msg_id: u128,
+        sender: &Sender,
+        ratchet_header: Vec<u8>,
+        body: Vec<u8>,
+        ttl: u32,
+        pow_difficulty: u32,
+        padding: &PaddingPolicy,
+    ) -> Option<Self> {
+        let kem = pq::encapsulate(kem_pub)?;
+        let payload = Payload { msg_id, sender_fp: sender.fingerprint(), ratchet_header, body, sender: None };
+        Some(Self::seal(to, Some(kem), msg_id, ttl, pow_difficulty, |ver, epk, kem_ct| {
+            payload.seal_bytes(Some(sender), ver, epk, kem_ct, padding)
+        }))
+    }
+    /// Erzeugt ein Cover‑Envelope: zufälliger Empfänger, zufällige
+    /// `msg_id` und eine zufällige Payload der Länge `payload_len`.  Es
+    /// durchläuft dieselbe Verschlüsselung und denselben Proof‑of‑Work
+    /// wie eine echte Nachricht und ist für Relays und Beobachter nicht
+    /// von dieser zu unterscheiden.  Niemand kann es entschlüsseln.
+    /// Mit `hybrid` (nur mit Feature `pqc`) trägt es einen zufälligen
+    /// KEM‑Ciphertext und gleicht hybriden Envelopes, sonst klassischen;
+    /// der Aufrufer wählt die Form passend zum echten Verkehr.
+    pub fn new_cover(payload_len: usize, ttl: u32, pow_difficulty: u32, hybrid: bool) -> Self {
+        let recipient = PublicKey::from(&StaticSecret::random_from_rng(OsRng));
+        let mut id_bytes = [0u8; 16];
+        OsRng.fill_bytes(&mut id_bytes);
+        let mut payload_bytes = vec![0u8; payload_len];
+        OsRng.fill_bytes(&mut payload_bytes);
+        #[cfg(feature = "pqc")]
+        let kem = hybrid.then(|| {
+            let mut kem_ct = vec![0u8; pq::CIPHERTEXT_LEN];
+            OsRng.fill_bytes(&mut kem_ct);
+            let mut secret = Zeroizing::new([0u8; 32]);
+            OsRng.fill_bytes(secret.as_mut());
+            (kem_ct, secret)
+        });
+        #[cfg(not(feature = "pqc"))]
+        let kem = {
+            let _ = hybrid;
+            None
+        };
+        let to = Subaddress::primary(&recipient);
+        Self::seal(&to, kem, u128::from_le_bytes(id_bytes), ttl, pow_difficulty, |_, _, _| payload_bytes)
+    }
+    /// Verschlüsselt Payload‑Bytes für `to` (Schritte 1–6 aus
+    /// [`Envelope::new`]).  Mit `kem` (Ciphertext und gekapseltes
+    /// Geheimnis) entsteht ein hybrides Envelope der Version 2.
+    /// `payload` erhält Version, Ephemeral‑Key und KEM‑Ciphertext, damit
+    /// das Absenderzertifikat daran gebunden werden kann.
+    pub(crate) fn seal(
+        to: &Subaddress,
+        kem: Option<(Vec<u8>, Zeroizing<[u8; 32]>)>,
+        msg_id: u128,
+        ttl: u32,
+        pow_difficulty: u32,
+        payload: impl FnOnce(u8, &[u8; 32], &[u8]) -> Vec<u8>,
+    ) -> Self {
+        // 1. Ephemerer Schlüssel und 2. ECDH mit der Adresse
+        let eph_secret = StaticSecret::random_from_rng(OsRng);
+        let (epk_bytes, shared) = to.agree(&eph_secret);
         // 3. HKDF zur Ableitung von enc_key und tag_key
-        let hk = Hkdf::<Sha256>::new(None, shared_bytes);
-        let mut okm = [0u8; 64];
-        hk.expand(b"pc.enc|pc.tag", &mut okm).expect("HKDF expand");
+        let (ver, kem_ct, okm) = match kem {
+            None => (1, Vec::new(), derive_keys(&shared, None, &epk_bytes, &[])),
+            Some((kem_ct, secret)) => {
+                let okm = derive_keys(&shared, Some(&*secret), &epk_bytes, &kem_ct);
+                (2, kem_ct, okm)
+            }
+        };
         let enc_key = &okm[..32];
-        let tag_key = &okm[32..64];
-        // 4. HMAC‑Tag über msg_id
-        let mut mac = <Hmac<Sha256>>::new_from_slice(tag_key).expect("HMAC key");
-        mac.update(&msg_id.to_le_bytes());
-        let tag_bytes = mac.finalize().into_bytes().to_vec();
-        // 5. Payload serialisieren und verschlüsseln
-        let payload = Payload { msg_id, sender_fp, ratchet_header, body };
-        let payload_bytes = payload.to_bytes();
+        let payload_bytes = Zeroizing::new(payload(ver, &epk_bytes, &kem_ct));
+        // 4. HMAC‑Tag über msg_id (und ggf. den Basispunkt)
+        let tag_bytes = compute_tag(&okm, msg_id, (!to.is_primary()).then_some(&to.base));
+        // 5. Payload verschlüsseln
         // Zufälliger Nonce für XChaCha20
         let mut nonce = [0u8; 24];
         OsRng.fill_bytes(&mut nonce);
         let cipher = XChaCha20Poly1305::new_from_slice(enc_key).expect("cipher");
-        let ciphertext = cipher.encrypt(XNonce::from_slice(&nonce), payload_bytes.as_ref()).expect("encrypt");
-        // extrahiere Poly1305‑Tag (letzte 16 Bytes)
+        let ciphertext = cipher.encrypt(XNonce::from_slice(&nonce), payload_bytes.as_slice()).expect("encrypt");
+        // extrahiere Poly1305‑Tag (letzte 16 Bytes)
         let (ciphertext_body, auth_tag) = ciphertext.split_at(ciphertext.len() - 16);
         let mut mac_arr = [0u8; 16];
         mac_arr.copy_from_slice(auth_tag);
-        // 6. Proof‑of‑Work
-        let mut header = Vec::new();
-        header.extend_from_slice(&1u8.to_le_bytes());
-        header.extend_from_slice(&0u64.to_le_bytes()); // ts placeholder
-        header.extend_from_slice(&0u32.to_le_bytes()); // ttl placeholder
-        header.extend_from_slice(&epk_bytes);
-        header.extend_from_slice(&tag_bytes);
-        let pow = Hashcash::new(pow_difficulty);
-        let nonce_pow = pow.compute_nonce(&header);
-        let ts = (std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as u64);
-        Self {
-            ver: 1,
+        // 6. Proof‑of‑Work über die endgültigen Headerfelder
+        let ts = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis() as u64;
+        let mut env = Self {
+            ver,
             ts,
             ttl,
             epk: epk_bytes,
+            kem_ct,
             tag: tag_bytes,
-            pow_nonce: nonce_pow,
+            pow_nonce: 0,
             nonce,
             ciphertext: ciphertext_body.to_vec(),
             mac: mac_arr,
+        };
+        env.pow_nonce = Hashcash::new(pow_difficulty).compute_nonce(&env.pow_input());
+        env
+    }
+    /// Setzt den Zeitstempel auf `ts` und berechnet den Proof‑of‑Work
+    /// neu.  Für Envelopes, die nicht sofort gesendet werden: Der alte
+    /// Zeitstempel verriete, wie lange sie gewartet haben.
+    /// Verschlüsselung und Absenderzertifikat hängen nicht vom
+    /// Zeitstempel ab.
+    pub fn restamp(&mut self, ts: u64, pow_difficulty: u32) {
+        self.ts = ts;
+        self.pow_nonce = Hashcash::new(pow_difficulty).compute_nonce(&self.pow_input());
+    }
+    /// Eingabe des Proof‑of‑Work: Version, Zeitstempel, TTL,
+    /// Ephemeral‑Key, ab Version 2 der KEM‑Ciphertext, Tag und Hash der
+    /// verschlüsselten Nutzlast.  Damit lässt sich ein gültiger Nonce
+    /// weder auf andere Inhalte noch auf einen verlängerten Zeitraum
+    /// übertragen.
+    pub fn pow_input(&self) -> Vec<u8> {
+        let mut header = Vec::with_capacity(1 + 8 + 4 + 32 + self.tag.len() + 32);
+        header.push(self.ver);
+        header.extend_from_slice(&self.ts.to_le_bytes());
+        header.extend_from_slice(&self.ttl.to_le_bytes());
+        header.extend_from_slice(&self.epk);
+        if self.ver >= 2 {
+            header.extend_from_slice(&self.kem_ct);
         }
+        header.extend_from_slice(&self.tag);
+        let mut body = self.ciphertext.clone();
+        body.extend_from_slice(&self.nonce);
+        body.extend_from_slice(&self.mac);
+        header.extend_from_slice(&sha256(&body));
+        header
+    }
+    /// Prüft, ob der Proof‑of‑Work mindestens `difficulty` Bit erfüllt.
+    pub fn verify_pow(&self, difficulty: u32) -> bool {
+        Hashcash::new(difficulty).verify(&self.pow_input(), self.pow_nonce)
+    }
+    /// Zeitpunkt (UNIX‑Millisekunden), ab dem Relays das Envelope
+    /// löschen dürfen: `ts + ttl`.
+    pub fn expires_at(&self) -> u64 {
+        self.ts.saturating_add(self.ttl as u64 * 1000)
+    }
+    /// Prüft, ob die TTL zum Zeitpunkt `now_ms` abgelaufen ist.
+    pub fn is_expired(&self, now_ms: u64) -> bool {
+        now_ms >= self.expires_at()
     }
     /// Serialisiert das Envelope in eine Bytefolge.
     pub fn to_bytes(&self) -> Vec<u8> {
@@ -158,6 +346,10 @@ impl Envelope {
         out.extend_from_slice(&self.ts.to_le_bytes());
         out.extend_from_slice(&self.ttl.to_le_bytes());
         out.extend_from_slice(&self.epk);
+        if self.ver >= 2 {
+            out.extend_from_slice(&(self.kem_ct.len() as u32).to_le_bytes());
+            out.extend_from_slice(&self.kem_ct);
+        }
         out.extend_from_slice(&(self.tag.len() as u32).to_le_bytes());
         out.extend_from_slice(&self.tag);
         out.extend_from_slice(&self.pow_nonce.to_le_bytes());
@@ -183,11 +375,21 @@ impl Envelope {
         let mut epk = [0u8; 32];
         epk.copy_from_slice(&data[cursor..cursor+32]);
         cursor += 32;
+        let mut kem_ct = Vec::new();
+        if ver >= 2 {
+            if cursor + 4 > data.len() { return None; }
+            let kem_len = u32::from_le_bytes(data[cursor..cursor+4].try_into().ok()?) as usize;
+            cursor += 4;
+            if cursor + kem_len > data.len() { return None; }
+            kem_ct = data[cursor..cursor+kem_len].to_vec();
+            cursor += kem_len;
+        }
         let tag_len = u32::from_le_bytes(data[cursor..cursor+4].try_into().ok()?) as usize;
         cursor += 4;
         if cursor + tag_len > data.len() { return None; }
         let tag = data[cursor..cursor+tag_len].to_vec();
         cursor += tag_len;
+        if cursor + 8 + 24 + 4 > data.len() { return None; }
         let pow_nonce = u64::from_le_bytes(data[cursor..cursor+8].try_into().ok()?);
         cursor += 8;
         let mut nonce = [0u8; 24];
@@ -200,45 +402,238 @@ impl Envelope {
         cursor += c_len;
         let mut mac = [0u8; 16];
         mac.copy_from_slice(&data[cursor..cursor+16]);
-        Some(Self { ver, ts, ttl, epk, tag, pow_nonce, nonce, ciphertext, mac })
+        Some(Self { ver, ts, ttl, epk, kem_ct, tag, pow_nonce, nonce, ciphertext, mac })
     }
     /// Entschlüsselt die Nutzlast, sofern der Empfänger über den passenden
     /// Spend‑Key verfügt.  Es werden das ECDH‑Geheimnis und HKDF
     /// verwendet, um den `enc_key` zu rekonstruieren.  Anschließend
     /// erfolgt die AEAD‑Entschlüsselung.  Bei Erfolg wird die
-    /// deserialisierte Payload zurückgegeben.
+    /// deserialisierte Payload zurückgegeben.  Hybride Envelopes
+    /// (Version 2) brauchen zusätzlich den KEM‑Schlüssel, siehe
+    /// `decrypt_hybrid`, und liefern hier `None`.
     pub fn decrypt(&self, spend_key: &SpendKey) -> Option<Payload> {
-        // 1. Gemeinsames Geheimnis berechnen
-        let remote_epk = PublicKey::from(self.epk);
-        let shared_bytes = spend_key.secret.diffie_hellman(&remote_epk).as_bytes().clone();
-        // 2. HKDF ableiten
-        let hk = Hkdf::<Sha256>::new(None, &shared_bytes);
-        let mut okm = [0u8; 64];
-        hk.expand(b"pc.enc|pc.tag", &mut okm).ok()?;
-        let enc_key = &okm[..32];
-        // 3. AEAD entschlüsseln
-        let cipher = XChaCha20Poly1305::new_from_slice(enc_key).ok()?;
-        let mut ct = self.ciphertext.clone();
-        ct.extend_from_slice(&self.mac);
-        let decrypted = cipher.decrypt(XNonce::from_slice(&self.nonce), ct.as_ref()).ok()?;
-        Payload::from_bytes(&decrypted)
+        let okm = self.classic_keys(spend_key)?;
+        self.decrypt_with(&okm)
+    }
+    /// Entschlüsselt das Envelope und prüft anschließend das Tag.  Gibt
+    /// nur dann eine Payload zurück, wenn das Envelope für die
+    /// Hauptadresse von `spend_key` bestimmt ist; fremde Envelopes liefern
+    /// `None`.
+    pub fn open(&self, spend_key: &SpendKey) -> Option<Payload> {
+        self.open_subaddress(spend_key, &[]).map(|(payload, _)| payload)
+    }
+    /// Wie [`Envelope::open`], erkennt aber auch Envelopes an die
+    /// Subadressen mit den Basispunkten `bases`.  Liefert die Payload und
+    /// den Index der Adresse in `bases` (`None` für die Hauptadresse).
+    pub fn open_subaddress(&self, spend_key: &SpendKey, bases: &[[u8; 32]]) -> Option<(Payload, Option<usize>)> {
+        let okm = self.classic_keys(spend_key)?;
+        self.open_with(&okm, bases)
+    }
+    /// Wie [`Envelope::decrypt`], entschlüsselt aber klassische und
+    /// hybride Envelopes.
+    #[cfg(feature = "pqc")]
+    pub fn decrypt_hybrid(&self, spend_key: &SpendKey, kem_key: &KemKey) -> Option<Payload> {
+        let okm = self.hybrid_keys(spend_key, kem_key)?;
+        self.decrypt_with(&okm)
+    }
+    /// Wie [`Envelope::open`] für klassische und hybride Envelopes.
+    #[cfg(feature = "pqc")]
+    pub fn open_hybrid(&self, spend_key: &SpendKey, kem_key: &KemKey) -> Option<Payload> {
+        self.open_hybrid_subaddress(spend_key, kem_key, &[]).map(|(payload, _)| payload)
+    }
+    /// Wie [`Envelope::open_subaddress`] für klassische und hybride
+    /// Envelopes.
+    #[cfg(feature = "pqc")]
+    pub fn open_hybrid_subaddress(
+        &self,
+        spend_key: &SpendKey,
+        kem_key: &KemKey,
+        bases: &[[u8; 32]],
+    ) -> Option<(Payload, Option<usize>)> {
+        let okm = self.hybrid_keys(spend_key, kem_key)?;
+        self.open_with(&okm, bases)
     }
     /// Prüft, ob dieses Envelope für den Empfänger bestimmt ist.  Dazu
     /// wird aus dem Spend‑Key das Tag‑Key rekonstruiert und ein HMAC
     /// über die `msg_id` gebildet.  Stimmt das Ergebnis, ist die
     /// Nachricht für den Empfänger bestimmt.  Da die `msg_id` in der
     /// verschlüsselten Payload steckt, muss diese Methode nach dem
-    /// Entschlüsseln aufgerufen werden.
+    /// Entschlüsseln aufgerufen werden.  Gilt nur für klassische
+    /// Envelopes an die Hauptadresse; bei hybriden prüft `open_hybrid`,
+    /// bei Subadressen `open_subaddress` das Tag.  Der Vergleich läuft in
+    /// konstanter Zeit.
     pub fn verify_recipient(&self, spend_key: &SpendKey, msg_id: u128) -> bool {
-        let remote_epk = PublicKey::from(self.epk);
-        let shared_bytes = spend_key.secret.diffie_hellman(&remote_epk).as_bytes().clone();
-        let hk = Hkdf::<Sha256>::new(None, &shared_bytes);
-        let mut okm = [0u8; 64];
-        if hk.expand(b"pc.enc|pc.tag", &mut okm).is_err() { return false; }
-        let tag_key = &okm[32..64];
-        let mut mac = <Hmac<Sha256>>::new_from_slice(tag_key).expect("HMAC key");
-        mac.update(&msg_id.to_le_bytes());
-        let calculated = mac.finalize().into_bytes();
-        calculated.as_slice() == self.tag.as_slice()
+        match self.classic_keys(spend_key) {
+            Some(okm) => ct_eq(&compute_tag(&okm, msg_id, None), &self.tag),
+            None => false,
+        }
+    }
+    /// Schlüsselmaterial eines klassischen Envelopes (Version 1).
+    fn classic_keys(&self, spend_key: &SpendKey) -> Option<Zeroizing<[u8; 64]>> {
+        if self.ver >= 2 {
+            return None;
+        }
+        let shared = spend_key.ecdh(&PublicKey::from(self.epk));
+        Some(derive_keys(&shared, None, &self.epk, &[]))
+    }
+    /// Schlüsselmaterial eines klassischen oder hybriden Envelopes.
+    #[cfg(feature = "pqc")]
+    fn hybrid_keys(&self, spend_key: &SpendKey, kem_key: &KemKey) -> Option<Zeroizing<[u8; 64]>> {
+        if self.ver < 2 {
+            return self.classic_keys(spend_key);
+        }
+        let secret = kem_key.decapsulate(&self.kem_ct)?;
+        let shared = spend_key.ecdh(&PublicKey::from(self.epk));
+        Some(derive_keys(&shared, Some(&*secret), &self.epk, &self.kem_ct))
+    }
+    /// Entschlüsselt die Payload und prüft ein vorhandenes
+    /// Absenderzertifikat; Payloads mit ungültigem Zertifikat liefern
+    /// `None`.
+    fn decrypt_with(&self, okm: &[u8; 64]) -> Option<Payload> {
+        let cipher = XChaCha20Poly1305::new_from_slice(&okm[..32]).ok()?;
+        let mut ct = self.ciphertext.clone();
+        ct.extend_from_slice(&self.mac);
+        let decrypted = Zeroizing::new(cipher.decrypt(XNonce::from_slice(&self.nonce), ct.as_ref()).ok()?);
+        let payload = Payload::from_bytes(&decrypted)?;
+        if let Some(cert) = &payload.sender {
+            if !cert.verify(&payload.context(self.ver, &self.epk, &self.kem_ct)) {
+                return None;
+            }
+        }
+        Some(payload)
+    }
+    /// Entschlüsselt und sucht die Adresse, deren Tag passt: erst die
+    /// Hauptadresse, dann die Basispunkte in `bases`.
+    fn open_with(&self, okm: &[u8; 64], bases: &[[u8; 32]]) -> Option<(Payload, Option<usize>)> {
+        let payload = self.decrypt_with(okm)?;
+        if ct_eq(&compute_tag(okm, payload.msg_id, None), &self.tag) {
+            return Some((payload, None));
+        }
+        let index = bases.iter().position(|base| ct_eq(&compute_tag(okm, payload.msg_id, Some(base)), &self.tag))?;
+        Some((payload, Some(index)))
+    }
+}
+
+/// Leitet `enc_key‖tag_key` ab.  Klassisch dient allein das
+/// ECDH‑Geheimnis als HKDF‑Eingabe; hybrid wird das KEM‑Geheimnis
+/// angehängt und der Kontext an Ephemeral‑Key und KEM‑Ciphertext
+/// gebunden.  Ausgabe und Zwischenwerte werden nach Gebrauch
+/// überschrieben.
+fn derive_keys(ecdh: &[u8; 32], kem_secret: Option<&[u8; 32]>, epk: &[u8; 32], kem_ct: &[u8]) -> Zeroizing<[u8; 64]> {
+    let mut okm = Zeroizing::new([0u8; 64]);
+    match kem_secret {
+        None => {
+            let hk = Hkdf::<Sha256>::new(None, ecdh);
+            hk.expand(b"pc.enc|pc.tag", okm.as_mut()).expect("HKDF expand");
+        }
+        Some(kem_secret) => {
+            let mut ikm = Zeroizing::new([0u8; 64]);
+            ikm[..32].copy_from_slice(ecdh);
+            ikm[32..].copy_from_slice(kem_secret);
+            let mut info = b"pc.hybrid.v2|pc.enc|pc.tag".to_vec();
+            info.extend_from_slice(epk);
+            info.extend_from_slice(&sha256(kem_ct));
+            let hk = Hkdf::<Sha256>::new(None, ikm.as_ref());
+            hk.expand(&info, okm.as_mut()).expect("HKDF expand");
+        }
+    }
+    okm
+}
+
+/// HMAC‑Tag über die `msg_id` mit dem `tag_key` aus `okm`; bei
+/// Subadressen folgt der Basispunkt `base`.
+fn compute_tag(okm: &[u8; 64], msg_id: u128, base: Option<&[u8; 32]>) -> Vec<u8> {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&okm[32..]).expect("HMAC key");
+    mac.update(&msg_id.to_le_bytes());
+    if let Some(base) = base {
+        mac.update(base);
+    }
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::IdentityKey;
+
+    #[test]
+    fn classic_round_trip_with_sender_certificate() {
+        let identity = IdentityKey::generate();
+        let sender = Sender { identity: &identity, device_id: 3 };
+        let spend = SpendKey::generate();
+        let env = Envelope::new_padded(&Subaddress::primary(&spend.public), 7, &sender, vec![1; 40], b"hallo".to_vec(), 60, 0, &PaddingPolicy::default());
+        let env = Envelope::from_bytes(&env.to_bytes()).unwrap();
+        assert_eq!(env.ver, 1);
+        let payload = env.open(&spend).unwrap();
+        assert_eq!((payload.msg_id, payload.body.as_slice(), payload.ratchet_header.len()), (7, &b"hallo"[..], 40));
+        let cert = payload.sender.unwrap();
+        assert_eq!((cert.identity_pub, cert.device_id), (identity.public, 3));
+        assert!(env.open(&SpendKey::generate()).is_none());
+    }
+
+    #[test]
+    fn tampered_ciphertext_is_rejected() {
+        let spend = SpendKey::generate();
+        let mut env = Envelope::new(&spend.public, 9, 0, vec![], b"klassisch".to_vec(), 60, 0);
+        assert!(env.open(&spend).is_some());
+        env.ciphertext[0] ^= 1;
+        assert!(env.open(&spend).is_none());
+    }
+
+    #[cfg(feature = "pqc")]
+    fn hybrid(to: &SpendKey, kem: &KemKey, sender: &Sender, padding: &PaddingPolicy) -> Envelope {
+        let to = Subaddress::primary(&to.public);
+        Envelope::new_hybrid(&to, &kem.public, 7, sender, vec![1; 40], b"hallo".to_vec(), 60, 0, padding).unwrap()
+    }
+
+    #[cfg(feature = "pqc")]
+    #[test]
+    fn hybrid_sender_and_classic_receiver() {
+        let identity = IdentityKey::generate();
+        let sender = Sender { identity: &identity, device_id: 0 };
+        let spend = SpendKey::generate();
+        // Ohne KEM‑Schlüssel des Empfängers sendet auch ein PQ‑Client klassisch
+        let classic = Envelope::new_padded(&Subaddress::primary(&spend.public), 7, &sender, vec![], b"hallo".to_vec(), 60, 0, &PaddingPolicy::None);
+        assert_eq!(classic.ver, 1);
+        assert_eq!(classic.open(&spend).unwrap().body, b"hallo");
+        // Ein Version‑2‑Envelope lässt sich ohne KEM‑Schlüssel nicht öffnen
+        let kem = KemKey::generate();
+        let env = Envelope::from_bytes(&hybrid(&spend, &kem, &sender, &PaddingPolicy::None).to_bytes()).unwrap();
+        assert_eq!(env.ver, 2);
+        assert!(env.open(&spend).is_none());
+        assert!(env.decrypt(&spend).is_none());
+        assert_eq!(env.open_hybrid(&spend, &kem).unwrap().body, b"hallo");
+        assert!(env.open_hybrid(&spend, &KemKey::generate()).is_none());
+    }
+
+    #[cfg(feature = "pqc")]
+    #[test]
+    fn classic_sender_and_hybrid_receiver() {
+        let spend = SpendKey::generate();
+        let kem = KemKey::generate();
+        let env = Envelope::new(&spend.public, 9, 0, vec![], b"klassisch".to_vec(), 60, 0);
+        let env = Envelope::from_bytes(&env.to_bytes()).unwrap();
+        assert_eq!(env.ver, 1);
+        let payload = env.open_hybrid(&spend, &kem).unwrap();
+        assert_eq!((payload.msg_id, payload.body.as_slice()), (9, &b"klassisch"[..]));
+        assert!(env.open_hybrid(&SpendKey::generate(), &kem).is_none());
+    }
+
+    #[cfg(feature = "pqc")]
+    #[test]
+    fn cover_traffic_matches_hybrid_and_classic_envelopes() {
+        let identity = IdentityKey::generate();
+        let sender = Sender { identity: &identity, device_id: 0 };
+        let padding = PaddingPolicy::default();
+        let real = hybrid(&SpendKey::generate(), &KemKey::generate(), &sender, &padding);
+        let cover = Envelope::new_cover(real.ciphertext.len(), 60, 0, true);
+        assert_eq!((cover.ver, cover.kem_ct.len()), (2, pq::CIPHERTEXT_LEN));
+        assert_eq!(cover.to_bytes().len(), real.to_bytes().len());
+        // Klassische Kontakte: Cover ohne KEM‑Ciphertext
+        let classic = Envelope::new_padded(&Subaddress::primary(&SpendKey::generate().public), 7, &sender, vec![1; 40], b"hallo".to_vec(), 60, 0, &padding);
+        assert_eq!(classic.ciphertext.len(), real.ciphertext.len());
+        let cover = Envelope::new_cover(classic.ciphertext.len(), 60, 0, false);
+        assert_eq!((cover.ver, cover.kem_ct.len()), (1, 0));
+        assert_eq!(cover.to_bytes().len(), classic.to_bytes().len());
     }
 }
@@ -0,0 +1,175 @@
+//! Menschlich vergleichbare Fingerprints (Safety Numbers, SAS).
+//!
+//! Um einen Man‑in‑the‑Middle beim Pairing zu erkennen, vergleichen zwei
+//! Personen einen Wert, der aus beiden Identity‑Keys abgeleitet wird.  Er
+//! ist unabhängig davon, wer ihn berechnet, weil die Schlüssel vorher
+//! sortiert werden.  Drei Darstellungen stehen zur Wahl:
+//!
+//! * **Safety Number** – 60 Ziffern in Zwölfergruppen zu je fünf Ziffern.
+//!   Je 30 Ziffern stammen aus einem iterierten SHA‑256 über einen der
+//!   beiden Schlüssel (vgl. Signal).
+//! * **Emoji** – sieben Symbole aus einer Liste von 64 (je 6 Bit).
+//! * **Wörter** – sechs Wörter aus einer Liste von 256 (je 8 Bit).
+//!
+//! Emoji und Wörter (SAS, Short Authentication String) stammen aus einem
+//! gemeinsamen Hash beider Schlüssel und eignen sich für den schnellen
+//! Vergleich am Telefon; die Safety Number bietet die volle Sicherheit.
+//!
+//! Zusätzlich liefert [`sender_fingerprint`] den kurzen Wert für
+//! [`Payload::sender_fp`](crate::Payload).
+
+use sha2::{Digest, Sha256};
+
+/// Anzahl der Hash‑Iterationen pro Schlüssel für die Safety Number.
+const ITERATIONS: usize = 1024;
+
+/// Emoji für die SAS‑Darstellung (64 Einträge).
+pub const EMOJI: [&str; 64] = [
+    "🐶", "🐱", "🦁", "🐴", "🦄", "🐷", "🐘", "🐰", "🐼", "🐓", "🐧",
+    "🐢", "🐟", "🐙", "🦋", "🌷", "🌳", "🌵", "🍄", "🌏", "🌙", "☁️",
+    "🔥", "🍌", "🍎", "🍓", "🌽", "🍕", "🎂", "❤️", "😀", "🤖", "🎩",
+    "👓", "🔧", "🎅", "👍", "☂️", "⌛", "⏰", "🎁", "💡", "📕",
+    "✏️", "📎", "✂️", "🔒", "🔑", "🔨", "☎️", "🏁", "🚂", "🚲",
+    "✈️", "🚀", "🏆", "⚽", "🎸", "🎺", "🔔", "⚓", "🎧", "📁", "📌",
+];
+
+/// Wörter für die SAS‑Darstellung (256 Einträge).
+pub const WORDS: [&str; 256] = [
+    "Abend", "Adler", "Ahorn", "Alpen", "Ampel", "Anker", "Apfel", "Asche", "Atlas",
+    "Auge", "Auto", "Bach", "Bahn", "Balken", "Ball", "Banane", "Bank", "Bart",
+    "Bauer", "Baum", "Becher", "Beere", "Berg", "Besen", "Biene", "Bild", "Birne",
+    "Blatt", "Blitz", "Blume", "Boden", "Bogen", "Boot", "Brett", "Brief", "Brille",
+    "Brot", "Bruder", "Buch", "Burg", "Butter", "Dach", "Dampf", "Decke", "Deich",
+    "Delfin", "Diamant", "Dorf", "Drache", "Draht", "Eber", "Ecke", "Efeu", "Eiche",
+    "Eimer", "Eis", "Elch", "Engel", "Ente", "Erbse", "Erde", "Esel", "Eule", "Faden",
+    "Fahne", "Falke", "Farbe", "Feder", "Fels", "Fenster", "Feuer", "Fisch", "Flagge",
+    "Flasche", "Flöte", "Fluss", "Fohlen", "Forelle", "Frosch", "Fuchs", "Funke",
+    "Gabel", "Garten", "Geige", "Geist", "Gipfel", "Glas", "Glocke", "Gold", "Gras",
+    "Gurke", "Hafen", "Hagel", "Hahn", "Hammer", "Hand", "Harfe", "Hase", "Haus",
+    "Hecht", "Hecke", "Held", "Helm", "Hemd", "Herz", "Heu", "Himmel", "Hirsch", "Hof",
+    "Honig", "Horn", "Hose", "Hummel", "Hund", "Hut", "Igel", "Insel", "Jacke",
+    "Jaguar", "Kabel", "Kaffee", "Kamel", "Kamm", "Kanne", "Karte", "Kasten", "Katze",
+    "Kegel", "Kerze", "Kessel", "Kette", "Kiesel", "Kino", "Kirsche", "Kiste", "Klee",
+    "Knopf", "Koch", "Koffer", "Komet", "Korb", "Krabbe", "Kran", "Kreide", "Krone",
+    "Kugel", "Kuh", "Kutsche", "Lampe", "Laterne", "Laub", "Leiter", "Licht", "Lilie",
+    "Linde", "Löffel", "Löwe", "Luchs", "Luft", "Magnet", "Mais", "Mantel", "Maus",
+    "Meer", "Mehl", "Messer", "Milch", "Mond", "Moos", "Motte", "Mühle", "Muschel",
+    "Nadel", "Nebel", "Nest", "Netz", "Nuss", "Ofen", "Ohr", "Olive", "Onkel", "Orgel",
+    "Otter", "Paddel", "Palme", "Papier", "Pfad", "Pferd", "Pflaume", "Pilz", "Pinsel",
+    "Planet", "Pony", "Puppe", "Quelle", "Rabe", "Rad", "Rahmen", "Regen", "Reh",
+    "Ring", "Robbe", "Rose", "Rübe", "Salz", "Sand", "Schaf", "Schal", "Schiff",
+    "Schirm", "Schloss", "Schnee", "Schuh", "Seil", "Sonne", "Spiegel", "Stern",
+    "Stiefel", "Storch", "Strand", "Stuhl", "Tanne", "Tasche", "Taube", "Teich",
+    "Teller", "Tiger", "Tisch", "Tomate", "Topf", "Traube", "Trommel", "Tulpe", "Turm",
+    "Uhr", "Ufer", "Vase", "Vogel", "Wagen", "Wal", "Wald", "Wasser", "Wiese", "Wind",
+    "Wolf", "Wolke", "Zange", "Zaun", "Zebra", "Zelt", "Ziege", "Zitrone", "Zucker",
+    "Zug", "Zwerg", "Zwiebel",
+];
+
+/// Fingerprint zweier Identity‑Keys in allen Darstellungen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint {
+    /// 60 Ziffern ohne Trennzeichen.
+    pub safety_number: String,
+    pub emoji: Vec<&'static str>,
+    pub words: Vec<&'static str>,
+}
+
+impl Fingerprint {
+    /// Berechnet den Fingerprint aus dem eigenen und dem fremden
+    /// Identity‑Key.  Die Reihenfolge der Argumente spielt keine Rolle.
+    pub fn new(ours: &[u8; 32], theirs: &[u8; 32]) -> Self {
+        let (first, second) = if ours <= theirs { (ours, theirs) } else { (theirs, ours) };
+        let safety_number = format!("{}{}", key_digits(first), key_digits(second));
+
+        let mut hasher = Sha256::new();
+        hasher.update(b"pc.sas.v1");
+        hasher.update(first);
+        hasher.update(second);
+        let sas = hasher.finalize();
+        // 7 × 6 Bit aus den ersten sechs Bytes für die Emoji, die Wörter aus
+        // davon unabhängigen Bytes
+        let bits = u64::from_be_bytes([0, 0, sas[0], sas[1], sas[2], sas[3], sas[4], sas[5]]);
+        let emoji = (0..7).map(|i| EMOJI[((bits >> (42 - 6 * (i + 1))) & 0x3f) as usize]).collect();
+        let words = sas[16..22].iter().map(|b| WORDS[*b as usize]).collect();
+        Self { safety_number, emoji, words }
+    }
+
+    /// Safety Number in Fünfergruppen, vier Gruppen pro Zeile.
+    pub fn safety_number_grouped(&self) -> String {
+        let groups: Vec<&str> = (0..self.safety_number.len())
+            .step_by(5)
+            .map(|i| &self.safety_number[i..i + 5])
+            .collect();
+        groups.chunks(4).map(|line| line.join(" ")).collect::<Vec<_>>().join("\n")
+    }
+}
+
+/// 30 Ziffern für einen einzelnen Schlüssel: sechs 5‑Byte‑Blöcke des
+/// iterierten Hashs, jeweils modulo 100000.
+fn key_digits(key: &[u8; 32]) -> String {
+    let mut hash = Sha256::digest([b"pc.fp.v1".as_slice(), key.as_slice()].concat());
+    for _ in 0..ITERATIONS {
+        let mut hasher = Sha256::new();
+        hasher.update(hash);
+        hasher.update(key);
+        hash = hasher.finalize();
+    }
+    hash[..30]
+        .chunks(5)
+        .map(|chunk| {
+            let n = chunk.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64);
+            format!("{:05}", n % 100_000)
+        })
+        .collect()
+}
+
+/// Kurzer Fingerprint eines Identity‑Keys für `Payload::sender_fp`.  Er
+/// dient dem Empfänger nur als Hinweis, welcher Kontakt die Nachricht
+/// vermutlich gesendet hat, und ersetzt keine Authentisierung; die
+/// leistet das Absenderzertifikat ([`crate::sealed`]).
+pub fn sender_fingerprint(identity_pub: &[u8; 32]) -> u32 {
+    let mut hasher = Sha256::new();
+    hasher.update(b"pc.sender_fp");
+    hasher.update(identity_pub);
+    let digest = hasher.finalize();
+    u32::from_le_bytes(digest[..4].try_into().expect("4 Byte"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::IdentityKey;
+
+    #[test]
+    fn same_from_both_sides_and_stable() {
+        let (alice, bob) = (IdentityKey::generate().public, IdentityKey::generate().public);
+        let fingerprint = Fingerprint::new(&alice, &bob);
+        assert_eq!(fingerprint, Fingerprint::new(&bob, &alice));
+        assert_eq!(fingerprint, Fingerprint::new(&alice, &bob));
+        assert_eq!(fingerprint.safety_number.len(), 60);
+        assert!(fingerprint.safety_number.chars().all(|c| c.is_ascii_digit()));
+        assert_eq!((fingerprint.emoji.len(), fingerprint.words.len()), (7, 6));
+        assert_eq!(fingerprint.safety_number_grouped().lines().count(), 3);
+    }
+
+    #[test]
+    fn key_change_changes_every_representation() {
+        let (alice, bob) = (IdentityKey::generate().public, IdentityKey::generate().public);
+        let before = Fingerprint::new(&alice, &bob);
+        let after = Fingerprint::new(&alice, &IdentityKey::generate().public);
+        assert_ne!(before.safety_number, after.safety_number);
+        assert_ne!(before.emoji, after.emoji);
+        assert_ne!(before.words, after.words);
+        // Die Hälfte des unveränderten Schlüssels bleibt gleich
+        assert!(before.safety_number.contains(&key_digits(&alice)));
+        assert!(after.safety_number.contains(&key_digits(&alice)));
+    }
+
+    #[test]
+    fn sender_fingerprint_is_stable() {
+        let identity = IdentityKey::generate().public;
+        assert_eq!(sender_fingerprint(&identity), sender_fingerprint(&identity));
+        assert_ne!(sender_fingerprint(&identity), sender_fingerprint(&IdentityKey::generate().public));
+    }
+}
@@ -0,0 +1,456 @@
+//! Gruppen mit Sender‑Keys.
+//!
+//! Jedes Mitglied einer Gruppe besitzt eine eigene Sendekette
+//! ([`SenderKey`]).  Deren Anfangsstand schickt es einmal über die
+//! paarweisen Ratchet‑Sessions an alle anderen Mitglieder
+//! ([`GroupControl::SenderKey`]); danach wird jede Gruppennachricht nur
+//! noch einmal verschlüsselt.  Aus dem Kettenschlüssel entstehen wie bei
+//! Signal Nachrichtenschlüssel und nächster Kettenschlüssel:
+//!
+//! ```text
+//! message_key = HMAC‑SHA256(chain_key, 0x01)
+//! chain_key'  = HMAC‑SHA256(chain_key, 0x02)
+//! ```
+//!
+//! Gruppennachrichten reisen in gewöhnlichen (klassischen) Envelopes an
+//! eine gemeinsame Gruppenadresse, deren Spend‑Key aus dem
+//! Gruppengeheimnis der aktuellen Epoche abgeleitet wird
+//! ([`group_spend_key`]).  Ein Envelope erreicht alle Mitglieder; Relays
+//! sehen wie bei Einzelnachrichten nur ein Stealth‑Tag und erfahren weder
+//! Größe noch Mitglieder der Gruppe.  Den Absender belegt das
+//! Absenderzertifikat der Payload.
+//!
+//! Mitglieder verwaltet allein der Gründer.  Jede Änderung beginnt eine
+//! neue Epoche: Der Gründer verteilt ein frisches Gruppengeheimnis
+//! ([`GroupControl::Update`]), und alle Mitglieder erzeugen neue
+//! Sender‑Keys.  Ausgeschiedene Mitglieder erkennen danach weder die
+//! Envelopes der Gruppe noch können sie deren Nachrichten lesen.
+//!
+//! Format des Bodys einer Gruppennachricht:
+//! `group_id(16) | epoch(4, BE) | key_id(4, BE) | iteration(4, BE) | ciphertext`.
+//! Der Kopf ist Associated Data von XChaCha20‑Poly1305; da jeder
+//! Nachrichtenschlüssel nur einmal verwendet wird, ist die Nonce null.
+//!
+//! Gruppengeheimnisse und Kettenschlüssel liegen in [`SecretBytes`] bzw.
+//! auf dem Transportweg in [`Zeroizing`] und werden beim Drop
+//! überschrieben.  Sendeketten lassen sich deshalb nicht klonen.
+
+use crate::keys::SpendKey;
+use crate::secret::SecretBytes;
+use chacha20poly1305::aead::{Aead, Payload};
+use chacha20poly1305::{KeyInit, XChaCha20Poly1305, XNonce};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use rand_core::{OsRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::{ZeroizeOnDrop, Zeroizing};
+
+/// Kennung einer Gruppe.
+pub type GroupId = [u8; 16];
+
+/// Länge des Kopfes einer Gruppennachricht.
+pub const HEADER_LEN: usize = 16 + 4 + 4 + 4;
+/// Höchstzahl übersprungener Nachrichtenschlüssel je Sendekette.
+pub const MAX_SKIP: u32 = 1000;
+
+const ADDRESS_INFO: &[u8] = b"pc.group.address.v1";
+
+/// Fehler beim Ver‑ oder Entschlüsseln einer Gruppennachricht.
+#[derive(Debug, thiserror::Error)]
+pub enum GroupError {
+    #[error("Gruppennachricht fehlerhaft")]
+    Malformed,
+    #[error("Sender‑Key passt nicht zur Nachricht")]
+    WrongKey,
+    #[error("zu viele übersprungene Nachrichten ({0})")]
+    TooFarAhead(u32),
+    #[error("Nachrichtenschlüssel bereits verbraucht")]
+    Duplicate,
+    #[error("Sendekette erschöpft")]
+    Exhausted,
+    #[error("Gruppennachricht lässt sich nicht entschlüsseln")]
+    Decrypt,
+}
+
+/// Erzeugt eine zufällige Gruppen‑ID.
+pub fn new_group_id() -> GroupId {
+    let mut id = [0u8; 16];
+    OsRng.fill_bytes(&mut id);
+    id
+}
+
+/// Erzeugt ein zufälliges Gruppengeheimnis für eine neue Epoche.
+pub fn new_group_secret() -> SecretBytes<32> {
+    SecretBytes::random()
+}
+
+/// Spend‑Key der gemeinsamen Gruppenadresse einer Epoche.  Alle
+/// Mitglieder leiten ihn aus dem Gruppengeheimnis ab.
+pub fn group_spend_key(secret: &SecretBytes<32>) -> SpendKey {
+    let hk = Hkdf::<Sha256>::new(None, secret.expose());
+    let mut bytes = Zeroizing::new([0u8; 32]);
+    hk.expand(ADDRESS_INFO, bytes.as_mut()).expect("HKDF expand");
+    let secret = StaticSecret::from(*bytes);
+    let public = PublicKey::from(&secret);
+    SpendKey { secret, public }
+}
+
+/// Mitglied einer Gruppe mit den öffentlichen Schlüsseln, die die anderen
+/// Mitglieder für paarweise Sessions brauchen.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupMember {
+    /// Anzeigename, wie ihn der Gründer vergeben hat.
+    pub name: String,
+    pub identity_pub: [u8; 32],
+    /// Adresse (Haupt‑ oder Subadresse) des Mitglieds, siehe
+    /// [`crate::Subaddress::to_bytes`].
+    pub address: Vec<u8>,
+    /// ML‑KEM‑Schlüssel, falls bekannt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kem_pub: Option<Vec<u8>>,
+}
+
+/// Stand einer Sendekette, wie ihn die anderen Mitglieder erhalten.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SenderKeyDistribution {
+    pub key_id: u32,
+    pub iteration: u32,
+    pub chain_key: Zeroizing<[u8; 32]>,
+}
+
+impl std::fmt::Debug for SenderKeyDistribution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SenderKeyDistribution")
+            .field("key_id", &self.key_id)
+            .field("iteration", &self.iteration)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Steuernachricht einer Gruppe.  Reist als [`crate::Content::Group`]
+/// über die paarweisen Sessions, nie über die Gruppenadresse.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupControl {
+    /// Einladung bzw. neue Mitgliederliste mit dem Gruppengeheimnis der
+    /// Epoche `epoch`; gilt nur vom Gründer.
+    Update {
+        group: GroupId,
+        name: String,
+        epoch: u32,
+        secret: Zeroizing<[u8; 32]>,
+        members: Vec<GroupMember>,
+    },
+    /// Sender‑Key des Absenders für die Epoche `epoch`.
+    SenderKey { group: GroupId, epoch: u32, key: SenderKeyDistribution },
+    /// Der Absender verlässt die Gruppe.  Vom Gründer an ein Mitglied:
+    /// Die Gruppe endet für den Empfänger (etwa weil er entfernt wurde).
+    Leave { group: GroupId },
+}
+
+impl GroupControl {
+    /// Gruppe, auf die sich die Nachricht bezieht.
+    pub fn group(&self) -> &GroupId {
+        match self {
+            GroupControl::Update { group, .. } | GroupControl::SenderKey { group, .. } | GroupControl::Leave { group } => {
+                group
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for GroupControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let group = crate::util::to_hex(self.group());
+        match self {
+            GroupControl::Update { name, epoch, members, .. } => f
+                .debug_struct("Update")
+                .field("group", &group)
+                .field("name", name)
+                .field("epoch", epoch)
+                .field("members", &members.len())
+                .finish_non_exhaustive(),
+            GroupControl::SenderKey { epoch, key, .. } => {
+                f.debug_struct("SenderKey").field("group", &group).field("epoch", epoch).field("key", key).finish()
+            }
+            GroupControl::Leave { .. } => f.debug_struct("Leave").field("group", &group).finish(),
+        }
+    }
+}
+
+/// Kopf einer Gruppennachricht.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GroupHeader {
+    pub group: GroupId,
+    pub epoch: u32,
+    pub key_id: u32,
+    pub iteration: u32,
+}
+
+impl GroupHeader {
+    pub fn to_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut out = [0u8; HEADER_LEN];
+        out[..16].copy_from_slice(&self.group);
+        out[16..20].copy_from_slice(&self.epoch.to_be_bytes());
+        out[20..24].copy_from_slice(&self.key_id.to_be_bytes());
+        out[24..28].copy_from_slice(&self.iteration.to_be_bytes());
+        out
+    }
+    /// Zerlegt einen Body in Kopf und Ciphertext.
+    pub fn parse(body: &[u8]) -> Result<(Self, &[u8]), GroupError> {
+        if body.len() < HEADER_LEN {
+            return Err(GroupError::Malformed);
+        }
+        let word = |i: usize| u32::from_be_bytes(body[i..i + 4].try_into().expect("4 Byte"));
+        let header = Self {
+            group: body[..16].try_into().expect("16 Byte"),
+            epoch: word(16),
+            key_id: word(20),
+            iteration: word(24),
+        };
+        Ok((header, &body[HEADER_LEN..]))
+    }
+}
+
+/// Eigene Sendekette.
+#[derive(Serialize, Deserialize)]
+pub struct SenderKey {
+    pub key_id: u32,
+    iteration: u32,
+    chain_key: SecretBytes<32>,
+}
+
+impl ZeroizeOnDrop for SenderKey {}
+
+impl std::fmt::Debug for SenderKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SenderKey").field("key_id", &self.key_id).field("iteration", &self.iteration).finish_non_exhaustive()
+    }
+}
+
+impl SenderKey {
+    /// Erzeugt eine neue Sendekette mit zufälliger Kennung.
+    pub fn generate() -> Self {
+        Self { key_id: OsRng.next_u32(), iteration: 0, chain_key: SecretBytes::random() }
+    }
+    /// Aktueller Stand zum Verteilen; frühere Nachrichten bleiben für
+    /// Empfänger dieses Stands unlesbar.
+    pub fn distribution(&self) -> SenderKeyDistribution {
+        let chain_key = Zeroizing::new(*self.chain_key.expose());
+        SenderKeyDistribution { key_id: self.key_id, iteration: self.iteration, chain_key }
+    }
+    /// Verschlüsselt `plaintext` als Gruppennachricht und rückt die Kette
+    /// weiter.  Nach `u32::MAX` Nachrichten ist die Kette erschöpft; dann
+    /// braucht es einen neuen Sender‑Key.
+    pub fn encrypt(&mut self, group: &GroupId, epoch: u32, plaintext: &[u8]) -> Result<Vec<u8>, GroupError> {
+        let header = GroupHeader { group: *group, epoch, key_id: self.key_id, iteration: self.iteration };
+        let iteration = self.iteration.checked_add(1).ok_or(GroupError::Exhausted)?;
+        let (message_key, next) = step(&self.chain_key);
+        self.chain_key = next;
+        self.iteration = iteration;
+        let header = header.to_bytes();
+        let cipher = XChaCha20Poly1305::new_from_slice(message_key.expose()).expect("32 Byte");
+        let ciphertext =
+            cipher.encrypt(&XNonce::default(), Payload { msg: plaintext, aad: &header }).expect("XChaCha20-Poly1305");
+        let mut out = header.to_vec();
+        out.extend_from_slice(&ciphertext);
+        Ok(out)
+    }
+}
+
+/// Empfangsstand der Sendekette eines anderen Mitglieds.
+#[derive(Serialize, Deserialize)]
+pub struct SenderKeyState {
+    pub key_id: u32,
+    iteration: u32,
+    chain_key: SecretBytes<32>,
+    /// Schlüssel übersprungener Nachrichten (`iteration`, Schlüssel).
+    #[serde(default)]
+    skipped: Vec<(u32, SecretBytes<32>)>,
+}
+
+impl ZeroizeOnDrop for SenderKeyState {}
+
+impl std::fmt::Debug for SenderKeyState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SenderKeyState")
+            .field("key_id", &self.key_id)
+            .field("iteration", &self.iteration)
+            .field("skipped", &self.skipped.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl SenderKeyState {
+    pub fn new(distribution: &SenderKeyDistribution) -> Self {
+        Self {
+            key_id: distribution.key_id,
+            iteration: distribution.iteration,
+            chain_key: SecretBytes::new(*distribution.chain_key),
+            skipped: Vec::new(),
+        }
+    }
+    /// Entschlüsselt eine Gruppennachricht mit Kopf `header`.  Der Zustand
+    /// ändert sich nur, wenn die Entschlüsselung gelingt.
+    pub fn decrypt(&mut self, header: &GroupHeader, ciphertext: &[u8]) -> Result<Vec<u8>, GroupError> {
+        if header.key_id != self.key_id {
+            return Err(GroupError::WrongKey);
+        }
+        if header.iteration < self.iteration {
+            let index =
+                self.skipped.iter().position(|(i, _)| *i == header.iteration).ok_or(GroupError::Duplicate)?;
+            let plain = open(&self.skipped[index].1, header, ciphertext)?;
+            self.skipped.remove(index);
+            return Ok(plain);
+        }
+        let gap = header.iteration - self.iteration;
+        if gap > MAX_SKIP {
+            return Err(GroupError::TooFarAhead(gap));
+        }
+        let iteration = header.iteration.checked_add(1).ok_or(GroupError::Exhausted)?;
+        // Die Kette rückt zunächst nur lokal vor und wird erst nach
+        // erfolgreicher Entschlüsselung übernommen.
+        let mut skipped = Vec::with_capacity(gap as usize);
+        let (mut message_key, mut chain_key) = step(&self.chain_key);
+        for skipped_iteration in self.iteration..header.iteration {
+            skipped.push((skipped_iteration, message_key));
+            (message_key, chain_key) = step(&chain_key);
+        }
+        let plain = open(&message_key, header, ciphertext)?;
+        self.chain_key = chain_key;
+        self.iteration = iteration;
+        self.skipped.extend(skipped);
+        // Älteste übersprungene Schlüssel zuerst verwerfen
+        let excess = self.skipped.len().saturating_sub(MAX_SKIP as usize);
+        self.skipped.drain(..excess);
+        Ok(plain)
+    }
+}
+
+/// Öffnet den Ciphertext einer Gruppennachricht mit `message_key`.
+fn open(message_key: &SecretBytes<32>, header: &GroupHeader, ciphertext: &[u8]) -> Result<Vec<u8>, GroupError> {
+    let cipher = XChaCha20Poly1305::new_from_slice(message_key.expose()).expect("32 Byte");
+    cipher
+        .decrypt(&XNonce::default(), Payload { msg: ciphertext, aad: &header.to_bytes() })
+        .map_err(|_| GroupError::Decrypt)
+}
+
+/// Ein Schritt der Kette: (Nachrichtenschlüssel, nächster Kettenschlüssel).
+fn step(chain_key: &SecretBytes<32>) -> (SecretBytes<32>, SecretBytes<32>) {
+    let derive = |label: u8| -> SecretBytes<32> {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(chain_key.expose()).expect("HMAC key");
+        mac.update(&[label]);
+        SecretBytes::new(mac.finalize().into_bytes().into())
+    };
+    (derive(0x01), derive(0x02))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GROUP: GroupId = [9u8; 16];
+
+    fn chain() -> (SenderKey, SenderKeyState) {
+        let key = SenderKey::generate();
+        let state = SenderKeyState::new(&key.distribution());
+        (key, state)
+    }
+
+    fn open(state: &mut SenderKeyState, body: &[u8]) -> Result<Vec<u8>, GroupError> {
+        let (header, ciphertext) = GroupHeader::parse(body)?;
+        state.decrypt(&header, ciphertext)
+    }
+
+    #[test]
+    fn round_trip_in_order() {
+        let (mut key, mut state) = chain();
+        for text in [&b"eins"[..], b"zwei", b"drei"] {
+            let body = key.encrypt(&GROUP, 1, text).unwrap();
+            assert_eq!(open(&mut state, &body).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn out_of_order_within_skip_limit() {
+        let (mut key, mut state) = chain();
+        let bodies: Vec<Vec<u8>> = (0..3u8).map(|i| key.encrypt(&GROUP, 1, &[i]).unwrap()).collect();
+        assert_eq!(open(&mut state, &bodies[2]).unwrap(), [2]);
+        assert_eq!(open(&mut state, &bodies[0]).unwrap(), [0]);
+        assert_eq!(open(&mut state, &bodies[1]).unwrap(), [1]);
+    }
+
+    #[test]
+    fn gaps_beyond_the_skip_limit_are_rejected() {
+        let (mut key, mut state) = chain();
+        let first = key.encrypt(&GROUP, 1, b"erste").unwrap();
+        for _ in 0..MAX_SKIP {
+            key.encrypt(&GROUP, 1, b"verloren").unwrap();
+        }
+        let far = key.encrypt(&GROUP, 1, b"zu weit").unwrap();
+        assert!(matches!(open(&mut state, &far), Err(GroupError::TooFarAhead(gap)) if gap == MAX_SKIP + 1));
+        // Der Zustand bleibt unverändert
+        assert_eq!(open(&mut state, &first).unwrap(), b"erste");
+    }
+
+    #[test]
+    fn duplicates_and_tampering_are_rejected() {
+        let (mut key, mut state) = chain();
+        let first = key.encrypt(&GROUP, 1, b"eins").unwrap();
+        let second = key.encrypt(&GROUP, 1, b"zwei").unwrap();
+        let mut forged = second.clone();
+        *forged.last_mut().unwrap() ^= 1;
+        assert!(matches!(open(&mut state, &forged), Err(GroupError::Decrypt)));
+
+        open(&mut state, &second).unwrap();
+        open(&mut state, &first).unwrap();
+        assert!(matches!(open(&mut state, &first), Err(GroupError::Duplicate)));
+        assert!(matches!(open(&mut state, &second), Err(GroupError::Duplicate)));
+    }
+
+    #[test]
+    fn exhausted_chains_are_rejected() {
+        let mut key = SenderKey { key_id: 1, iteration: u32::MAX, chain_key: SecretBytes::random() };
+        assert!(matches!(key.encrypt(&GROUP, 1, b"x"), Err(GroupError::Exhausted)));
+
+        let distribution = SenderKeyDistribution { key_id: 1, iteration: u32::MAX, chain_key: Zeroizing::new([3u8; 32]) };
+        let mut state = SenderKeyState::new(&distribution);
+        let header = GroupHeader { group: GROUP, epoch: 1, key_id: 1, iteration: u32::MAX };
+        assert!(matches!(state.decrypt(&header, &[0u8; 16]), Err(GroupError::Exhausted)));
+    }
+
+    #[test]
+    fn rekey_makes_old_keys_unusable() {
+        let (mut old_key, mut removed) = chain();
+        let old_secret = new_group_secret();
+        open(&mut removed, &old_key.encrypt(&GROUP, 1, b"vorher").unwrap()).unwrap();
+
+        // Mitgliedswechsel: neues Gruppengeheimnis und neue Sender‑Keys
+        let new_secret = new_group_secret();
+        assert_ne!(group_spend_key(&old_secret).public.as_bytes(), group_spend_key(&new_secret).public.as_bytes());
+        let (mut new_key, mut member) = chain();
+        let body = new_key.encrypt(&GROUP, 2, b"nachher").unwrap();
+        assert!(matches!(open(&mut removed, &body), Err(GroupError::WrongKey)));
+        assert_eq!(open(&mut member, &body).unwrap(), b"nachher");
+
+        // Wer erst den neuen Stand erhält, liest ältere Nachrichten nicht
+        let earlier = new_key.encrypt(&GROUP, 2, b"vor dem Beitritt").unwrap();
+        let mut late = SenderKeyState::new(&new_key.distribution());
+        assert!(matches!(open(&mut late, &earlier), Err(GroupError::Duplicate)));
+        assert!(matches!(open(&mut late, &old_key.encrypt(&GROUP, 1, b"alt").unwrap()), Err(GroupError::WrongKey)));
+    }
+
+    #[test]
+    fn state_survives_serialization() {
+        let (mut key, mut state) = chain();
+        let skipped = key.encrypt(&GROUP, 1, b"eins").unwrap();
+        open(&mut state, &key.encrypt(&GROUP, 1, b"zwei").unwrap()).unwrap();
+        let mut bytes = Vec::new();
+        ciborium::into_writer(&state, &mut bytes).unwrap();
+        let mut restored: SenderKeyState = ciborium::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(open(&mut restored, &skipped).unwrap(), b"eins");
+        assert_eq!(open(&mut restored, &key.encrypt(&GROUP, 1, b"drei").unwrap()).unwrap(), b"drei");
+    }
+}
@@ -2,9 +2,9 @@
 //!
 //! Jede Instanz besitzt drei Schlüsselpaare:
 //!
-//! * Eine **Identity‑Key** (id_key), die zur Authentisierung der App
-//!   verwendet wird.  In späteren Versionen könnte hier Ed25519 zum
-//!   Signieren verwendet werden.
+//! * Eine **Identity‑Key** (id_key), ein Ed25519‑Schlüsselpaar, mit dem
+//!   die App signiert, z.&nbsp;B. Pairing‑Bundles (siehe
+//!   [`crate::pairing`]).
 //! * Einen **View‑Key** (view_key), bestehend aus privatem und öffentlichem
 //!   X25519‑Schlüssel.  Der Empfänger nutzt den privaten view_key, um
 //!   aus eingehenden Envelopes das HMAC‑Tag zu reproduzieren und so seine
@@ -14,76 +14,115 @@
 //!   Nutzlast.
 //!
 //! Die tatsächliche Implementierung nutzt x25519‑dalek, um die
-//! Schlüssel zu generieren und Diffie‑Hellman durchzuführen.  Für eine
-//! produktionsreife Version sollte das Handling der Schlüssel (z.&nbsp;B.
-//! Serialisierung, Zeroization) sorgfältig implementiert werden.
+//! Schlüssel zu generieren und Diffie‑Hellman durchzuführen.  Private
+//! Schlüssel werden beim Drop überschrieben und in `Debug` nicht
+//! ausgegeben; die Typen sind bewusst nicht `Clone` (siehe
+//! [`crate::secret`]).
 
+use crate::secret::SecretBytes;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
 use rand_core::OsRng;
 use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroizing;
 
-/// Identity‑Keypair.  Im MVP wird nur das öffentliche Element gespeichert;
-/// Signaturen sind noch nicht implementiert.
-#[derive(Debug, Clone)]
+/// Identity‑Keypair (Ed25519).
 pub struct IdentityKey {
-    /// Öffentlicher Teil (32 Byte)
+    /// Öffentlicher Teil (32 Byte)
     pub public: [u8; 32],
-    /// Privater Teil (optional, 32 Byte)
-    pub private: [u8; 32],
+    /// Privater Seed (32 Byte)
+    private: SecretBytes<32>,
 }
 
-/// View‑Keypair (X25519)
-#[derive(Debug, Clone)]
+/// View‑Keypair (X25519).  `StaticSecret` überschreibt sich beim Drop
+/// selbst.
 pub struct ViewKey {
     pub secret: StaticSecret,
     pub public: PublicKey,
 }
 
 /// Spend‑Keypair (X25519)
-#[derive(Debug, Clone)]
 pub struct SpendKey {
     pub secret: StaticSecret,
     pub public: PublicKey,
 }
 
+impl std::fmt::Debug for IdentityKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("IdentityKey").field("public", &crate::util::to_hex(&self.public)).finish_non_exhaustive()
+    }
+}
+
+impl std::fmt::Debug for ViewKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ViewKey").field("public", &crate::util::to_hex(self.public.as_bytes())).finish_non_exhaustive()
+    }
+}
+
+impl std::fmt::Debug for SpendKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpendKey").field("public", &crate::util::to_hex(self.public.as_bytes())).finish_non_exhaustive()
+    }
+}
+
 impl IdentityKey {
     /// Erzeugt ein neues Identity‑Keypair mit zufälligem privaten Schlüssel.
     pub fn generate() -> Self {
-        let mut priv_bytes = [0u8; 32];
-        OsRng.fill_bytes(&mut priv_bytes);
-        // In einer echten Implementierung würde hier aus priv_bytes ein
-        // Ed25519‑ oder andere Identity‑Key abgeleitet werden.  Zur
-        // Vereinfachung speichern wir die Bytes direkt.
-        let public = priv_bytes; // Platzhalter: Identity‑Key = priv
-        Self { public, private: priv_bytes }
+        Self::from_secret(SecretBytes::random())
+    }
+    /// Leitet das Keypair aus einem gespeicherten Seed ab.  Ältere
+    /// Schlüsseldateien enthielten als öffentlichen Teil noch den Seed
+    /// selbst; der öffentliche Schlüssel wird deshalb immer neu berechnet.
+    pub fn from_private(private: [u8; 32]) -> Self {
+        Self::from_secret(SecretBytes::new(private))
+    }
+    fn from_secret(private: SecretBytes<32>) -> Self {
+        let public = SigningKey::from_bytes(private.expose()).verifying_key().to_bytes();
+        Self { public, private }
+    }
+    /// Privater Seed, etwa zum Versiegeln im Schlüsselspeicher.
+    pub fn private_bytes(&self) -> &[u8; 32] {
+        self.private.expose()
+    }
+    /// Signiert `msg` (Ed25519).
+    pub fn sign(&self, msg: &[u8]) -> [u8; 64] {
+        SigningKey::from_bytes(self.private.expose()).sign(msg).to_bytes()
+    }
+}
+
+/// Prüft eine Ed25519‑Signatur über `msg` gegen einen Identity‑Public‑Key.
+pub fn verify_identity_signature(public: &[u8; 32], msg: &[u8], signature: &[u8; 64]) -> bool {
+    match VerifyingKey::from_bytes(public) {
+        Ok(key) => key.verify(msg, &Signature::from_bytes(signature)).is_ok(),
+        Err(_) => false,
     }
 }
 
 impl ViewKey {
     /// Erzeugt ein neues View‑Keypair.
     pub fn generate() -> Self {
-        let secret = StaticSecret::new(&mut OsRng);
+        let secret = StaticSecret::random_from_rng(OsRng);
         let public = PublicKey::from(&secret);
         Self { secret, public }
     }
     /// Berechnet ein gemeinsames Geheimnis mit dem Spend‑Key des
     /// Empfängers.  Dieses Geheimnis dient als Input für HKDF.
-    pub fn ecdh(&self, remote: &SpendKey) -> [u8; 32] {
+    pub fn ecdh(&self, remote: &SpendKey) -> Zeroizing<[u8; 32]> {
         let shared = self.secret.diffie_hellman(&remote.public);
-        *shared.as_bytes()
+        Zeroizing::new(*shared.as_bytes())
     }
 }
 
 impl SpendKey {
     /// Erzeugt ein neues Spend‑Keypair.
     pub fn generate() -> Self {
-        let secret = StaticSecret::new(&mut OsRng);
+        let secret = StaticSecret::random_from_rng(OsRng);
         let public = PublicKey::from(&secret);
         Self { secret, public }
     }
     /// Berechnet ein gemeinsames Geheimnis mit dem Ephemeral‑Key des
     /// Senders.
-    pub fn ecdh(&self, remote_epk: &PublicKey) -> [u8; 32] {
+    pub fn ecdh(&self, remote_epk: &PublicKey) -> Zeroizing<[u8; 32]> {
         let shared = self.secret.diffie_hellman(remote_epk);
-        *shared.as_bytes()
+        Zeroizing::new(*shared.as_bytes())
     }
 }
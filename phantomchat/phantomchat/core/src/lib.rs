@@ -8,12 +8,35 @@
 //! durch geprüften Produktionscode ersetzt werden.
 
 pub mod keys;
+pub mod attachment;
+pub mod content;
+pub mod device;
 pub mod envelope;
+pub mod fingerprint;
+pub mod group;
+pub mod padding;
+pub mod pairing;
 pub mod pow;
+#[cfg(feature = "pqc")]
+pub mod pq;
+#[cfg(feature = "pqc")]
+pub mod pq_ratchet;
 pub mod ratchet;
+pub mod sealed;
+pub mod secret;
+pub mod subaddress;
 pub mod util;
 
 pub use keys::{IdentityKey, ViewKey, SpendKey};
+pub use attachment::{AttachmentError, Chunk, Manifest};
+pub use content::{Content, ContentError};
+pub use device::{DeviceCertificate, DeviceError, DeviceList, LinkRequest, Provisioning};
 pub use envelope::{Envelope, Payload};
+pub use fingerprint::Fingerprint;
+pub use group::{GroupControl, GroupError, GroupId, GroupMember, SenderKey, SenderKeyDistribution, SenderKeyState};
+pub use padding::PaddingPolicy;
+pub use pairing::{PairingBundle, PairingError};
 pub use pow::{Hashcash};
-pub use ratchet::{RatchetState, RatchetError};
+pub use ratchet::{RatchetHeader, RatchetState, RatchetError, SessionRecord};
+pub use sealed::{Sender, SenderCertificate};
+pub use subaddress::Subaddress;
@@ -0,0 +1,56 @@
+//! Längen‑Padding für Payloads.
+//!
+//! Ein passiver Beobachter sieht die Länge jedes Envelopes.  Damit echte
+//! Nachrichten und Cover‑Traffic nicht an ihrer Größe unterscheidbar
+//! sind, wird die serialisierte Payload vor der Verschlüsselung mit
+//! Nullbytes auf eine feste Bucket‑Größe aufgefüllt.  `Payload::from_bytes`
+//! ignoriert die angehängten Bytes, das Padding ist daher für den
+//! Empfänger transparent.
+
+/// Standard‑Buckets in Bytes (Länge der serialisierten Payload).
+pub const DEFAULT_BUCKETS: [usize; 7] = [256, 512, 1024, 2048, 4096, 8192, 16384];
+
+/// Regel, auf welche Länge eine Payload aufgefüllt wird.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaddingPolicy {
+    /// Kein Padding.
+    None,
+    /// Auffüllen auf den kleinsten passenden Bucket; größere Payloads
+    /// werden auf ein Vielfaches des größten Buckets aufgefüllt.
+    Buckets(Vec<usize>),
+    /// Auffüllen auf ein Vielfaches der Blockgröße.
+    Block(usize),
+}
+
+impl Default for PaddingPolicy {
+    fn default() -> Self {
+        PaddingPolicy::Buckets(DEFAULT_BUCKETS.to_vec())
+    }
+}
+
+impl PaddingPolicy {
+    /// Berechnet die Ziellänge für eine Payload der Länge `len`.
+    pub fn padded_len(&self, len: usize) -> usize {
+        match self {
+            PaddingPolicy::None => len,
+            PaddingPolicy::Block(block) => {
+                let block = (*block).max(1);
+                len.div_ceil(block).max(1) * block
+            }
+            PaddingPolicy::Buckets(buckets) => {
+                if let Some(b) = buckets.iter().copied().filter(|b| *b >= len).min() {
+                    return b;
+                }
+                match buckets.iter().copied().max() {
+                    Some(largest) if largest > 0 => len.div_ceil(largest) * largest,
+                    _ => len,
+                }
+            }
+        }
+    }
+    /// Füllt `bytes` mit Nullbytes auf die Ziellänge auf.
+    pub fn pad(&self, bytes: &mut Vec<u8>) {
+        let target = self.padded_len(bytes.len());
+        bytes.resize(target, 0);
+    }
+}
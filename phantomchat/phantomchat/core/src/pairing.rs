@@ -0,0 +1,377 @@
+//! Signierte Pairing‑Bundles.
+//!
+//! Beim persönlichen Onboarding tauschen zwei Geräte ihre öffentlichen
+//! Schlüssel über einen QR‑Code aus.  Das [`PairingBundle`] enthält
+//! Identity‑, View‑ und Spend‑Public‑Key sowie optionale Relay‑Hinweise
+//! und ist mit dem Identity‑Key signiert, so dass View‑ und Spend‑Key nicht
+//! unbemerkt ausgetauscht werden können.
+//!
+//! Binärformat (alle Längen in Byte):
+//!
+//! ```text
+//! version(1) | identity_pub(32) | view_pub(32) | spend_pub(32)
+//! | relay_count(1) | { len(1) | url(len) }* | signature(64)
+//! ```
+//!
+//! Version 2 trägt zusätzlich den öffentlichen ML‑KEM‑768‑Schlüssel für
+//! den post‑quanten‑hybriden Schlüsselaustausch (siehe Feature `pqc`):
+//!
+//! ```text
+//! version(1) | identity_pub(32) | view_pub(32) | spend_pub(32)
+//! | kem_len(2) | kem_pub(kem_len) | relay_count(1) | … | signature(64)
+//! ```
+//!
+//! Version 3 gibt statt der Hauptadresse eine Subadresse weiter (siehe
+//! [`crate::subaddress`]): `spend_pub` ist deren Schlüssel `C_i`, davor
+//! steht der Basispunkt `D_i`; `kem_len` ist 0, wenn kein KEM‑Schlüssel
+//! folgt:
+//!
+//! ```text
+//! version(1) | identity_pub(32) | view_pub(32) | spend_pub(32) | base(32)
+//! | kem_len(2) | kem_pub(kem_len) | relay_count(1) | … | signature(64)
+//! ```
+//!
+//! Es wird jeweils die kleinste passende Version geschrieben, so dass
+//! ältere Clients Bundles ohne KEM‑Schlüssel und Subadresse lesen können.
+//!
+//! Die Signatur deckt alle vorangehenden Bytes ab (mit Domänentrenner
+//! `pc.pairing.v1`).  Die Textform ist `PHANTOMCHAT:1:<HEX>`, wobei
+//! `<HEX>` das Binärformat plus eine 4‑Byte‑Prüfsumme (SHA‑256‑Präfix) in
+//! Großbuchstaben kodiert.  Großbuchstaben‑Hex liegt vollständig im
+//! alphanumerischen QR‑Modus und ergibt damit kaum größere Codes als
+//! Base64 im Byte‑Modus; Tippfehler beim manuellen Abtippen fängt die
+//! Prüfsumme ab.
+
+use crate::keys::{verify_identity_signature, IdentityKey};
+use crate::subaddress::Subaddress;
+use crate::util::{from_hex, sha256};
+use x25519_dalek::PublicKey;
+
+/// Formatversion ohne KEM‑Schlüssel.
+pub const PAIRING_VERSION: u8 = 1;
+/// Formatversion mit KEM‑Schlüssel.
+pub const PAIRING_VERSION_PQ: u8 = 2;
+/// Formatversion mit Subadresse.
+pub const PAIRING_VERSION_SUBADDRESS: u8 = 3;
+/// Präfix der Textform.
+pub const PAIRING_PREFIX: &str = "PHANTOMCHAT:1:";
+/// Maximale Anzahl Relay‑Hinweise.
+pub const MAX_RELAYS: usize = 8;
+
+const SIGN_DOMAIN: &[u8] = b"pc.pairing.v1";
+const CHECKSUM_LEN: usize = 4;
+
+/// Fehler beim Lesen eines Pairing‑Bundles.
+#[derive(Debug, thiserror::Error)]
+pub enum PairingError {
+    #[error("kein PhantomChat‑Pairing‑Code")]
+    NotAPairingCode,
+    #[error("ungültige Kodierung")]
+    Encoding,
+    #[error("Prüfsumme stimmt nicht (Tippfehler?)")]
+    Checksum,
+    #[error("nicht unterstützte Version {0}")]
+    Version(u8),
+    #[error("Bundle verkürzt oder fehlerhaft")]
+    Malformed,
+    #[error("Signatur ungültig")]
+    BadSignature,
+    #[error("zu viele oder zu lange Relay‑Hinweise")]
+    TooManyRelays,
+}
+
+/// Öffentliche Pairing‑Daten einer Identität.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PairingBundle {
+    pub identity_pub: [u8; 32],
+    pub view_pub: [u8; 32],
+    pub spend_pub: [u8; 32],
+    /// Basispunkt, wenn `spend_pub` zu einer Subadresse gehört.
+    pub base: Option<[u8; 32]>,
+    /// Öffentlicher ML‑KEM‑768‑Schlüssel, falls die Identität den
+    /// hybriden Schlüsselaustausch unterstützt.
+    pub kem_pub: Option<Vec<u8>>,
+    /// Relays, auf denen die Identität erreichbar ist.
+    pub relays: Vec<String>,
+    pub signature: [u8; 64],
+}
+
+impl PairingBundle {
+    /// Erstellt und signiert ein Bundle.
+    pub fn new(
+        identity: &IdentityKey,
+        view_pub: &PublicKey,
+        spend_pub: &PublicKey,
+        relays: Vec<String>,
+    ) -> Result<Self, PairingError> {
+        if relays.len() > MAX_RELAYS || relays.iter().any(|r| r.len() > u8::MAX as usize) {
+            return Err(PairingError::TooManyRelays);
+        }
+        let mut bundle = Self {
+            identity_pub: identity.public,
+            view_pub: *view_pub.as_bytes(),
+            spend_pub: *spend_pub.as_bytes(),
+            base: None,
+            kem_pub: None,
+            relays,
+            signature: [0u8; 64],
+        };
+        bundle.signature = identity.sign(&bundle.signed_bytes());
+        Ok(bundle)
+    }
+
+    /// Ergänzt den öffentlichen KEM‑Schlüssel und signiert neu.
+    pub fn with_kem_pub(mut self, identity: &IdentityKey, kem_pub: Vec<u8>) -> Result<Self, PairingError> {
+        if kem_pub.len() > u16::MAX as usize {
+            return Err(PairingError::Malformed);
+        }
+        self.kem_pub = Some(kem_pub);
+        self.signature = identity.sign(&self.signed_bytes());
+        Ok(self)
+    }
+
+    /// Gibt statt der Hauptadresse die Subadresse `address` weiter und
+    /// signiert neu.
+    pub fn with_subaddress(mut self, identity: &IdentityKey, address: &Subaddress) -> Self {
+        self.spend_pub = address.spend_pub;
+        self.base = (!address.is_primary()).then_some(address.base);
+        self.signature = identity.sign(&self.signed_bytes());
+        self
+    }
+
+    /// Empfangsadresse, an die das Gegenüber sendet.
+    pub fn address(&self) -> Subaddress {
+        match self.base {
+            Some(base) => Subaddress { base, spend_pub: self.spend_pub },
+            None => Subaddress::primary(&PublicKey::from(self.spend_pub)),
+        }
+    }
+
+    fn version(&self) -> u8 {
+        match (&self.base, &self.kem_pub) {
+            (Some(_), _) => PAIRING_VERSION_SUBADDRESS,
+            (None, Some(_)) => PAIRING_VERSION_PQ,
+            (None, None) => PAIRING_VERSION,
+        }
+    }
+
+    fn body(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(1 + 128 + 1 + 64);
+        let version = self.version();
+        out.push(version);
+        out.extend_from_slice(&self.identity_pub);
+        out.extend_from_slice(&self.view_pub);
+        out.extend_from_slice(&self.spend_pub);
+        if let Some(base) = &self.base {
+            out.extend_from_slice(base);
+        }
+        if version >= PAIRING_VERSION_PQ {
+            let kem_pub = self.kem_pub.as_deref().unwrap_or_default();
+            out.extend_from_slice(&(kem_pub.len() as u16).to_be_bytes());
+            out.extend_from_slice(kem_pub);
+        }
+        out.push(self.relays.len() as u8);
+        for relay in &self.relays {
+            out.push(relay.len() as u8);
+            out.extend_from_slice(relay.as_bytes());
+        }
+        out
+    }
+
+    fn signed_bytes(&self) -> Vec<u8> {
+        let mut msg = SIGN_DOMAIN.to_vec();
+        msg.extend_from_slice(&self.body());
+        msg
+    }
+
+    /// Prüft die Signatur mit dem enthaltenen Identity‑Key.
+    pub fn verify(&self) -> bool {
+        verify_identity_signature(&self.identity_pub, &self.signed_bytes(), &self.signature)
+    }
+
+    /// Serialisiert das Bundle im Binärformat.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.body();
+        out.extend_from_slice(&self.signature);
+        out
+    }
+
+    /// Liest ein Bundle aus dem Binärformat und prüft die Signatur.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PairingError> {
+        let mut pos = 0;
+        let version = take(bytes, &mut pos, 1)?[0];
+        if !(PAIRING_VERSION..=PAIRING_VERSION_SUBADDRESS).contains(&version) {
+            return Err(PairingError::Version(version));
+        }
+        let identity_pub: [u8; 32] = take(bytes, &mut pos, 32)?.try_into().unwrap();
+        let view_pub: [u8; 32] = take(bytes, &mut pos, 32)?.try_into().unwrap();
+        let spend_pub: [u8; 32] = take(bytes, &mut pos, 32)?.try_into().unwrap();
+        let base = if version == PAIRING_VERSION_SUBADDRESS {
+            Some(take(bytes, &mut pos, 32)?.try_into().unwrap())
+        } else {
+            None
+        };
+        let kem_pub = if version >= PAIRING_VERSION_PQ {
+            let len = u16::from_be_bytes(take(bytes, &mut pos, 2)?.try_into().unwrap()) as usize;
+            Some(take(bytes, &mut pos, len)?.to_vec()).filter(|kem_pub| !kem_pub.is_empty())
+        } else {
+            None
+        };
+        let count = take(bytes, &mut pos, 1)?[0] as usize;
+        if count > MAX_RELAYS {
+            return Err(PairingError::TooManyRelays);
+        }
+        let mut relays = Vec::with_capacity(count);
+        for _ in 0..count {
+            let len = take(bytes, &mut pos, 1)?[0] as usize;
+            let url = std::str::from_utf8(take(bytes, &mut pos, len)?).map_err(|_| PairingError::Malformed)?;
+            relays.push(url.to_owned());
+        }
+        let signature: [u8; 64] = take(bytes, &mut pos, 64)?.try_into().unwrap();
+        if pos != bytes.len() {
+            return Err(PairingError::Malformed);
+        }
+        let bundle = Self { identity_pub, view_pub, spend_pub, base, kem_pub, relays, signature };
+        if !bundle.verify() {
+            return Err(PairingError::BadSignature);
+        }
+        Ok(bundle)
+    }
+
+    /// Textform für QR‑Codes und zum Kopieren.
+    pub fn to_text(&self) -> String {
+        encode_text(PAIRING_PREFIX, &self.to_bytes())
+    }
+
+    /// Liest die Textform.  Leerzeichen und Zeilenumbrüche werden
+    /// ignoriert, die Groß‑/Kleinschreibung ebenfalls.
+    pub fn from_text(text: &str) -> Result<Self, PairingError> {
+        Self::from_bytes(&decode_text(PAIRING_PREFIX, text)?)
+    }
+}
+
+/// Kodiert `bytes` samt Prüfsumme als `<prefix><HEX>`.  Auch Link‑Codes
+/// ([`crate::device::LinkRequest`]) nutzen diese Form.
+pub(crate) fn encode_text(prefix: &str, bytes: &[u8]) -> String {
+    let checksum = sha256(bytes);
+    let hex: String = bytes.iter().chain(&checksum[..CHECKSUM_LEN]).map(|b| format!("{:02X}", b)).collect();
+    format!("{prefix}{hex}")
+}
+
+/// Gegenstück zu [`encode_text`]; prüft Präfix und Prüfsumme.
+pub(crate) fn decode_text(prefix: &str, text: &str) -> Result<Vec<u8>, PairingError> {
+    let compact: String = text.chars().filter(|c| !c.is_whitespace()).collect();
+    let upper = compact.to_ascii_uppercase();
+    let hex = upper.strip_prefix(prefix).ok_or(PairingError::NotAPairingCode)?;
+    let mut bytes = from_hex(hex).ok_or(PairingError::Encoding)?;
+    if bytes.len() < CHECKSUM_LEN {
+        return Err(PairingError::Malformed);
+    }
+    let checksum = bytes.split_off(bytes.len() - CHECKSUM_LEN);
+    if sha256(&bytes)[..CHECKSUM_LEN] != *checksum {
+        return Err(PairingError::Checksum);
+    }
+    Ok(bytes)
+}
+
+/// Liest `n` Bytes ab `pos` und rückt `pos` vor.
+fn take<'a>(bytes: &'a [u8], pos: &mut usize, n: usize) -> Result<&'a [u8], PairingError> {
+    let slice = bytes.get(*pos..*pos + n).ok_or(PairingError::Malformed)?;
+    *pos += n;
+    Ok(slice)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::{SpendKey, ViewKey};
+
+    fn bundle(identity: &IdentityKey) -> (ViewKey, SpendKey, PairingBundle) {
+        let (view, spend) = (ViewKey::generate(), SpendKey::generate());
+        let relays = vec!["wss://relay.example".to_owned(), "wss://backup.example".to_owned()];
+        let bundle = PairingBundle::new(identity, &view.public, &spend.public, relays).unwrap();
+        (view, spend, bundle)
+    }
+
+    #[test]
+    fn text_round_trip_fits_qr_alphanumeric_mode() {
+        let (_, _, bundle) = bundle(&IdentityKey::generate());
+        let text = bundle.to_text();
+        assert!(text.starts_with(PAIRING_PREFIX));
+        assert!(text.chars().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == ':'));
+        assert_eq!(PairingBundle::from_text(&text).unwrap(), bundle);
+
+        // Abgetippt: klein geschrieben und in Blöcke umbrochen.
+        let lower = text.to_ascii_lowercase();
+        let (prefix, hex) = lower.split_at(PAIRING_PREFIX.len());
+        let blocks: Vec<&str> = hex.as_bytes().chunks(8).map(|b| std::str::from_utf8(b).unwrap()).collect();
+        let typed = format!("{prefix}\n{}\n", blocks.join(" "));
+        assert_eq!(PairingBundle::from_text(&typed).unwrap(), bundle);
+    }
+
+    #[test]
+    fn single_character_typo_fails_the_checksum() {
+        let (_, _, bundle) = bundle(&IdentityKey::generate());
+        let text = bundle.to_text();
+        for pos in [PAIRING_PREFIX.len(), PAIRING_PREFIX.len() + 70, text.len() - 1] {
+            let mut typo = text.clone().into_bytes();
+            typo[pos] = if typo[pos] == b'A' { b'B' } else { b'A' };
+            let typo = String::from_utf8(typo).unwrap();
+            assert!(matches!(PairingBundle::from_text(&typo), Err(PairingError::Checksum)), "pos {pos}");
+        }
+        assert!(matches!(PairingBundle::from_text("PHANTOMCHAT:1:XYZ"), Err(PairingError::Encoding)));
+        assert!(matches!(PairingBundle::from_text("OTHERAPP:1:00"), Err(PairingError::NotAPairingCode)));
+    }
+
+    #[test]
+    fn every_supported_version_parses() {
+        let identity = IdentityKey::generate();
+        let (view, spend, v1) = bundle(&identity);
+        let v2 = v1.clone().with_kem_pub(&identity, vec![7u8; 1184]).unwrap();
+        let address = Subaddress::derive(&view, &spend.public, 3);
+        let v3 = v1.clone().with_subaddress(&identity, &address);
+        let v3_pq = v2.clone().with_subaddress(&identity, &address);
+
+        for (bundle, version) in [
+            (&v1, PAIRING_VERSION),
+            (&v2, PAIRING_VERSION_PQ),
+            (&v3, PAIRING_VERSION_SUBADDRESS),
+            (&v3_pq, PAIRING_VERSION_SUBADDRESS),
+        ] {
+            let bytes = bundle.to_bytes();
+            assert_eq!(bytes[0], version);
+            assert_eq!(&PairingBundle::from_bytes(&bytes).unwrap(), bundle);
+        }
+        assert_eq!(PairingBundle::from_bytes(&v2.to_bytes()).unwrap().kem_pub, Some(vec![7u8; 1184]));
+        assert_eq!(PairingBundle::from_bytes(&v3.to_bytes()).unwrap().address(), address);
+        assert!(v1.address().is_primary());
+
+        let mut future = v1.to_bytes();
+        future[0] = 9;
+        assert!(matches!(PairingBundle::from_bytes(&future), Err(PairingError::Version(9))));
+        let truncated = &v1.to_bytes()[..100];
+        assert!(matches!(PairingBundle::from_bytes(truncated), Err(PairingError::Malformed)));
+    }
+
+    #[test]
+    fn bad_signature_is_rejected() {
+        let (_, _, bundle) = bundle(&IdentityKey::generate());
+        let bytes = bundle.to_bytes();
+
+        let mut forged = bytes.clone();
+        *forged.last_mut().unwrap() ^= 1;
+        assert!(matches!(PairingBundle::from_bytes(&forged), Err(PairingError::BadSignature)));
+
+        // Ausgetauschter Spend‑Key bei gültiger Prüfsumme der Textform.
+        let mut swapped = bytes;
+        swapped[1 + 64] ^= 1;
+        let text = encode_text(PAIRING_PREFIX, &swapped);
+        assert!(matches!(PairingBundle::from_text(&text), Err(PairingError::BadSignature)));
+
+        // Gleiche Schlüssel, aber von einer fremden Identität signiert.
+        let mut foreign = bundle.clone();
+        foreign.signature = IdentityKey::generate().sign(&bundle.signed_bytes());
+        assert!(!foreign.verify());
+        assert!(matches!(PairingBundle::from_bytes(&foreign.to_bytes()), Err(PairingError::BadSignature)));
+    }
+}
@@ -0,0 +1,129 @@
+//! Post‑quanten‑hybrider Schlüsselaustausch (Feature `pqc`).
+//!
+//! ML‑KEM‑768 (FIPS 203) wird mit X25519 kombiniert: Beide Geheimnisse
+//! fließen gemeinsam in die HKDF, so dass ein Angreifer beide Verfahren
+//! brechen muss.  Verwendet wird das an zwei Stellen:
+//!
+//! * pro Envelope, siehe [`crate::Envelope::new_hybrid`] (Version 2 mit
+//!   KEM‑Ciphertext im Envelope), und
+//! * beim Aufbau einer Session, siehe [`hybrid_root`].
+//!
+//! Den öffentlichen KEM‑Schlüssel verteilt das Pairing‑Bundle.  Peers ohne
+//! KEM‑Schlüssel erhalten weiterhin klassische Envelopes (Version 1).
+
+use crate::secret::SecretBytes;
+use hkdf::Hkdf;
+use ml_kem::kem::{Decapsulate, Encapsulate};
+use ml_kem::{Ciphertext, EncodedSizeUser, KemCore, MlKem768};
+use rand::rngs::OsRng;
+use sha2::Sha256;
+use zeroize::{Zeroize, Zeroizing};
+
+type DecapsulationKey = <MlKem768 as KemCore>::DecapsulationKey;
+type EncapsulationKey = <MlKem768 as KemCore>::EncapsulationKey;
+
+/// Länge eines öffentlichen ML‑KEM‑768‑Schlüssels.
+pub const PUBLIC_LEN: usize = 1184;
+/// Länge eines ML‑KEM‑768‑Ciphertexts.
+pub const CIPHERTEXT_LEN: usize = 1088;
+/// Länge eines privaten ML‑KEM‑768‑Schlüssels.
+pub const SECRET_LEN: usize = 2400;
+
+/// ML‑KEM‑768‑Schlüsselpaar.  Der private Teil wird beim Drop
+/// überschrieben; `ml-kem` 0.2 tut das nicht selbst, daher übernimmt es
+/// der `Drop` von `KemKey`.
+pub struct KemKey {
+    secret: DecapsulationKey,
+    /// Öffentlicher Teil (1184 Byte)
+    pub public: Vec<u8>,
+}
+
+impl std::fmt::Debug for KemKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("KemKey").field("public", &crate::util::to_hex(&self.public[..8])).finish_non_exhaustive()
+    }
+}
+
+impl KemKey {
+    /// Erzeugt ein neues Schlüsselpaar.
+    pub fn generate() -> Self {
+        let (secret, public) = MlKem768::generate(&mut OsRng);
+        Self { secret, public: public.as_bytes().to_vec() }
+    }
+    /// Serialisiert den privaten Schlüssel.
+    pub fn to_bytes(&self) -> Zeroizing<Vec<u8>> {
+        let mut encoded = self.secret.as_bytes();
+        let bytes = Zeroizing::new(encoded.to_vec());
+        encoded[..].zeroize();
+        bytes
+    }
+    /// Liest einen privaten Schlüssel; der öffentliche Teil wird daraus
+    /// berechnet.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let mut encoded = bytes.try_into().ok()?;
+        let secret = DecapsulationKey::from_bytes(&encoded);
+        encoded[..].zeroize();
+        let public = secret.encapsulation_key().as_bytes().to_vec();
+        Some(Self { secret, public })
+    }
+    /// Gewinnt das gemeinsame Geheimnis aus einem KEM‑Ciphertext.
+    pub fn decapsulate(&self, ciphertext: &[u8]) -> Option<Zeroizing<[u8; 32]>> {
+        let ct: Ciphertext<MlKem768> = ciphertext.try_into().ok()?;
+        let shared = self.secret.decapsulate(&ct).ok()?;
+        Some(Zeroizing::new(shared.into()))
+    }
+}
+
+impl Drop for KemKey {
+    fn drop(&mut self) {
+        let blank = DecapsulationKey::from_bytes(&Default::default());
+        // SAFETY: `self.secret` ist gültig und ausgerichtet.  Der alte Wert
+        // wird ohne Drop überschrieben; er besteht nur aus Arrays.
+        unsafe {
+            std::ptr::write_volatile(&mut self.secret, blank);
+        }
+    }
+}
+
+/// Kapselt ein frisches Geheimnis für `public`.  Liefert den
+/// KEM‑Ciphertext und das gemeinsame Geheimnis; `None` bei ungültigem
+/// Schlüssel.
+pub fn encapsulate(public: &[u8]) -> Option<(Vec<u8>, Zeroizing<[u8; 32]>)> {
+    if public.len() != PUBLIC_LEN {
+        return None;
+    }
+    let key = EncapsulationKey::from_bytes(&public.try_into().ok()?);
+    let (ct, shared) = key.encapsulate(&mut OsRng).ok()?;
+    Some((ct.to_vec(), Zeroizing::new(shared.into())))
+}
+
+/// Verbindet einen klassisch abgeleiteten Session‑Root mit einem
+/// KEM‑Geheimnis zum hybriden Root‑Key.
+pub fn hybrid_root(classic_root: &[u8; 32], kem_secret: &[u8; 32]) -> SecretBytes<32> {
+    let hk = Hkdf::<Sha256>::new(Some(classic_root), kem_secret);
+    let mut root = Zeroizing::new([0u8; 32]);
+    hk.expand(b"pc.session.pq.v1", root.as_mut()).expect("HKDF expand");
+    SecretBytes::new(*root)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hybrid_root_needs_the_kem_secret() {
+        let key = KemKey::generate();
+        let (ct, secret) = encapsulate(&key.public).unwrap();
+        let received = KemKey::from_bytes(&key.to_bytes()).unwrap().decapsulate(&ct).unwrap();
+        let root = hybrid_root(&[1u8; 32], &secret);
+        assert_eq!(root.expose(), hybrid_root(&[1u8; 32], &received).expose());
+        assert_ne!(root.expose(), hybrid_root(&[1u8; 32], &[0u8; 32]).expose());
+        assert_ne!(root.expose(), &[1u8; 32]);
+    }
+
+    #[test]
+    fn invalid_public_keys_are_rejected() {
+        assert!(encapsulate(&[0u8; PUBLIC_LEN - 1]).is_none());
+        assert!(KemKey::from_bytes(&[0u8; 10]).is_none());
+    }
+}
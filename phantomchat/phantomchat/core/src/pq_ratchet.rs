@@ -0,0 +1,497 @@
+//! Sparse post‑quanten Ratchet (Feature `pqc`).
+//!
+//! Der hybride Handshake (siehe [`crate::pq`]) schützt nur den Beginn
+//! einer Session.  Damit sich eine langlebige Session auch gegenüber einem
+//! Angreifer mit Quantenrechner von einer Kompromittierung erholt, mischen
+//! beide Seiten in Abständen ein frisches ML‑KEM‑Geheimnis in den
+//! Root‑Key (ähnlich Signals SPQR bzw. Apples PQ3).
+//!
+//! Ablauf einer Epoche `e`:
+//!
+//! 1. Eine Seite hält einen frischen KEM‑Schlüssel für `e` und verteilt den
+//!    öffentlichen Teil in Fragmenten über die Ratchet‑Header ihrer
+//!    Nachrichten.
+//! 2. Hat die Gegenseite den Schlüssel vollständig und ist eine Erneuerung
+//!    fällig (alle [`PqConfig::interval_messages`] Nachrichten bzw. nach
+//!    [`PqConfig::interval_ms`]), kapselt sie ein Geheimnis und verschickt
+//!    den Ciphertext fragmentiert.  Zugleich erzeugt sie den KEM‑Schlüssel
+//!    für `e + 1` und verteilt ihn.
+//! 3. Die erste Seite entkapselt das Geheimnis, sobald der Ciphertext
+//!    vollständig ist, und die Rollen wechseln.
+//!
+//! Eingemischt wird das Geheimnis erst beim nächsten DH‑Schritt der
+//! entkapselnden Seite, damit beide Seiten es an derselben Stelle der
+//! Root‑Kette verwenden (siehe [`crate::ratchet`]).
+//!
+//! Pro Nachricht reist höchstens ein Fragment mit ([`FRAGMENT_LEN`] Byte),
+//! der Overhead bleibt damit klein.  Fragmente werden reihum wiederholt,
+//! bis die Gegenseite die nächste Epoche erreicht hat; verlorene oder
+//! umsortierte Nachrichten verzögern die Erneuerung also nur.
+
+use crate::pq::KemKey;
+use hkdf::Hkdf;
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+/// Nutzdaten pro Fragment.
+pub const FRAGMENT_LEN: usize = 256;
+
+/// Wann ein frisches KEM‑Geheimnis eingemischt wird.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PqConfig {
+    /// Nach so vielen gesendeten Nachrichten seit der letzten Erneuerung.
+    pub interval_messages: u32,
+    /// Bzw. nach dieser Zeit in Millisekunden.
+    pub interval_ms: u64,
+}
+
+impl Default for PqConfig {
+    fn default() -> Self {
+        Self { interval_messages: 50, interval_ms: 10 * 60 * 1000 }
+    }
+}
+
+/// Art eines Fragments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FragmentKind {
+    /// Öffentlicher KEM‑Schlüssel.
+    Public = 1,
+    /// KEM‑Ciphertext.
+    Ciphertext = 2,
+}
+
+/// Ein Fragment im Ratchet‑Header.
+///
+/// ```text
+/// epoch(4, LE) | kind(1) | index(1) | total(1) | data
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PqFragment {
+    pub epoch: u32,
+    pub kind: FragmentKind,
+    pub index: u8,
+    pub total: u8,
+    pub data: Vec<u8>,
+}
+
+impl PqFragment {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(7 + self.data.len());
+        out.extend_from_slice(&self.epoch.to_le_bytes());
+        out.push(self.kind as u8);
+        out.push(self.index);
+        out.push(self.total);
+        out.extend_from_slice(&self.data);
+        out
+    }
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        if data.len() < 7 {
+            return None;
+        }
+        let kind = match data[4] {
+            1 => FragmentKind::Public,
+            2 => FragmentKind::Ciphertext,
+            _ => return None,
+        };
+        let (index, total) = (data[5], data[6]);
+        if total == 0 || index >= total || data.len() - 7 > FRAGMENT_LEN {
+            return None;
+        }
+        Some(Self { epoch: u32::from_le_bytes(data[0..4].try_into().ok()?), kind, index, total, data: data[7..].to_vec() })
+    }
+}
+
+/// Teilt `bytes` in Fragmente auf.
+fn fragments(epoch: u32, kind: FragmentKind, bytes: &[u8]) -> Vec<PqFragment> {
+    let chunks: Vec<&[u8]> = bytes.chunks(FRAGMENT_LEN).collect();
+    let total = chunks.len() as u8;
+    chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| PqFragment { epoch, kind, index: i as u8, total, data: chunk.to_vec() })
+        .collect()
+}
+
+/// Sammelt die Fragmente eines Schlüssels bzw. Ciphertexts.
+#[derive(Debug, Clone)]
+struct Reassembly {
+    epoch: u32,
+    parts: Vec<Option<Vec<u8>>>,
+}
+
+impl Reassembly {
+    /// Nimmt ein Fragment auf und liefert die vollständigen Daten, sobald
+    /// alle Teile da sind.  Fragmente einer neueren Epoche verwerfen
+    /// einen unvollständigen Stand.
+    fn add(slot: &mut Option<Self>, fragment: &PqFragment) -> Option<Vec<u8>> {
+        let current = slot.as_ref().map(|r| (r.epoch, r.parts.len()));
+        if current != Some((fragment.epoch, fragment.total as usize)) {
+            if matches!(current, Some((epoch, _)) if epoch > fragment.epoch) {
+                return None;
+            }
+            *slot = Some(Self { epoch: fragment.epoch, parts: vec![None; fragment.total as usize] });
+        }
+        let r = slot.as_mut()?;
+        r.parts[fragment.index as usize] = Some(fragment.data.clone());
+        if r.parts.iter().any(Option::is_none) {
+            return None;
+        }
+        let data = r.parts.iter().flatten().flatten().copied().collect();
+        *slot = None;
+        Some(data)
+    }
+}
+
+/// Zustand des Sparse‑PQ‑Ratchets einer Session.
+#[derive(Debug)]
+pub struct PqRatchet {
+    config: PqConfig,
+    /// Anzahl der eingemischten KEM‑Geheimnisse.
+    epoch: u32,
+    /// Eigener KEM‑Schlüssel, dessen öffentlicher Teil verteilt wird.
+    own: Option<(u32, KemKey)>,
+    /// Vollständig empfangener KEM‑Schlüssel der Gegenseite.
+    peer_public: Option<(u32, Vec<u8>)>,
+    /// Verschickter Ciphertext, bis die Gegenseite ihn verarbeitet hat.
+    sending_ct: Option<(u32, Vec<u8>)>,
+    incoming_public: Option<Reassembly>,
+    incoming_ct: Option<Reassembly>,
+    /// Reihum‑Position beim Versand der Fragmente.
+    cursor: u32,
+    sent_since_mix: u32,
+    last_mix_ms: u64,
+}
+
+impl PqRatchet {
+    /// Startet den PQ‑Ratchet.  Der Initiator der Session verteilt den
+    /// ersten KEM‑Schlüssel, die Gegenseite kapselt darauf.
+    pub fn new(initiator: bool, config: PqConfig) -> Self {
+        Self {
+            config,
+            epoch: 0,
+            own: initiator.then(|| (0, KemKey::generate())),
+            peer_public: None,
+            sending_ct: None,
+            incoming_public: None,
+            incoming_ct: None,
+            cursor: 0,
+            sent_since_mix: 0,
+            last_mix_ms: now_ms(),
+        }
+    }
+
+    /// Anzahl der bisher eingemischten KEM‑Geheimnisse.
+    pub fn epoch(&self) -> u32 {
+        self.epoch
+    }
+
+    /// Vor dem Senden einer Nachricht: kapselt bei Fälligkeit ein neues
+    /// Geheimnis (Rückgabe, zum Einmischen in den Root‑Key) und wählt das
+    /// Fragment für den Header.
+    pub fn on_send(&mut self) -> (Option<Zeroizing<[u8; 32]>>, Option<PqFragment>) {
+        self.sent_since_mix = self.sent_since_mix.saturating_add(1);
+        let mut mixed = None;
+        if self.sending_ct.is_none() && self.is_due() {
+            if let Some((epoch, public)) = self.peer_public.take() {
+                if let Some((ct, secret)) = crate::pq::encapsulate(&public) {
+                    self.sending_ct = Some((epoch, ct));
+                    self.own = Some((epoch + 1, KemKey::generate()));
+                    self.advance(epoch);
+                    mixed = Some(secret);
+                }
+            }
+        }
+        (mixed, self.next_fragment())
+    }
+
+    /// Verarbeitet ein empfangenes Fragment.  Ist ein Ciphertext
+    /// vollständig, wird das Geheimnis zum Einmischen zurückgegeben.
+    pub fn on_receive(&mut self, fragment: &PqFragment) -> Option<Zeroizing<[u8; 32]>> {
+        match fragment.kind {
+            FragmentKind::Public => {
+                // Schlüssel für eine bereits abgeschlossene Epoche oder
+                // während eines laufenden eigenen Ciphertexts ignorieren
+                if fragment.epoch < self.epoch || self.peer_public.as_ref().is_some_and(|(e, _)| *e == fragment.epoch) {
+                    return None;
+                }
+                let public = Reassembly::add(&mut self.incoming_public, fragment)?;
+                if public.len() == crate::pq::PUBLIC_LEN {
+                    self.peer_public = Some((fragment.epoch, public));
+                }
+                None
+            }
+            FragmentKind::Ciphertext => {
+                let (epoch, key) = self.own.as_ref()?;
+                if fragment.epoch != *epoch {
+                    return None;
+                }
+                let ct = Reassembly::add(&mut self.incoming_ct, fragment)?;
+                let secret = key.decapsulate(&ct)?;
+                let epoch = *epoch;
+                // Der eigene Schlüssel ist verbraucht, und die Gegenseite
+                // hat unseren letzten Ciphertext verarbeitet.
+                self.own = None;
+                self.sending_ct = None;
+                self.advance(epoch);
+                Some(secret)
+            }
+        }
+    }
+
+    fn is_due(&self) -> bool {
+        self.epoch == 0
+            || self.sent_since_mix >= self.config.interval_messages
+            || now_ms().saturating_sub(self.last_mix_ms) >= self.config.interval_ms
+    }
+
+    fn advance(&mut self, epoch: u32) {
+        self.epoch = epoch + 1;
+        self.sent_since_mix = 0;
+        self.last_mix_ms = now_ms();
+    }
+
+    /// Nächstes Fragment reihum aus Ciphertext und eigenem Schlüssel.
+    fn next_fragment(&mut self) -> Option<PqFragment> {
+        let mut pending = Vec::new();
+        if let Some((epoch, ct)) = &self.sending_ct {
+            pending.extend(fragments(*epoch, FragmentKind::Ciphertext, ct));
+        }
+        if let Some((epoch, key)) = &self.own {
+            pending.extend(fragments(*epoch, FragmentKind::Public, &key.public));
+        }
+        if pending.is_empty() {
+            return None;
+        }
+        let fragment = pending.swap_remove(self.cursor as usize % pending.len());
+        self.cursor = self.cursor.wrapping_add(1);
+        Some(fragment)
+    }
+
+    /// Serialisiert den Zustand (enthält privates Schlüsselmaterial).
+    pub fn to_bytes(&self) -> Zeroizing<Vec<u8>> {
+        let mut out = Zeroizing::new(Vec::new());
+        out.extend_from_slice(&self.config.interval_messages.to_le_bytes());
+        out.extend_from_slice(&self.config.interval_ms.to_le_bytes());
+        out.extend_from_slice(&self.epoch.to_le_bytes());
+        out.extend_from_slice(&self.cursor.to_le_bytes());
+        out.extend_from_slice(&self.sent_since_mix.to_le_bytes());
+        out.extend_from_slice(&self.last_mix_ms.to_le_bytes());
+        let own = self.own.as_ref().map(|(e, key)| (*e, key.to_bytes()));
+        put(&mut out, own.as_ref().map(|(e, bytes)| (*e, bytes.as_slice())));
+        put(&mut out, self.peer_public.as_ref().map(|(e, bytes)| (*e, bytes.as_slice())));
+        put(&mut out, self.sending_ct.as_ref().map(|(e, bytes)| (*e, bytes.as_slice())));
+        for slot in [&self.incoming_public, &self.incoming_ct] {
+            match slot {
+                None => out.push(0),
+                Some(r) => {
+                    out.push(1);
+                    out.extend_from_slice(&r.epoch.to_le_bytes());
+                    out.push(r.parts.len() as u8);
+                    for part in &r.parts {
+                        put(&mut out, part.as_deref().map(|p| (0, p)));
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// Stellt einen mit [`PqRatchet::to_bytes`] gespeicherten Zustand
+    /// wieder her.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let mut r = Reader { data, pos: 0 };
+        let config = PqConfig { interval_messages: r.u32()?, interval_ms: r.u64()? };
+        let epoch = r.u32()?;
+        let cursor = r.u32()?;
+        let sent_since_mix = r.u32()?;
+        let last_mix_ms = r.u64()?;
+        let own = match r.opt()? {
+            Some((e, bytes)) => Some((e, KemKey::from_bytes(&Zeroizing::new(bytes))?)),
+            None => None,
+        };
+        let peer_public = r.opt()?;
+        let sending_ct = r.opt()?;
+        let mut slots = [None, None];
+        for slot in slots.iter_mut() {
+            if r.take(1)?[0] == 1 {
+                let epoch = r.u32()?;
+                let count = r.take(1)?[0] as usize;
+                let mut parts = Vec::with_capacity(count);
+                for _ in 0..count {
+                    parts.push(r.opt()?.map(|(_, p)| p));
+                }
+                *slot = Some(Reassembly { epoch, parts });
+            }
+        }
+        let [incoming_public, incoming_ct] = slots;
+        if r.pos != data.len() {
+            return None;
+        }
+        Some(Self {
+            config,
+            epoch,
+            own,
+            peer_public,
+            sending_ct,
+            incoming_public,
+            incoming_ct,
+            cursor,
+            sent_since_mix,
+            last_mix_ms,
+        })
+    }
+}
+
+/// Mischt ein KEM‑Geheimnis in den Root‑Key.
+pub fn mix_root(root: &[u8; 32], secret: &[u8; 32], epoch: u32) -> Zeroizing<[u8; 32]> {
+    let hk = Hkdf::<Sha256>::new(Some(root), secret);
+    let mut info = b"pc.ratchet.pq.v1".to_vec();
+    info.extend_from_slice(&epoch.to_le_bytes());
+    let mut out = Zeroizing::new([0u8; 32]);
+    hk.expand(&info, out.as_mut()).expect("HKDF expand");
+    out
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+/// Schreibt ein optionales `(epoch, bytes)`‑Paar.
+fn put(out: &mut Vec<u8>, value: Option<(u32, &[u8])>) {
+    match value {
+        None => out.push(0),
+        Some((epoch, bytes)) => {
+            out.push(1);
+            out.extend_from_slice(&epoch.to_le_bytes());
+            out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            out.extend_from_slice(bytes);
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let slice = self.data.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(slice)
+    }
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+    fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().ok()?))
+    }
+    fn opt(&mut self) -> Option<Option<(u32, Vec<u8>)>> {
+        if self.take(1)?[0] == 0 {
+            return Some(None);
+        }
+        let epoch = self.u32()?;
+        let len = self.u32()? as usize;
+        Some(Some((epoch, self.take(len)?.to_vec())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn public_fragments(ratchet: &PqRatchet) -> Vec<PqFragment> {
+        let (epoch, key) = ratchet.own.as_ref().unwrap();
+        fragments(*epoch, FragmentKind::Public, &key.public)
+    }
+
+    #[test]
+    fn fragment_bytes_round_trip_and_reject_garbage() {
+        let fragment = PqFragment { epoch: 7, kind: FragmentKind::Ciphertext, index: 2, total: 5, data: vec![9u8; FRAGMENT_LEN] };
+        assert_eq!(PqFragment::from_bytes(&fragment.to_bytes()).unwrap(), fragment);
+
+        let bad = |f: &dyn Fn(&mut Vec<u8>)| {
+            let mut bytes = fragment.to_bytes();
+            f(&mut bytes);
+            PqFragment::from_bytes(&bytes).is_none()
+        };
+        assert!(bad(&|b| b.truncate(6)));
+        assert!(bad(&|b| b[4] = 3));
+        assert!(bad(&|b| b[5] = 5));
+        assert!(bad(&|b| b[6] = 0));
+        assert!(bad(&|b| b.push(0)));
+    }
+
+    #[test]
+    fn reassembly_waits_for_missing_and_ignores_duplicate_fragments() {
+        let data: Vec<u8> = (0..crate::pq::PUBLIC_LEN).map(|i| i as u8).collect();
+        let parts = fragments(3, FragmentKind::Public, &data);
+        assert_eq!(parts.len(), crate::pq::PUBLIC_LEN.div_ceil(FRAGMENT_LEN));
+
+        let mut slot = None;
+        // Umsortiert, mit Duplikaten; das erste Fragment fehlt noch.
+        for fragment in parts.iter().skip(1).rev().chain(&parts[1..3]) {
+            assert!(Reassembly::add(&mut slot, fragment).is_none());
+        }
+        // Fragmente einer älteren Epoche stören den Stand nicht.
+        let stale = PqFragment { epoch: 2, ..parts[0].clone() };
+        assert!(Reassembly::add(&mut slot, &stale).is_none());
+        assert_eq!(Reassembly::add(&mut slot, &parts[0]).unwrap(), data);
+        assert!(slot.is_none());
+
+        // Eine neuere Epoche verwirft den unvollständigen Stand.
+        let newer = fragments(4, FragmentKind::Public, &data);
+        assert!(Reassembly::add(&mut slot, &parts[0]).is_none());
+        for fragment in &newer[1..] {
+            assert!(Reassembly::add(&mut slot, fragment).is_none());
+        }
+        assert_eq!(slot.as_ref().unwrap().epoch, 4);
+        assert_eq!(Reassembly::add(&mut slot, &newer[0]).unwrap(), data);
+    }
+
+    #[test]
+    fn both_sides_agree_on_the_mixed_secret() {
+        let (mut alice, mut bob) = (PqRatchet::new(true, PqConfig::default()), PqRatchet::new(false, PqConfig::default()));
+        for fragment in public_fragments(&alice) {
+            assert!(bob.on_receive(&fragment).is_none());
+        }
+        let (secret, first) = bob.on_send();
+        let secret = secret.unwrap();
+        assert_eq!(bob.epoch(), 1);
+
+        let mut received = None;
+        let mut fragment = first;
+        for _ in 0..64 {
+            let f = fragment.unwrap();
+            if f.kind == FragmentKind::Ciphertext {
+                received = received.or(alice.on_receive(&f));
+            }
+            fragment = bob.on_send().1;
+        }
+        assert_eq!(*received.unwrap(), *secret);
+        assert_eq!(alice.epoch(), 1);
+    }
+
+    #[test]
+    fn state_survives_serialization_mid_exchange() {
+        let (alice, mut bob) = (PqRatchet::new(true, PqConfig::default()), PqRatchet::new(false, PqConfig::default()));
+        let parts = public_fragments(&alice);
+        let (last, rest) = parts.split_last().unwrap();
+        for fragment in rest {
+            bob.on_receive(fragment);
+        }
+
+        let bytes = bob.to_bytes();
+        let mut restored = PqRatchet::from_bytes(&bytes).unwrap();
+        assert_eq!(*restored.to_bytes(), *bytes);
+        assert_eq!((restored.epoch(), restored.config), (bob.epoch(), bob.config));
+        assert!(PqRatchet::from_bytes(&bytes[..bytes.len() - 1]).is_none());
+
+        // Der wiederhergestellte Zustand setzt die Übertragung fort.
+        restored.on_receive(last);
+        assert!(restored.on_send().0.is_some());
+
+        let alice = PqRatchet::from_bytes(&alice.to_bytes()).unwrap();
+        assert_eq!(public_fragments(&alice), parts);
+    }
+}
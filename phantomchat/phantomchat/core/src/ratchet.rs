@@ -1,108 +1,1029 @@
 //! Double‑Ratchet‑Engine
 //!
-//! Diese Umsetzung ist stark vereinfacht und dient lediglich als
-//! Anschauungsobjekt.  Der vollständige Double‑Ratchet umfaßt viele
-//! Details wie KDF‑Ketten für Root, Senden und Empfangen, das
-//! Speichern übersprungener Nachrichtenschlüssel, Headerverschlüsselung
-//! usw.  Hier implementieren wir lediglich einen rudimentären
-//! Nachrichten‑Zähler und erzeugen pro Nachricht einen neuen
-//! Message‑Key.
-
-use crate::keys::{ViewKey, SpendKey};
-use crate::util::sha256;
+//! Umsetzung des Double‑Ratchet nach Signal mit X25519, HKDF‑SHA256 und
+//! XChaCha20‑Poly1305:
+//!
+//! ```text
+//! root_key' ‖ chain_key = HKDF(salt = root_key, ikm = DH(ratchet_key, peer_ratchet_pub),
+//!                              info = "pc.ratchet.root.v1")
+//! message_key = HMAC‑SHA256(chain_key, 0x01)
+//! chain_key'  = HMAC‑SHA256(chain_key, 0x02)
+//! ```
+//!
+//! Den ersten Root‑Key liefert [`session_root`] aus einem ECDH zwischen
+//! einem Session‑Ephemeral‑Key des Initiators und dem Spend‑Key der
+//! Gegenseite.  Der Initiator sendet zunächst auf einer Startkette, die
+//! ohne DH direkt aus dem Root‑Key entsteht (`ikm` leer, Info
+//! `"pc.ratchet.init.v1"`), und legt jeder Nachricht seinen
+//! Ephemeral‑Key bei; die Gegenseite empfängt darauf.  Ab der ersten
+//! Antwort läuft der DH‑Ratchet: Wer einen neuen Ratchet‑Key des
+//! Gegenübers sieht, leitet daraus die Empfangskette ab und erzeugt vor der
+//! nächsten eigenen Nachricht ein neues Ratchet‑Keypair samt Sendekette.
+//!
+//! Jede Nachricht wird mit ihrem eigenen Message‑Key verschlüsselt; da
+//! jeder Schlüssel nur einmal verwendet wird, ist die Nonce null.
+//! Associated Data sind die Felder des [`RatchetHeader`], die die Ratchet
+//! selbst setzt.  Schlüssel übersprungener Nachrichten werden aufgehoben
+//! (höchstens [`MAX_SKIP`]), so dass verlorene oder umsortierte Nachrichten
+//! lesbar bleiben; jeder Schlüssel öffnet genau eine Nachricht.
+//!
+//! Mit Feature `pqc` kann eine Session zusätzlich einen Sparse‑PQ‑Ratchet
+//! führen ([`crate::pq_ratchet`]), dessen Fragmente im [`RatchetHeader`]
+//! reisen.  Seine Geheimnisse fließen beim nächsten DH‑Schritt in den
+//! Root‑Key und damit in alle folgenden Ketten: Die Seite, die einen
+//! KEM‑Ciphertext entkapselt hat, mischt das Geheimnis vor ihrer nächsten
+//! Sendekette ein und vermerkt die Epoche im Header; die kapselnde Seite
+//! kennt das Geheimnis bereits und mischt es ein, wenn sie diesen Schritt
+//! nachvollzieht.
+
+#[cfg(feature = "pqc")]
+use crate::pq_ratchet::{self, PqFragment, PqRatchet};
+use crate::secret::SecretBytes;
+use chacha20poly1305::aead::{Aead, Payload};
+use chacha20poly1305::{KeyInit, XChaCha20Poly1305, XNonce};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
 use rand_core::OsRng;
+use sha2::Sha256;
 use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroizing;
+
+/// Höchstzahl übersprungener Nachrichtenschlüssel, die eine Session
+/// aufhebt; zugleich die größte Lücke innerhalb einer Kette.
+pub const MAX_SKIP: u32 = 1000;
+
+/// Höchstzahl früherer Sessions, die ein [`SessionRecord`] neben der
+/// aktuellen aufhebt.
+pub const MAX_PREVIOUS_SESSIONS: usize = 4;
+
+const SESSION_INFO: &[u8] = b"pc.session.v3";
+const ROOT_INFO: &[u8] = b"pc.ratchet.root.v1";
+const INIT_INFO: &[u8] = b"pc.ratchet.init.v1";
+/// Höchstzahl aufgehobener PQ‑Geheimnisse, die noch auf ihren DH‑Schritt
+/// warten.
+#[cfg(feature = "pqc")]
+const MAX_PQ_SECRETS: usize = 4;
 
 /// Fehler, der bei der Ratchet‑Verarbeitung auftreten kann.
 #[derive(Debug, thiserror::Error)]
 pub enum RatchetError {
     #[error("Entschlüsselung fehlgeschlagen")]
     DecryptionFailed,
+    #[error("ungültiger Ratchet‑Header")]
+    InvalidHeader,
+    #[error("zu viele übersprungene Nachrichten ({0})")]
+    TooFarAhead(u32),
+}
+
+/// Position einer Nachricht in der Sendekette des Absenders.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ChainPosition {
+    /// Nummer der Nachricht in der aktuellen Sendekette.
+    pub n: u32,
+    /// Länge der vorigen Sendekette.
+    pub previous: u32,
+    /// Epoche des PQ‑Ratchets, deren Geheimnis vor dieser Kette in den
+    /// Root‑Key gemischt wurde.
+    pub pq_epoch: Option<u32>,
+}
+
+impl ChainPosition {
+    fn to_bytes(self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(12);
+        out.extend_from_slice(&self.n.to_be_bytes());
+        out.extend_from_slice(&self.previous.to_be_bytes());
+        if let Some(epoch) = self.pq_epoch {
+            out.extend_from_slice(&epoch.to_be_bytes());
+        }
+        out
+    }
+    fn from_bytes(data: &[u8]) -> Option<Self> {
+        let word = |i: usize| u32::from_be_bytes(data[i..i + 4].try_into().expect("4 Byte"));
+        match data.len() {
+            8 => Some(Self { n: word(0), previous: word(4), pq_epoch: None }),
+            12 => Some(Self { n: word(0), previous: word(4), pq_epoch: Some(word(8)) }),
+            _ => None,
+        }
+    }
+}
+
+/// Header einer Ratchet‑Nachricht.
+///
+/// ```text
+/// ratchet_pub(32) | { type(1) | len(2, BE) | value(len) }*
+/// ```
+///
+/// Typ 1 trägt den KEM‑Ciphertext des hybriden Session‑Aufbaus (bis zur
+/// ersten Antwort), Typ 2 ein Fragment des Sparse‑PQ‑Ratchets,
+/// Typ 3 die Adresse, an die das Gegenüber antworten soll
+/// ([`crate::Subaddress`], 64 Byte), Typ 4 die signierte Geräteliste des
+/// Senders ([`crate::device::DeviceList`]), Typ 5 dessen Geräte‑ID
+/// (4 Byte, Big‑Endian; fehlt sie, sendet das Primärgerät) und Typ 6 die
+/// Position in der Sendekette ([`ChainPosition`]: `n(4) ‖ previous(4)`,
+/// optional `‖ pq_epoch(4)`, alle Big‑Endian) und Typ 7 den
+/// Session‑Ephemeral‑Key des Initiators (32 Byte, bis zur ersten Antwort).
+/// Unbekannte Typen werden übersprungen.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RatchetHeader {
+    /// Aktueller Ratchet‑Public‑Key des Senders.
+    pub ratchet_pub: [u8; 32],
+    /// KEM‑Ciphertext des hybriden Session‑Aufbaus.
+    pub session_kem: Option<Vec<u8>>,
+    /// Serialisiertes Fragment des PQ‑Ratchets.
+    pub pq_fragment: Option<Vec<u8>>,
+    /// Aktuelle Empfangsadresse des Senders für Antworten.
+    pub reply_address: Option<Vec<u8>>,
+    /// Serialisierte Geräteliste des Senders.
+    pub device_list: Option<Vec<u8>>,
+    /// Gerät, von dem die Nachricht stammt.
+    pub sender_device: Option<u32>,
+    /// Position der Nachricht in der Sendekette.
+    pub chain: Option<ChainPosition>,
+    /// Session‑Ephemeral‑Key des Initiators, aus dem der Root‑Key stammt.
+    pub session_ephemeral: Option<[u8; 32]>,
+}
+
+const HEADER_SESSION_KEM: u8 = 1;
+const HEADER_PQ_FRAGMENT: u8 = 2;
+const HEADER_REPLY_ADDRESS: u8 = 3;
+const HEADER_DEVICE_LIST: u8 = 4;
+const HEADER_SENDER_DEVICE: u8 = 5;
+const HEADER_CHAIN: u8 = 6;
+const HEADER_SESSION_EPHEMERAL: u8 = 7;
+
+impl RatchetHeader {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.ratchet_pub.to_vec();
+        let sender_device = self.sender_device.map(|id| id.to_be_bytes().to_vec());
+        let chain = self.chain.map(ChainPosition::to_bytes);
+        let session_ephemeral = self.session_ephemeral.map(|key| key.to_vec());
+        let fields = [
+            (HEADER_SESSION_KEM, &self.session_kem),
+            (HEADER_PQ_FRAGMENT, &self.pq_fragment),
+            (HEADER_REPLY_ADDRESS, &self.reply_address),
+            (HEADER_DEVICE_LIST, &self.device_list),
+            (HEADER_SENDER_DEVICE, &sender_device),
+            (HEADER_CHAIN, &chain),
+            (HEADER_SESSION_EPHEMERAL, &session_ephemeral),
+        ];
+        for (kind, value) in fields {
+            if let Some(value) = value {
+                out.push(kind);
+                out.extend_from_slice(&(value.len() as u16).to_be_bytes());
+                out.extend_from_slice(value);
+            }
+        }
+        out
+    }
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let mut header = Self { ratchet_pub: data.get(..32)?.try_into().ok()?, ..Self::default() };
+        let mut pos = 32;
+        while pos < data.len() {
+            let kind = data[pos];
+            let len = u16::from_be_bytes(data.get(pos + 1..pos + 3)?.try_into().ok()?) as usize;
+            let value = data.get(pos + 3..pos + 3 + len)?.to_vec();
+            pos += 3 + len;
+            match kind {
+                HEADER_SESSION_KEM => header.session_kem = Some(value),
+                HEADER_PQ_FRAGMENT => header.pq_fragment = Some(value),
+                HEADER_REPLY_ADDRESS => header.reply_address = Some(value),
+                HEADER_DEVICE_LIST => header.device_list = Some(value),
+                HEADER_SENDER_DEVICE => header.sender_device = Some(u32::from_be_bytes(value.try_into().ok()?)),
+                HEADER_CHAIN => header.chain = Some(ChainPosition::from_bytes(&value)?),
+                HEADER_SESSION_EPHEMERAL => header.session_ephemeral = Some(value.try_into().ok()?),
+                _ => {}
+            }
+        }
+        Some(header)
+    }
+    /// Associated Data der Nachricht: die Felder, die die Ratchet selbst
+    /// setzt.  Antwortadresse, Geräteliste und Geräte‑ID ergänzt der
+    /// Aufrufer; sie schützt das Absenderzertifikat des Envelopes.
+    fn associated_data(&self) -> Vec<u8> {
+        Self {
+            ratchet_pub: self.ratchet_pub,
+            session_kem: self.session_kem.clone(),
+            pq_fragment: self.pq_fragment.clone(),
+            chain: self.chain,
+            session_ephemeral: self.session_ephemeral,
+            ..Self::default()
+        }
+        .to_bytes()
+    }
 }
 
-/// Zustand einer Double‑Ratchet‑Session.
-#[derive(Debug, Clone)]
+/// Aufgehobener Schlüssel einer übersprungenen Nachricht.
+struct SkippedKey {
+    ratchet_pub: [u8; 32],
+    n: u32,
+    key: SecretBytes<32>,
+}
+
+/// PQ‑Geheimnis, das auf seinen DH‑Schritt wartet.  `decapsulated`: Wir
+/// haben es entkapselt und mischen es vor der nächsten eigenen
+/// Sendekette ein; sonst haben wir es gekapselt und warten auf die
+/// Kette der Gegenseite, die seine Epoche nennt.
+struct PqSecret {
+    epoch: u32,
+    decapsulated: bool,
+    secret: SecretBytes<32>,
+}
+
+/// Zustand einer Double‑Ratchet‑Session.  Schlüssel werden beim Drop
+/// überschrieben; `Debug` zeigt nur die Zähler.
 pub struct RatchetState {
-    /// Root‑Key aus der letzten DH‑Operation.
-    root_key: [u8; 32],
-    /// Aktueller Sende‑Chain‑Key.
-    send_chain: [u8; 32],
-    /// Aktueller Empfangs‑Chain‑Key.
-    recv_chain: [u8; 32],
+    /// Root‑Key aus dem letzten DH‑Schritt.
+    root_key: SecretBytes<32>,
+    /// Session‑Ephemeral‑Key des Initiators; unterscheidet die Sessions
+    /// eines [`SessionRecord`].
+    origin: [u8; 32],
+    /// Der Initiator hat noch keine Antwort erhalten und legt seinen
+    /// Nachrichten Ephemeral‑Key und KEM‑Ciphertext bei.
+    pending_init: bool,
+    /// KEM‑Ciphertext des hybriden Session‑Aufbaus.
+    session_kem: Option<Vec<u8>>,
+    /// Aktueller Sende‑Chain‑Key; fehlt, solange nach einem neuen
+    /// Ratchet‑Key des Gegenübers noch keine eigene Nachricht ging.
+    send_chain: Option<SecretBytes<32>>,
+    /// Aktueller Empfangs‑Chain‑Key; fehlt beim Initiator bis zur ersten
+    /// Antwort.
+    recv_chain: Option<SecretBytes<32>>,
     /// Aktuelles Ratchet‑Keypair (privat).
     ratchet_secret: StaticSecret,
     /// Aktueller Ratchet‑Public‑Key des Peers.
-    peer_ratchet_public: PublicKey,
+    peer_ratchet_public: Option<PublicKey>,
     /// Nachrichtenzähler für Sendekette.
     send_count: u32,
     /// Nachrichtenzähler für Empfangskette.
     recv_count: u32,
+    /// Länge der vorigen Sendekette.
+    previous_count: u32,
+    /// Schlüssel übersprungener Nachrichten, älteste zuerst.
+    skipped: Vec<SkippedKey>,
+    /// PQ‑Geheimnisse, die noch nicht im Root‑Key stecken.
+    pq_secrets: Vec<PqSecret>,
+    /// Epoche des PQ‑Geheimnisses, das vor der aktuellen Sendekette
+    /// eingemischt wurde.
+    send_pq_epoch: Option<u32>,
+    /// Sparse‑PQ‑Ratchet, falls für die Session aktiviert.
+    #[cfg(feature = "pqc")]
+    pq: Option<PqRatchet>,
+}
+
+impl std::fmt::Debug for RatchetState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RatchetState")
+            .field("send_count", &self.send_count)
+            .field("recv_count", &self.recv_count)
+            .field("skipped", &self.skipped.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Ergebnis eines Empfangsschritts, das erst nach erfolgreicher
+/// Entschlüsselung übernommen wird.
+struct Receiving {
+    /// Neuer Root‑Key und Ratchet‑Key des Gegenübers nach einem DH‑Schritt.
+    step: Option<(SecretBytes<32>, PublicKey)>,
+    chain: SecretBytes<32>,
+    count: u32,
+    skipped: Vec<SkippedKey>,
+    /// Index des verbrauchten PQ‑Geheimnisses.
+    pq_used: Option<usize>,
 }
 
 impl RatchetState {
-    /// Initialisiert einen neuen Ratchet‑Zustand.  Dazu werden ein
-    /// gemeinsamer Root‑Key und das anfängliche Ratchet‑Keypair
-    /// benötigt.
-    pub fn new(root_key: [u8; 32], peer_ratchet_public: PublicKey) -> Self {
-        // Generiere eigenes Ratchet‑Keypair
-        let ratchet_secret = StaticSecret::new(&mut OsRng);
-        // Ableitung der Anfangs‑Chain‑Keys: in der echten
-        // Implementierung erfolgt dies per HKDF aus dem Root‑Key und dem
-        // DH‑Output; hier verwenden wir einfach Hashes zur Demonstration.
-        let send_chain = sha256(&[0u8]);
-        let recv_chain = sha256(&[1u8]);
+    /// Legt die Session des Initiators aus dem Root‑Key von
+    /// [`session_root`] an; `origin` ist sein Session‑Ephemeral‑Key.  Er
+    /// sendet auf der Startkette und legt seinen Nachrichten den
+    /// Ephemeral‑Key bei, bis die erste Antwort eintrifft.
+    pub fn initiator(root_key: SecretBytes<32>, origin: [u8; 32]) -> Self {
+        let (root_key, chain) = kdf_root(root_key.expose(), &[], INIT_INFO);
+        Self::with_chains(root_key, origin, Some(chain), None, None)
+    }
+    /// Legt die Session der Gegenseite an, sobald eine Nachricht des
+    /// Initiators mit Session‑Ephemeral‑Key `origin` und Ratchet‑Key
+    /// `peer_ratchet_public` eintrifft.
+    pub fn responder(root_key: SecretBytes<32>, origin: [u8; 32], peer_ratchet_public: PublicKey) -> Self {
+        let (root_key, chain) = kdf_root(root_key.expose(), &[], INIT_INFO);
+        Self::with_chains(root_key, origin, None, Some(chain), Some(peer_ratchet_public))
+    }
+    fn with_chains(
+        root_key: SecretBytes<32>,
+        origin: [u8; 32],
+        send_chain: Option<SecretBytes<32>>,
+        recv_chain: Option<SecretBytes<32>>,
+        peer_ratchet_public: Option<PublicKey>,
+    ) -> Self {
         Self {
             root_key,
-            send_chain: send_chain.try_into().expect("32 bytes"),
-            recv_chain: recv_chain.try_into().expect("32 bytes"),
-            ratchet_secret,
+            origin,
+            pending_init: send_chain.is_some(),
+            session_kem: None,
+            send_chain,
+            recv_chain,
+            ratchet_secret: StaticSecret::random_from_rng(OsRng),
             peer_ratchet_public,
             send_count: 0,
             recv_count: 0,
+            previous_count: 0,
+            skipped: Vec::new(),
+            pq_secrets: Vec::new(),
+            send_pq_epoch: None,
+            #[cfg(feature = "pqc")]
+            pq: None,
         }
     }
-    /// Erzeugt einen neuen Nachrichten‑Schlüssel aus der Sende‑Kette und
-    /// erhöht den Zähler.  In der echten Implementierung wird dabei ein
-    /// KDF (z.&nbsp;B. HMAC/SHA‑256) genutzt; hier wird zur Vereinfachung
-    /// SHA‑256 über die Kette und den Zähler gebildet.
-    fn next_send_key(&mut self) -> [u8; 32] {
-        self.send_count += 1;
-        let mut data = Vec::new();
-        data.extend_from_slice(&self.send_chain);
-        data.extend_from_slice(&self.send_count.to_le_bytes());
-        let digest = sha256(&data);
-        digest.try_into().expect("32 bytes")
-    }
-    /// Entschlüsselt eine Nachricht anhand der Empfangs‑Kette.  Auch
-    /// hier wird nur eine Hashfunktion genutzt, um den Message‑Key
-    /// abzuleiten.  Bei jedem Aufruf erhöht sich der Empfangs‑Zähler.
-    fn next_recv_key(&mut self) -> [u8; 32] {
-        self.recv_count += 1;
-        let mut data = Vec::new();
-        data.extend_from_slice(&self.recv_chain);
-        data.extend_from_slice(&self.recv_count.to_le_bytes());
-        let digest = sha256(&data);
-        digest.try_into().expect("32 bytes")
+    /// Session‑Ephemeral‑Key des Initiators.
+    pub fn origin(&self) -> &[u8; 32] {
+        &self.origin
+    }
+    /// Hängt den KEM‑Ciphertext des hybriden Session‑Aufbaus an die
+    /// Nachrichten des Initiators bis zur ersten Antwort.
+    #[cfg(feature = "pqc")]
+    pub fn with_session_kem(mut self, ciphertext: Vec<u8>) -> Self {
+        self.session_kem = Some(ciphertext);
+        self
+    }
+    /// Aktiviert den Sparse‑PQ‑Ratchet für diese Session.
+    #[cfg(feature = "pqc")]
+    pub fn with_pq(mut self, pq: PqRatchet) -> Self {
+        self.pq = Some(pq);
+        self
+    }
+    /// Anzahl der eingemischten PQ‑Geheimnisse; `None` ohne PQ‑Ratchet.
+    #[cfg(feature = "pqc")]
+    pub fn pq_epoch(&self) -> Option<u32> {
+        self.pq.as_ref().map(PqRatchet::epoch)
+    }
+    /// Serialisiert den Zustand zur lokalen Speicherung.  Das Ergebnis
+    /// enthält privates Schlüsselmaterial und darf nur verschlüsselt
+    /// abgelegt werden.  Format (Version 4, Zahlen Little Endian):
+    ///
+    /// ```text
+    /// 4 | flags(1) | root(32) | origin(32) | [send_chain(32)] | [recv_chain(32)]
+    ///   | ratchet_secret(32) | [peer_ratchet_pub(32)]
+    ///   | send_count(4) | recv_count(4) | previous_count(4) | [send_pq_epoch(4)]
+    ///   | skipped_count(2) | { ratchet_pub(32) | n(4) | key(32) }*
+    ///   | pq_count(1) | { epoch(4) | decapsulated(1) | secret(32) }*
+    ///   | [kem_len(2) | session_kem] | [pq_ratchet]
+    /// ```
+    ///
+    /// Die Flags zeigen, welche optionalen Felder vorhanden sind (Bit 0
+    /// Sende‑, Bit 1 Empfangskette, Bit 2 Peer‑Key, Bit 3 PQ‑Epoche der
+    /// Sendekette, Bit 4 PQ‑Ratchet, Bit 6 KEM‑Ciphertext); Bit 5 steht für
+    /// einen noch unbeantworteten Session‑Aufbau.  Builds ohne Feature
+    /// `pqc` lesen den Zustand des PQ‑Ratchets nicht.  Ältere Versionen
+    /// stammen aus der Zeit vor der Verschlüsselung bzw. vor dem Root‑Key
+    /// aus [`session_root`] und werden nicht mehr gelesen.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(1 + 1 + 6 * 32 + 3 * 4 + 2 + 1);
+        out.push(STATE_VERSION);
+        #[cfg(feature = "pqc")]
+        let has_pq = self.pq.is_some();
+        #[cfg(not(feature = "pqc"))]
+        let has_pq = false;
+        let flags = [
+            self.send_chain.is_some(),
+            self.recv_chain.is_some(),
+            self.peer_ratchet_public.is_some(),
+            self.send_pq_epoch.is_some(),
+            has_pq,
+            self.pending_init,
+            self.session_kem.is_some(),
+        ]
+        .iter()
+        .enumerate()
+        .fold(0u8, |flags, (bit, set)| flags | (u8::from(*set) << bit));
+        out.push(flags);
+        out.extend_from_slice(self.root_key.expose());
+        out.extend_from_slice(&self.origin);
+        for chain in [&self.send_chain, &self.recv_chain].into_iter().flatten() {
+            out.extend_from_slice(chain.expose());
+        }
+        out.extend_from_slice(Zeroizing::new(self.ratchet_secret.to_bytes()).as_ref());
+        if let Some(peer) = &self.peer_ratchet_public {
+            out.extend_from_slice(peer.as_bytes());
+        }
+        for count in [self.send_count, self.recv_count, self.previous_count] {
+            out.extend_from_slice(&count.to_le_bytes());
+        }
+        if let Some(epoch) = self.send_pq_epoch {
+            out.extend_from_slice(&epoch.to_le_bytes());
+        }
+        out.extend_from_slice(&(self.skipped.len() as u16).to_le_bytes());
+        for skipped in &self.skipped {
+            out.extend_from_slice(&skipped.ratchet_pub);
+            out.extend_from_slice(&skipped.n.to_le_bytes());
+            out.extend_from_slice(skipped.key.expose());
+        }
+        out.push(self.pq_secrets.len() as u8);
+        for pending in &self.pq_secrets {
+            out.extend_from_slice(&pending.epoch.to_le_bytes());
+            out.push(u8::from(pending.decapsulated));
+            out.extend_from_slice(pending.secret.expose());
+        }
+        if let Some(kem) = &self.session_kem {
+            out.extend_from_slice(&(kem.len() as u16).to_le_bytes());
+            out.extend_from_slice(kem);
+        }
+        #[cfg(feature = "pqc")]
+        if let Some(pq) = &self.pq {
+            out.extend_from_slice(&pq.to_bytes());
+        }
+        out
+    }
+    /// Stellt einen mit [`RatchetState::to_bytes`] gespeicherten Zustand
+    /// wieder her.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let mut r = Reader { data, pos: 0 };
+        if r.take(1)?[0] != STATE_VERSION {
+            return None;
+        }
+        let flags = r.take(1)?[0];
+        let flag = |bit: u8| flags & (1 << bit) != 0;
+        let root_key = r.secret()?;
+        let origin = r.array()?;
+        let send_chain = if flag(0) { Some(r.secret()?) } else { None };
+        let recv_chain = if flag(1) { Some(r.secret()?) } else { None };
+        let ratchet_secret = StaticSecret::from(*Zeroizing::new(r.array()?));
+        let peer_ratchet_public = if flag(2) { Some(PublicKey::from(r.array()?)) } else { None };
+        let (send_count, recv_count, previous_count) = (r.u32()?, r.u32()?, r.u32()?);
+        let send_pq_epoch = if flag(3) { Some(r.u32()?) } else { None };
+        let skipped_count = u16::from_le_bytes(r.take(2)?.try_into().ok()?);
+        let mut skipped = Vec::with_capacity(skipped_count.min(MAX_SKIP as u16) as usize);
+        for _ in 0..skipped_count {
+            skipped.push(SkippedKey { ratchet_pub: r.array()?, n: r.u32()?, key: r.secret()? });
+        }
+        let pq_count = r.take(1)?[0];
+        let mut pq_secrets = Vec::with_capacity(pq_count as usize);
+        for _ in 0..pq_count {
+            let epoch = r.u32()?;
+            let decapsulated = r.take(1)?[0] == 1;
+            pq_secrets.push(PqSecret { epoch, decapsulated, secret: r.secret()? });
+        }
+        let session_kem = if flag(6) {
+            let len = u16::from_le_bytes(r.take(2)?.try_into().ok()?) as usize;
+            Some(r.take(len)?.to_vec())
+        } else {
+            None
+        };
+        if !flag(4) && r.pos != data.len() {
+            return None;
+        }
+        #[cfg(feature = "pqc")]
+        let pq = if flag(4) { Some(PqRatchet::from_bytes(&data[r.pos..])?) } else { None };
+        Some(Self {
+            root_key,
+            origin,
+            pending_init: flag(5),
+            session_kem,
+            send_chain,
+            recv_chain,
+            ratchet_secret,
+            peer_ratchet_public,
+            send_count,
+            recv_count,
+            previous_count,
+            skipped,
+            pq_secrets,
+            send_pq_epoch,
+            #[cfg(feature = "pqc")]
+            pq,
+        })
     }
     /// Verschlüsselt eine Nachricht.  Diese Funktion gibt den
-    /// Message‑Key und einen Platzhalter‑Header zurück.  In der
-    /// vollständigen Umsetzung würde hier der Ratchet‑Header mit
-    /// Ratchet‑Public‑Key und Positionsindizes serialisiert.
+    /// Ciphertext und den serialisierten [`RatchetHeader`] zurück.
     pub fn encrypt(&mut self, plaintext: &[u8]) -> (Vec<u8>, Vec<u8>) {
-        let msg_key = self.next_send_key();
-        // TODO: echte AEAD‑Verschlüsselung durch XChaCha20‑Poly1305
-        // Hier wird zur Demo der Klartext einfach zurückgegeben.
-        let ciphertext = plaintext.to_vec();
-        // Header kann den aktuellen Ratchet‑Public‑Key enthalten
-        let header = self.ratchet_secret.public_key().as_bytes().to_vec();
+        let (ciphertext, header) = self.encrypt_with_header(plaintext);
+        (ciphertext, header.to_bytes())
+    }
+    /// Wie [`RatchetState::encrypt`], liefert den Header aber
+    /// unserialisiert, so dass der Aufrufer ihn ergänzen kann.  Steht nach
+    /// einem neuen Ratchet‑Key des Gegenübers die erste eigene Nachricht
+    /// an, beginnt zuvor ein DH‑Schritt eine neue Sendekette.  Bis zur
+    /// ersten Antwort trägt der Header des Initiators Session‑Ephemeral‑Key
+    /// und KEM‑Ciphertext des Session‑Aufbaus.  Mit
+    /// PQ‑Ratchet wird bei Fälligkeit ein frisches KEM‑Geheimnis gekapselt
+    /// und ein Fragment angehängt.
+    pub fn encrypt_with_header(&mut self, plaintext: &[u8]) -> (Vec<u8>, RatchetHeader) {
+        if self.send_chain.is_none() {
+            self.step_send();
+        }
+        let mut header = RatchetHeader { ratchet_pub: *PublicKey::from(&self.ratchet_secret).as_bytes(), ..Default::default() };
+        if self.pending_init {
+            header.session_ephemeral = Some(self.origin);
+            header.session_kem = self.session_kem.clone();
+        }
+        #[cfg(feature = "pqc")]
+        if let Some(pq) = &mut self.pq {
+            let (secret, fragment) = pq.on_send();
+            if let Some(secret) = secret {
+                push_pq_secret(&mut self.pq_secrets, pq.epoch(), false, &secret);
+            }
+            header.pq_fragment = fragment.map(|f| f.to_bytes());
+        }
+        let chain = self.send_chain.take().expect("Sendekette nach DH‑Schritt");
+        let (message_key, next) = chain_step(chain.expose());
+        self.send_chain = Some(next);
+        header.chain = Some(ChainPosition { n: self.send_count, previous: self.previous_count, pq_epoch: self.send_pq_epoch });
+        self.send_count = self.send_count.wrapping_add(1);
+        let cipher = XChaCha20Poly1305::new_from_slice(message_key.as_ref()).expect("32 Byte");
+        let aad = header.associated_data();
+        let ciphertext =
+            cipher.encrypt(&XNonce::default(), Payload { msg: plaintext, aad: &aad }).expect("XChaCha20-Poly1305");
         (ciphertext, header)
     }
-    /// Entschlüsselt eine Nachricht.  Für die Demonstration wird der
-    /// Klartext direkt zurückgegeben.  In einer echten Implementierung
-    /// würde der Header geprüft und ggf. ein DH‑Ratchet durchgeführt.
-    pub fn decrypt(&mut self, _header: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, RatchetError> {
-        let _msg_key = self.next_recv_key();
-        // TODO: echte AEAD‑Entschlüsselung mit msg_key
-        Ok(ciphertext.to_vec())
+    /// Entschlüsselt eine Nachricht.  Ein neuer Ratchet‑Key des
+    /// Gegenübers löst einen DH‑Schritt aus; Schlüssel übersprungener
+    /// Nachrichten werden aufgehoben.  Der Zustand ändert sich nur, wenn
+    /// die Entschlüsselung gelingt.  Anschließend wird ein Fragment des
+    /// PQ‑Ratchets verarbeitet; ein vollständiger KEM‑Ciphertext liefert
+    /// das Geheimnis für den nächsten eigenen DH‑Schritt.
+    pub fn decrypt(&mut self, header: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, RatchetError> {
+        let header = RatchetHeader::from_bytes(header).ok_or(RatchetError::InvalidHeader)?;
+        let position = header.chain.ok_or(RatchetError::InvalidHeader)?;
+        let aad = header.associated_data();
+        let skipped =
+            self.skipped.iter().position(|k| k.ratchet_pub == header.ratchet_pub && k.n == position.n);
+        let plaintext = match skipped {
+            Some(index) => {
+                let plaintext = open(self.skipped[index].key.expose(), &aad, ciphertext)?;
+                self.skipped.remove(index);
+                plaintext
+            }
+            None => {
+                let mut receiving = self.receiving(&header.ratchet_pub, position)?;
+                let (message_key, next) = chain_step(receiving.chain.expose());
+                let plaintext = open(&message_key, &aad, ciphertext)?;
+                receiving.chain = next;
+                self.commit(receiving, position.n);
+                plaintext
+            }
+        };
+        // Die Gegenseite kennt die Session
+        self.pending_init = false;
+        self.session_kem = None;
+        #[cfg(feature = "pqc")]
+        if let (Some(pq), Some(fragment)) = (&mut self.pq, header.pq_fragment.as_deref().and_then(PqFragment::from_bytes)) {
+            if let Some(secret) = pq.on_receive(&fragment) {
+                push_pq_secret(&mut self.pq_secrets, pq.epoch(), true, &secret);
+            }
+        }
+        Ok(plaintext)
+    }
+    /// Bereitet den Empfang der Nachricht `position.n` mit Ratchet‑Key
+    /// `ratchet_pub` vor, ohne den Zustand zu ändern: Empfangskette bis
+    /// vor die Nachricht, ggf. nach einem DH‑Schritt.
+    fn receiving(&self, ratchet_pub: &[u8; 32], position: ChainPosition) -> Result<Receiving, RatchetError> {
+        let mut skipped = Vec::new();
+        let same_key = self.peer_ratchet_public.is_some_and(|peer| peer.as_bytes() == ratchet_pub);
+        if let (true, Some(chain)) = (same_key, &self.recv_chain) {
+            if position.n < self.recv_count {
+                // Schlüssel bereits verbraucht
+                return Err(RatchetError::DecryptionFailed);
+            }
+            let mut chain = SecretBytes::new(*chain.expose());
+            let count = skip(&mut chain, self.recv_count, position.n, ratchet_pub, &mut skipped)?;
+            return Ok(Receiving { step: None, chain, count, skipped, pq_used: None });
+        }
+        // Neuer Ratchet‑Key: Rest der bisherigen Empfangskette aufheben
+        if let (Some(peer), Some(chain)) = (&self.peer_ratchet_public, &self.recv_chain) {
+            let mut chain = SecretBytes::new(*chain.expose());
+            skip(&mut chain, self.recv_count, position.previous, peer.as_bytes(), &mut skipped)?;
+        }
+        #[cfg_attr(not(feature = "pqc"), allow(unused_mut))]
+        let mut root = Zeroizing::new(*self.root_key.expose());
+        let pq_used = match position.pq_epoch {
+            None => None,
+            #[cfg(feature = "pqc")]
+            Some(epoch) => {
+                let index = self
+                    .pq_secrets
+                    .iter()
+                    .position(|s| !s.decapsulated && s.epoch == epoch)
+                    .ok_or(RatchetError::DecryptionFailed)?;
+                root = pq_ratchet::mix_root(&root, self.pq_secrets[index].secret.expose(), epoch);
+                Some(index)
+            }
+            #[cfg(not(feature = "pqc"))]
+            Some(_) => return Err(RatchetError::DecryptionFailed),
+        };
+        let peer = PublicKey::from(*ratchet_pub);
+        let dh = Zeroizing::new(*self.ratchet_secret.diffie_hellman(&peer).as_bytes());
+        let (root, mut chain) = kdf_root(&root, dh.as_ref(), ROOT_INFO);
+        let count = skip(&mut chain, 0, position.n, ratchet_pub, &mut skipped)?;
+        Ok(Receiving { step: Some((root, peer)), chain, count, skipped, pq_used })
+    }
+    /// Übernimmt einen vorbereiteten Empfangsschritt nach Nachricht `n`.
+    fn commit(&mut self, receiving: Receiving, n: u32) {
+        if let Some((root, peer)) = receiving.step {
+            self.root_key = root;
+            self.peer_ratchet_public = Some(peer);
+            // Die nächste eigene Nachricht beginnt eine neue Sendekette
+            self.send_chain = None;
+        }
+        if let Some(index) = receiving.pq_used {
+            self.pq_secrets.remove(index);
+        }
+        debug_assert_eq!(receiving.count, n);
+        self.recv_chain = Some(receiving.chain);
+        self.recv_count = n.wrapping_add(1);
+        self.skipped.extend(receiving.skipped);
+        // Älteste übersprungene Schlüssel zuerst verwerfen
+        let excess = self.skipped.len().saturating_sub(MAX_SKIP as usize);
+        self.skipped.drain(..excess);
+    }
+    /// DH‑Schritt vor der ersten eigenen Nachricht nach einem neuen
+    /// Ratchet‑Key des Gegenübers.  Ein entkapseltes PQ‑Geheimnis wird
+    /// zuvor in den Root‑Key gemischt.
+    fn step_send(&mut self) {
+        let peer = self.peer_ratchet_public.expect("Ratchet‑Key des Gegenübers");
+        self.ratchet_secret = StaticSecret::random_from_rng(OsRng);
+        #[cfg_attr(not(feature = "pqc"), allow(unused_mut))]
+        let mut root = Zeroizing::new(*self.root_key.expose());
+        self.send_pq_epoch = None;
+        #[cfg(feature = "pqc")]
+        if let Some(index) = self.pq_secrets.iter().position(|s| s.decapsulated) {
+            let pending = self.pq_secrets.remove(index);
+            root = pq_ratchet::mix_root(&root, pending.secret.expose(), pending.epoch);
+            self.send_pq_epoch = Some(pending.epoch);
+        }
+        let dh = Zeroizing::new(*self.ratchet_secret.diffie_hellman(&peer).as_bytes());
+        let (root, chain) = kdf_root(&root, dh.as_ref(), ROOT_INFO);
+        self.root_key = root;
+        self.send_chain = Some(chain);
+        self.previous_count = self.send_count;
+        self.send_count = 0;
+    }
+}
+
+const STATE_VERSION: u8 = 4;
+
+/// Root‑Key einer neuen Session.  `shared` ist das ECDH‑Geheimnis
+/// zwischen dem Session‑Ephemeral‑Key des Initiators und dem Spend‑Key
+/// der Gegenseite (siehe [`crate::Subaddress::agree`]); `context` bindet
+/// den Schlüssel an die Beteiligten.
+pub fn session_root(shared: &[u8; 32], context: &[u8]) -> SecretBytes<32> {
+    let hk = Hkdf::<Sha256>::new(None, shared);
+    let mut root = Zeroizing::new([0u8; 32]);
+    hk.expand_multi_info(&[SESSION_INFO, context], root.as_mut()).expect("HKDF expand");
+    SecretBytes::new(*root)
+}
+
+/// Alle Sessions mit einem Gerät, die aktuelle zuerst.  Bauen beide
+/// Seiten gleichzeitig eine Session auf, entstehen zwei; Nachrichten auf
+/// beiden bleiben lesbar, und die Session, auf der zuletzt eine Nachricht
+/// eintraf, wird zur aktuellen.
+#[derive(Debug)]
+pub struct SessionRecord {
+    states: Vec<RatchetState>,
+}
+
+impl SessionRecord {
+    pub fn new(state: RatchetState) -> Self {
+        Self { states: vec![state] }
+    }
+    /// Session, auf der gesendet wird.
+    pub fn current(&mut self) -> &mut RatchetState {
+        &mut self.states[0]
+    }
+    /// Gibt es schon eine Session zum Session‑Ephemeral‑Key `origin`?
+    pub fn contains(&self, origin: &[u8; 32]) -> bool {
+        self.states.iter().any(|state| state.origin == *origin)
+    }
+    /// Macht `state` zur aktuellen Session; die älteste frühere fällt
+    /// heraus.
+    pub fn adopt(&mut self, state: RatchetState) {
+        self.states.insert(0, state);
+        self.states.truncate(1 + MAX_PREVIOUS_SESSIONS);
+    }
+    /// Entschlüsselt eine Nachricht mit der Session, zu der ihr Header
+    /// gehört: bei Session‑Ephemeral‑Key der passenden, sonst der ersten,
+    /// die sie öffnet.  Diese wird zur aktuellen.
+    pub fn decrypt(&mut self, header: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, RatchetError> {
+        let origin = RatchetHeader::from_bytes(header).ok_or(RatchetError::InvalidHeader)?.session_ephemeral;
+        let mut result = Err(RatchetError::DecryptionFailed);
+        for index in 0..self.states.len() {
+            if origin.is_some_and(|origin| origin != self.states[index].origin) {
+                continue;
+            }
+            result = self.states[index].decrypt(header, ciphertext);
+            if result.is_ok() {
+                let state = self.states.remove(index);
+                self.states.insert(0, state);
+                break;
+            }
+        }
+        result
+    }
+    /// Serialisiert alle Sessions (`count(1) | { len(4, LE) | state }*`);
+    /// siehe [`RatchetState::to_bytes`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![self.states.len() as u8];
+        for state in &self.states {
+            let bytes = Zeroizing::new(state.to_bytes());
+            out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            out.extend_from_slice(&bytes);
+        }
+        out
+    }
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        let mut r = Reader { data, pos: 0 };
+        let count = r.take(1)?[0];
+        if count == 0 || count as usize > 1 + MAX_PREVIOUS_SESSIONS {
+            return None;
+        }
+        let mut states = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            let len = r.u32()? as usize;
+            states.push(RatchetState::from_bytes(r.take(len)?)?);
+        }
+        (r.pos == data.len()).then_some(Self { states })
+    }
+}
+
+/// Merkt sich ein PQ‑Geheimnis bis zu seinem DH‑Schritt.
+#[cfg(feature = "pqc")]
+fn push_pq_secret(pending: &mut Vec<PqSecret>, epoch: u32, decapsulated: bool, secret: &[u8; 32]) {
+    pending.push(PqSecret { epoch, decapsulated, secret: SecretBytes::new(*secret) });
+    let excess = pending.len().saturating_sub(MAX_PQ_SECRETS);
+    pending.drain(..excess);
+}
+
+/// Root‑KDF: neuer Root‑Key und Chain‑Key aus `ikm`.
+fn kdf_root(root: &[u8; 32], ikm: &[u8], info: &[u8]) -> (SecretBytes<32>, SecretBytes<32>) {
+    let hk = Hkdf::<Sha256>::new(Some(root), ikm);
+    let mut okm = Zeroizing::new([0u8; 64]);
+    hk.expand(info, okm.as_mut()).expect("HKDF expand");
+    (SecretBytes::new(okm[..32].try_into().expect("32 Byte")), SecretBytes::new(okm[32..].try_into().expect("32 Byte")))
+}
+
+/// Ein Schritt der Kette: (Message‑Key, nächster Chain‑Key).
+fn chain_step(chain_key: &[u8; 32]) -> (Zeroizing<[u8; 32]>, SecretBytes<32>) {
+    let derive = |label: u8| -> [u8; 32] {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(chain_key).expect("HMAC key");
+        mac.update(&[label]);
+        mac.finalize().into_bytes().into()
+    };
+    (Zeroizing::new(derive(0x01)), SecretBytes::new(derive(0x02)))
+}
+
+/// Rückt `chain` von Nachricht `from` bis vor `until` vor und hebt die
+/// Schlüssel dazwischen auf.  Liefert den neuen Zählerstand.
+fn skip(
+    chain: &mut SecretBytes<32>,
+    from: u32,
+    until: u32,
+    ratchet_pub: &[u8; 32],
+    out: &mut Vec<SkippedKey>,
+) -> Result<u32, RatchetError> {
+    let gap = until.saturating_sub(from);
+    if gap > MAX_SKIP {
+        return Err(RatchetError::TooFarAhead(gap));
+    }
+    for n in from..until {
+        let (message_key, next) = chain_step(chain.expose());
+        out.push(SkippedKey { ratchet_pub: *ratchet_pub, n, key: SecretBytes::new(*message_key) });
+        *chain = next;
+    }
+    Ok(from.max(until))
+}
+
+/// Entschlüsselt mit einem Message‑Key.
+fn open(message_key: &[u8; 32], aad: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>, RatchetError> {
+    let cipher = XChaCha20Poly1305::new_from_slice(message_key).expect("32 Byte");
+    cipher.decrypt(&XNonce::default(), Payload { msg: ciphertext, aad }).map_err(|_| RatchetError::DecryptionFailed)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let slice = self.data.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(slice)
+    }
+    fn array(&mut self) -> Option<[u8; 32]> {
+        self.take(32)?.try_into().ok()
+    }
+    fn secret(&mut self) -> Option<SecretBytes<32>> {
+        Some(SecretBytes::new(self.array()?))
+    }
+    fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().ok()?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair() -> (RatchetState, RatchetState, Vec<u8>, Vec<u8>) {
+        let root = [7u8; 32];
+        let mut alice = RatchetState::initiator(SecretBytes::new(root), [1u8; 32]);
+        let (ct, header) = alice.encrypt(b"hallo");
+        let bob = responder_for(&header, root);
+        (alice, bob, ct, header)
+    }
+
+    fn responder_for(header: &[u8], root: [u8; 32]) -> RatchetState {
+        let header = RatchetHeader::from_bytes(header).unwrap();
+        RatchetState::responder(SecretBytes::new(root), header.session_ephemeral.unwrap(), PublicKey::from(header.ratchet_pub))
+    }
+
+    #[test]
+    fn round_trip_both_directions() {
+        let (mut alice, mut bob, ct, header) = pair();
+        assert_ne!(ct.as_slice(), b"hallo");
+        assert_eq!(bob.decrypt(&header, &ct).unwrap(), b"hallo");
+        for round in 0..5u8 {
+            let (ct, header) = bob.encrypt(&[round; 3]);
+            assert_eq!(alice.decrypt(&header, &ct).unwrap(), [round; 3]);
+            let (ct, header) = alice.encrypt(&[round; 4]);
+            assert_eq!(bob.decrypt(&header, &ct).unwrap(), [round; 4]);
+        }
+    }
+
+    #[test]
+    fn each_turn_uses_a_new_ratchet_key() {
+        let (mut alice, mut bob, ct, header) = pair();
+        bob.decrypt(&header, &ct).unwrap();
+        let first = RatchetHeader::from_bytes(&header).unwrap().ratchet_pub;
+        let (ct, header) = bob.encrypt(b"a");
+        alice.decrypt(&header, &ct).unwrap();
+        let (_, header) = alice.encrypt(b"b");
+        assert_ne!(RatchetHeader::from_bytes(&header).unwrap().ratchet_pub, first);
+    }
+
+    #[test]
+    fn wrong_root_fails() {
+        let mut alice = RatchetState::initiator(SecretBytes::new([1u8; 32]), [1u8; 32]);
+        let (ct, header) = alice.encrypt(b"geheim");
+        let mut mallory = responder_for(&header, [2u8; 32]);
+        assert!(matches!(mallory.decrypt(&header, &ct), Err(RatchetError::DecryptionFailed)));
+    }
+
+    #[test]
+    fn out_of_order_and_lost_messages() {
+        let (mut alice, mut bob, ct0, h0) = pair();
+        let m1 = alice.encrypt(b"1");
+        let m2 = alice.encrypt(b"2");
+        let m3 = alice.encrypt(b"3");
+        assert_eq!(bob.decrypt(&m3.1, &m3.0).unwrap(), b"3");
+        assert_eq!(bob.decrypt(&m1.1, &m1.0).unwrap(), b"1");
+        // Antwort, während m0 und m2 noch unterwegs sind
+        let reply = bob.encrypt(b"r");
+        alice.decrypt(&reply.1, &reply.0).unwrap();
+        let m4 = alice.encrypt(b"4");
+        assert_eq!(bob.decrypt(&m4.1, &m4.0).unwrap(), b"4");
+        assert_eq!(bob.decrypt(&m2.1, &m2.0).unwrap(), b"2");
+        assert_eq!(bob.decrypt(&h0, &ct0).unwrap(), b"hallo");
+    }
+
+    #[test]
+    fn duplicates_are_rejected() {
+        let (_, mut bob, ct, header) = pair();
+        bob.decrypt(&header, &ct).unwrap();
+        assert!(bob.decrypt(&header, &ct).is_err());
+    }
+
+    #[test]
+    fn tampering_fails_without_changing_state() {
+        let (mut alice, mut bob, ct, header) = pair();
+        let mut forged = ct.clone();
+        forged[0] ^= 1;
+        assert!(bob.decrypt(&header, &forged).is_err());
+        // Geänderte Kettenposition (Associated Data)
+        let mut moved = RatchetHeader::from_bytes(&header).unwrap();
+        moved.chain = Some(ChainPosition { n: 1, ..moved.chain.unwrap() });
+        assert!(bob.decrypt(&moved.to_bytes(), &ct).is_err());
+        assert_eq!(bob.decrypt(&header, &ct).unwrap(), b"hallo");
+        let (ct, header) = alice.encrypt(b"weiter");
+        assert_eq!(bob.decrypt(&header, &ct).unwrap(), b"weiter");
+    }
+
+    #[test]
+    fn too_far_ahead_is_rejected() {
+        let (mut alice, mut bob, _, _) = pair();
+        let mut last = None;
+        for _ in 0..MAX_SKIP + 2 {
+            last = Some(alice.encrypt(b"x"));
+        }
+        let (ct, header) = last.unwrap();
+        assert!(matches!(bob.decrypt(&header, &ct), Err(RatchetError::TooFarAhead(_))));
+    }
+
+    #[test]
+    fn header_without_chain_position_is_invalid() {
+        let (_, mut bob, ct, header) = pair();
+        let mut header = RatchetHeader::from_bytes(&header).unwrap();
+        header.chain = None;
+        assert!(matches!(bob.decrypt(&header.to_bytes(), &ct), Err(RatchetError::InvalidHeader)));
+    }
+
+    #[test]
+    fn state_survives_serialization() {
+        let (alice, mut bob, ct, header) = pair();
+        let mut alice = RatchetState::from_bytes(&alice.to_bytes()).unwrap();
+        let m1 = alice.encrypt(b"1");
+        let m2 = alice.encrypt(b"2");
+        bob.decrypt(&m2.1, &m2.0).unwrap();
+        let mut bob = RatchetState::from_bytes(&bob.to_bytes()).unwrap();
+        assert_eq!(bob.decrypt(&m1.1, &m1.0).unwrap(), b"1");
+        assert_eq!(bob.decrypt(&header, &ct).unwrap(), b"hallo");
+        let (ct, header) = bob.encrypt(b"antwort");
+        let mut alice = RatchetState::from_bytes(&alice.to_bytes()).unwrap();
+        assert_eq!(alice.decrypt(&header, &ct).unwrap(), b"antwort");
+        assert_eq!(alice.to_bytes(), RatchetState::from_bytes(&alice.to_bytes()).unwrap().to_bytes());
+    }
+
+    #[test]
+    fn old_state_versions_are_not_read() {
+        let mut legacy = vec![1u8];
+        legacy.extend_from_slice(&[0u8; 5 * 32 + 8]);
+        assert!(RatchetState::from_bytes(&legacy).is_none());
+    }
+
+    #[test]
+    fn session_ephemeral_until_first_reply() {
+        let (mut alice, mut bob, ct, header) = pair();
+        let (ct1, header1) = alice.encrypt(b"noch einmal");
+        assert_eq!(RatchetHeader::from_bytes(&header1).unwrap().session_ephemeral, Some([1u8; 32]));
+        bob.decrypt(&header, &ct).unwrap();
+        bob.decrypt(&header1, &ct1).unwrap();
+        let (ct, header) = bob.encrypt(b"antwort");
+        assert_eq!(RatchetHeader::from_bytes(&header).unwrap().session_ephemeral, None);
+        alice.decrypt(&header, &ct).unwrap();
+        let (_, header) = alice.encrypt(b"weiter");
+        assert_eq!(RatchetHeader::from_bytes(&header).unwrap().session_ephemeral, None);
+    }
+
+    #[test]
+    fn session_root_depends_on_secret_and_context() {
+        let root = session_root(&[1u8; 32], b"ctx");
+        assert_eq!(root.expose(), session_root(&[1u8; 32], b"ctx").expose());
+        assert_ne!(root.expose(), session_root(&[2u8; 32], b"ctx").expose());
+        assert_ne!(root.expose(), session_root(&[1u8; 32], b"other").expose());
+    }
+
+    #[test]
+    fn simultaneous_initiation_converges() {
+        // Beide Seiten bauen gleichzeitig eine Session auf
+        let mut alice = SessionRecord::new(RatchetState::initiator(SecretBytes::new([1u8; 32]), [1u8; 32]));
+        let mut bob = SessionRecord::new(RatchetState::initiator(SecretBytes::new([2u8; 32]), [2u8; 32]));
+        let (ct_a, header_a) = alice.current().encrypt(b"von alice");
+        let (ct_b, header_b) = bob.current().encrypt(b"von bob");
+        bob.adopt(responder_for(&header_a, [1u8; 32]));
+        alice.adopt(responder_for(&header_b, [2u8; 32]));
+        assert_eq!(bob.decrypt(&header_a, &ct_a).unwrap(), b"von alice");
+        assert_eq!(alice.decrypt(&header_b, &ct_b).unwrap(), b"von bob");
+        for round in 0..3u8 {
+            let (ct, header) = alice.current().encrypt(&[round]);
+            assert_eq!(bob.decrypt(&header, &ct).unwrap(), [round]);
+            let (ct, header) = bob.current().encrypt(&[round, round]);
+            assert_eq!(alice.decrypt(&header, &ct).unwrap(), [round, round]);
+        }
+        assert_eq!(alice.current().origin(), bob.current().origin());
+        let mut alice = SessionRecord::from_bytes(&alice.to_bytes()).unwrap();
+        let (ct, header) = alice.current().encrypt(b"gespeichert");
+        assert_eq!(bob.decrypt(&header, &ct).unwrap(), b"gespeichert");
+    }
+
+    #[cfg(feature = "pqc")]
+    #[test]
+    fn pq_epochs_keep_both_sides_in_sync() {
+        use crate::pq_ratchet::PqConfig;
+        let config = PqConfig { interval_messages: 1, interval_ms: 0 };
+        let mut alice =
+            RatchetState::initiator(SecretBytes::new([7u8; 32]), [1u8; 32]).with_pq(PqRatchet::new(true, config));
+        let (ct, header) = alice.encrypt(b"hallo");
+        let mut bob = responder_for(&header, [7u8; 32]).with_pq(PqRatchet::new(false, config));
+        bob.decrypt(&header, &ct).unwrap();
+        for round in 0..40u8 {
+            let (ct, header) = bob.encrypt(&[round]);
+            assert_eq!(alice.decrypt(&header, &ct).unwrap(), [round]);
+            let (ct, header) = alice.encrypt(&[round, 1]);
+            bob = RatchetState::from_bytes(&bob.to_bytes()).unwrap();
+            assert_eq!(bob.decrypt(&header, &ct).unwrap(), [round, 1]);
+        }
+        assert!(alice.pq_epoch().unwrap() >= 2);
+        assert_eq!(alice.pq_epoch(), bob.pq_epoch());
+    }
+
+    #[test]
+    fn header_round_trip() {
+        let header = RatchetHeader {
+            ratchet_pub: [3u8; 32],
+            reply_address: Some(vec![1; 64]),
+            sender_device: Some(2),
+            chain: Some(ChainPosition { n: 5, previous: 9, pq_epoch: Some(1) }),
+            session_ephemeral: Some([4u8; 32]),
+            ..Default::default()
+        };
+        assert_eq!(RatchetHeader::from_bytes(&header.to_bytes()), Some(header));
     }
 }
@@ -0,0 +1,180 @@
+//! Sealed Sender: Absenderauthentisierung innerhalb der Payload.
+//!
+//! `Payload::sender_fp` ist nur ein Hinweis; wer den Spend‑Key eines
+//! Empfängers kennt, kann ihn beliebig setzen.  Deshalb trägt die
+//! verschlüsselte Payload zusätzlich ein [`SenderCertificate`]: Identity‑Key
+//! und Geräte‑ID des Absenders und eine Ed25519‑Signatur des Identity‑Keys
+//! über den Kontext des Envelopes:
+//!
+//! ```text
+//! "pc.sealed.v1" | ver(1) | epk(32) | SHA‑256(kem_ct) | msg_id(16, LE)
+//!                | identity_pub(32) | device_id(4, BE) | SHA‑256(ratchet_header) | SHA‑256(body)
+//! ```
+//!
+//! Weil `epk` eingeht, lässt sich ein signierter Inhalt nicht in ein
+//! anderes Envelope umpacken: Ein neues Envelope mit demselben `epk` an
+//! einen anderen Empfänger kann nur bauen, wer das ephemere Geheimnis des
+//! Absenders kennt.  Das Zertifikat liegt ausschließlich im Ciphertext;
+//! Relays erfahren über den Absender weiterhin nichts.
+//!
+//! Format: `version(1) | identity_pub(32) | device_id(4, BE) | signature(64)`.
+
+use crate::fingerprint::sender_fingerprint;
+use crate::keys::{verify_identity_signature, IdentityKey};
+use crate::util::sha256;
+
+/// Länge der Binärform eines [`SenderCertificate`].
+pub const CERTIFICATE_LEN: usize = 1 + 32 + 4 + 64;
+
+const VERSION: u8 = 1;
+const DOMAIN: &[u8] = b"pc.sealed.v1";
+
+/// Absender einer Nachricht beim Versiegeln.
+#[derive(Debug, Clone, Copy)]
+pub struct Sender<'a> {
+    pub identity: &'a IdentityKey,
+    /// Geräte‑ID des sendenden Geräts (siehe [`crate::device`]).
+    pub device_id: u32,
+}
+
+impl Sender<'_> {
+    /// Kurz‑Fingerprint für `Payload::sender_fp`.
+    pub fn fingerprint(&self) -> u32 {
+        sender_fingerprint(&self.identity.public)
+    }
+}
+
+/// Kontext eines Envelopes, über den der Absender signiert.
+#[derive(Debug, Clone, Copy)]
+pub struct SealContext<'a> {
+    pub ver: u8,
+    pub epk: &'a [u8; 32],
+    pub kem_ct: &'a [u8],
+    pub msg_id: u128,
+    pub ratchet_header: &'a [u8],
+    pub body: &'a [u8],
+}
+
+/// Signierte Absenderangabe in der Payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SenderCertificate {
+    pub identity_pub: [u8; 32],
+    pub device_id: u32,
+    pub signature: [u8; 64],
+}
+
+impl SenderCertificate {
+    /// Signiert `context` im Namen von `sender`.
+    pub fn sign(sender: &Sender, context: &SealContext) -> Self {
+        let identity_pub = sender.identity.public;
+        let signature = sender.identity.sign(&signed_bytes(&identity_pub, sender.device_id, context));
+        Self { identity_pub, device_id: sender.device_id, signature }
+    }
+    /// Prüft die Signatur gegen den Kontext des empfangenen Envelopes.
+    pub fn verify(&self, context: &SealContext) -> bool {
+        verify_identity_signature(&self.identity_pub, &signed_bytes(&self.identity_pub, self.device_id, context), &self.signature)
+    }
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(CERTIFICATE_LEN);
+        out.push(VERSION);
+        out.extend_from_slice(&self.identity_pub);
+        out.extend_from_slice(&self.device_id.to_be_bytes());
+        out.extend_from_slice(&self.signature);
+        out
+    }
+    /// Liest ein Zertifikat; `None` bei falscher Länge oder Version.  Die
+    /// Signatur wird hier nicht geprüft, dafür fehlt der Kontext.
+    pub fn from_bytes(data: &[u8]) -> Option<Self> {
+        if data.len() != CERTIFICATE_LEN || data[0] != VERSION {
+            return None;
+        }
+        Some(Self {
+            identity_pub: data[1..33].try_into().ok()?,
+            device_id: u32::from_be_bytes(data[33..37].try_into().ok()?),
+            signature: data[37..].try_into().ok()?,
+        })
+    }
+}
+
+fn signed_bytes(identity_pub: &[u8; 32], device_id: u32, context: &SealContext) -> Vec<u8> {
+    let mut msg = DOMAIN.to_vec();
+    msg.push(context.ver);
+    msg.extend_from_slice(context.epk);
+    msg.extend_from_slice(&sha256(context.kem_ct));
+    msg.extend_from_slice(&context.msg_id.to_le_bytes());
+    msg.extend_from_slice(identity_pub);
+    msg.extend_from_slice(&device_id.to_be_bytes());
+    msg.extend_from_slice(&sha256(context.ratchet_header));
+    msg.extend_from_slice(&sha256(context.body));
+    msg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::envelope::{Envelope, Payload};
+    use crate::keys::SpendKey;
+    use crate::subaddress::Subaddress;
+
+    /// Versiegelt eine Payload an `to`; `certify` erhält den Kontext des
+    /// entstehenden Envelopes und liefert das Zertifikat.
+    fn seal_with(to: &SpendKey, certify: impl FnOnce(&SealContext) -> SenderCertificate) -> Envelope {
+        let (msg_id, header, body) = (7, vec![1u8; 8], b"hallo".to_vec());
+        Envelope::seal(&Subaddress::primary(&to.public), None, msg_id, 60, 0, |ver, epk, kem_ct| {
+            let context = SealContext { ver, epk, kem_ct, msg_id, ratchet_header: &header, body: &body };
+            let sender = Some(certify(&context));
+            Payload { msg_id, sender_fp: 0, ratchet_header: header.clone(), body: body.clone(), sender }.to_bytes()
+        })
+    }
+
+    #[test]
+    fn valid_certificate_is_accepted() {
+        let (identity, to) = (IdentityKey::generate(), SpendKey::generate());
+        let env = seal_with(&to, |context| SenderCertificate::sign(&Sender { identity: &identity, device_id: 2 }, context));
+        let cert = env.open(&to).unwrap().sender.unwrap();
+        assert_eq!((cert.identity_pub, cert.device_id), (identity.public, 2));
+        assert_eq!(SenderCertificate::from_bytes(&cert.to_bytes()), Some(cert));
+    }
+
+    #[test]
+    fn forged_signature_is_rejected() {
+        let (identity, to) = (IdentityKey::generate(), SpendKey::generate());
+        let env = seal_with(&to, |context| {
+            let mut cert = SenderCertificate::sign(&Sender { identity: &identity, device_id: 2 }, context);
+            cert.signature[0] ^= 1;
+            cert
+        });
+        assert!(env.open(&to).is_none());
+        // Auch die Geräte‑ID ist unterschrieben
+        let env = seal_with(&to, |context| {
+            let mut cert = SenderCertificate::sign(&Sender { identity: &identity, device_id: 2 }, context);
+            cert.device_id = 3;
+            cert
+        });
+        assert!(env.open(&to).is_none());
+    }
+
+    #[test]
+    fn certificate_for_another_identity_is_rejected() {
+        let (mallory, alice, to) = (IdentityKey::generate(), IdentityKey::generate(), SpendKey::generate());
+        let env = seal_with(&to, |context| {
+            let mut cert = SenderCertificate::sign(&Sender { identity: &mallory, device_id: 0 }, context);
+            cert.identity_pub = alice.public;
+            cert
+        });
+        assert!(env.open(&to).is_none());
+    }
+
+    #[test]
+    fn repacked_payload_is_rejected() {
+        let (identity, bob, carol) = (IdentityKey::generate(), SpendKey::generate(), SpendKey::generate());
+        let env = seal_with(&bob, |context| SenderCertificate::sign(&Sender { identity: &identity, device_id: 0 }, context));
+        let payload = env.open(&bob).unwrap();
+        // Bob packt die signierte Payload in ein neues Envelope an Carol
+        let repacked = Envelope::seal(&Subaddress::primary(&carol.public), None, payload.msg_id, 60, 0, |_, _, _| payload.to_bytes());
+        assert!(repacked.decrypt(&carol).is_none());
+        // Die Signatur gilt nur im Kontext des ursprünglichen Envelopes
+        let context = SealContext { ver: 1, epk: &repacked.epk, kem_ct: &[], msg_id: payload.msg_id, ratchet_header: &payload.ratchet_header, body: &payload.body };
+        assert!(!payload.sender.unwrap().verify(&context));
+    }
+}
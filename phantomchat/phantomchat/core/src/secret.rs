@@ -0,0 +1,193 @@
+//! Typen für geheimes Schlüsselmaterial.
+//!
+//! [`SecretBytes`] hält langlebige Schlüssel (Identity‑Seed, Ratchet‑Ketten)
+//! auf dem Heap, sperrt die Seite nach Möglichkeit gegen Auslagerung
+//! (`mlock`), überschreibt den Inhalt beim Drop und gibt ihn in `Debug` nie
+//! aus.  Sperren gelten für ganze Seiten und zählen nicht mit; mehrere
+//! Geheimnisse teilen sich aber oft eine Seite.  Deshalb führt das Modul
+//! je Seite einen Zähler und entsperrt sie erst, wenn das letzte
+//! Geheimnis darauf freigegeben ist.  `Clone` ist bewusst nicht implementiert; wer eine Kopie braucht,
+//! muss [`SecretBytes::expose`] ausdrücklich aufrufen.  Kurzlebige
+//! Zwischenwerte (ECDH‑Geheimnisse, HKDF‑Ausgaben) stecken in
+//! [`zeroize::Zeroizing`].
+//!
+//! Vergleiche von Tags und MACs laufen über [`ct_eq`] in konstanter Zeit.
+//!
+//! Serde schreibt [`SecretBytes`] wie ein `[u8; N]`; das ist nur für
+//! Speicher gedacht, die selbst verschlüsselt sind.
+
+use rand_core::{OsRng, RngCore};
+use serde::de::{self, SeqAccess, Visitor};
+use serde::ser::SerializeTuple;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(unix)]
+use std::collections::BTreeMap;
+#[cfg(unix)]
+use std::sync::{Mutex, OnceLock, PoisonError};
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Geheimnis fester Länge mit Zeroize beim Drop und redigiertem `Debug`.
+pub struct SecretBytes<const N: usize>(Box<[u8; N]>);
+
+impl<const N: usize> SecretBytes<N> {
+    /// Übernimmt `bytes`; die übergebene Kopie wird überschrieben.
+    pub fn new(mut bytes: [u8; N]) -> Self {
+        let mut secret = Self::zeroed();
+        secret.0.copy_from_slice(&bytes);
+        bytes.zeroize();
+        secret
+    }
+    /// Erzeugt ein zufälliges Geheimnis.
+    pub fn random() -> Self {
+        let mut secret = Self::zeroed();
+        OsRng.fill_bytes(secret.0.as_mut());
+        secret
+    }
+    fn zeroed() -> Self {
+        let boxed = Box::new([0u8; N]);
+        lock(boxed.as_ptr(), N);
+        Self(boxed)
+    }
+    /// Liest das Geheimnis.  Kopien des Ergebnisses sind Sache des
+    /// Aufrufers.
+    pub fn expose(&self) -> &[u8; N] {
+        &self.0
+    }
+    /// Vergleich in konstanter Zeit.
+    pub fn ct_eq(&self, other: &[u8]) -> bool {
+        ct_eq(self.0.as_ref(), other)
+    }
+}
+
+impl<const N: usize> Drop for SecretBytes<N> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+        unlock(self.0.as_ptr(), N);
+    }
+}
+
+impl<const N: usize> ZeroizeOnDrop for SecretBytes<N> {}
+
+impl<const N: usize> std::fmt::Debug for SecretBytes<N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "SecretBytes<{N}>(***)")
+    }
+}
+
+impl<const N: usize> Serialize for SecretBytes<N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut tuple = serializer.serialize_tuple(N)?;
+        for byte in self.0.iter() {
+            tuple.serialize_element(byte)?;
+        }
+        tuple.end()
+    }
+}
+
+impl<'de, const N: usize> Deserialize<'de> for SecretBytes<N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BytesVisitor<const N: usize>;
+        impl<'de, const N: usize> Visitor<'de> for BytesVisitor<N> {
+            type Value = SecretBytes<N>;
+            fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "{N} Byte")
+            }
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                // Direkt in den gesperrten Speicher lesen, ohne Zwischenkopie
+                let mut secret = SecretBytes::zeroed();
+                for (i, byte) in secret.0.iter_mut().enumerate() {
+                    *byte = seq.next_element()?.ok_or_else(|| de::Error::invalid_length(i, &self))?;
+                }
+                Ok(secret)
+            }
+        }
+        deserializer.deserialize_tuple(N, BytesVisitor::<N>)
+    }
+}
+
+/// Vergleicht zwei Bytefolgen in konstanter Zeit (abhängig nur von der
+/// Länge).
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    a.ct_eq(b).into()
+}
+
+/// Anzahl der Geheimnisse je gesperrter Seite (Seitenadresse → Zähler).
+#[cfg(unix)]
+static LOCKED_PAGES: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+
+#[cfg(unix)]
+fn page_size() -> usize {
+    static PAGE_SIZE: OnceLock<usize> = OnceLock::new();
+    // SAFETY: `sysconf` liest nur eine Systemkonstante.
+    *PAGE_SIZE.get_or_init(|| usize::try_from(unsafe { libc::sysconf(libc::_SC_PAGESIZE) }).unwrap_or(0).max(4096))
+}
+
+/// Anfangsadressen der Seiten, die `len` Bytes ab `ptr` berühren.
+#[cfg(unix)]
+fn pages(ptr: *const u8, len: usize) -> impl Iterator<Item = usize> {
+    let size = page_size();
+    let start = ptr as usize;
+    (start / size * size..start + len).step_by(size)
+}
+
+/// Sperrt den Speicherbereich gegen Auslagerung.  Schlägt das fehl (etwa
+/// wegen `RLIMIT_MEMLOCK`), bleibt der Speicher ungesperrt.
+#[cfg(unix)]
+fn lock(ptr: *const u8, len: usize) {
+    let mut locked = LOCKED_PAGES.lock().unwrap_or_else(PoisonError::into_inner);
+    for page in pages(ptr, len) {
+        let count = locked.entry(page).or_insert(0);
+        if *count == 0 {
+            // SAFETY: Die Seite gehört zu einer lebenden Allokation; `mlock`
+            // ändert nur ihre Auslagerbarkeit.
+            unsafe {
+                libc::mlock(page as *const libc::c_void, page_size());
+            }
+        }
+        *count += 1;
+    }
+}
+
+/// Gibt den Bereich frei; Seiten, auf denen noch andere Geheimnisse
+/// liegen, bleiben gesperrt.
+#[cfg(unix)]
+fn unlock(ptr: *const u8, len: usize) {
+    let mut locked = LOCKED_PAGES.lock().unwrap_or_else(PoisonError::into_inner);
+    for page in pages(ptr, len) {
+        let Some(count) = locked.get_mut(&page) else { continue };
+        *count -= 1;
+        if *count == 0 {
+            locked.remove(&page);
+            // SAFETY: wie `lock`; die Allokation lebt bis nach diesem Aufruf.
+            unsafe {
+                libc::munlock(page as *const libc::c_void, page_size());
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn lock(_ptr: *const u8, _len: usize) {}
+
+#[cfg(not(unix))]
+fn unlock(_ptr: *const u8, _len: usize) {}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn locked(secret: &SecretBytes<32>) -> bool {
+        let page = pages(secret.0.as_ptr(), 32).next().unwrap();
+        LOCKED_PAGES.lock().unwrap().contains_key(&page)
+    }
+
+    #[test]
+    fn shared_pages_stay_locked() {
+        let first = SecretBytes::<32>::random();
+        let neighbours: Vec<_> = (0..16).map(|_| SecretBytes::<32>::random()).collect();
+        assert!(locked(&first));
+        drop(neighbours);
+        assert!(locked(&first));
+    }
+}
@@ -0,0 +1,149 @@
+//! Subadressen: mehrere Empfangsadressen pro Identität.
+//!
+//! Aus View‑Key und Spend‑Public‑Key einer Identität lassen sich beliebig
+//! viele Empfangsadressen ableiten, ähnlich den Subadressen in Monero.
+//! Jede Adresse besteht aus einem Basispunkt `D_i` und einem Schlüssel
+//! `C_i`:
+//!
+//! ```text
+//! k_i = HMAC‑SHA256(view_secret, "pc.subaddr.v1" ‖ i)
+//! D_i = k_i·G        C_i = k_i·B        (B = Spend‑Public‑Key)
+//! ```
+//!
+//! Der Sender wählt `r` zufällig, schreibt `R = r·D_i` als Ephemeral‑Key
+//! ins Envelope und bildet das ECDH‑Geheimnis als `r·C_i`.  Der
+//! Empfänger rechnet wie bisher `b·R` mit seinem Spend‑Key.  Die
+//! Hauptadresse ist der Sonderfall `D_0 = G`, `C_0 = B`; ein einziger
+//! ECDH pro Envelope deckt damit Hauptadresse und alle Subadressen ab.
+//! Welche Adresse gemeint war, zeigt erst das Tag, das bei Subadressen
+//! den Basispunkt einbezieht (ein HMAC je aktiver Adresse, siehe
+//! [`crate::Envelope::open_subaddress`]).
+//!
+//! Ohne den privaten View‑Key lassen sich zwei Subadressen weder
+//! miteinander noch mit dem Spend‑Key in Verbindung bringen.  Welche
+//! Adresse an wen geht und wann sie rotiert wird, entscheidet der Client.
+
+use crate::keys::ViewKey;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, StaticSecret, X25519_BASEPOINT_BYTES};
+use zeroize::Zeroizing;
+
+/// Länge der Binärform (`base ‖ spend_pub`).
+pub const ADDRESS_LEN: usize = 64;
+
+/// Öffentliche Empfangsadresse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Subaddress {
+    /// Basispunkt `D_i`; bei der Hauptadresse der X25519‑Basispunkt.
+    pub base: [u8; 32],
+    /// Schlüssel `C_i`, mit dem der Sender das ECDH bildet.
+    pub spend_pub: [u8; 32],
+}
+
+impl Subaddress {
+    /// Hauptadresse zu einem Spend‑Public‑Key.
+    pub fn primary(spend_pub: &PublicKey) -> Self {
+        Self { base: X25519_BASEPOINT_BYTES, spend_pub: *spend_pub.as_bytes() }
+    }
+
+    /// Leitet die Adresse mit Index `index` ab; Index 0 ist die
+    /// Hauptadresse.  Der private Spend‑Key wird dafür nicht gebraucht.
+    pub fn derive(view: &ViewKey, spend_pub: &PublicKey, index: u32) -> Self {
+        if index == 0 {
+            return Self::primary(spend_pub);
+        }
+        let view_secret = Zeroizing::new(view.secret.to_bytes());
+        let mut mac = <Hmac<Sha256>>::new_from_slice(view_secret.as_ref()).expect("HMAC key");
+        mac.update(b"pc.subaddr.v1");
+        mac.update(&index.to_be_bytes());
+        let scalar = Zeroizing::new(<[u8; 32]>::from(mac.finalize().into_bytes()));
+        let k = StaticSecret::from(*scalar);
+        Self { base: *PublicKey::from(&k).as_bytes(), spend_pub: *k.diffie_hellman(spend_pub).as_bytes() }
+    }
+
+    pub fn is_primary(&self) -> bool {
+        self.base == X25519_BASEPOINT_BYTES
+    }
+
+    pub fn spend_public(&self) -> PublicKey {
+        PublicKey::from(self.spend_pub)
+    }
+
+    /// Ephemeral‑Key `R = r·D` und ECDH‑Geheimnis `r·C` für den Sender.
+    pub fn agree(&self, eph: &StaticSecret) -> ([u8; 32], Zeroizing<[u8; 32]>) {
+        let epk = if self.is_primary() {
+            *PublicKey::from(eph).as_bytes()
+        } else {
+            *eph.diffie_hellman(&PublicKey::from(self.base)).as_bytes()
+        };
+        let shared = eph.diffie_hellman(&self.spend_public());
+        (epk, Zeroizing::new(*shared.as_bytes()))
+    }
+
+    pub fn to_bytes(&self) -> [u8; ADDRESS_LEN] {
+        let mut out = [0u8; ADDRESS_LEN];
+        out[..32].copy_from_slice(&self.base);
+        out[32..].copy_from_slice(&self.spend_pub);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != ADDRESS_LEN {
+            return None;
+        }
+        Some(Self { base: bytes[..32].try_into().ok()?, spend_pub: bytes[32..].try_into().ok()? })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::{IdentityKey, SpendKey};
+    use crate::padding::PaddingPolicy;
+    use crate::sealed::Sender;
+    use crate::Envelope;
+
+    fn send_to(to: &Subaddress, identity: &IdentityKey) -> Envelope {
+        let sender = Sender { identity, device_id: 1 };
+        let env = Envelope::new_padded(to, 42, &sender, vec![], b"hallo".to_vec(), 60, 0, &PaddingPolicy::None);
+        Envelope::from_bytes(&env.to_bytes()).unwrap()
+    }
+
+    #[test]
+    fn derived_subaddress_opens_with_its_index() {
+        let (view, spend, identity) = (ViewKey::generate(), SpendKey::generate(), IdentityKey::generate());
+        let addresses: Vec<Subaddress> = (1..=3).map(|i| Subaddress::derive(&view, &spend.public, i)).collect();
+        let bases: Vec<[u8; 32]> = addresses.iter().map(|a| a.base).collect();
+        assert_eq!(Subaddress::derive(&view, &spend.public, 2), addresses[1]);
+        assert_eq!(Subaddress::derive(&view, &spend.public, 0), Subaddress::primary(&spend.public));
+
+        for (index, address) in addresses.iter().enumerate() {
+            let env = send_to(address, &identity);
+            let (payload, found) = env.open_subaddress(&spend, &bases).unwrap();
+            assert_eq!((payload.body.as_slice(), found), (&b"hallo"[..], Some(index)));
+            // Ohne den Basispunkt wird die Subadresse nicht erkannt.
+            assert!(env.open(&spend).is_none());
+            assert!(env.open_subaddress(&SpendKey::generate(), &bases).is_none());
+        }
+
+        let primary = send_to(&Subaddress::primary(&spend.public), &identity);
+        assert_eq!(primary.open_subaddress(&spend, &bases).unwrap().1, None);
+    }
+
+    #[test]
+    fn subaddresses_share_no_public_bytes() {
+        let (view, spend) = (ViewKey::generate(), SpendKey::generate());
+        let (a, b) = (Subaddress::derive(&view, &spend.public, 1), Subaddress::derive(&view, &spend.public, 2));
+        let primary = Subaddress::primary(&spend.public).to_bytes();
+        let (a, b) = (a.to_bytes(), b.to_bytes());
+        // Kein Basispunkt und kein Schlüssel taucht, auch nicht in Teilen,
+        // in der anderen Adresse oder der Hauptadresse auf.
+        for window in a.windows(8) {
+            assert!(!b.windows(8).any(|w| w == window));
+            assert!(!primary.windows(8).any(|w| w == window));
+        }
+        assert_eq!(Subaddress::from_bytes(&a).unwrap().to_bytes(), a);
+        assert!(Subaddress::from_bytes(&a[1..]).is_none());
+    }
+}
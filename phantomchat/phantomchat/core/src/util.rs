@@ -28,3 +28,15 @@ pub fn leading_zero_bits(bytes: &[u8]) -> u32 {
     }
     count
 }
+
+/// Dekodiert einen hexkodierten String (Groß‑ oder Kleinschreibung).
+/// Gibt `None` bei ungerader Länge oder ungültigen Zeichen zurück.
+pub fn from_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| s.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+        .collect()
+}
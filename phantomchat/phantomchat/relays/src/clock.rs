@@ -0,0 +1,72 @@
+//! Zeitquellen für die Relay‑Schicht.
+//!
+//! Relays benötigen die aktuelle Zeit, um abgelaufene Envelopes zu
+//! verwerfen und Historien ab einem Zeitpunkt auszuliefern.  Damit Tests
+//! nicht von der Systemuhr abhängen, wird die Zeit über das Trait
+//! [`Clock`] injiziert.  [`SystemClock`] liefert die echte Zeit,
+//! [`ManualClock`] eine manuell weitergestellte Uhr für deterministische
+//! Tests.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Liefert die aktuelle Zeit als UNIX‑Zeitstempel in Millisekunden
+/// (dieselbe Einheit wie `Envelope.ts`).
+pub trait Clock: Send + Sync {
+    fn now_ms(&self) -> u64;
+}
+
+/// Systemuhr.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_ms(&self) -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+    }
+}
+
+/// Manuell gesteuerte Uhr.  Klone teilen sich denselben Zeitwert, so
+/// dass ein Test die Uhr weiterstellen kann, während ein Relay sie
+/// bereits hält.
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    now: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    /// Erstellt eine Uhr, die bei `start_ms` steht.
+    pub fn new(start_ms: u64) -> Self {
+        Self { now: Arc::new(AtomicU64::new(start_ms)) }
+    }
+    /// Setzt die Uhr auf einen absoluten Zeitpunkt.
+    pub fn set(&self, now_ms: u64) {
+        self.now.store(now_ms, Ordering::SeqCst);
+    }
+    /// Stellt die Uhr um `delta_ms` Millisekunden vor.
+    pub fn advance(&self, delta_ms: u64) {
+        self.now.fetch_add(delta_ms, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now_ms(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manual_clock_clones_share_time() {
+        let clock = ManualClock::new(1_000);
+        let held: Arc<dyn Clock> = Arc::new(clock.clone());
+        clock.advance(500);
+        assert_eq!(held.now_ms(), 1_500);
+        clock.set(42);
+        assert_eq!(held.now_ms(), 42);
+    }
+}
@@ -0,0 +1,286 @@
+//! Cover‑Traffic nach dem Loopix‑Prinzip.
+//!
+//! Ein passiver globaler Beobachter erkennt Aktivität allein daran, wann
+//! ein Client überhaupt sendet.  Der [`CoverScheduler`] sendet deshalb in
+//! einem Poisson‑Takt (exponentialverteilte Abstände mit Rate λ) und füllt
+//! jeden Slot entweder mit einer wartenden echten Nachricht oder mit einem
+//! Cover‑Envelope.  Echte Nachrichten werden nie außerhalb des Takts
+//! gesendet, so dass das Timing keine Aktivität verrät.  Beim Entnehmen
+//! erhalten sie Zeitstempel und Proof‑of‑Work des Slots
+//! ([`Envelope::restamp`]); sonst zeigte `ts`, wie lange sie gewartet
+//! haben.  Schlägt die Veröffentlichung fehl, kommen sie zurück an den
+//! Anfang der Queue.
+//!
+//! Cover‑Envelopes werden mit [`Envelope::new_cover`] erzeugt: zufälliger
+//! Empfänger, gleicher Proof‑of‑Work und eine Form (Payload‑Länge und ob
+//! hybrid mit KEM‑Ciphertext), die aus den zuletzt beobachteten echten
+//! Nachrichten gezogen wird.  Zusammen mit dem Bucket‑Padding der echten
+//! Nachrichten ergibt das dieselbe Verteilung von Größen und Versionen.  Ein Bandbreitenbudget (Token‑Bucket) begrenzt den
+//! Cover‑Anteil; echte Nachrichten werden durch das Budget nie blockiert.
+
+use crate::{BridgeProvider, Clock, SystemClock};
+use phantomchat_core::padding::DEFAULT_BUCKETS;
+use phantomchat_core::Envelope;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+/// Anzahl der echten Nachrichten, aus deren Form Cover‑Envelopes gezogen
+/// werden.
+const SHAPE_HISTORY: usize = 256;
+
+/// Einstellungen des Cover‑Traffics.
+#[derive(Debug, Clone)]
+pub struct CoverConfig {
+    /// Mittlere Anzahl Slots pro Sekunde (λ des Poisson‑Prozesses).
+    pub rate_per_sec: f64,
+    /// Bandbreitenbudget für Cover‑Envelopes in Bytes pro Sekunde.
+    pub budget_bytes_per_sec: u64,
+    /// Maximal ansparbares Budget (Burst) in Bytes.
+    pub budget_burst_bytes: u64,
+    /// TTL der Cover‑Envelopes; sollte der TTL echter Nachrichten
+    /// entsprechen.
+    pub ttl: u32,
+    /// PoW‑Schwierigkeit; muss der echter Nachrichten entsprechen.
+    pub pow_difficulty: u32,
+    /// Payload‑Längen, aus denen gezogen wird, solange noch keine echte
+    /// Nachricht beobachtet wurde; diese Cover‑Envelopes sind klassisch.
+    pub default_sizes: Vec<usize>,
+}
+
+impl Default for CoverConfig {
+    fn default() -> Self {
+        Self {
+            rate_per_sec: 0.2,
+            budget_bytes_per_sec: 2048,
+            budget_burst_bytes: 64 * 1024,
+            ttl: 60,
+            pow_difficulty: 16,
+            default_sizes: DEFAULT_BUCKETS[..2].to_vec(),
+        }
+    }
+}
+
+/// Zähler für gesendete Slots.
+#[derive(Debug, Clone, Default)]
+pub struct CoverStats {
+    pub real_sent: u64,
+    pub cover_sent: u64,
+    /// Slots, in denen mangels Budget nichts gesendet wurde.
+    pub skipped: u64,
+    pub publish_errors: u64,
+    /// Echte Nachrichten, die nach einem Fehler wieder eingereiht wurden.
+    pub requeued: u64,
+}
+
+/// Token‑Bucket für das Cover‑Budget.
+#[derive(Debug)]
+struct Budget {
+    tokens: f64,
+    last: Instant,
+}
+
+/// Poisson‑getakteter Sender für echte und Cover‑Envelopes.
+pub struct CoverScheduler<P: BridgeProvider> {
+    relay: Arc<P>,
+    config: CoverConfig,
+    queue: Mutex<VecDeque<Envelope>>,
+    /// Payload‑Länge und Hybrid‑Flag der letzten echten Nachrichten.
+    shapes: Mutex<VecDeque<(usize, bool)>>,
+    budget: Mutex<Budget>,
+    rng: Mutex<StdRng>,
+    stats: Mutex<CoverStats>,
+}
+
+impl<P: BridgeProvider + 'static> CoverScheduler<P> {
+    pub fn new(relay: Arc<P>, config: CoverConfig) -> Self {
+        Self::with_rng(relay, config, StdRng::from_entropy())
+    }
+    /// Deterministischer Takt für Tests.
+    pub fn with_seed(relay: Arc<P>, config: CoverConfig, seed: u64) -> Self {
+        Self::with_rng(relay, config, StdRng::seed_from_u64(seed))
+    }
+    fn with_rng(relay: Arc<P>, config: CoverConfig, rng: StdRng) -> Self {
+        let budget = Budget { tokens: config.budget_burst_bytes as f64, last: Instant::now() };
+        Self {
+            relay,
+            config,
+            queue: Mutex::new(VecDeque::new()),
+            shapes: Mutex::new(VecDeque::new()),
+            budget: Mutex::new(budget),
+            rng: Mutex::new(rng),
+            stats: Mutex::new(CoverStats::default()),
+        }
+    }
+    /// Reiht eine echte Nachricht für den nächsten freien Slot ein.
+    pub fn enqueue(&self, env: Envelope) {
+        let mut shapes = self.shapes.lock().unwrap();
+        shapes.push_back((env.ciphertext.len(), env.ver >= 2));
+        if shapes.len() > SHAPE_HISTORY {
+            shapes.pop_front();
+        }
+        self.queue.lock().unwrap().push_back(env);
+    }
+    /// Anzahl wartender echter Nachrichten.
+    pub fn pending(&self) -> usize {
+        self.queue.lock().unwrap().len()
+    }
+    pub fn stats(&self) -> CoverStats {
+        self.stats.lock().unwrap().clone()
+    }
+    /// Startet den Takt als Hintergrund‑Task.
+    pub fn spawn(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                let wait = self.next_interval();
+                tokio::time::sleep(wait).await;
+                self.tick().await;
+            }
+        })
+    }
+    /// Zieht den Abstand zum nächsten Slot (exponentialverteilt).
+    pub fn next_interval(&self) -> Duration {
+        let rate = self.config.rate_per_sec.max(f64::MIN_POSITIVE);
+        let u: f64 = self.rng.lock().unwrap().gen_range(f64::EPSILON..1.0);
+        Duration::from_secs_f64(-u.ln() / rate)
+    }
+    /// Bedient einen Slot: echte Nachricht, sonst Cover‑Envelope im
+    /// Rahmen des Budgets.  Eine echte Nachricht, die sich nicht
+    /// veröffentlichen lässt, kommt zurück an den Anfang der Queue.
+    pub async fn tick(&self) {
+        let real = self.queue.lock().unwrap().pop_front();
+        let (ttl, difficulty) = (self.config.ttl, self.config.pow_difficulty);
+        let (env, is_real) = match real {
+            Some(mut env) => {
+                let ts = SystemClock.now_ms();
+                let restamped = tokio::task::spawn_blocking(move || {
+                    env.restamp(ts, difficulty);
+                    env
+                });
+                match restamped.await {
+                    Ok(env) => (env, true),
+                    Err(_) => return,
+                }
+            }
+            None => {
+                let (size, hybrid) = self.sample_shape();
+                if !self.take_budget(size) {
+                    self.stats.lock().unwrap().skipped += 1;
+                    return;
+                }
+                // Der Proof‑of‑Work ist rechenintensiv und läuft daher
+                // außerhalb des Async‑Executors.
+                match tokio::task::spawn_blocking(move || Envelope::new_cover(size, ttl, difficulty, hybrid)).await {
+                    Ok(env) => (env, false),
+                    Err(_) => return,
+                }
+            }
+        };
+        let retry = is_real.then(|| env.clone());
+        let result = self.relay.publish(env).await;
+        let mut stats = self.stats.lock().unwrap();
+        match (result, retry) {
+            (Err(_), Some(env)) => {
+                stats.publish_errors += 1;
+                stats.requeued += 1;
+                self.queue.lock().unwrap().push_front(env);
+            }
+            (Err(_), None) => stats.publish_errors += 1,
+            (Ok(()), Some(_)) => stats.real_sent += 1,
+            (Ok(()), None) => stats.cover_sent += 1,
+        }
+    }
+    /// Zieht Payload‑Länge und Hybrid‑Flag aus den beobachteten echten
+    /// Nachrichten.
+    fn sample_shape(&self) -> (usize, bool) {
+        let shapes = self.shapes.lock().unwrap();
+        let mut rng = self.rng.lock().unwrap();
+        if shapes.is_empty() {
+            let defaults = &self.config.default_sizes;
+            if defaults.is_empty() {
+                return (DEFAULT_BUCKETS[0], false);
+            }
+            return (defaults[rng.gen_range(0..defaults.len())], false);
+        }
+        shapes[rng.gen_range(0..shapes.len())]
+    }
+    /// Entnimmt `bytes` aus dem Token‑Bucket, falls genug vorhanden ist.
+    fn take_budget(&self, bytes: usize) -> bool {
+        let mut budget = self.budget.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now.duration_since(budget.last).as_secs_f64();
+        budget.last = now;
+        budget.tokens = (budget.tokens + elapsed * self.config.budget_bytes_per_sec as f64)
+            .min(self.config.budget_burst_bytes as f64);
+        if budget.tokens < bytes as f64 {
+            return false;
+        }
+        budget.tokens -= bytes as f64;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SimConfig, SimulatedRelay};
+
+    #[tokio::test]
+    async fn real_envelopes_are_restamped_and_requeued() {
+        let relay = Arc::new(SimulatedRelay::new("sim", SimConfig::default(), 1));
+        let config = CoverConfig { pow_difficulty: 4, budget_burst_bytes: 0, ..CoverConfig::default() };
+        let scheduler = CoverScheduler::with_seed(relay.clone(), config, 1);
+        let mut env = Envelope::new_cover(64, 60, 4, false);
+        // Lange in der Queue: Mit dem alten Zeitstempel wäre es abgelaufen
+        env.ts -= 3_600_000;
+        scheduler.enqueue(env);
+
+        relay.set_partitioned(true);
+        scheduler.tick().await;
+        assert_eq!(scheduler.pending(), 1);
+        assert_eq!((scheduler.stats().publish_errors, scheduler.stats().requeued), (1, 1));
+
+        relay.set_partitioned(false);
+        let before = SystemClock.now_ms();
+        scheduler.tick().await;
+        assert_eq!((scheduler.pending(), scheduler.stats().real_sent), (0, 1));
+        while relay.inner().stored() == 0 {
+            tokio::task::yield_now().await;
+        }
+        let sent = relay.inner().history_since(0).remove(0);
+        assert!(sent.ts >= before);
+        assert!(sent.verify_pow(4));
+    }
+
+    #[tokio::test]
+    async fn cover_stays_within_budget() {
+        let relay = Arc::new(SimulatedRelay::new("sim", SimConfig::default(), 1));
+        let config = CoverConfig { pow_difficulty: 0, budget_bytes_per_sec: 0, budget_burst_bytes: 600, ..CoverConfig::default() };
+        let scheduler = CoverScheduler::with_seed(relay, config, 1);
+        for _ in 0..4 {
+            scheduler.tick().await;
+        }
+        let stats = scheduler.stats();
+        assert_eq!(stats.cover_sent + stats.skipped, 4);
+        assert!(stats.cover_sent >= 1 && stats.skipped >= 1);
+    }
+
+    #[tokio::test]
+    async fn cover_mirrors_the_shape_of_real_traffic() {
+        let relay = Arc::new(SimulatedRelay::new("sim", SimConfig::default(), 1));
+        let config = CoverConfig { pow_difficulty: 0, ..CoverConfig::default() };
+        let scheduler = CoverScheduler::with_seed(relay.clone(), config, 1);
+        scheduler.enqueue(Envelope::new_cover(100, 60, 0, false));
+        scheduler.tick().await;
+        scheduler.tick().await;
+        assert_eq!((scheduler.stats().real_sent, scheduler.stats().cover_sent), (1, 1));
+        while relay.inner().stored() < 2 {
+            tokio::task::yield_now().await;
+        }
+        let sent = relay.inner().history_since(0);
+        assert_eq!((sent[1].ver, sent[1].ciphertext.len()), (sent[0].ver, sent[0].ciphertext.len()));
+    }
+}
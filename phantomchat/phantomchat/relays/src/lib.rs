@@ -2,15 +2,37 @@
 //!
 //! Ein Bridge‑Provider definiert die minimale Schnittstelle für den
 //! Nachrichtentransport.  Implementierungen können auf Nostr‑Relays
-//! basieren, in‑memory (für Tests) oder auf andere Transportprotokolle
-//! abstrahiert werden.  Die aktuelle Implementierung liefert nur
-//! Platzhalter ohne echte Netzwerkfunktionen.
+//! basieren, in‑memory (für Tests), als gestörtes Netz simuliert
+//! ([`SimulatedRelay`]) oder auf andere Transportprotokolle abstrahiert
+//! werden.  Mehrere Relays werden über einen [`RelayPool`] parallel
+//! angesprochen.  Relay‑Verbindungen laufen über die
+//! [`transport`]‑Schicht, die optional einen Tor‑SOCKS5‑Proxy mit
+//! Stream‑Isolation verwendet.
+//!
+//! Neben Envelopes speichern Relays die verschlüsselten Blöcke von
+//! Anhängen ([`Chunk`]), adressiert über ihren Hash.
+
+pub mod clock;
+pub mod cover;
+pub mod mixing;
+pub mod nostr;
+pub mod pool;
+pub mod simulated;
+pub mod transport;
 
 use async_trait::async_trait;
-use phantomchat_core::Envelope;
+use phantomchat_core::{Chunk, Envelope};
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
-use std::collections::VecDeque;
-use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+pub use clock::{Clock, ManualClock, SystemClock};
+pub use cover::{CoverConfig, CoverScheduler, CoverStats};
+pub use mixing::{MixConfig, MixStats, MixingQueue};
+pub use nostr::NostrRelay;
+pub use pool::RelayPool;
+pub use simulated::{Latency, SimConfig, SimStats, SimulatedRelay};
+pub use transport::{RelayConfig, TorConfig, TransportError};
 
 /// Gesundheit eines Relays: Latenz, Uptime und Fehlerrate.
 #[derive(Debug, Clone)]
@@ -32,21 +54,111 @@ pub trait BridgeProvider: Send + Sync {
     async fn subscribe<F>(&self, handler: F) -> anyhow::Result<()>
     where
         F: Fn(Envelope) + Send + 'static;
+    /// Lädt alle noch gespeicherten Envelopes mit `ts >= since_ms` nach
+    /// (Backfill nach einer Offline‑Phase).  Provider ohne Historie
+    /// liefern eine leere Liste.
+    async fn fetch_since(&self, _since_ms: u64) -> anyhow::Result<Vec<Envelope>> {
+        Ok(Vec::new())
+    }
+    /// Legt einen Anhangsblock unter seinem Hash ab.  Provider ohne
+    /// Blockspeicher lehnen ab.
+    async fn publish_chunk(&self, _chunk: Chunk) -> anyhow::Result<()> {
+        anyhow::bail!("{} speichert keine Anhänge", self.id())
+    }
+    /// Lädt Anhangsblöcke anhand ihrer Hashes.  Unbekannte Blöcke fehlen
+    /// im Ergebnis; ob ein Block zu seinem Hash passt, prüft der Aufrufer.
+    async fn fetch_chunks(&self, _ids: &[[u8; 32]]) -> anyhow::Result<Vec<Chunk>> {
+        Ok(Vec::new())
+    }
     /// Liefert eine grobe Health‑Schätzung für das Relay.
     async fn health(&self) -> BridgeHealth;
 }
 
 /// In‑Memory‑Relay für Tests.  Alle veröffentlichten Envelopes werden
-/// an alle Abonnenten verteilt.  Dieses Relay läuft im selben Prozess
-/// und dient lediglich als Dummy.
+/// an alle Abonnenten verteilt (Broadcast) und bis zum Ablauf ihrer TTL
+/// gespeichert, so dass spätere Abonnenten die Historie ab einem
+/// Zeitpunkt nachladen können.  Die Zeit stammt aus einer injizierten
+/// [`Clock`]; mit [`ManualClock`] laufen Tests ohne echte Wartezeiten.
+/// Dieses Relay läuft im selben Prozess und dient lediglich als Dummy.
 pub struct InMemoryRelay {
     id: String,
-    queue: Arc<Mutex<VecDeque<Envelope>>>,
+    clock: Arc<dyn Clock>,
+    store: Arc<Mutex<Vec<Envelope>>>,
+    chunks: Arc<Mutex<HashMap<[u8; 32], Chunk>>>,
+    subscribers: Arc<Mutex<Vec<mpsc::UnboundedSender<Envelope>>>>,
 }
 
 impl InMemoryRelay {
+    /// Erstellt ein Relay, das die Systemuhr verwendet.
     pub fn new(id: &str) -> Self {
-        Self { id: id.to_owned(), queue: Arc::new(Mutex::new(VecDeque::new())) }
+        Self::with_clock(id, Arc::new(SystemClock))
+    }
+    /// Erstellt ein Relay mit einer eigenen Zeitquelle.
+    pub fn with_clock(id: &str, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            id: id.to_owned(),
+            clock,
+            store: Arc::new(Mutex::new(Vec::new())),
+            chunks: Arc::new(Mutex::new(HashMap::new())),
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+    /// Anzahl der aktuell gespeicherten (nicht abgelaufenen) Envelopes.
+    pub fn stored(&self) -> usize {
+        let mut store = self.store.lock().unwrap();
+        Self::purge(&mut store, self.clock.now_ms());
+        store.len()
+    }
+    /// Liefert alle gespeicherten Envelopes mit `ts >= since_ms` in
+    /// Veröffentlichungsreihenfolge.
+    pub fn history_since(&self, since_ms: u64) -> Vec<Envelope> {
+        let mut store = self.store.lock().unwrap();
+        Self::purge(&mut store, self.clock.now_ms());
+        store.iter().filter(|env| env.ts >= since_ms).cloned().collect()
+    }
+    /// Wie [`BridgeProvider::subscribe`], liefert aber zuerst die
+    /// gespeicherte Historie ab `since_ms` aus.  Historie und Live‑Abo
+    /// werden unter derselben Sperre verbunden, so dass kein Envelope
+    /// verloren geht oder doppelt ankommt.
+    pub async fn subscribe_since<F>(&self, since_ms: u64, handler: F) -> anyhow::Result<()>
+    where
+        F: Fn(Envelope) + Send + 'static,
+    {
+        let (tx, rx) = mpsc::unbounded_channel();
+        {
+            let mut store = self.store.lock().unwrap();
+            Self::purge(&mut store, self.clock.now_ms());
+            for env in store.iter().filter(|env| env.ts >= since_ms) {
+                let _ = tx.send(env.clone());
+            }
+            self.subscribers.lock().unwrap().push(tx);
+        }
+        Self::spawn_handler(rx, handler);
+        Ok(())
+    }
+    /// Legt einen Block unter `id` ab.  Ein ehrliches Relay nutzt dessen
+    /// Hash; [`SimulatedRelay`] kann so manipulierte Blöcke ausliefern.
+    pub(crate) fn store_chunk(&self, id: [u8; 32], chunk: Chunk) {
+        let now = self.clock.now_ms();
+        let mut chunks = self.chunks.lock().unwrap();
+        chunks.retain(|_, c| !c.is_expired(now));
+        if !chunk.is_expired(now) {
+            chunks.insert(id, chunk);
+        }
+    }
+    /// Entfernt alle Envelopes, deren TTL abgelaufen ist.
+    fn purge(store: &mut Vec<Envelope>, now_ms: u64) {
+        store.retain(|env| !env.is_expired(now_ms));
+    }
+    fn spawn_handler<F>(mut rx: mpsc::UnboundedReceiver<Envelope>, handler: F)
+    where
+        F: Fn(Envelope) + Send + 'static,
+    {
+        tokio::spawn(async move {
+            while let Some(env) = rx.recv().await {
+                handler(env);
+            }
+        });
     }
 }
 
@@ -56,66 +168,127 @@ impl BridgeProvider for InMemoryRelay {
         &self.id
     }
     async fn publish(&self, env: Envelope) -> anyhow::Result<()> {
-        let mut q = self.queue.lock().unwrap();
-        q.push_back(env);
+        let now = self.clock.now_ms();
+        if env.is_expired(now) {
+            // Bereits abgelaufene Envelopes werden wie bei einem echten
+            // Relay stillschweigend verworfen.
+            return Ok(());
+        }
+        let mut store = self.store.lock().unwrap();
+        Self::purge(&mut store, now);
+        store.push(env.clone());
+        // Broadcast an alle Abonnenten; beendete Abos werden entfernt.
+        let mut subs = self.subscribers.lock().unwrap();
+        subs.retain(|tx| tx.send(env.clone()).is_ok());
         Ok(())
     }
-    async fn subscribe<F>(&self, mut handler: F) -> anyhow::Result<()>
+    async fn subscribe<F>(&self, handler: F) -> anyhow::Result<()>
     where
         F: Fn(Envelope) + Send + 'static,
     {
-        let queue = self.queue.clone();
-        tokio::spawn(async move {
-            loop {
-                if let Some(env) = {
-                    let mut q = queue.lock().unwrap();
-                    q.pop_front()
-                } {
-                    handler(env);
-                } else {
-                    tokio::time::sleep(Duration::from_millis(100)).await;
-                }
-            }
-        });
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.subscribers.lock().unwrap().push(tx);
+        Self::spawn_handler(rx, handler);
+        Ok(())
+    }
+    async fn fetch_since(&self, since_ms: u64) -> anyhow::Result<Vec<Envelope>> {
+        Ok(self.history_since(since_ms))
+    }
+    async fn publish_chunk(&self, chunk: Chunk) -> anyhow::Result<()> {
+        self.store_chunk(chunk.id(), chunk);
         Ok(())
     }
+    async fn fetch_chunks(&self, ids: &[[u8; 32]]) -> anyhow::Result<Vec<Chunk>> {
+        let now = self.clock.now_ms();
+        let chunks = self.chunks.lock().unwrap();
+        Ok(ids.iter().filter_map(|id| chunks.get(id)).filter(|c| !c.is_expired(now)).cloned().collect())
+    }
     async fn health(&self) -> BridgeHealth {
         BridgeHealth { latency_ms: 1, uptime: 1.0, failure_rate: 0.0 }
     }
 }
 
-/// Platzhalter für einen Nostr‑Relay‑Adapter.  Diese Implementierung
-/// definiert lediglich die Struktur und besitzt keine echte
-/// Netzwerkverbindung.
-pub struct NostrRelay {
-    id: String,
-    url: String,
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
 
-impl NostrRelay {
-    pub fn new(url: &str) -> Self {
-        Self { id: url.to_owned(), url: url.to_owned() }
+    const START_MS: u64 = 1_700_000_000_000;
+
+    fn envelope_at(ts: u64, ttl: u32) -> Envelope {
+        let mut env = Envelope::new_cover(32, ttl, 0, false);
+        env.ts = ts;
+        env
     }
-}
 
-#[async_trait]
-impl BridgeProvider for NostrRelay {
-    fn id(&self) -> &str {
-        &self.id
+    /// Handler, der empfangene Envelopes in einen Kanal weiterreicht.
+    fn collector() -> (impl Fn(Envelope) + Send + 'static, mpsc::UnboundedReceiver<Envelope>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        (move |env| drop(tx.send(env)), rx)
     }
-    async fn publish(&self, _env: Envelope) -> anyhow::Result<()> {
-        // TODO: WebSocket‑Verbindung aufbauen, Event serialisieren und senden
-        Ok(())
+
+    async fn next(rx: &mut mpsc::UnboundedReceiver<Envelope>) -> Envelope {
+        tokio::time::timeout(Duration::from_secs(1), rx.recv()).await.unwrap().unwrap()
     }
-    async fn subscribe<F>(&self, _handler: F) -> anyhow::Result<()>
-    where
-        F: Fn(Envelope) + Send + 'static,
-    {
-        // TODO: WebSocket‑Abo einrichten, Filter setzen und Envelopes ausliefern
-        Ok(())
+
+    #[tokio::test]
+    async fn publish_fans_out_to_every_subscriber() {
+        let relay = InMemoryRelay::with_clock("mem", Arc::new(ManualClock::new(START_MS)));
+        let mut receivers = Vec::new();
+        for _ in 0..3 {
+            let (handler, rx) = collector();
+            relay.subscribe(handler).await.unwrap();
+            receivers.push(rx);
+        }
+        let sent = [envelope_at(START_MS, 60), envelope_at(START_MS + 1, 60)];
+        for env in &sent {
+            relay.publish(env.clone()).await.unwrap();
+        }
+        for rx in &mut receivers {
+            for env in &sent {
+                assert_eq!(next(rx).await.to_bytes(), env.to_bytes());
+            }
+            assert!(rx.try_recv().is_err());
+        }
     }
-    async fn health(&self) -> BridgeHealth {
-        // TODO: Messen der Latenz und Erfolgsrate
-        BridgeHealth { latency_ms: 100, uptime: 0.9, failure_rate: 0.1 }
+
+    #[tokio::test]
+    async fn expired_envelopes_are_dropped_and_purged() {
+        let clock = ManualClock::new(START_MS);
+        let relay = InMemoryRelay::with_clock("mem", Arc::new(clock.clone()));
+        relay.publish(envelope_at(START_MS - 60_000, 60)).await.unwrap();
+        assert_eq!(relay.stored(), 0);
+
+        relay.publish(envelope_at(START_MS, 60)).await.unwrap();
+        relay.publish(envelope_at(START_MS, 120)).await.unwrap();
+        assert_eq!(relay.stored(), 2);
+        clock.advance(59_999);
+        assert_eq!(relay.stored(), 2);
+        clock.advance(1);
+        assert_eq!(relay.fetch_since(0).await.unwrap().len(), 1);
+        clock.advance(60_000);
+        assert_eq!(relay.stored(), 0);
+    }
+
+    #[tokio::test]
+    async fn subscribe_since_replays_history_before_live_envelopes() {
+        let clock = ManualClock::new(START_MS);
+        let relay = InMemoryRelay::with_clock("mem", Arc::new(clock.clone()));
+        let old = envelope_at(START_MS - 10_000, 60);
+        let recent = envelope_at(START_MS - 1_000, 60);
+        relay.publish(old).await.unwrap();
+        relay.publish(recent.clone()).await.unwrap();
+
+        let (handler, mut rx) = collector();
+        relay.subscribe_since(START_MS - 5_000, handler).await.unwrap();
+        clock.advance(1_000);
+        let live = envelope_at(START_MS + 1_000, 60);
+        relay.publish(live.clone()).await.unwrap();
+
+        assert_eq!(next(&mut rx).await.to_bytes(), recent.to_bytes());
+        assert_eq!(next(&mut rx).await.to_bytes(), live.to_bytes());
+        assert!(rx.try_recv().is_err());
+        let since: Vec<_> = relay.fetch_since(START_MS - 5_000).await.unwrap().iter().map(Envelope::to_bytes).collect();
+        assert_eq!(since, vec![recent.to_bytes(), live.to_bytes()]);
     }
 }
@@ -0,0 +1,392 @@
+//! Sendeseitige Mix‑Queue gegen Timing‑Korrelation.
+//!
+//! Ein Relay, das sowohl Alices Veröffentlichung als auch Bobs Abruf
+//! sieht, kann beide Zeitpunkte korrelieren.  Die [`MixingQueue`] hält
+//! Envelopes deshalb eine zufällige Zeit zurück und gibt sie gesammelt
+//! (Batch) in zufälliger Reihenfolge frei.  Jede Konversation kann ein
+//! eigenes Latenzbudget haben, das die Verzögerung nach oben begrenzt.
+//! Beim Multipath‑Versand erhält jedes Relay zusätzlich eine eigene
+//! Verzögerung, so dass dasselbe Envelope nicht gleichzeitig auf allen
+//! Relays erscheint.  Der Versand selbst erfolgt über
+//! [`BridgeProvider::publish`].
+//!
+//! Bei der Freigabe erhält jedes Envelope Zeitstempel und Proof‑of‑Work
+//! des Freigabezeitpunkts ([`Envelope::restamp`]); mit dem ursprünglichen
+//! `ts` könnte ein Relay Sendezeit und Reihenfolge zurückrechnen.  Schlägt
+//! die Veröffentlichung auf einem Relay fehl, wird das Envelope für dieses
+//! Relay mit seiner restlichen Frist erneut eingereiht, bis seine TTL
+//! abgelaufen ist.
+
+use crate::{BridgeProvider, Clock, SystemClock};
+use phantomchat_core::Envelope;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// Einstellungen der Mix‑Queue.
+#[derive(Debug, Clone)]
+pub struct MixConfig {
+    /// Untere Grenze der zufälligen Verzögerung.
+    pub min_delay: Duration,
+    /// Obere Grenze der zufälligen Verzögerung.
+    pub max_delay: Duration,
+    /// Mindestanzahl freigabebereiter Envelopes für einen Batch.
+    pub batch_size: usize,
+    /// Prüfintervall der Queue.
+    pub batch_interval: Duration,
+    /// Maximale zusätzliche Verzögerung pro Relay.
+    pub relay_jitter: Duration,
+    /// Latenzbudget für Konversationen ohne eigenes Budget.
+    pub default_budget: Duration,
+    /// PoW‑Schwierigkeit beim Neustempeln; muss der beim Erzeugen
+    /// verwendeten entsprechen.
+    pub pow_difficulty: u32,
+}
+
+impl Default for MixConfig {
+    fn default() -> Self {
+        Self {
+            min_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+            batch_size: 4,
+            batch_interval: Duration::from_millis(250),
+            relay_jitter: Duration::from_secs(3),
+            default_budget: Duration::from_secs(30),
+            pow_difficulty: 16,
+        }
+    }
+}
+
+/// Zähler der Mix‑Queue.
+#[derive(Debug, Clone, Default)]
+pub struct MixStats {
+    pub submitted: u64,
+    pub batches: u64,
+    /// Einzelne Relay‑Veröffentlichungen.
+    pub published: u64,
+    pub publish_errors: u64,
+    /// Nach einem Fehler für ein Relay erneut eingereihte Envelopes.
+    pub requeued: u64,
+    /// Envelopes, deren TTL vor einer erfolgreichen Veröffentlichung
+    /// abgelaufen ist.
+    pub expired: u64,
+    /// Batches, die wegen eines erreichten Latenzbudgets vorzeitig (unter
+    /// `batch_size`) freigegeben wurden.
+    pub forced: u64,
+}
+
+#[derive(Debug)]
+struct Pending {
+    env: Envelope,
+    release_at: Instant,
+    deadline: Instant,
+    /// Ende der TTL, gerechnet ab dem Einreihen.
+    expires_at: Instant,
+    /// Indizes der Relays, die das Envelope noch erhalten sollen.
+    targets: Vec<usize>,
+}
+
+/// Verzögernde, bündelnde Sende‑Queue über mehrere Relays.
+pub struct MixingQueue<P: BridgeProvider> {
+    relays: Arc<Vec<Arc<P>>>,
+    config: MixConfig,
+    budgets: Mutex<HashMap<String, Duration>>,
+    pending: Arc<Mutex<Vec<Pending>>>,
+    rng: Mutex<StdRng>,
+    stats: Arc<Mutex<MixStats>>,
+}
+
+impl<P: BridgeProvider + 'static> MixingQueue<P> {
+    pub fn new(relays: Vec<Arc<P>>, config: MixConfig) -> Self {
+        Self::with_rng(relays, config, StdRng::from_entropy())
+    }
+    /// Deterministische Verzögerungen für Tests.
+    pub fn with_seed(relays: Vec<Arc<P>>, config: MixConfig, seed: u64) -> Self {
+        Self::with_rng(relays, config, StdRng::seed_from_u64(seed))
+    }
+    fn with_rng(relays: Vec<Arc<P>>, config: MixConfig, rng: StdRng) -> Self {
+        Self {
+            relays: Arc::new(relays),
+            config,
+            budgets: Mutex::new(HashMap::new()),
+            pending: Arc::new(Mutex::new(Vec::new())),
+            rng: Mutex::new(rng),
+            stats: Arc::new(Mutex::new(MixStats::default())),
+        }
+    }
+    /// Setzt das Latenzbudget einer Konversation.
+    pub fn set_budget(&self, conversation: &str, budget: Duration) {
+        self.budgets.lock().unwrap().insert(conversation.to_owned(), budget);
+    }
+    /// Reiht ein Envelope ein.  Die Verzögerung wird zufällig zwischen
+    /// `min_delay` und `max_delay` gezogen und durch das Latenzbudget der
+    /// Konversation begrenzt.
+    pub fn submit(&self, conversation: &str, env: Envelope) {
+        let budget = self
+            .budgets
+            .lock()
+            .unwrap()
+            .get(conversation)
+            .copied()
+            .unwrap_or(self.config.default_budget);
+        let now = Instant::now();
+        let deadline = now + budget;
+        let delay = self.random_delay(self.config.min_delay, self.config.max_delay).min(budget);
+        let release_at = now + delay;
+        let expires_at = now + Duration::from_secs(env.ttl as u64);
+        let targets = (0..self.relays.len()).collect();
+        self.pending.lock().unwrap().push(Pending { env, release_at, deadline, expires_at, targets });
+        self.stats.lock().unwrap().submitted += 1;
+    }
+    /// Anzahl zurückgehaltener Envelopes.
+    pub fn pending(&self) -> usize {
+        self.pending.lock().unwrap().len()
+    }
+    pub fn stats(&self) -> MixStats {
+        self.stats.lock().unwrap().clone()
+    }
+    /// Startet die periodische Freigabe als Hintergrund‑Task.
+    pub fn spawn(self: Arc<Self>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(self.config.batch_interval);
+            loop {
+                interval.tick().await;
+                self.flush();
+            }
+        })
+    }
+    /// Gibt einen Batch frei, wenn genügend Envelopes bereit sind oder
+    /// ein Latenzbudget sonst überschritten würde.  Gibt die Anzahl der
+    /// freigegebenen Envelopes zurück.  Envelopes mit abgelaufener TTL
+    /// werden verworfen.
+    pub fn flush(&self) -> usize {
+        let now = Instant::now();
+        let horizon = now + self.config.batch_interval;
+        let batch: Vec<Pending> = {
+            let mut pending = self.pending.lock().unwrap();
+            let before = pending.len();
+            pending.retain(|p| p.expires_at > now);
+            let expired = before - pending.len();
+            if expired > 0 {
+                self.stats.lock().unwrap().expired += expired as u64;
+            }
+            let ready = pending.iter().filter(|p| p.release_at <= now).count();
+            let urgent = pending.iter().any(|p| p.deadline <= horizon);
+            if !urgent && (ready == 0 || ready < self.config.batch_size) {
+                return 0;
+            }
+            if ready < self.config.batch_size {
+                self.stats.lock().unwrap().forced += 1;
+            }
+            // Dringende Envelopes werden mitgenommen, auch wenn ihre
+            // zufällige Verzögerung noch nicht abgelaufen ist.
+            let (batch, rest): (Vec<Pending>, Vec<Pending>) = pending
+                .drain(..)
+                .partition(|p| p.release_at <= now || p.deadline <= horizon);
+            *pending = rest;
+            batch
+        };
+        let mut batch = batch;
+        let count = batch.len();
+        {
+            let mut rng = self.rng.lock().unwrap();
+            batch.shuffle(&mut *rng);
+        }
+        self.stats.lock().unwrap().batches += 1;
+        for item in batch {
+            let remaining = item.deadline.saturating_duration_since(now);
+            // Eigene Verzögerung pro Relay, begrenzt durch das Budget
+            let jitters: Vec<(usize, Duration)> = item
+                .targets
+                .iter()
+                .map(|&target| (target, self.random_delay(Duration::ZERO, self.config.relay_jitter).min(remaining)))
+                .collect();
+            let relays = self.relays.clone();
+            let pending = self.pending.clone();
+            let stats = self.stats.clone();
+            let difficulty = self.config.pow_difficulty;
+            let Pending { mut env, deadline, expires_at, .. } = item;
+            tokio::spawn(async move {
+                // Der Proof‑of‑Work ist rechenintensiv und läuft daher
+                // außerhalb des Async‑Executors.
+                let ts = SystemClock.now_ms();
+                let restamped = tokio::task::spawn_blocking(move || {
+                    env.restamp(ts, difficulty);
+                    env
+                });
+                let Ok(env) = restamped.await else { return };
+                for (target, jitter) in jitters {
+                    let relay = relays[target].clone();
+                    let env = env.clone();
+                    let pending = pending.clone();
+                    let stats = stats.clone();
+                    tokio::spawn(async move {
+                        tokio::time::sleep(jitter).await;
+                        let result = relay.publish(env.clone()).await;
+                        let mut stats = stats.lock().unwrap();
+                        if result.is_ok() {
+                            stats.published += 1;
+                            return;
+                        }
+                        stats.publish_errors += 1;
+                        stats.requeued += 1;
+                        let retry = Pending { env, release_at: Instant::now(), deadline, expires_at, targets: vec![target] };
+                        pending.lock().unwrap().push(retry);
+                    });
+                }
+            });
+        }
+        count
+    }
+    fn random_delay(&self, min: Duration, max: Duration) -> Duration {
+        if max <= min {
+            return min;
+        }
+        let ms = self.rng.lock().unwrap().gen_range(min.as_millis() as u64..=max.as_millis() as u64);
+        Duration::from_millis(ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BridgeHealth;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Relay, das Ankunftszeit und Envelope jeder Veröffentlichung
+    /// festhält und auf Wunsch ablehnt.
+    #[derive(Default)]
+    struct Recorder {
+        failing: AtomicBool,
+        log: Mutex<Vec<(Instant, Envelope)>>,
+    }
+
+    impl Recorder {
+        fn received(&self) -> Vec<(Instant, Envelope)> {
+            self.log.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl BridgeProvider for Recorder {
+        fn id(&self) -> &str {
+            "recorder"
+        }
+        async fn publish(&self, env: Envelope) -> anyhow::Result<()> {
+            if self.failing.load(Ordering::SeqCst) {
+                anyhow::bail!("abgelehnt");
+            }
+            self.log.lock().unwrap().push((Instant::now(), env));
+            Ok(())
+        }
+        async fn subscribe<F>(&self, _handler: F) -> anyhow::Result<()>
+        where
+            F: Fn(Envelope) + Send + 'static,
+        {
+            Ok(())
+        }
+        async fn health(&self) -> BridgeHealth {
+            BridgeHealth { latency_ms: 0, uptime: 1.0, failure_rate: 0.0 }
+        }
+    }
+
+    fn config() -> MixConfig {
+        MixConfig { min_delay: Duration::ZERO, max_delay: Duration::ZERO, pow_difficulty: 4, ..MixConfig::default() }
+    }
+
+    fn queue(relays: usize, config: MixConfig) -> (Vec<Arc<Recorder>>, MixingQueue<Recorder>) {
+        let relays: Vec<Arc<Recorder>> = (0..relays).map(|_| Arc::new(Recorder::default())).collect();
+        let queue = MixingQueue::with_seed(relays.clone(), config, 7);
+        (relays, queue)
+    }
+
+    /// Lässt die Versand‑Tasks laufen, bis `done` erfüllt ist.
+    async fn settle(done: impl Fn() -> bool) {
+        while !done() {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn batch_waits_for_batch_size() {
+        let (relays, queue) = queue(1, MixConfig { batch_size: 3, ..config() });
+        queue.submit("c", Envelope::new_cover(64, 60, 0, false));
+        queue.submit("c", Envelope::new_cover(64, 60, 0, false));
+        assert_eq!(queue.flush(), 0);
+        assert_eq!(queue.pending(), 2);
+
+        queue.submit("c", Envelope::new_cover(64, 60, 0, false));
+        assert_eq!(queue.flush(), 3);
+        settle(|| relays[0].received().len() == 3).await;
+        let stats = queue.stats();
+        assert_eq!((stats.batches, stats.forced, stats.published), (1, 0, 3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn budget_deadline_forces_a_flush() {
+        let config = MixConfig { batch_size: 4, min_delay: Duration::from_secs(5), max_delay: Duration::from_secs(10), ..config() };
+        let (relays, queue) = queue(1, config);
+        queue.set_budget("eilig", Duration::from_secs(1));
+        queue.submit("eilig", Envelope::new_cover(64, 60, 0, false));
+        assert_eq!(queue.flush(), 0);
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(queue.flush(), 1);
+        assert_eq!(queue.stats().forced, 1);
+        settle(|| relays[0].received().len() == 1).await;
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn relays_get_own_jitter_and_a_fresh_stamp() {
+        let (relays, queue) = queue(3, MixConfig { batch_size: 1, ..config() });
+        let mut env = Envelope::new_cover(64, 60, 4, false);
+        // Lange zurückgehalten: der alte Zeitstempel verriete die Wartezeit
+        env.ts -= 3_600_000;
+        let epk = env.epk;
+        let before = SystemClock.now_ms();
+        queue.submit("c", env);
+        assert_eq!(queue.flush(), 1);
+        settle(|| relays.iter().all(|relay| relay.received().len() == 1)).await;
+
+        let arrivals: Vec<Instant> = relays.iter().map(|relay| relay.received()[0].0).collect();
+        assert!(arrivals[0] != arrivals[1] && arrivals[1] != arrivals[2] && arrivals[0] != arrivals[2]);
+        for relay in &relays {
+            let sent = &relay.received()[0].1;
+            assert_eq!(sent.epk, epk);
+            assert!(sent.ts >= before);
+            assert!(sent.verify_pow(4));
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn failed_publishes_are_requeued_until_expiry() {
+        let (relays, queue) = queue(2, MixConfig { batch_size: 1, ..config() });
+        relays[0].failing.store(true, Ordering::SeqCst);
+        queue.submit("c", Envelope::new_cover(64, 60, 4, false));
+        assert_eq!(queue.flush(), 1);
+        settle(|| queue.pending() == 1).await;
+        assert_eq!(relays[1].received().len(), 1);
+        assert_eq!(queue.stats().requeued, 1);
+
+        // Nur das ausgefallene Relay erhält das Envelope erneut
+        relays[0].failing.store(false, Ordering::SeqCst);
+        assert_eq!(queue.flush(), 1);
+        settle(|| relays[0].received().len() == 1).await;
+        assert_eq!(relays[1].received().len(), 1);
+        assert_eq!((queue.pending(), queue.stats().published), (0, 2));
+
+        relays[0].failing.store(true, Ordering::SeqCst);
+        queue.submit("c", Envelope::new_cover(64, 1, 4, false));
+        queue.flush();
+        settle(|| queue.pending() == 1).await;
+        tokio::time::advance(Duration::from_secs(1)).await;
+        assert_eq!(queue.flush(), 0);
+        assert_eq!((queue.pending(), queue.stats().expired), (0, 1));
+    }
+}
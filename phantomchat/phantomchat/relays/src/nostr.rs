@@ -0,0 +1,329 @@
+//! Nostr‑Relay‑Adapter.
+//!
+//! Envelopes werden als Base64‑kodierter `content` eines Events vom
+//! Kind [`EVENT_KIND`] veröffentlicht (siehe SPEC.md, Abschnitt 6),
+//! Anhangsblöcke als Events vom Kind [`CHUNK_KIND`] mit dem hex‑kodierten
+//! Blockhash im Tag `x`.  Die WebSocket‑Verbindung läuft über die
+//! [`transport`](crate::transport)‑Schicht und damit wahlweise direkt
+//! oder über Tor.  Jedes Event wird mit einem eigenen, flüchtigen
+//! secp256k1‑Schlüssel signiert (BIP‑340‑Schnorr, NIP‑01): Relays
+//! akzeptieren nur signierte Events, ein dauerhafter Nostr‑Schlüssel
+//! würde aber die Events eines Absenders verknüpfbar machen.
+
+use crate::transport::{self, RelayConfig};
+use crate::{BridgeHealth, BridgeProvider};
+use async_trait::async_trait;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use futures::{SinkExt, StreamExt};
+use k256::schnorr::{Signature, SigningKey, VerifyingKey};
+use phantomchat_core::util::{from_hex, sha256, to_hex};
+use phantomchat_core::{Chunk, Envelope};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+
+/// Event‑Kind für PhantomChat‑Envelopes.
+pub const EVENT_KIND: u64 = 30001;
+/// Event‑Kind für Anhangsblöcke.
+pub const CHUNK_KIND: u64 = 30002;
+
+/// Zeitlimit für Verbindungsaufbau und Relay‑Antworten.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(15);
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Laufende Messwerte für die Health‑Schätzung.
+#[derive(Debug, Default)]
+struct RelayStats {
+    last_latency_ms: Option<u32>,
+    attempts: u32,
+    failures: u32,
+}
+
+/// Adapter für ein einzelnes Nostr‑Relay.
+pub struct NostrRelay {
+    id: String,
+    config: RelayConfig,
+    /// Isolationsschlüssel für Tor (z.&nbsp;B. Fingerprint der Identität).
+    identity: String,
+    stats: Arc<Mutex<RelayStats>>,
+}
+
+impl NostrRelay {
+    /// Direkte Verbindung zu `url`.
+    pub fn new(url: &str) -> Self {
+        Self::with_config(RelayConfig::direct(url), "")
+    }
+    /// Verbindung gemäß `config`.  `identity` trennt bei aktivem Tor die
+    /// Circuits verschiedener Identitäten.
+    pub fn with_config(config: RelayConfig, identity: &str) -> Self {
+        Self {
+            id: config.url.clone(),
+            config,
+            identity: identity.to_owned(),
+            stats: Arc::new(Mutex::new(RelayStats::default())),
+        }
+    }
+    /// Die URL des Relays.
+    pub fn url(&self) -> &str {
+        &self.config.url
+    }
+    /// Baut die WebSocket‑Verbindung auf und misst die Latenz.
+    async fn connect(&self) -> anyhow::Result<WsStream> {
+        let started = Instant::now();
+        let result = tokio::time::timeout(RESPONSE_TIMEOUT, async {
+            let tcp = transport::connect(&self.config, &self.identity).await?;
+            let (ws, _) = tokio_tungstenite::client_async_tls(self.config.url.as_str(), tcp).await?;
+            anyhow::Ok(ws)
+        })
+        .await;
+        let mut stats = self.stats.lock().unwrap();
+        stats.attempts += 1;
+        match result {
+            Ok(Ok(ws)) => {
+                stats.last_latency_ms = Some(started.elapsed().as_millis().min(u32::MAX as u128) as u32);
+                Ok(ws)
+            }
+            Ok(Err(e)) => {
+                stats.failures += 1;
+                Err(e.context(format!("Verbindung zu {} fehlgeschlagen", self.config.url)))
+            }
+            Err(_) => {
+                stats.failures += 1;
+                anyhow::bail!("Zeitüberschreitung beim Verbinden mit {}", self.config.url)
+            }
+        }
+    }
+    /// Veröffentlicht ein Event und wartet auf die Bestätigung des Relays.
+    async fn send_event(&self, event: Value) -> anyhow::Result<()> {
+        let event_id = event["id"].as_str().unwrap_or_default().to_owned();
+        let mut ws = self.connect().await?;
+        ws.send(Message::Text(json!(["EVENT", event]).to_string())).await?;
+        // Auf die OK‑Antwort des Relays warten (NIP‑20)
+        let answer = tokio::time::timeout(RESPONSE_TIMEOUT, async {
+            while let Some(msg) = ws.next().await {
+                if let Message::Text(text) = msg? {
+                    let value: Value = serde_json::from_str(&text)?;
+                    if value[0] == "OK" && value[1] == event_id.as_str() {
+                        return anyhow::Ok(value);
+                    }
+                }
+            }
+            anyhow::bail!("Relay {} hat die Verbindung geschlossen", self.config.url)
+        })
+        .await;
+        let _ = ws.close(None).await;
+        let answer = answer.map_err(|_| anyhow::anyhow!("keine Antwort von {}", self.config.url))??;
+        if answer[2].as_bool() != Some(true) {
+            self.stats.lock().unwrap().failures += 1;
+            anyhow::bail!("Relay {} lehnt das Event ab: {}", self.config.url, answer[3]);
+        }
+        Ok(())
+    }
+    /// Fragt gespeicherte Events zu `filter` ab (bis `EOSE`).
+    async fn query(&self, filter: Value) -> anyhow::Result<Vec<Value>> {
+        let mut ws = self.connect().await?;
+        let sub_id = subscription_id();
+        ws.send(Message::Text(json!(["REQ", sub_id, filter]).to_string())).await?;
+        let mut out = Vec::new();
+        let collected = tokio::time::timeout(RESPONSE_TIMEOUT, async {
+            while let Some(msg) = ws.next().await {
+                let Message::Text(text) = msg? else { continue };
+                let mut value: Value = serde_json::from_str(&text)?;
+                if value[1] != sub_id.as_str() {
+                    continue;
+                }
+                match value[0].as_str() {
+                    Some("EVENT") => out.push(value[2].take()),
+                    // Ende der gespeicherten Events
+                    Some("EOSE") => break,
+                    _ => {}
+                }
+            }
+            anyhow::Ok(())
+        })
+        .await;
+        let _ = ws.send(Message::Text(json!(["CLOSE", sub_id]).to_string())).await;
+        let _ = ws.close(None).await;
+        collected.map_err(|_| anyhow::anyhow!("Zeitüberschreitung beim Nachladen von {}", self.config.url))??;
+        Ok(out)
+    }
+}
+
+/// Kodiert ein Envelope als Nostr‑Event.
+pub fn envelope_to_event(env: &Envelope) -> Value {
+    let tags = vec![vec!["expiration".into(), (env.expires_at() / 1000).to_string()]];
+    event(EVENT_KIND, env.ts, tags, BASE64.encode(env.to_bytes()))
+}
+
+/// Kodiert einen Anhangsblock als Nostr‑Event.
+pub fn chunk_to_event(chunk: &Chunk) -> Value {
+    let tags = vec![
+        vec!["x".into(), to_hex(&chunk.id())],
+        vec!["expiration".into(), (chunk.expires_at() / 1000).to_string()],
+    ];
+    event(CHUNK_KIND, chunk.ts, tags, BASE64.encode(chunk.to_bytes()))
+}
+
+/// Baut und signiert ein Event mit einem frischen Schlüssel.
+fn event(kind: u64, ts_ms: u64, tags: Vec<Vec<String>>, content: String) -> Value {
+    let key = SigningKey::random(&mut OsRng);
+    let pubkey = to_hex(&key.verifying_key().to_bytes());
+    let created_at = ts_ms / 1000;
+    let id = event_id(&pubkey, created_at, kind, &tags, &content);
+    let mut aux = [0u8; 32];
+    OsRng.fill_bytes(&mut aux);
+    let sig = key.sign_raw(&id, &aux).expect("Schnorr‑Signatur über 32 Bytes");
+    json!({
+        "id": to_hex(&id),
+        "pubkey": pubkey,
+        "created_at": created_at,
+        "kind": kind,
+        "tags": tags,
+        "content": content,
+        "sig": to_hex(&sig.to_bytes()),
+    })
+}
+
+/// Event‑ID nach NIP‑01: SHA‑256 der Serialisierung
+/// `[0, pubkey, created_at, kind, tags, content]`.
+fn event_id<T: serde::Serialize>(pubkey: &str, created_at: u64, kind: u64, tags: &T, content: &str) -> Vec<u8> {
+    sha256(json!([0, pubkey, created_at, kind, tags, content]).to_string().as_bytes())
+}
+
+/// Prüft ID und Signatur eines Events wie ein Nostr‑Relay.
+pub fn verify_event(event: &Value) -> bool {
+    let (Some(pubkey), Some(created_at), Some(kind), Some(content)) =
+        (event["pubkey"].as_str(), event["created_at"].as_u64(), event["kind"].as_u64(), event["content"].as_str())
+    else {
+        return false;
+    };
+    let id = event_id(pubkey, created_at, kind, &event["tags"], content);
+    if event["id"].as_str() != Some(to_hex(&id).as_str()) {
+        return false;
+    }
+    let key = from_hex(pubkey).and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok());
+    let sig = event["sig"].as_str().and_then(from_hex).and_then(|bytes| Signature::try_from(bytes.as_slice()).ok());
+    match (key, sig) {
+        (Some(key), Some(sig)) => key.verify_raw(&id, &sig).is_ok(),
+        _ => false,
+    }
+}
+
+/// Dekodiert ein Envelope aus einem Nostr‑Event.  Fremde Event‑Kinds
+/// und ungültige Inhalte ergeben `None`.
+pub fn envelope_from_event(event: &Value) -> Option<Envelope> {
+    if event["kind"].as_u64()? != EVENT_KIND {
+        return None;
+    }
+    let bytes = BASE64.decode(event["content"].as_str()?).ok()?;
+    Envelope::from_bytes(&bytes)
+}
+
+/// Dekodiert einen Anhangsblock aus einem Nostr‑Event.
+pub fn chunk_from_event(event: &Value) -> Option<Chunk> {
+    if event["kind"].as_u64()? != CHUNK_KIND {
+        return None;
+    }
+    let bytes = BASE64.decode(event["content"].as_str()?).ok()?;
+    Chunk::from_bytes(&bytes)
+}
+
+fn subscription_id() -> String {
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap();
+    format!("pc-{}", &to_hex(&sha256(&now.as_nanos().to_le_bytes()))[..16])
+}
+
+#[async_trait]
+impl BridgeProvider for NostrRelay {
+    fn id(&self) -> &str {
+        &self.id
+    }
+    async fn publish(&self, env: Envelope) -> anyhow::Result<()> {
+        self.send_event(envelope_to_event(&env)).await
+    }
+    async fn subscribe<F>(&self, handler: F) -> anyhow::Result<()>
+    where
+        F: Fn(Envelope) + Send + 'static,
+    {
+        let mut ws = self.connect().await?;
+        let sub_id = subscription_id();
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+        ws.send(Message::Text(json!(["REQ", sub_id, {"kinds": [EVENT_KIND], "since": now}]).to_string()))
+            .await?;
+        let stats = self.stats.clone();
+        tokio::spawn(async move {
+            while let Some(msg) = ws.next().await {
+                let text = match msg {
+                    Ok(Message::Text(text)) => text,
+                    Ok(_) => continue,
+                    Err(_) => {
+                        stats.lock().unwrap().failures += 1;
+                        break;
+                    }
+                };
+                let Ok(value) = serde_json::from_str::<Value>(&text) else { continue };
+                if value[0] == "EVENT" && value[1] == sub_id.as_str() {
+                    if let Some(env) = envelope_from_event(&value[2]) {
+                        handler(env);
+                    }
+                }
+            }
+        });
+        Ok(())
+    }
+    async fn fetch_since(&self, since_ms: u64) -> anyhow::Result<Vec<Envelope>> {
+        let events = self.query(json!({"kinds": [EVENT_KIND], "since": since_ms / 1000})).await?;
+        Ok(events.iter().filter_map(envelope_from_event).filter(|env| env.ts >= since_ms).collect())
+    }
+    async fn publish_chunk(&self, chunk: Chunk) -> anyhow::Result<()> {
+        self.send_event(chunk_to_event(&chunk)).await
+    }
+    async fn fetch_chunks(&self, ids: &[[u8; 32]]) -> anyhow::Result<Vec<Chunk>> {
+        let ids: Vec<String> = ids.iter().map(|id| to_hex(&id[..])).collect();
+        let events = self.query(json!({"kinds": [CHUNK_KIND], "#x": ids})).await?;
+        Ok(events.iter().filter_map(chunk_from_event).collect())
+    }
+    async fn health(&self) -> BridgeHealth {
+        let stats = self.stats.lock().unwrap();
+        let failure_rate = if stats.attempts == 0 { 0.0 } else { stats.failures as f32 / stats.attempts as f32 };
+        BridgeHealth {
+            // Ohne Messung wird ein konservativer Schätzwert angenommen.
+            latency_ms: stats.last_latency_ms.unwrap_or(100),
+            uptime: 1.0 - failure_rate,
+            failure_rate,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_are_signed_with_fresh_keys() {
+        let env = Envelope::new_cover(64, 60, 0, false);
+        let first = envelope_to_event(&env);
+        let second = envelope_to_event(&env);
+        assert!(verify_event(&first) && verify_event(&second));
+        assert_ne!(first["pubkey"], second["pubkey"]);
+        assert_eq!(envelope_from_event(&first).map(|e| e.to_bytes()), Some(env.to_bytes()));
+    }
+
+    #[test]
+    fn tampered_events_fail_verification() {
+        let mut event = envelope_to_event(&Envelope::new_cover(64, 60, 0, false));
+        event["created_at"] = json!(event["created_at"].as_u64().unwrap() + 1);
+        assert!(!verify_event(&event));
+        let mut event = envelope_to_event(&Envelope::new_cover(64, 60, 0, false));
+        let other = envelope_to_event(&Envelope::new_cover(64, 60, 0, false));
+        event["sig"] = other["sig"].clone();
+        assert!(!verify_event(&event));
+    }
+}
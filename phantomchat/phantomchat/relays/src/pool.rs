@@ -0,0 +1,192 @@
+//! Multipath‑Relay‑Pool mit Deduplizierung.
+//!
+//! Ein [`RelayPool`] veröffentlicht jedes Envelope parallel auf allen
+//! Relays und fasst die eingehenden Ströme zu einem einzigen zusammen.
+//! Da dasselbe Envelope über mehrere Relays (oder von einem Relay
+//! doppelt) ankommen kann, wird jedes Envelope anhand des SHA‑256 seiner
+//! Serialisierung nur einmal an den Handler weitergegeben.  Manipulierte
+//! Kopien haben einen anderen Hash und werden daher nicht unterdrückt;
+//! sie scheitern später an der AEAD‑Prüfung.
+//!
+//! Anhangsblöcke gehen ebenfalls an alle Relays.  Beim Laden zählt je
+//! Hash der erste Block, der tatsächlich zu ihm passt; verfälschte Kopien
+//! einzelner Relays fallen so nicht ins Gewicht.
+
+use crate::{BridgeHealth, BridgeProvider};
+use async_trait::async_trait;
+use futures::future::join_all;
+use phantomchat_core::util::sha256;
+use phantomchat_core::{Chunk, Envelope};
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// Standardgröße des Deduplizierungsfensters.
+pub const DEFAULT_DEDUP_CAPACITY: usize = 4096;
+
+/// Begrenzter Speicher bereits gesehener Envelope‑Hashes.  Ist die
+/// Kapazität erreicht, wird der älteste Eintrag verdrängt.
+#[derive(Debug)]
+pub struct DedupCache {
+    seen: HashSet<Vec<u8>>,
+    order: VecDeque<Vec<u8>>,
+    capacity: usize,
+}
+
+impl DedupCache {
+    pub fn new(capacity: usize) -> Self {
+        Self { seen: HashSet::new(), order: VecDeque::new(), capacity: capacity.max(1) }
+    }
+    /// Trägt das Envelope ein.  Gibt `true` zurück, wenn es zum ersten
+    /// Mal gesehen wurde.
+    pub fn insert(&mut self, env: &Envelope) -> bool {
+        let key = sha256(&env.to_bytes());
+        if !self.seen.insert(key.clone()) {
+            return false;
+        }
+        self.order.push_back(key);
+        if self.order.len() > self.capacity {
+            if let Some(old) = self.order.pop_front() {
+                self.seen.remove(&old);
+            }
+        }
+        true
+    }
+}
+
+/// Pool gleichartiger Relays.  Der Pool ist selbst ein
+/// [`BridgeProvider`] und kann daher überall eingesetzt werden, wo ein
+/// einzelnes Relay erwartet wird.
+pub struct RelayPool<P: BridgeProvider> {
+    relays: Vec<Arc<P>>,
+    dedup: Arc<Mutex<DedupCache>>,
+}
+
+impl<P: BridgeProvider + 'static> RelayPool<P> {
+    pub fn new(relays: Vec<Arc<P>>) -> Self {
+        Self::with_capacity(relays, DEFAULT_DEDUP_CAPACITY)
+    }
+    /// Erstellt einen Pool mit eigener Größe des Deduplizierungsfensters.
+    pub fn with_capacity(relays: Vec<Arc<P>>, capacity: usize) -> Self {
+        Self { relays, dedup: Arc::new(Mutex::new(DedupCache::new(capacity))) }
+    }
+    /// Die Relays des Pools.
+    pub fn relays(&self) -> &[Arc<P>] {
+        &self.relays
+    }
+    /// Health‑Werte aller Relays, jeweils mit deren ID.
+    pub async fn health_all(&self) -> Vec<(String, BridgeHealth)> {
+        let futures = self.relays.iter().map(|r| async move { (r.id().to_owned(), r.health().await) });
+        join_all(futures).await
+    }
+}
+
+#[async_trait]
+impl<P: BridgeProvider + 'static> BridgeProvider for RelayPool<P> {
+    fn id(&self) -> &str {
+        "pool"
+    }
+    /// Veröffentlicht parallel auf allen Relays.  Erfolgreich ist der
+    /// Aufruf, sobald mindestens ein Relay das Envelope angenommen hat.
+    async fn publish(&self, env: Envelope) -> anyhow::Result<()> {
+        let results = join_all(self.relays.iter().map(|r| r.publish(env.clone()))).await;
+        let mut last_err = None;
+        let mut ok = 0;
+        for res in results {
+            match res {
+                Ok(()) => ok += 1,
+                Err(e) => last_err = Some(e),
+            }
+        }
+        match (ok, last_err) {
+            (0, Some(e)) => Err(e.context("kein Relay hat das Envelope angenommen")),
+            (0, None) => anyhow::bail!("Relay‑Pool ist leer"),
+            _ => Ok(()),
+        }
+    }
+    async fn subscribe<F>(&self, handler: F) -> anyhow::Result<()>
+    where
+        F: Fn(Envelope) + Send + 'static,
+    {
+        let handler = Arc::new(Mutex::new(handler));
+        for relay in &self.relays {
+            let handler = handler.clone();
+            let dedup = self.dedup.clone();
+            relay
+                .subscribe(move |env| {
+                    if dedup.lock().unwrap().insert(&env) {
+                        (handler.lock().unwrap())(env);
+                    }
+                })
+                .await?;
+        }
+        Ok(())
+    }
+    async fn fetch_since(&self, since_ms: u64) -> anyhow::Result<Vec<Envelope>> {
+        let results = join_all(self.relays.iter().map(|r| r.fetch_since(since_ms))).await;
+        let mut out = Vec::new();
+        let mut any_ok = false;
+        let mut last_err = None;
+        for res in results {
+            match res {
+                Ok(envs) => {
+                    any_ok = true;
+                    let mut dedup = self.dedup.lock().unwrap();
+                    out.extend(envs.into_iter().filter(|env| dedup.insert(env)));
+                }
+                Err(e) => last_err = Some(e),
+            }
+        }
+        match (any_ok, last_err) {
+            (false, Some(e)) => Err(e),
+            _ => Ok(out),
+        }
+    }
+    /// Erfolgreich, sobald mindestens ein Relay den Block angenommen hat.
+    async fn publish_chunk(&self, chunk: Chunk) -> anyhow::Result<()> {
+        let results = join_all(self.relays.iter().map(|r| r.publish_chunk(chunk.clone()))).await;
+        let mut last_err = None;
+        for res in results {
+            match res {
+                Ok(()) => return Ok(()),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        match last_err {
+            Some(e) => Err(e.context("kein Relay hat den Block angenommen")),
+            None => anyhow::bail!("Relay‑Pool ist leer"),
+        }
+    }
+    async fn fetch_chunks(&self, ids: &[[u8; 32]]) -> anyhow::Result<Vec<Chunk>> {
+        let results = join_all(self.relays.iter().map(|r| r.fetch_chunks(ids))).await;
+        let mut wanted: HashSet<[u8; 32]> = ids.iter().copied().collect();
+        let mut out = Vec::new();
+        let mut any_ok = false;
+        let mut last_err = None;
+        for res in results {
+            match res {
+                Ok(chunks) => {
+                    any_ok = true;
+                    out.extend(chunks.into_iter().filter(|c| wanted.remove(&c.id())));
+                }
+                Err(e) => last_err = Some(e),
+            }
+        }
+        match (any_ok, last_err) {
+            (false, Some(e)) => Err(e),
+            _ => Ok(out),
+        }
+    }
+    /// Aggregierte Health: beste Latenz, mittlere Uptime und Fehlerrate.
+    async fn health(&self) -> BridgeHealth {
+        let all = self.health_all().await;
+        if all.is_empty() {
+            return BridgeHealth { latency_ms: u32::MAX, uptime: 0.0, failure_rate: 1.0 };
+        }
+        let n = all.len() as f32;
+        BridgeHealth {
+            latency_ms: all.iter().map(|(_, h)| h.latency_ms).min().unwrap_or(u32::MAX),
+            uptime: all.iter().map(|(_, h)| h.uptime).sum::<f32>() / n,
+            failure_rate: all.iter().map(|(_, h)| h.failure_rate).sum::<f32>() / n,
+        }
+    }
+}
@@ -0,0 +1,465 @@
+//! Netzwerksimulator für Protokolltests.
+//!
+//! [`SimulatedRelay`] verhält sich nach außen wie ein normales Relay,
+//! schaltet aber zwischen Sender und Speicher eine gestörte Leitung:
+//! Latenzverteilungen, Paketverlust, Umordnung, Duplikate, Partitionen
+//! und böswillige Manipulation.  Alle Zufallsentscheidungen stammen aus
+//! einem geseedeten RNG und werden synchron beim `publish` getroffen,
+//! so dass ein Testlauf mit gleichem Seed und gleicher Aufrufreihenfolge
+//! reproduzierbar ist.  Verzögerungen laufen über `tokio::time`; mit
+//! `tokio::time::pause()` vergehen sie im Test ohne echte Wartezeit.
+
+use crate::{BridgeHealth, BridgeProvider, Clock, InMemoryRelay, SystemClock};
+use async_trait::async_trait;
+use phantomchat_core::{Chunk, Envelope};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Verteilung der Zustellverzögerung.
+#[derive(Debug, Clone)]
+pub enum Latency {
+    /// Konstante Verzögerung.
+    Fixed(Duration),
+    /// Gleichverteilt zwischen `min` und `max`.
+    Uniform { min: Duration, max: Duration },
+    /// Exponentialverteilt mit Mittelwert `mean` (lange Ausreißer).
+    Exponential { mean: Duration },
+}
+
+impl Latency {
+    fn sample(&self, rng: &mut StdRng) -> Duration {
+        match self {
+            Latency::Fixed(d) => *d,
+            Latency::Uniform { min, max } => {
+                if max <= min {
+                    return *min;
+                }
+                let ms = rng.gen_range(min.as_millis() as u64..=max.as_millis() as u64);
+                Duration::from_millis(ms)
+            }
+            Latency::Exponential { mean } => {
+                let u: f64 = rng.gen_range(f64::EPSILON..1.0);
+                Duration::from_secs_f64(-u.ln() * mean.as_secs_f64())
+            }
+        }
+    }
+}
+
+/// Störungsprofil eines simulierten Relays.  Wahrscheinlichkeiten liegen
+/// im Bereich `0.0..=1.0`.
+#[derive(Debug, Clone)]
+pub struct SimConfig {
+    /// Verzögerung zwischen `publish` und Speicherung/Auslieferung.
+    pub latency: Latency,
+    /// Wahrscheinlichkeit, dass ein Envelope verloren geht.
+    pub loss: f64,
+    /// Wahrscheinlichkeit, dass ein Envelope doppelt ausgeliefert wird.
+    pub duplicate: f64,
+    /// Wahrscheinlichkeit, dass ein Envelope zusätzlich um
+    /// `reorder_delay` zurückgehalten und damit überholt wird.
+    pub reorder: f64,
+    pub reorder_delay: Duration,
+    /// Wahrscheinlichkeit, dass das Relay ein Bit in Tag, Ciphertext oder
+    /// MAC kippt (böswilliges Relay).
+    pub tamper: f64,
+    /// Partitioniertes Relay: `publish` und `fetch_since` schlagen fehl.
+    pub partitioned: bool,
+}
+
+impl Default for SimConfig {
+    /// Störungsfreie Leitung ohne Verzögerung.
+    fn default() -> Self {
+        Self {
+            latency: Latency::Fixed(Duration::ZERO),
+            loss: 0.0,
+            duplicate: 0.0,
+            reorder: 0.0,
+            reorder_delay: Duration::from_millis(500),
+            tamper: 0.0,
+            partitioned: false,
+        }
+    }
+}
+
+/// Zähler für die tatsächlich injizierten Fehler.
+#[derive(Debug, Clone, Default)]
+pub struct SimStats {
+    pub published: u64,
+    pub dropped: u64,
+    pub duplicated: u64,
+    pub reordered: u64,
+    pub tampered: u64,
+    pub rejected: u64,
+}
+
+/// Relay mit Fehlerinjektion.  Intern wird ein [`InMemoryRelay`] als
+/// Speicher verwendet; Abonnenten sehen daher Broadcast‑Semantik und
+/// TTL‑Ablauf wie dort.
+pub struct SimulatedRelay {
+    inner: Arc<InMemoryRelay>,
+    config: Arc<Mutex<SimConfig>>,
+    rng: Arc<Mutex<StdRng>>,
+    stats: Arc<Mutex<SimStats>>,
+}
+
+impl SimulatedRelay {
+    /// Erstellt ein simuliertes Relay mit Systemuhr.
+    pub fn new(id: &str, config: SimConfig, seed: u64) -> Self {
+        Self::with_clock(id, config, seed, Arc::new(SystemClock))
+    }
+    /// Erstellt ein simuliertes Relay mit eigener Zeitquelle für den
+    /// TTL‑Ablauf.
+    pub fn with_clock(id: &str, config: SimConfig, seed: u64, clock: Arc<dyn Clock>) -> Self {
+        Self {
+            inner: Arc::new(InMemoryRelay::with_clock(id, clock)),
+            config: Arc::new(Mutex::new(config)),
+            rng: Arc::new(Mutex::new(StdRng::seed_from_u64(seed))),
+            stats: Arc::new(Mutex::new(SimStats::default())),
+        }
+    }
+    /// Ersetzt das Störungsprofil zur Laufzeit.
+    pub fn set_config(&self, config: SimConfig) {
+        *self.config.lock().unwrap() = config;
+    }
+    /// Trennt das Relay vom Netz bzw. verbindet es wieder.
+    pub fn set_partitioned(&self, partitioned: bool) {
+        self.config.lock().unwrap().partitioned = partitioned;
+    }
+    /// Liefert die bisher injizierten Fehler.
+    pub fn stats(&self) -> SimStats {
+        self.stats.lock().unwrap().clone()
+    }
+    /// Zugriff auf den darunterliegenden Speicher, z.&nbsp;B. um die
+    /// Historie unabhängig von der Störung zu prüfen.
+    pub fn inner(&self) -> &InMemoryRelay {
+        &self.inner
+    }
+    /// Kippt ein zufälliges Bit in Tag, Ciphertext oder MAC.
+    fn tamper(env: &mut Envelope, rng: &mut StdRng) {
+        let field = rng.gen_range(0..3);
+        let target: &mut [u8] = match field {
+            0 if !env.tag.is_empty() => &mut env.tag,
+            1 if !env.ciphertext.is_empty() => &mut env.ciphertext,
+            _ => &mut env.mac,
+        };
+        let idx = rng.gen_range(0..target.len());
+        target[idx] ^= 1 << rng.gen_range(0..8);
+    }
+    /// Stellt ein Envelope nach `delay` in den Speicher.
+    fn deliver(&self, env: Envelope, delay: Duration) {
+        let inner = self.inner.clone();
+        tokio::spawn(async move {
+            if !delay.is_zero() {
+                tokio::time::sleep(delay).await;
+            }
+            let _ = inner.publish(env).await;
+        });
+    }
+}
+
+#[async_trait]
+impl BridgeProvider for SimulatedRelay {
+    fn id(&self) -> &str {
+        self.inner.id()
+    }
+    async fn publish(&self, env: Envelope) -> anyhow::Result<()> {
+        let config = self.config.lock().unwrap().clone();
+        let mut stats = self.stats.lock().unwrap();
+        if config.partitioned {
+            stats.rejected += 1;
+            anyhow::bail!("Relay {} ist partitioniert", self.id());
+        }
+        stats.published += 1;
+        // Alle Zufallsentscheidungen werden hier synchron getroffen, damit
+        // die Reihenfolge der RNG‑Ziehungen nicht vom Scheduler abhängt.
+        let mut rng = self.rng.lock().unwrap();
+        if rng.gen_bool(config.loss.clamp(0.0, 1.0)) {
+            // Verlust ist für den Sender nicht erkennbar.
+            stats.dropped += 1;
+            return Ok(());
+        }
+        let mut env = env;
+        if rng.gen_bool(config.tamper.clamp(0.0, 1.0)) {
+            Self::tamper(&mut env, &mut rng);
+            stats.tampered += 1;
+        }
+        let copies = if rng.gen_bool(config.duplicate.clamp(0.0, 1.0)) {
+            stats.duplicated += 1;
+            2
+        } else {
+            1
+        };
+        let mut deliveries = Vec::with_capacity(copies);
+        for _ in 0..copies {
+            let mut delay = config.latency.sample(&mut rng);
+            if rng.gen_bool(config.reorder.clamp(0.0, 1.0)) {
+                delay += config.reorder_delay;
+                stats.reordered += 1;
+            }
+            deliveries.push(delay);
+        }
+        drop(rng);
+        drop(stats);
+        for delay in deliveries {
+            self.deliver(env.clone(), delay);
+        }
+        Ok(())
+    }
+    async fn subscribe<F>(&self, handler: F) -> anyhow::Result<()>
+    where
+        F: Fn(Envelope) + Send + 'static,
+    {
+        self.inner.subscribe(handler).await
+    }
+    async fn fetch_since(&self, since_ms: u64) -> anyhow::Result<Vec<Envelope>> {
+        if self.config.lock().unwrap().partitioned {
+            anyhow::bail!("Relay {} ist partitioniert", self.id());
+        }
+        self.inner.fetch_since(since_ms).await
+    }
+    /// Blöcke werden ohne Verzögerung gespeichert, können aber verloren
+    /// gehen oder verfälscht werden.
+    async fn publish_chunk(&self, chunk: Chunk) -> anyhow::Result<()> {
+        let config = self.config.lock().unwrap().clone();
+        let id = chunk.id();
+        let mut chunk = chunk;
+        {
+            let mut stats = self.stats.lock().unwrap();
+            if config.partitioned {
+                stats.rejected += 1;
+                anyhow::bail!("Relay {} ist partitioniert", self.id());
+            }
+            stats.published += 1;
+            let mut rng = self.rng.lock().unwrap();
+            if rng.gen_bool(config.loss.clamp(0.0, 1.0)) {
+                stats.dropped += 1;
+                return Ok(());
+            }
+            if rng.gen_bool(config.tamper.clamp(0.0, 1.0)) && !chunk.data.is_empty() {
+                let idx = rng.gen_range(0..chunk.data.len());
+                chunk.data[idx] ^= 1 << rng.gen_range(0..8);
+                stats.tampered += 1;
+            }
+        }
+        // Manipulierte Blöcke bleiben unter dem ursprünglichen Hash
+        // abrufbar und fallen erst beim Empfänger auf.
+        self.inner.store_chunk(id, chunk);
+        Ok(())
+    }
+    async fn fetch_chunks(&self, ids: &[[u8; 32]]) -> anyhow::Result<Vec<Chunk>> {
+        if self.config.lock().unwrap().partitioned {
+            anyhow::bail!("Relay {} ist partitioniert", self.id());
+        }
+        self.inner.fetch_chunks(ids).await
+    }
+    async fn health(&self) -> BridgeHealth {
+        let config = self.config.lock().unwrap().clone();
+        if config.partitioned {
+            return BridgeHealth { latency_ms: u32::MAX, uptime: 0.0, failure_rate: 1.0 };
+        }
+        let latency = match config.latency {
+            Latency::Fixed(d) => d,
+            Latency::Uniform { min, max } => (min + max) / 2,
+            Latency::Exponential { mean } => mean,
+        };
+        BridgeHealth {
+            latency_ms: latency.as_millis().min(u32::MAX as u128) as u32,
+            uptime: 1.0,
+            failure_rate: (config.loss + config.tamper).min(1.0) as f32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ManualClock, RelayPool};
+    use phantomchat_core::{RatchetHeader, RatchetState, SpendKey};
+    use phantomchat_core::secret::SecretBytes;
+    use std::collections::BTreeSet;
+    use tokio::sync::mpsc;
+    use x25519_dalek::PublicKey;
+
+    const ROOT: [u8; 32] = [7u8; 32];
+
+    fn chaos() -> SimConfig {
+        SimConfig {
+            latency: Latency::Uniform { min: Duration::from_millis(10), max: Duration::from_millis(300) },
+            loss: 0.3,
+            duplicate: 0.3,
+            reorder: 0.3,
+            ..SimConfig::default()
+        }
+    }
+
+    fn relays(config: SimConfig, clock: &ManualClock) -> Vec<Arc<SimulatedRelay>> {
+        (0..3u64).map(|seed| Arc::new(SimulatedRelay::with_clock("sim", config.clone(), seed, Arc::new(clock.clone())))).collect()
+    }
+
+    /// Eine Seite der Unterhaltung mit eigenem Pool über dieselben Relays.
+    struct Party {
+        spend: SpendKey,
+        state: Option<RatchetState>,
+        pool: RelayPool<SimulatedRelay>,
+        inbox: mpsc::UnboundedReceiver<Envelope>,
+    }
+
+    impl Party {
+        async fn new(relays: &[Arc<SimulatedRelay>], state: Option<RatchetState>) -> Self {
+            let pool = RelayPool::new(relays.to_vec());
+            let (tx, inbox) = mpsc::unbounded_channel();
+            pool.subscribe(move |env| drop(tx.send(env))).await.unwrap();
+            Self { spend: SpendKey::generate(), state, pool, inbox }
+        }
+        async fn send(&mut self, to: &PublicKey, msg_id: u128, text: &[u8]) {
+            let (ct, header) = self.state.as_mut().unwrap().encrypt(text);
+            let env = Envelope::new(to, msg_id, 0, header, ct, 60, 0);
+            self.pool.publish(env).await.unwrap();
+        }
+        /// Öffnet und entschlüsselt alles Angekommene.
+        fn receive(&mut self) -> Vec<Vec<u8>> {
+            let mut out = Vec::new();
+            while let Ok(env) = self.inbox.try_recv() {
+                let Some(payload) = env.open(&self.spend) else { continue };
+                let state = self.state.get_or_insert_with(|| {
+                    let header = RatchetHeader::from_bytes(&payload.ratchet_header).unwrap();
+                    RatchetState::responder(
+                        SecretBytes::new(ROOT),
+                        header.session_ephemeral.unwrap(),
+                        PublicKey::from(header.ratchet_pub),
+                    )
+                });
+                if let Ok(text) = state.decrypt(&payload.ratchet_header, &payload.body) {
+                    out.push(text);
+                }
+            }
+            out
+        }
+    }
+
+    /// Nachrichten‑IDs aller Envelopes an `spend`, die mindestens ein
+    /// Relay gespeichert hat.
+    fn stored_for(relays: &[Arc<SimulatedRelay>], spend: &SpendKey) -> BTreeSet<u128> {
+        relays
+            .iter()
+            .flat_map(|r| r.inner().history_since(0))
+            .filter_map(|env| env.open(spend))
+            .map(|payload| payload.msg_id)
+            .collect()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn conversation_survives_loss_duplication_and_reordering() {
+        let clock = ManualClock::new(SystemClock.now_ms());
+        let relays = relays(chaos(), &clock);
+        let mut alice = Party::new(&relays, Some(RatchetState::initiator(SecretBytes::new(ROOT), [1u8; 32]))).await;
+        let mut bob = Party::new(&relays, None).await;
+
+        for round in 0..4u8 {
+            for i in 0..10u8 {
+                alice.send(&bob.spend.public, (round * 20 + i) as u128, &[round, i]).await;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            // Duplikate werden vom Pool bzw. vom Ratchet verworfen.
+            let received = bob.receive();
+            let unique: BTreeSet<_> = received.iter().collect();
+            assert_eq!(unique.len(), received.len());
+            if bob.state.is_none() {
+                continue;
+            }
+            for i in 0..10u8 {
+                bob.send(&alice.spend.public, (round * 20 + 10 + i) as u128, &[round, 10 + i]).await;
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            alice.receive();
+        }
+        let stats: Vec<_> = relays.iter().map(|r| r.stats()).collect();
+        assert!(stats.iter().all(|s| s.dropped > 0 && s.duplicated > 0 && s.reordered > 0));
+
+        // Nach dem Chaos geht die Unterhaltung auf einer sauberen Leitung
+        // in beide Richtungen weiter.
+        for relay in &relays {
+            relay.set_config(SimConfig::default());
+        }
+        alice.send(&bob.spend.public, 1000, b"noch da?").await;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(bob.receive(), vec![b"noch da?".to_vec()]);
+        bob.send(&alice.spend.public, 1001, b"ja").await;
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(alice.receive(), vec![b"ja".to_vec()]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn everything_stored_is_delivered_once() {
+        let clock = ManualClock::new(SystemClock.now_ms());
+        let relays = relays(chaos(), &clock);
+        let mut alice = Party::new(&relays, Some(RatchetState::initiator(SecretBytes::new(ROOT), [1u8; 32]))).await;
+        let mut bob = Party::new(&relays, None).await;
+        for i in 0..30u8 {
+            alice.send(&bob.spend.public, i as u128, &[i]).await;
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        let received: BTreeSet<u128> = bob.receive().iter().map(|text| text[0] as u128).collect();
+        assert_eq!(received, stored_for(&relays, &bob.spend));
+        assert!(received.len() < 30 && !received.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn tampered_copies_fail_but_honest_copies_arrive() {
+        let clock = ManualClock::new(SystemClock.now_ms());
+        let relays = relays(SimConfig::default(), &clock);
+        relays[0].set_config(SimConfig { tamper: 1.0, ..SimConfig::default() });
+        let mut alice = Party::new(&relays, Some(RatchetState::initiator(SecretBytes::new(ROOT), [1u8; 32]))).await;
+        let mut bob = Party::new(&relays[..2], None).await;
+        for i in 0..10u8 {
+            alice.send(&bob.spend.public, i as u128, &[i]).await;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(bob.receive(), (0..10u8).map(|i| vec![i]).collect::<Vec<_>>());
+        assert_eq!(relays[0].stats().tampered, 10);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn same_seed_injects_same_faults() {
+        let clock = ManualClock::new(SystemClock.now_ms());
+        let runs: Vec<_> = (0..2).map(|_| SimulatedRelay::with_clock("sim", chaos(), 9, Arc::new(clock.clone()))).collect();
+        let envelopes: Vec<_> = (0..50).map(|_| Envelope::new_cover(32, 60, 0, false)).collect();
+        for relay in &runs {
+            for env in &envelopes {
+                relay.publish(env.clone()).await.unwrap();
+            }
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(format!("{:?}", runs[0].stats()), format!("{:?}", runs[1].stats()));
+        let stored = |relay: &SimulatedRelay| relay.inner().history_since(0).iter().map(Envelope::to_bytes).collect::<BTreeSet<_>>();
+        assert_eq!(stored(&runs[0]), stored(&runs[1]));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn partition_rejects_until_healed() {
+        let relay = SimulatedRelay::new("sim", SimConfig::default(), 1);
+        relay.set_partitioned(true);
+        assert!(relay.publish(Envelope::new_cover(32, 60, 0, false)).await.is_err());
+        assert!(relay.fetch_since(0).await.is_err());
+        assert_eq!(relay.health().await.failure_rate, 1.0);
+        relay.set_partitioned(false);
+        relay.publish(Envelope::new_cover(32, 60, 0, false)).await.unwrap();
+        tokio::task::yield_now().await;
+        assert_eq!(relay.fetch_since(0).await.unwrap().len(), 1);
+        assert_eq!((relay.stats().rejected, relay.stats().published), (1, 1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn late_deliveries_expire_on_the_injected_clock() {
+        let clock = ManualClock::new(SystemClock.now_ms());
+        let config = SimConfig { latency: Latency::Fixed(Duration::from_secs(5)), ..SimConfig::default() };
+        let relay = SimulatedRelay::with_clock("sim", config, 1, Arc::new(clock.clone()));
+        relay.publish(Envelope::new_cover(32, 60, 0, false)).await.unwrap();
+        clock.advance(61_000);
+        tokio::time::sleep(Duration::from_secs(6)).await;
+        assert_eq!(relay.inner().stored(), 0);
+        assert_eq!(relay.stats().published, 1);
+    }
+}
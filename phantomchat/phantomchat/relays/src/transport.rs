@@ -0,0 +1,297 @@
+//! Transportschicht für Relay‑Verbindungen (direkt oder über Tor).
+//!
+//! Für Nutzer in feindlichen Netzen kann jede Relay‑Verbindung über einen
+//! SOCKS5‑Proxy (typischerweise den lokalen Tor‑Client) geführt werden.
+//! Domainnamen werden dabei unaufgelöst an den Proxy übergeben, so dass
+//! keine DNS‑Anfrage das Gerät verlässt.  Für die Stream‑Isolation
+//! erhält jede Kombination aus Relay und Identität eigene
+//! SOCKS‑Zugangsdaten; Tor (`IsolateSOCKSAuth`, standardmäßig aktiv)
+//! baut dafür jeweils einen eigenen Circuit auf.  Ist der Proxy nicht
+//! erreichbar, schlägt die Verbindung fehl – ein stiller Rückfall auf
+//! eine Direktverbindung findet nicht statt.
+
+use phantomchat_core::util::{sha256, to_hex};
+use std::net::{IpAddr, SocketAddr};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use url::{Host, Url};
+
+/// Standardadresse des SOCKS‑Ports eines lokalen Tor‑Clients.
+pub const DEFAULT_TOR_PROXY: &str = "127.0.0.1:9050";
+
+/// Fehler beim Aufbau einer Relay‑Verbindung.
+#[derive(Debug, thiserror::Error)]
+pub enum TransportError {
+    #[error("ungültige Relay‑URL: {0}")]
+    InvalidUrl(String),
+    #[error("SOCKS5‑Proxy {proxy} nicht erreichbar: {source}")]
+    ProxyUnavailable {
+        proxy: SocketAddr,
+        #[source]
+        source: std::io::Error,
+    },
+    #[error("SOCKS5‑Proxy lehnt die Authentifizierung ab")]
+    ProxyAuthRejected,
+    #[error("SOCKS5‑Proxy meldet Fehler {0:#04x} beim Verbindungsaufbau")]
+    ProxyConnectFailed(u8),
+    #[error("ungültige SOCKS5‑Antwort")]
+    ProxyProtocol,
+    #[error("Verbindung fehlgeschlagen: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Tor‑Einstellungen eines Relays.
+#[derive(Debug, Clone)]
+pub struct TorConfig {
+    /// Adresse des SOCKS5‑Proxys.
+    pub proxy: SocketAddr,
+    /// Eigener Circuit pro Relay und Identität (Stream‑Isolation).
+    pub isolate: bool,
+}
+
+impl Default for TorConfig {
+    fn default() -> Self {
+        Self { proxy: DEFAULT_TOR_PROXY.parse().expect("gültige Adresse"), isolate: true }
+    }
+}
+
+/// Verbindungsparameter eines einzelnen Relays.
+#[derive(Debug, Clone)]
+pub struct RelayConfig {
+    /// WebSocket‑URL (`ws://` oder `wss://`).
+    pub url: String,
+    /// Optionaler Tor‑Transport; `None` verbindet direkt.
+    pub tor: Option<TorConfig>,
+}
+
+impl RelayConfig {
+    /// Direkte Verbindung ohne Proxy.
+    pub fn direct(url: &str) -> Self {
+        Self { url: url.to_owned(), tor: None }
+    }
+    /// Verbindung über Tor mit Standardeinstellungen.
+    pub fn tor(url: &str) -> Self {
+        Self { url: url.to_owned(), tor: Some(TorConfig::default()) }
+    }
+}
+
+/// Zerlegt eine Relay‑URL in Host und Port.  IPv6‑Adressen stehen in
+/// eckigen Klammern, so dass `host:port` eindeutig bleibt.
+pub fn host_port(url: &str) -> Result<(String, u16), TransportError> {
+    let parsed = Url::parse(url).map_err(|e| TransportError::InvalidUrl(format!("{url}: {e}")))?;
+    let host = match parsed.host() {
+        Some(Host::Domain(domain)) => domain.to_owned(),
+        Some(Host::Ipv4(addr)) => addr.to_string(),
+        Some(Host::Ipv6(addr)) => format!("[{addr}]"),
+        None => return Err(TransportError::InvalidUrl(url.to_owned())),
+    };
+    let port = parsed
+        .port_or_known_default()
+        .ok_or_else(|| TransportError::InvalidUrl(url.to_owned()))?;
+    Ok((host, port))
+}
+
+/// IP‑Literal eines Hosts aus [`host_port`], `None` für Domainnamen.
+fn ip_literal(host: &str) -> Option<IpAddr> {
+    host.strip_prefix('[').and_then(|h| h.strip_suffix(']')).unwrap_or(host).parse().ok()
+}
+
+/// Leitet die SOCKS‑Zugangsdaten für die Stream‑Isolation ab.  Gleiche
+/// Eingaben ergeben denselben Circuit, verschiedene Relays oder
+/// Identitäten nie.
+pub fn isolation_credentials(relay_url: &str, identity: &str) -> (String, String) {
+    let mut data = Vec::new();
+    data.extend_from_slice(b"pc.isolate|");
+    data.extend_from_slice(identity.as_bytes());
+    data.push(0);
+    data.extend_from_slice(relay_url.as_bytes());
+    let digest = sha256(&data);
+    (format!("pc-{}", to_hex(&digest[..12])), to_hex(&digest[12..24]))
+}
+
+/// Öffnet die TCP‑Verbindung zum Relay, direkt oder über den in
+/// `config` angegebenen Proxy.  `identity` bestimmt zusammen mit der
+/// Relay‑URL die Isolationsgruppe.
+pub async fn connect(config: &RelayConfig, identity: &str) -> Result<TcpStream, TransportError> {
+    let (host, port) = host_port(&config.url)?;
+    match &config.tor {
+        None => Ok(TcpStream::connect(format!("{host}:{port}")).await?),
+        Some(tor) => {
+            let creds = if tor.isolate { Some(isolation_credentials(&config.url, identity)) } else { None };
+            socks5_connect(tor.proxy, &host, port, creds.as_ref().map(|(u, p)| (u.as_str(), p.as_str()))).await
+        }
+    }
+}
+
+/// Minimaler SOCKS5‑Client (RFC 1928) mit optionaler
+/// Benutzername/Passwort‑Authentifizierung (RFC 1929).  Domainnamen
+/// werden unaufgelöst übergeben, IP‑Literale (auch `[…]`) als Adresse.
+pub async fn socks5_connect(
+    proxy: SocketAddr,
+    host: &str,
+    port: u16,
+    credentials: Option<(&str, &str)>,
+) -> Result<TcpStream, TransportError> {
+    let mut stream = TcpStream::connect(proxy)
+        .await
+        .map_err(|source| TransportError::ProxyUnavailable { proxy, source })?;
+    // 1. Begrüßung mit der gewünschten Authentifizierungsmethode
+    let method = if credentials.is_some() { 0x02 } else { 0x00 };
+    stream.write_all(&[0x05, 0x01, method]).await?;
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != 0x05 {
+        return Err(TransportError::ProxyProtocol);
+    }
+    if reply[1] != method {
+        return Err(TransportError::ProxyAuthRejected);
+    }
+    // 2. Benutzername/Passwort für die Stream‑Isolation
+    if let Some((user, pass)) = credentials {
+        if user.len() > 255 || pass.len() > 255 {
+            return Err(TransportError::ProxyProtocol);
+        }
+        let mut auth = vec![0x01, user.len() as u8];
+        auth.extend_from_slice(user.as_bytes());
+        auth.push(pass.len() as u8);
+        auth.extend_from_slice(pass.as_bytes());
+        stream.write_all(&auth).await?;
+        let mut status = [0u8; 2];
+        stream.read_exact(&mut status).await?;
+        if status[1] != 0x00 {
+            return Err(TransportError::ProxyAuthRejected);
+        }
+    }
+    // 3. CONNECT mit IPv4 (ATYP 0x01), IPv6 (0x04) oder Domainname (0x03)
+    let mut req = vec![0x05, 0x01, 0x00];
+    match ip_literal(host) {
+        Some(IpAddr::V4(addr)) => {
+            req.push(0x01);
+            req.extend_from_slice(&addr.octets());
+        }
+        Some(IpAddr::V6(addr)) => {
+            req.push(0x04);
+            req.extend_from_slice(&addr.octets());
+        }
+        None => {
+            if host.len() > 255 {
+                return Err(TransportError::InvalidUrl(host.to_owned()));
+            }
+            req.extend_from_slice(&[0x03, host.len() as u8]);
+            req.extend_from_slice(host.as_bytes());
+        }
+    }
+    req.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&req).await?;
+    let mut head = [0u8; 4];
+    stream.read_exact(&mut head).await?;
+    if head[0] != 0x05 {
+        return Err(TransportError::ProxyProtocol);
+    }
+    if head[1] != 0x00 {
+        return Err(TransportError::ProxyConnectFailed(head[1]));
+    }
+    // Gebundene Adresse überspringen
+    let addr_len = match head[3] {
+        0x01 => 4,
+        0x04 => 16,
+        0x03 => {
+            let mut len = [0u8; 1];
+            stream.read_exact(&mut len).await?;
+            len[0] as usize
+        }
+        _ => return Err(TransportError::ProxyProtocol),
+    };
+    let mut rest = vec![0u8; addr_len + 2];
+    stream.read_exact(&mut rest).await?;
+    Ok(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    /// Lokaler SOCKS5‑Ersatz für eine Verbindung: prüft die Begrüßung,
+    /// nimmt Zugangsdaten an, falls angeboten, und liefert diese sowie die
+    /// CONNECT‑Anfrage zurück.  Danach sendet er `hello`.
+    async fn socks_stand_in() -> (SocketAddr, tokio::task::JoinHandle<(Option<(String, String)>, Vec<u8>)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let task = tokio::spawn(async move {
+            let (mut s, _) = listener.accept().await.unwrap();
+            let mut greeting = [0u8; 3];
+            s.read_exact(&mut greeting).await.unwrap();
+            assert_eq!(greeting[..2], [0x05, 0x01]);
+            s.write_all(&[0x05, greeting[2]]).await.unwrap();
+            let mut creds = None;
+            if greeting[2] == 0x02 {
+                let mut head = [0u8; 2];
+                s.read_exact(&mut head).await.unwrap();
+                let mut user = vec![0u8; head[1] as usize];
+                s.read_exact(&mut user).await.unwrap();
+                let mut len = [0u8; 1];
+                s.read_exact(&mut len).await.unwrap();
+                let mut pass = vec![0u8; len[0] as usize];
+                s.read_exact(&mut pass).await.unwrap();
+                creds = Some((String::from_utf8(user).unwrap(), String::from_utf8(pass).unwrap()));
+                s.write_all(&[0x01, 0x00]).await.unwrap();
+            }
+            let mut request = vec![0u8; 512];
+            let n = s.read(&mut request).await.unwrap();
+            request.truncate(n);
+            s.write_all(&[0x05, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0]).await.unwrap();
+            s.write_all(b"hello").await.unwrap();
+            (creds, request)
+        });
+        (addr, task)
+    }
+
+    #[tokio::test]
+    async fn socks5_passes_isolation_credentials_and_domain() {
+        let (proxy, task) = socks_stand_in().await;
+        let url = "wss://relay.example:7447";
+        let config = RelayConfig { url: url.into(), tor: Some(TorConfig { proxy, isolate: true }) };
+        let mut stream = connect(&config, "alice").await.unwrap();
+        let mut hello = [0u8; 5];
+        stream.read_exact(&mut hello).await.unwrap();
+        assert_eq!(&hello, b"hello");
+
+        let (creds, request) = task.await.unwrap();
+        assert_eq!(creds, Some(isolation_credentials(url, "alice")));
+        let mut expected = vec![0x05, 0x01, 0x00, 0x03, 13];
+        expected.extend_from_slice(b"relay.example");
+        expected.extend_from_slice(&7447u16.to_be_bytes());
+        assert_eq!(request, expected);
+    }
+
+    #[tokio::test]
+    async fn socks5_sends_ipv6_literals_as_addresses() {
+        let (proxy, task) = socks_stand_in().await;
+        let config = RelayConfig { url: "ws://[2001:db8::1]:80".into(), tor: Some(TorConfig { proxy, isolate: false }) };
+        connect(&config, "").await.unwrap();
+        let (creds, request) = task.await.unwrap();
+        assert_eq!(creds, None);
+        let addr: std::net::Ipv6Addr = "2001:db8::1".parse().unwrap();
+        assert_eq!(request[..4], [0x05, 0x01, 0x00, 0x04]);
+        assert_eq!(request[4..20], addr.octets());
+        assert_eq!(request[20..], 80u16.to_be_bytes());
+    }
+
+    #[test]
+    fn isolation_separates_relays_and_identities() {
+        let base = isolation_credentials("wss://a.example", "alice");
+        assert_eq!(base, isolation_credentials("wss://a.example", "alice"));
+        assert_ne!(base, isolation_credentials("wss://b.example", "alice"));
+        assert_ne!(base, isolation_credentials("wss://a.example", "bob"));
+    }
+
+    #[test]
+    fn host_port_brackets_ipv6() {
+        assert_eq!(host_port("wss://[::1]:7000").unwrap(), ("[::1]".into(), 7000));
+        assert_eq!(host_port("wss://relay.example").unwrap(), ("relay.example".into(), 443));
+        assert_eq!(host_port("ws://10.0.0.1").unwrap(), ("10.0.0.1".into(), 80));
+        assert_eq!(ip_literal("[::1]"), Some("::1".parse().unwrap()));
+        assert_eq!(ip_literal("relay.example"), None);
+    }
+}